@@ -0,0 +1,113 @@
+//! Benchmarks for the storage layer: inserting at the start, middle and end
+//! of a large buffer, searching it the way `/` does, and drawing a screenful
+//! of it through the editor (frame and screen diff) on a headless terminal.
+//!
+//! Run with `cargo bench`. Each case is timed over a fixed number of
+//! iterations after a warm-up, and the median per-iteration time is printed,
+//! which is enough to spot a regression between two commits. A small
+//! std-only harness keeps the dev-dependency list empty.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use hecto::{
+    buffer::Buffer,
+    script::ScriptCommand,
+    search,
+    terminal::{Size, Terminal, TestBackend},
+    Editor,
+};
+
+/// Lines in the buffer every case starts from.
+const LINES: usize = 100_000;
+/// Screen size used by the rendering case.
+const SCREEN_WIDTH: usize = 120;
+const SCREEN_HEIGHT: usize = 50;
+
+const WARM_UP: usize = 10;
+const SAMPLES: usize = 100;
+
+fn sample_text() -> String {
+    (0..LINES)
+        .map(|line| format!("{line:>6}\tthe quick brown fox jumps over the lazy dog, 日本語\n"))
+        .collect()
+}
+
+fn sample_buffer() -> Buffer {
+    let mut buffer = Buffer::default();
+    buffer.replace_text(&sample_text());
+    buffer
+}
+
+/// An editor on a headless terminal, with the sample text open.
+fn sample_editor() -> Editor {
+    let path = std::env::temp_dir().join(format!("hecto-bench-{}.txt", std::process::id()));
+    std::fs::write(&path, sample_text()).expect("cannot write the sample file");
+    let backend = TestBackend::new(Size {
+        width: SCREEN_WIDTH,
+        height: SCREEN_HEIGHT,
+    });
+    let mut editor = Editor::with_terminal(Terminal::new(backend));
+    let open = ScriptCommand::Open(path.to_string_lossy().into_owned());
+    editor
+        .run_script_command(&open)
+        .expect("cannot open the sample file");
+    std::fs::remove_file(path).expect("cannot remove the sample file");
+    editor
+}
+
+/// Times `routine` over [`SAMPLES`] runs and prints the median.
+fn bench(name: &str, mut routine: impl FnMut()) {
+    for _ in 0..WARM_UP {
+        routine();
+    }
+    let mut times: Vec<Duration> = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            routine();
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    let median = times[times.len() / 2];
+    println!("{name:<24} {median:>12.2?}");
+}
+
+fn main() {
+    let mut buffer = sample_buffer();
+    bench("insert at start", || buffer.insert(0, 0, black_box("x")));
+
+    let mut buffer = sample_buffer();
+    bench("insert in the middle", || {
+        buffer.insert(LINES / 2, 10, black_box("x"));
+    });
+
+    let mut buffer = sample_buffer();
+    bench("insert at end", || {
+        let last = buffer.line_count() - 1;
+        buffer.insert(last, usize::MAX, black_box("x"));
+    });
+
+    let mut buffer = sample_buffer();
+    bench("type a line in the middle", || {
+        for col in 0..80 {
+            buffer.insert(LINES / 2, col, black_box("y"));
+        }
+        buffer.insert(LINES / 2, 80, "\n");
+    });
+
+    let buffer = sample_buffer();
+    bench("search", || {
+        black_box(search::find(&buffer, (0, 0), black_box("99999"), true));
+    });
+
+    let mut editor = sample_editor();
+    let insert = ScriptCommand::Insert("x".to_string());
+    bench("render a screen", || {
+        // Edit the top line so its layout and row are redone each time.
+        editor.run_script_command(&insert).expect("cannot insert");
+        editor.draw().expect("cannot draw");
+    });
+}
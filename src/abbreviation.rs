@@ -0,0 +1,107 @@
+//! # Abbreviations
+//!
+//! Words that turn into other text as they are typed: in insert mode, a
+//! word the `[abbreviations]` section of the config file names is
+//! replaced with its expansion once a space, a punctuation mark or a line
+//! break follows it. An `[abbreviations.FILETYPE]` section sets them for
+//! one [filetype](crate::filetype) only, winning over the others:
+//!
+//! ```toml
+//! [abbreviations]
+//! teh = "the"
+//!
+//! [abbreviations.rust]
+//! fnmain = "fn main() {\n    $0\n}"
+//! ```
+//!
+//! An expansion is a snippet like the ones code completion puts in: the
+//! cursor goes to its first tab stop (`$1`, or else `$0`), in which case a
+//! space or line break that set it off is dropped. Its lines after the
+//! first are indented like the line it was typed on, and it may use the
+//! [template](crate::template) variables, like `$TM_FILENAME`. `insert-literal`
+//! (`Alt+V`) types the next key as it is, leaving the word before it alone.
+
+use crate::completion;
+
+/// The expansion of `word` in a file of filetype `filetype`, by
+/// `abbreviations` (filetype, or `""` for any; word; expansion). The last
+/// one for the filetype wins, then the last one for any.
+#[must_use]
+pub fn expansion<'a>(
+    abbreviations: &'a [(String, String, String)],
+    filetype: &str,
+    word: &str,
+) -> Option<&'a str> {
+    let find = |wanted: &str| {
+        abbreviations
+            .iter()
+            .rev()
+            .find(|(for_filetype, abbreviation, _)| for_filetype == wanted && abbreviation == word)
+            .map(|(_, _, expansion)| expansion.as_str())
+    };
+    find(filetype).or_else(|| find(""))
+}
+
+/// The text `expansion` puts in on a line indented with `indentation`, its
+/// variables replaced by `variable`, and the byte of it where the cursor
+/// goes, if not at the end.
+#[must_use]
+pub fn expand(
+    expansion: &str,
+    indentation: &str,
+    variable: impl Fn(&str) -> Option<String>,
+) -> (String, Option<usize>) {
+    let (text, cursor) = completion::expand_snippet_with(expansion, variable);
+    let indent = |part: &str| part.replace('\n', &format!("\n{indentation}"));
+    let before = indent(text.get(..cursor).unwrap_or_default());
+    let after = indent(text.get(cursor..).unwrap_or_default());
+    let cursor = (!after.is_empty()).then_some(before.len());
+    (format!("{before}{after}"), cursor)
+}
+
+/// Where the word `text` ends with starts, in bytes, if it ends with one.
+#[must_use]
+pub fn word_start(text: &str) -> Option<usize> {
+    let start = text
+        .char_indices()
+        .rev()
+        .take_while(|&(_, ch)| completion::is_word_char(ch))
+        .last()?
+        .0;
+    Some(start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_abbreviations_expand_by_filetype() {
+        let abbreviations = [
+            (String::new(), "teh".to_string(), "the".to_string()),
+            (
+                "rust".to_string(),
+                "fnmain".to_string(),
+                "fn main() {\n    $0\n}".to_string(),
+            ),
+            ("rust".to_string(), "teh".to_string(), "THE".to_string()),
+        ];
+        assert_eq!(expansion(&abbreviations, "text", "teh"), Some("the"));
+        assert_eq!(expansion(&abbreviations, "rust", "teh"), Some("THE"));
+        assert_eq!(expansion(&abbreviations, "text", "fnmain"), None);
+
+        let fnmain = expansion(&abbreviations, "rust", "fnmain").unwrap();
+        assert_eq!(
+            expand(fnmain, "  ", |_| None),
+            ("fn main() {\n      \n  }".to_string(), Some(18))
+        );
+        assert_eq!(expand("the", "  ", |_| None), ("the".to_string(), None));
+        assert_eq!(
+            expand("by $AUTHOR", "", |_| Some("Ada".to_string())),
+            ("by Ada".to_string(), None)
+        );
+
+        assert_eq!(word_start("a teh"), Some(2));
+        assert_eq!(word_start("a teh."), None);
+    }
+}
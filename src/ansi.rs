@@ -0,0 +1,321 @@
+//! # ANSI Colors
+//!
+//! Logs from CI jobs, `script` and the like are full of the escapes that
+//! color a terminal's output (`ESC[31m`), which would otherwise show as
+//! `▯[31m` between the words. With the `ansi` option on, a file that has
+//! any is opened read-only, with the escapes taken out of the text and
+//! its colors shown instead: SGR sequences set the foreground and
+//! background (the 16 named colors, the 256-color palette and `#rrggbb`),
+//! bold, dim, italic, underlined and reverse, until a later one resets
+//! them, across lines as in a terminal. Other escapes, like those that
+//! move the cursor or name a hyperlink, are dropped. Text appended while
+//! the file loads or is [followed](crate::follow) is read the same way,
+//! even if an escape is split between two reads.
+//!
+//! Since its text is no longer what the file holds, such a buffer is not
+//! saved; turn the option off and open the file again to edit it.
+
+use std::{mem, ops::Range};
+
+use crossterm::style::{Attribute, Color, ContentStyle};
+
+const ESCAPE: char = '\x1b';
+
+/// The colors of the SGR codes 30 to 37 (and 40 to 47), then those of 90
+/// to 97 (and 100 to 107).
+const NAMED: [Color; 16] = [
+    Color::Black,
+    Color::DarkRed,
+    Color::DarkGreen,
+    Color::DarkYellow,
+    Color::DarkBlue,
+    Color::DarkMagenta,
+    Color::DarkCyan,
+    Color::Grey,
+    Color::DarkGrey,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+];
+
+/// Part of a line shown in the style escapes set for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Run {
+    /// The bytes of the line, as it is without its escapes.
+    pub bytes: Range<usize>,
+    pub style: ContentStyle,
+}
+
+/// Takes the escapes out of text as it comes in, keeping the colors they
+/// gave each line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Colors {
+    /// The runs of each line so far.
+    lines: Vec<Vec<Run>>,
+    /// What the escapes so far leave the text that follows in.
+    style: ContentStyle,
+    /// How long the last line is so far, in bytes.
+    length: usize,
+    /// An escape the text so far ended in the middle of.
+    pending: String,
+}
+
+/// Whether `text` has escapes to take out.
+#[must_use]
+pub fn has_escapes(text: &str) -> bool {
+    text.contains(ESCAPE)
+}
+
+impl Colors {
+    /// `text`, the next part of what is read, without its escapes; it
+    /// continues the last line.
+    pub fn feed(&mut self, text: &str) -> String {
+        let text = mem::take(&mut self.pending) + text;
+        if self.lines.is_empty() {
+            self.lines.push(Vec::new());
+        }
+        let mut plain = String::with_capacity(text.len());
+        let mut rest = text.as_str();
+        while !rest.is_empty() {
+            if rest.starts_with(ESCAPE) {
+                let Some(length) = escape_length(rest) else {
+                    self.pending = rest.to_string();
+                    break;
+                };
+                if let Some(codes) = rest
+                    .get(..length)
+                    .and_then(|escape| escape.strip_prefix("\x1b["))
+                    .and_then(|escape| escape.strip_suffix('m'))
+                {
+                    select_graphic_rendition(&mut self.style, codes);
+                }
+                rest = rest.get(length..).unwrap_or_default();
+                continue;
+            }
+            let end = rest.find(ESCAPE).unwrap_or(rest.len());
+            for (index, part) in rest[..end].split('\n').enumerate() {
+                if index > 0 {
+                    plain.push('\n');
+                    self.lines.push(Vec::new());
+                    self.length = 0;
+                }
+                self.add(part.len());
+                plain.push_str(part);
+            }
+            rest = &rest[end..];
+        }
+        plain
+    }
+
+    /// The runs of line `line` that are not in the plain style.
+    #[must_use]
+    pub fn on_line(&self, line: usize) -> &[Run] {
+        self.lines.get(line).map_or(&[], Vec::as_slice)
+    }
+
+    /// Adds `length` bytes in the current style to the last line.
+    fn add(&mut self, length: usize) {
+        let start = self.length;
+        self.length = start.saturating_add(length);
+        if length == 0 || self.style == ContentStyle::default() {
+            return;
+        }
+        let Some(runs) = self.lines.last_mut() else {
+            return;
+        };
+        match runs.last_mut() {
+            Some(run) if run.bytes.end == start && run.style == self.style => {
+                run.bytes.end = self.length;
+            }
+            _ => runs.push(Run {
+                bytes: start..self.length,
+                style: self.style,
+            }),
+        }
+    }
+}
+
+/// How many bytes the escape `text` starts with takes, or `None` if
+/// `text` ends before it does. A broken one ends where it stops making
+/// sense, so no text is lost to it.
+fn escape_length(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    match bytes.get(1)? {
+        // CSI: parameters, intermediates, then a final byte.
+        b'[' => {
+            for (index, &byte) in bytes.iter().enumerate().skip(2) {
+                match byte {
+                    0x20..=0x3f => {}
+                    0x40..=0x7e => return Some(index.saturating_add(1)),
+                    _ => return Some(index),
+                }
+            }
+            None
+        }
+        // OSC: ends with a bell or `ESC \`.
+        b']' => {
+            for (index, &byte) in bytes.iter().enumerate().skip(2) {
+                match byte {
+                    0x07 => return Some(index.saturating_add(1)),
+                    b'\n' => return Some(index),
+                    0x1b => {
+                        return match bytes.get(index.saturating_add(1))? {
+                            b'\\' => Some(index.saturating_add(2)),
+                            _ => Some(index),
+                        };
+                    }
+                    _ => {}
+                }
+            }
+            None
+        }
+        b'\n' => Some(1),
+        _ => Some(
+            text.chars()
+                .take(2)
+                .map(char::len_utf8)
+                .fold(0, usize::saturating_add),
+        ),
+    }
+}
+
+/// Changes `style` as the SGR parameters `codes` (like `1;31`) say.
+fn select_graphic_rendition(style: &mut ContentStyle, codes: &str) {
+    let mut codes = codes
+        .split(';')
+        .map(|code| code.parse::<u16>().unwrap_or_default());
+    while let Some(code) = codes.next() {
+        match code {
+            0 => *style = ContentStyle::default(),
+            1 => style.attributes.set(Attribute::Bold),
+            2 => style.attributes.set(Attribute::Dim),
+            3 => style.attributes.set(Attribute::Italic),
+            4 => style.attributes.set(Attribute::Underlined),
+            7 => style.attributes.set(Attribute::Reverse),
+            22 => {
+                style.attributes.unset(Attribute::Bold);
+                style.attributes.unset(Attribute::Dim);
+            }
+            23 => style.attributes.unset(Attribute::Italic),
+            24 => style.attributes.unset(Attribute::Underlined),
+            27 => style.attributes.unset(Attribute::Reverse),
+            30..=37 | 90..=97 => style.foreground_color = named(code.saturating_sub(30)),
+            40..=47 | 100..=107 => style.background_color = named(code.saturating_sub(40)),
+            38 => style.foreground_color = extended(&mut codes),
+            48 => style.background_color = extended(&mut codes),
+            39 => style.foreground_color = None,
+            49 => style.background_color = None,
+            _ => {}
+        }
+    }
+}
+
+/// The named color `offset` stands for: 0 to 7, or 60 to 67 for the
+/// bright ones.
+fn named(offset: u16) -> Option<Color> {
+    let index = if offset >= 60 {
+        offset.saturating_sub(52)
+    } else {
+        offset
+    };
+    NAMED.get(usize::from(index)).copied()
+}
+
+/// The color of a `38;5;N` or `38;2;R;G;B` code, from what follows the
+/// `38` (or `48`).
+fn extended(codes: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    let mut next = || codes.next().and_then(|code| u8::try_from(code).ok());
+    match next()? {
+        5 => Some(Color::AnsiValue(next()?)),
+        2 => Some(Color::Rgb {
+            r: next()?,
+            g: next()?,
+            b: next()?,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn style(foreground: Option<Color>, attributes: &[Attribute]) -> ContentStyle {
+        let mut style = ContentStyle {
+            foreground_color: foreground,
+            ..ContentStyle::default()
+        };
+        for &attribute in attributes {
+            style.attributes.set(attribute);
+        }
+        style
+    }
+
+    #[test]
+    fn test_escapes_are_taken_out_and_their_colors_kept() {
+        let mut colors = Colors::default();
+        let plain =
+            colors.feed("ok \x1b[1;31mFAIL\x1b[22m x\n\x1b[0Kdone\x1b[0m.\n\x1b]8;;http://x\x07y");
+        assert_eq!(plain, "ok FAIL x\ndone.\ny");
+        let red = Some(Color::DarkRed);
+        assert_eq!(
+            colors.on_line(0),
+            [
+                Run {
+                    bytes: 3..7,
+                    style: style(red, &[Attribute::Bold]),
+                },
+                Run {
+                    bytes: 7..9,
+                    style: style(red, &[]),
+                },
+            ]
+        );
+        assert_eq!(
+            colors.on_line(1),
+            [Run {
+                bytes: 0..4,
+                style: style(red, &[]),
+            }]
+        );
+        assert!(colors.on_line(2).is_empty());
+        assert!(colors.on_line(9).is_empty());
+    }
+
+    #[test]
+    fn test_escapes_split_between_reads_are_kept_for_the_next() {
+        let mut colors = Colors::default();
+        assert_eq!(colors.feed("a\x1b[38;5"), "a");
+        assert_eq!(colors.feed(";208mb\x1b[48;2;1;2;3"), "b");
+        assert_eq!(colors.feed("mc\x1b"), "c");
+        assert_eq!(colors.feed("[94md"), "d");
+        let runs: Vec<(Range<usize>, Option<Color>, Option<Color>)> = colors
+            .on_line(0)
+            .iter()
+            .map(|run| {
+                (
+                    run.bytes.clone(),
+                    run.style.foreground_color,
+                    run.style.background_color,
+                )
+            })
+            .collect();
+        let orange = Some(Color::AnsiValue(208));
+        let rgb = Some(Color::Rgb { r: 1, g: 2, b: 3 });
+        assert_eq!(
+            runs,
+            [
+                (1..2, orange, None),
+                (2..3, orange, rgb),
+                (3..4, Some(Color::Blue), rgb),
+            ]
+        );
+        assert_eq!(colors.feed("\x1b[5\n!"), "\n!");
+        assert!(has_escapes("\x1b[m"));
+        assert!(!has_escapes("plain"));
+    }
+}
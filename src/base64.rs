@@ -0,0 +1,106 @@
+//! # Base64
+//!
+//! Standard (RFC 4648) base64 encoding, with padding. Used to pass text to
+//! the terminal in OSC 52 clipboard sequences, and by `base64-encode` and
+//! `base64-decode` on the selected text. Decoding also takes the URL-safe
+//! alphabet (`-` and `_`), line breaks, and missing padding.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `bytes` as base64.
+#[must_use]
+pub fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3).saturating_mul(4));
+    for chunk in bytes.chunks(3) {
+        let [a, b, c] = [0, 1, 2].map(|index| chunk.get(index).copied().unwrap_or(0));
+        let group = (u32::from(a) << 16) | (u32::from(b) << 8) | u32::from(c);
+        // A chunk of n bytes yields n + 1 significant characters.
+        let significant = chunk.len().saturating_add(1);
+        for (index, shift) in [18_u32, 12, 6, 0].into_iter().enumerate() {
+            if index < significant {
+                let sextet = (group >> shift) & 0x3f;
+                let symbol = usize::try_from(sextet)
+                    .ok()
+                    .and_then(|sextet| ALPHABET.get(sextet))
+                    .copied()
+                    .unwrap_or(b'=');
+                encoded.push(char::from(symbol));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodes base64 `text`.
+///
+/// # Errors
+///
+/// Fails with what is wrong if `text` is not base64.
+pub fn decode(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim_end_matches(|ch: char| ch == '=' || ch.is_whitespace());
+    let mut decoded = Vec::with_capacity(text.len().saturating_mul(3).div_ceil(4));
+    let (mut group, mut bits) = (0_u32, 0_u32);
+    for ch in text.chars().filter(|ch| !ch.is_whitespace()) {
+        let sextet = match ch {
+            'A'..='Z' => u32::from(ch).saturating_sub(u32::from('A')),
+            'a'..='z' => u32::from(ch)
+                .saturating_sub(u32::from('a'))
+                .saturating_add(26),
+            '0'..='9' => u32::from(ch)
+                .saturating_sub(u32::from('0'))
+                .saturating_add(52),
+            '+' | '-' => 62,
+            '/' | '_' => 63,
+            _ => return Err(format!("Not base64: `{ch}`.")),
+        };
+        group = (group << 6) | sextet;
+        bits = bits.saturating_add(6);
+        if bits >= 8 {
+            bits = bits.saturating_sub(8);
+            decoded.extend(u8::try_from((group >> bits) & 0xff).ok());
+        }
+    }
+    if bits >= 6 {
+        return Err("Not base64: it is a character too long.".to_string());
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc_4648_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (plain, encoded) in vectors {
+            assert_eq!(encode(plain.as_bytes()), encoded, "for {plain:?}");
+        }
+    }
+
+    #[test]
+    fn test_non_ascii() {
+        assert_eq!(encode("héllo".as_bytes()), "aMOpbGxv");
+    }
+
+    #[test]
+    fn test_decoding_undoes_encoding() {
+        for plain in ["", "f", "fo", "foo", "foob", "fooba", "foobar", "héllo"] {
+            assert_eq!(decode(&encode(plain.as_bytes())).unwrap(), plain.as_bytes());
+        }
+        assert_eq!(decode("Zm9v\nYmE").unwrap(), b"fooba");
+        assert_eq!(decode("-_8=").unwrap(), [0xfb, 0xff]);
+        assert_eq!(decode("Zm9v!").unwrap_err(), "Not base64: `!`.");
+        assert!(decode("Zm9vY").is_err());
+    }
+}
@@ -1,25 +1,1265 @@
-use std::fs;
+use std::{
+    borrow::Cow,
+    cmp::{max, min, Ordering},
+    collections::HashSet,
+    fmt, io,
+    ops::Range,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
-use crate::error::Result;
+use crate::{
+    ansi::{self, Colors, Run},
+    compression::Compression,
+    diagnostic::Diagnostic,
+    diff::Hunk,
+    editorconfig::Properties,
+    error::{Error, Result},
+    filetype::{self, Filetype},
+    highlight::{StateCache, Token},
+    indent,
+    layout::{LayoutCache, LineLayout, TAB_WIDTH},
+    piece_table::PieceTable,
+    undo::{Edit, History, Step},
+};
+
+/// How indentation is typed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    #[default]
+    Tabs,
+    /// Spaces up to the next multiple of this many columns.
+    Spaces(usize),
+}
+
+impl fmt::Display for Indent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tabs => write!(f, "tabs"),
+            Self::Spaces(size) => write!(f, "spaces:{size}"),
+        }
+    }
+}
+
+/// How [`Buffer::sort_lines`] orders lines, as `sort`'s flags say.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SortOptions {
+    /// Upper and lower case compare the same (`i`).
+    pub ignore_case: bool,
+    /// By the first number on each line, lines without one first (`n`).
+    pub numeric: bool,
+    /// Only the first of lines that compare the same is kept (`u`).
+    pub unique: bool,
+}
+
+impl SortOptions {
+    fn compare(self, a: &str, b: &str) -> Ordering {
+        if self.numeric {
+            first_number(a).cmp(&first_number(b))
+        } else if self.ignore_case {
+            a.to_lowercase().cmp(&b.to_lowercase())
+        } else {
+            a.cmp(b)
+        }
+    }
+}
+
+/// The first decimal number in `line`, with its `-` if it has one.
+fn first_number(line: &str) -> Option<i64> {
+    let start = line.find(|ch: char| ch.is_ascii_digit())?;
+    let digits = &line[start..];
+    let digits = &digits[..digits
+        .find(|ch: char| !ch.is_ascii_digit())
+        .unwrap_or(digits.len())];
+    let negative = line[..start].ends_with('-');
+    // Too many digits for 64 bits still sort after the rest.
+    let magnitude = digits.parse::<i64>().unwrap_or(i64::MAX);
+    Some(if negative {
+        magnitude.saturating_neg()
+    } else {
+        magnitude
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+        }
+    }
+}
+
+/// How a buffer is laid out, indented and written back. The editor fills
+/// it in from the options, then from the file's `.editorconfig`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    /// Columns between tab stops.
+    pub tab_width: usize,
+    pub indent: Indent,
+    /// The line ending every line is saved with; `None` keeps each as is.
+    pub end_of_line: Option<LineEnding>,
+    /// Whether spaces and tabs at line ends are removed on save.
+    pub trim_trailing_whitespace: bool,
+    /// Whether a line break is added at the end of the file on save.
+    pub insert_final_newline: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            tab_width: TAB_WIDTH,
+            indent: Indent::default(),
+            end_of_line: None,
+            trim_trailing_whitespace: false,
+            insert_final_newline: false,
+        }
+    }
+}
+
+impl Settings {
+    /// Applies the save-time settings (line endings, trailing whitespace,
+    /// final newline) to `text`.
+    #[must_use]
+    pub fn format(&self, text: &str) -> String {
+        let mut formatted = String::with_capacity(text.len());
+        let mut last_ending = None;
+        for line in text.split_inclusive('\n') {
+            let (body, ending) = match line.strip_suffix('\n') {
+                Some(body) => match body.strip_suffix('\r') {
+                    Some(body) => (body, Some("\r\n")),
+                    None => (body, Some("\n")),
+                },
+                None => (line, None),
+            };
+            let body = if self.trim_trailing_whitespace {
+                body.trim_end_matches([' ', '\t'])
+            } else {
+                body
+            };
+            formatted.push_str(body);
+            let ending = ending.map(|ending| self.end_of_line.map_or(ending, LineEnding::as_str));
+            if let Some(ending) = ending {
+                formatted.push_str(ending);
+                last_ending = Some(ending);
+            } else if self.insert_final_newline && !body.is_empty() {
+                let ending = self
+                    .end_of_line
+                    .map_or_else(|| last_ending.unwrap_or("\n"), LineEnding::as_str);
+                formatted.push_str(ending);
+            }
+        }
+        formatted
+    }
+}
 
 #[derive(Debug, Default, Clone)]
 pub struct Buffer {
-    pub lines: Vec<String>,
+    /// The text, stored as a piece table so edits anywhere stay cheap.
+    text: PieceTable,
+    /// The file this buffer was loaded from (and is saved to).
+    pub file_name: Option<PathBuf>,
+    /// The directory this buffer [lists](crate::directory), if it is a
+    /// listing.
+    pub directory: Option<PathBuf>,
+    /// How the file is stored on disk; the buffer itself always holds text.
+    pub compression: Compression,
+    /// Whether the buffer has changes that were not written to disk yet.
+    pub modified: bool,
+    /// Whether it cannot be edited, as for the diffs the editor shows:
+    /// edits and undoing change nothing, and saving fails. Its whole text
+    /// can still be [replaced](Buffer::replace_text) or
+    /// [appended](Buffer::append) to, as when a file is read.
+    pub read_only: bool,
+    /// Counts edits, so it changes whenever the text does.
+    revision: usize,
+    /// Per-line layouts, dropped whenever the line they describe changes.
+    layouts: LayoutCache,
+    /// What each line leaves open for highlighting the next, kept the same
+    /// way.
+    states: StateCache,
+    settings: Settings,
+    /// What the `.editorconfig` files above the file say; this wins over
+    /// the options when the settings are worked out.
+    pub editorconfig: Properties,
+    /// What kind of file it is, worked out when it was opened.
+    pub filetype: Filetype,
+    /// How the file was [indented](crate::indent) when it was opened, if
+    /// that could be told.
+    pub detected_indent: Option<Indent>,
+    /// What the language server last said about the text.
+    pub diagnostics: Vec<Diagnostic>,
+    /// How the text differs from the file's last [commit](crate::git), as
+    /// of when that was last looked at.
+    pub changes: Vec<Hunk>,
+    /// The edits that can be [undone](crate::undo) and redone.
+    history: History,
+    /// The colors of the [ANSI escapes](crate::ansi) taken out of the
+    /// text, if they were.
+    ansi: Option<Colors>,
 }
 
 impl Buffer {
+    /// An empty, uncompressed buffer for `path`, to be filled with
+    /// [`Buffer::append`] while the file is read in the background.
+    #[must_use]
+    pub fn for_file(path: &Path) -> Self {
+        Self {
+            file_name: Some(path.to_path_buf()),
+            filetype: filetype::detect(Some(path), None),
+            history: History::saved(),
+            ..Self::default()
+        }
+    }
+
+    /// Reads `filename` (decompressing it if needed) into a new buffer.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be read or is not valid UTF-8.
     pub fn load(filename: &str) -> Result<Self> {
-        let file_contents = fs::read_to_string(filename)?;
+        let path = Path::new(filename);
+        let compression = Compression::detect(path);
+        let file_contents = compression.read(path)?;
+        let filetype = filetype::detect(Some(path), file_contents.lines().next());
+        let detected_indent = indent::detect(&file_contents);
         Ok(Self {
-            lines: file_contents.lines().map(str::to_string).collect(),
+            text: PieceTable::new(file_contents),
+            file_name: Some(path.to_path_buf()),
+            directory: None,
+            compression,
+            modified: false,
+            read_only: false,
+            revision: 0,
+            layouts: LayoutCache::default(),
+            states: StateCache::default(),
+            settings: Settings::default(),
+            editorconfig: Properties::default(),
+            filetype,
+            detected_indent,
+            diagnostics: Vec::new(),
+            changes: Vec::new(),
+            history: History::saved(),
+            ansi: None,
         })
     }
 
+    /// Appends raw text (as read from the file) to the end of the buffer.
+    ///
+    /// If the buffer does not end with a line break, the first line of
+    /// `text` continues its last line.
+    pub fn append(&mut self, text: &str) {
+        let last_line = self.line_count().saturating_sub(1);
+        self.invalidate_from(last_line);
+        let text = match &mut self.ansi {
+            Some(colors) => Cow::Owned(colors.feed(text)),
+            None => Cow::Borrowed(text),
+        };
+        self.text.insert(self.text.len(), &text);
+        self.revision = self.revision.wrapping_add(1);
+    }
+
+    /// Replaces the whole text of the buffer, keeping its file association.
+    /// What could be undone is forgotten.
+    pub fn replace_text(&mut self, text: &str) {
+        let text = match &mut self.ansi {
+            Some(colors) => {
+                *colors = Colors::default();
+                colors.feed(text)
+            }
+            None => text.to_string(),
+        };
+        self.text = PieceTable::new(text);
+        self.revision = self.revision.wrapping_add(1);
+        self.layouts.clear();
+        self.states.clear();
+        self.history = History::default();
+    }
+
+    /// Changes the text to `text` as an edit, which can be undone: only
+    /// what differs is replaced.
+    pub fn edit_text(&mut self, text: &str) {
+        let old = self.text.text();
+        let mut prefix = common_prefix(old.bytes(), text.bytes());
+        while !old.is_char_boundary(prefix) || !text.is_char_boundary(prefix) {
+            prefix = prefix.saturating_sub(1);
+        }
+        let suffix = common_prefix(old[prefix..].bytes().rev(), text[prefix..].bytes().rev());
+        let (mut old_end, mut new_end) = (
+            old.len().saturating_sub(suffix),
+            text.len().saturating_sub(suffix),
+        );
+        while !old.is_char_boundary(old_end) || !text.is_char_boundary(new_end) {
+            old_end = old_end.saturating_add(1);
+            new_end = new_end.saturating_add(1);
+        }
+        if prefix == old_end && prefix == new_end {
+            return;
+        }
+        let line = self.line_of(prefix);
+        self.replace_at(prefix..old_end, &text[prefix..new_end]);
+        self.invalidate_from(line);
+        self.mark_modified();
+    }
+
+    /// Writes the buffer back to the file it was loaded from, recompressing
+    /// it the same way. Buffers without a file name are left untouched.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be written, or the buffer is read-only;
+    /// the buffer stays modified.
+    pub fn save(&mut self) -> Result<()> {
+        let Some(file_name) = &self.file_name else {
+            return Ok(());
+        };
+        if self.ansi.is_some() {
+            return Err(Error::at(
+                file_name,
+                io::Error::other("shown with its ANSI escapes taken out, so not saved"),
+            ));
+        }
+        if self.read_only {
+            return Err(Error::at(
+                file_name,
+                io::Error::other("the buffer is read-only, so not saved"),
+            ));
+        }
+        let text = self.text.text();
+        let formatted = self.settings.format(&text);
+        self.compression.write(file_name, &formatted)?;
+        if formatted != text {
+            self.edit_text(&formatted);
+        }
+        self.history.mark_saved();
+        self.modified = false;
+        Ok(())
+    }
+
+    /// Takes the [ANSI escapes](crate::ansi) out of the text, now and as
+    /// it is appended to, showing their colors instead, and makes the
+    /// buffer read-only. Returns `false`, changing nothing, if there are
+    /// none, or the buffer has changes that were not saved.
+    pub fn interpret_ansi(&mut self) -> bool {
+        if self.ansi.is_some() || self.modified {
+            return false;
+        }
+        let text = self.text.text();
+        if !ansi::has_escapes(&text) {
+            return false;
+        }
+        self.ansi = Some(Colors::default());
+        self.replace_text(&text);
+        self.read_only = true;
+        true
+    }
+
+    /// The parts of line `index` that [ANSI escapes](crate::ansi) colored.
+    #[must_use]
+    pub fn ansi_runs(&self, index: usize) -> &[Run] {
+        self.ansi
+            .as_ref()
+            .map_or(&[], |colors| colors.on_line(index))
+    }
+
+    /// The edits that can be undone and redone.
+    #[must_use]
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Takes `history` as the edits that can be undone and redone.
+    pub fn set_history(&mut self, history: History) {
+        self.history = history;
+    }
+
+    /// Ends the current undo step; the next edit starts another, which
+    /// puts the cursor back to `cursor` when it is undone.
+    pub fn seal(&mut self, cursor: (usize, usize)) {
+        self.history.seal(cursor);
+    }
+
+    /// Takes back the last step of edits. Returns where the cursor was
+    /// before it, or `None` if there was nothing to undo.
+    pub fn undo(&mut self) -> Option<(usize, usize)> {
+        if self.read_only {
+            return None;
+        }
+        let step = self.history.undo()?;
+        for edit in step.edits.iter().rev() {
+            let end = edit.at.saturating_add(edit.inserted.len());
+            self.apply(edit.at..end, &edit.removed);
+        }
+        Some(self.after_history(&step))
+    }
+
+    /// Makes the last step undone again. Returns where the cursor was
+    /// before it, or `None` if there was nothing to redo.
+    pub fn redo(&mut self) -> Option<(usize, usize)> {
+        if self.read_only {
+            return None;
+        }
+        let step = self.history.redo()?;
+        for edit in &step.edits {
+            let end = edit.at.saturating_add(edit.removed.len());
+            self.apply(edit.at..end, &edit.inserted);
+        }
+        Some(self.after_history(&step))
+    }
+
+    /// Replaces `range` with `text` without recording it, for undo and
+    /// redo.
+    fn apply(&mut self, range: Range<usize>, text: &str) {
+        let end = min(range.end, self.text.len());
+        let start = min(range.start, end);
+        let line = self.line_of(start);
+        self.text.delete(start..end);
+        self.text.insert(start, text);
+        self.invalidate_from(line);
+    }
+
+    fn after_history(&mut self, step: &Step) -> (usize, usize) {
+        self.revision = self.revision.wrapping_add(1);
+        self.modified = !self.history.is_saved();
+        step.cursor
+    }
+
+    /// A number that changes with every edit, to tell whether something
+    /// changed the text.
+    #[must_use]
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+
+    /// The whole text of the buffer.
+    #[must_use]
+    pub fn text(&self) -> String {
+        self.text.text()
+    }
+
+    /// The name shown to the user for this buffer.
+    pub fn display_name(&self) -> String {
+        match (&self.file_name, &self.directory) {
+            (Some(path), _) => path.display().to_string(),
+            (None, Some(dir)) => format!("{}/", dir.display().to_string().trim_end_matches('/')),
+            (None, None) => "[No Name]".to_string(),
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
-        self.lines.is_empty()
+        self.text.is_empty()
+    }
+
+    /// Number of lines (a trailing line break does not start a new line).
+    pub fn line_count(&self) -> usize {
+        self.text.line_count()
+    }
+
+    /// The text of line `index`, without its line ending.
+    pub fn get(&self, index: usize) -> Option<Cow<'_, str>> {
+        self.text.line(index).map(|line| match line {
+            Cow::Borrowed(line) => Cow::Borrowed(line.strip_suffix('\r').unwrap_or(line)),
+            Cow::Owned(mut line) => {
+                if line.ends_with('\r') {
+                    line.pop();
+                }
+                Cow::Owned(line)
+            }
+        })
+    }
+
+    /// The layout of line `index` (graphemes, columns, tab stops), cached
+    /// until the line is edited.
+    pub fn layout(&self, index: usize) -> Rc<LineLayout> {
+        self.layouts.get_or_insert_with(index, || {
+            self.get(index)
+                .map(|line| LineLayout::with_tab_width(&line, self.settings.tab_width))
+                .unwrap_or_default()
+        })
+    }
+
+    /// The highlighted pieces of line `index`, as its filetype's language
+    /// colors them given the lines above.
+    pub fn highlight(&self, index: usize) -> Vec<Token> {
+        let language = self.filetype.language;
+        let state = self
+            .states
+            .state_before(index, language, |line| self.get(line).map(Cow::into_owned));
+        self.get(index)
+            .map(|line| language.highlight_line(&line, &state).0)
+            .unwrap_or_default()
+    }
+
+    pub fn settings(&self) -> Settings {
+        self.settings
+    }
+
+    /// Changes the settings, laying every line out again if the tab stops
+    /// moved.
+    pub fn set_settings(&mut self, settings: Settings) {
+        if settings.tab_width != self.settings.tab_width {
+            self.layouts.clear();
+        }
+        self.settings = settings;
+    }
+
+    /// Length of line `index` in graphemes (0 past the end of the buffer).
+    pub fn line_len(&self, index: usize) -> usize {
+        self.layout(index).len()
+    }
+
+    /// Byte offset of grapheme `col` of line `line`, clamped to the end of
+    /// the line (or of the text, past the last line).
+    fn offset_of(&self, line: usize, col: usize) -> usize {
+        let Some(range) = self.text.line_range(line) else {
+            return self.text.len();
+        };
+        range
+            .start
+            .saturating_add(self.layout(line).byte_offset(col))
+    }
+
+    /// The line the byte at `at` is in.
+    fn line_of(&self, at: usize) -> usize {
+        let (mut low, mut high) = (0, self.line_count());
+        while high.saturating_sub(low) > 1 {
+            let middle = low.saturating_add(high.saturating_sub(low) >> 1);
+            match self.text.line_start(middle) {
+                Some(start) if start <= at => low = middle,
+                _ => high = middle,
+            }
+        }
+        low
+    }
+
+    /// Replaces `range` of the text with `text`, recording it to be undone.
+    /// Every edit comes through here, so a read-only buffer is kept as it
+    /// is.
+    fn replace_at(&mut self, range: Range<usize>, text: &str) {
+        if self.read_only {
+            return;
+        }
+        let removed = self.text.slice(range.clone()).into_owned();
+        self.text.delete(range.clone());
+        self.text.insert(range.start, text);
+        self.history.record(Edit {
+            at: range.start,
+            removed,
+            inserted: text.to_string(),
+        });
+    }
+
+    /// Inserts `text` before grapheme `col` of line `line`.
+    pub fn insert(&mut self, line: usize, col: usize, text: &str) {
+        let at = self.offset_of(line, col);
+        self.replace_at(at..at, text);
+        if text.contains('\n') {
+            self.invalidate_from(line);
+        } else {
+            self.invalidate(line);
+        }
+        self.mark_modified();
+    }
+
+    /// Deletes the lines in `lines` (as far as they exist) along with their
+    /// line breaks. Returns `false` if there were none.
+    pub fn delete_lines(&mut self, lines: Range<usize>) -> bool {
+        let last = min(lines.end, self.line_count());
+        let Some(range) = self
+            .text
+            .line_range(lines.start)
+            .filter(|_| lines.start < last)
+        else {
+            return false;
+        };
+        let (start, end) = match (self.text.line_start(last), lines.start.checked_sub(1)) {
+            (Some(end), _) => (range.start, end),
+            // The last line has no line break of its own, so the one ending
+            // the line before goes instead.
+            (None, Some(previous)) => (
+                self.offset_of(previous, self.line_len(previous)),
+                self.text.len(),
+            ),
+            (None, None) => (range.start, self.text.len()),
+        };
+        self.replace_at(start..end, "");
+        self.invalidate_from(lines.start.saturating_sub(1));
+        self.mark_modified();
+        true
     }
 
-    pub fn get(&self, index: usize) -> Option<&str> {
-        self.lines.get(index).map(String::as_str)
+    /// Indents each non-blank line in `lines` by one level (a tab, or
+    /// spaces if the buffer is indented with spaces), or with `outdent`,
+    /// takes up to one level of indentation off each.
+    pub fn shift_lines(&mut self, lines: Range<usize>, outdent: bool) {
+        let (unit, width) = match self.settings.indent {
+            Indent::Tabs => ("\t".to_string(), self.settings.tab_width),
+            Indent::Spaces(size) => (" ".repeat(size), size),
+        };
+        for line in lines.start..min(lines.end, self.line_count()) {
+            let Some(text) = self.get(line) else {
+                continue;
+            };
+            if !outdent {
+                if !text.trim().is_empty() {
+                    drop(text);
+                    self.insert(line, 0, &unit);
+                }
+                continue;
+            }
+            let mut removed = 0_usize;
+            for ch in text.chars() {
+                match ch {
+                    ' ' if removed < width => removed = removed.saturating_add(1),
+                    '\t' => {
+                        removed = removed.saturating_add(1);
+                        break;
+                    }
+                    _ => break,
+                }
+            }
+            drop(text);
+            self.delete_range((line, 0), (line, removed));
+        }
+    }
+
+    /// Comments out each non-blank line in `lines`, putting `comment` and a
+    /// space after its indentation, or if they all are commented out
+    /// already, takes the comments off again.
+    pub fn toggle_comment(&mut self, lines: Range<usize>, comment: &str) {
+        let lines = lines.start..min(lines.end, self.line_count());
+        // Each non-blank line, with where its text starts and how much of
+        // it is a comment marker.
+        let commented: Vec<(usize, usize, Option<usize>)> = lines
+            .filter_map(|line| {
+                let text = self.get(line)?;
+                let body = text.trim_start_matches([' ', '\t']);
+                if body.is_empty() {
+                    return None;
+                }
+                let indent = text.len().saturating_sub(body.len());
+                let marker = body.strip_prefix(comment).map(|rest| {
+                    let space = usize::from(rest.starts_with(' '));
+                    comment.chars().count().saturating_add(space)
+                });
+                Some((line, indent, marker))
+            })
+            .collect();
+        let uncomment = commented.iter().all(|(_, _, marker)| marker.is_some());
+        for (line, indent, marker) in commented {
+            match marker {
+                Some(len) if uncomment => {
+                    self.delete_range((line, indent), (line, indent.saturating_add(len)));
+                }
+                _ => self.insert(line, indent, &format!("{comment} ")),
+            }
+        }
+    }
+
+    /// Lines up `delimiter` (the first of them, and each after it) in the
+    /// lines `lines`, padding what comes before each with spaces; lines
+    /// without it stay as they are. A delimiter that follows its text
+    /// directly on the first line, like a `,`, keeps doing so, with the
+    /// padding after it instead. Returns how many lines changed.
+    pub fn align(&mut self, lines: Range<usize>, delimiter: &str) -> usize {
+        let end = min(lines.end, self.line_count());
+        if delimiter.is_empty() || lines.start >= end {
+            return 0;
+        }
+        let last = end.saturating_sub(1);
+        let text = self.slice((lines.start, 0), (last, self.line_len(last)));
+        let rows: Vec<Vec<&str>> = text
+            .split('\n')
+            .map(|line| line.split(delimiter).map(str::trim).collect())
+            .collect();
+        // The first cell keeps its indentation, to line up across it.
+        let first_cells: Vec<&str> = text
+            .split('\n')
+            .map(|line| line.split(delimiter).next().unwrap_or_default().trim_end())
+            .collect();
+        let hugs = text
+            .split('\n')
+            .find_map(|line| line.split_once(delimiter))
+            .is_some_and(|(before, _)| !before.is_empty() && !before.ends_with([' ', '\t']));
+        let mut widths: Vec<usize> = Vec::new();
+        for (row, first) in rows.iter().zip(&first_cells) {
+            let cells = row.len().saturating_sub(1);
+            for (column, cell) in row.iter().take(cells).enumerate() {
+                let width = if column == 0 { first } else { cell }.chars().count();
+                match widths.get_mut(column) {
+                    Some(widest) => *widest = max(*widest, width),
+                    None => widths.push(width),
+                }
+            }
+        }
+        let mut changed = 0_usize;
+        let aligned: Vec<String> = text
+            .split('\n')
+            .zip(rows.iter().zip(&first_cells))
+            .map(|(line, (row, first))| {
+                if row.len() < 2 {
+                    return line.to_string();
+                }
+                let mut out = String::new();
+                for (column, (cell, width)) in row.iter().zip(&widths).enumerate() {
+                    let cell = if column == 0 { first } else { cell };
+                    let padding = " ".repeat(width.saturating_sub(cell.chars().count()));
+                    if hugs {
+                        out.push_str(cell);
+                        out.push_str(delimiter);
+                        out.push_str(&padding);
+                    } else {
+                        // An empty first column, as before a table's `|`,
+                        // takes no space.
+                        if *width > 0 {
+                            out.push_str(cell);
+                            out.push_str(&padding);
+                            out.push(' ');
+                        }
+                        out.push_str(delimiter);
+                    }
+                    out.push(' ');
+                }
+                out.push_str(row.last().copied().unwrap_or_default());
+                let out = out.trim_end().to_string();
+                if out != line {
+                    changed = changed.saturating_add(1);
+                }
+                out
+            })
+            .collect();
+        if changed > 0 {
+            self.delete_range((lines.start, 0), (last, self.line_len(last)));
+            self.insert(lines.start, 0, &aligned.join("\n"));
+        }
+        changed
+    }
+
+    /// Sorts the lines in `lines` as `options` says, keeping lines that
+    /// compare the same in the order they were in; with `unique`, only the
+    /// first of them. Returns how many lines were dropped.
+    pub fn sort_lines(&mut self, lines: Range<usize>, options: SortOptions) -> usize {
+        self.rearrange_lines(lines, |lines| {
+            lines.sort_by(|a, b| options.compare(a, b));
+            if options.unique {
+                lines.dedup_by(|a, b| options.compare(a, b) == Ordering::Equal);
+            }
+        })
+    }
+
+    /// Drops each line in `lines` that is the same as one before it there,
+    /// leaving the rest in their order. Returns how many lines were dropped.
+    pub fn dedupe_lines(&mut self, lines: Range<usize>) -> usize {
+        self.rearrange_lines(lines, |lines| {
+            let mut seen = HashSet::new();
+            lines.retain(|line| seen.insert(*line));
+        })
+    }
+
+    /// Replaces the lines in `lines` with what `rearrange` makes of them,
+    /// as one edit if anything changed. Returns how many fewer lines there
+    /// are.
+    fn rearrange_lines(
+        &mut self,
+        lines: Range<usize>,
+        rearrange: impl FnOnce(&mut Vec<&str>),
+    ) -> usize {
+        let end = min(lines.end, self.line_count());
+        if lines.start >= end {
+            return 0;
+        }
+        let last = end.saturating_sub(1);
+        let text = self.slice((lines.start, 0), (last, self.line_len(last)));
+        let ending = if text.contains("\r\n") { "\r\n" } else { "\n" };
+        let mut rearranged: Vec<&str> = text
+            .split('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .collect();
+        let count = rearranged.len();
+        rearrange(&mut rearranged);
+        let rearranged = rearranged.join(ending);
+        if rearranged != text {
+            self.delete_range((lines.start, 0), (last, self.line_len(last)));
+            self.insert(lines.start, 0, &rearranged);
+        }
+        count.saturating_sub(rearranged.split('\n').count())
+    }
+
+    /// Rewrites the indentation of every line the way the buffer is
+    /// indented: leading tabs become spaces, or runs of spaces as wide as a
+    /// tab stop become tabs. It is one edit, undone at once. Returns how
+    /// many lines changed.
+    pub fn retab(&mut self) -> usize {
+        let tab_width = self.settings.tab_width;
+        let mut changed = 0_usize;
+        let text = self.text.text();
+        let mut retabbed = String::with_capacity(text.len());
+        for line in text.split_inclusive('\n') {
+            let body = line.trim_start_matches([' ', '\t']);
+            let leading = &line[..line.len().saturating_sub(body.len())];
+            let width = leading.chars().fold(0_usize, |width, ch| match ch {
+                '\t' => width.saturating_add(
+                    tab_width.saturating_sub(width.checked_rem(tab_width).unwrap_or(0)),
+                ),
+                _ => width.saturating_add(1),
+            });
+            let indentation = match self.settings.indent {
+                Indent::Spaces(_) => " ".repeat(width),
+                Indent::Tabs => {
+                    let tabs = width.checked_div(tab_width).unwrap_or(0);
+                    let spaces = width.checked_rem(tab_width).unwrap_or(width);
+                    format!("{}{}", "\t".repeat(tabs), " ".repeat(spaces))
+                }
+            };
+            if indentation != leading {
+                changed = changed.saturating_add(1);
+            }
+            retabbed.push_str(&indentation);
+            retabbed.push_str(body);
+        }
+        self.edit_text(&retabbed);
+        changed
+    }
+
+    /// The text from grapheme `start.1` of line `start.0` up to, but not
+    /// including, `end`.
+    #[must_use]
+    pub fn slice(&self, start: (usize, usize), end: (usize, usize)) -> String {
+        let from = self.offset_of(start.0, start.1);
+        let to = self.offset_of(end.0, end.1).max(from);
+        self.text.slice(from..to).into_owned()
+    }
+
+    /// Deletes the text [`Buffer::slice`] would return.
+    pub fn delete_range(&mut self, start: (usize, usize), end: (usize, usize)) {
+        let from = self.offset_of(start.0, start.1);
+        let to = self.offset_of(end.0, end.1);
+        if to > from {
+            self.replace_at(from..to, "");
+            self.invalidate_from(start.0);
+            self.mark_modified();
+        }
+    }
+
+    /// Forgets what was cached about line `line`, after an edit within it.
+    fn invalidate(&mut self, line: usize) {
+        self.layouts.invalidate(line);
+        self.states.invalidate(line);
+    }
+
+    /// Forgets what was cached about line `line` and everything below it.
+    fn invalidate_from(&mut self, line: usize) {
+        self.layouts.invalidate_from(line);
+        self.states.invalidate_from(line);
+    }
+
+    fn mark_modified(&mut self) {
+        if self.read_only {
+            return;
+        }
+        self.modified = true;
+        self.revision = self.revision.wrapping_add(1);
+    }
+
+    /// Deletes the grapheme at `col` of line `line`; at the end of a line,
+    /// joins it with the next one instead. Returns `false` if there was
+    /// nothing to delete (at the end of the buffer).
+    pub fn delete(&mut self, line: usize, col: usize) -> bool {
+        let at = self.offset_of(line, col);
+        let end = if col < self.line_len(line) {
+            self.offset_of(line, col.saturating_add(1))
+        } else {
+            // Remove the whole line ending, "\r\n" included.
+            match self.text.line_start(line.saturating_add(1)) {
+                Some(next) if next > at => next,
+                _ => return false,
+            }
+        };
+        if end <= at {
+            return false;
+        }
+        self.replace_at(at..end, "");
+        self.invalidate_from(line);
+        self.mark_modified();
+        true
+    }
+}
+
+/// How many items `a` and `b` start with in common.
+fn common_prefix<T: PartialEq>(
+    a: impl IntoIterator<Item = T>,
+    b: impl IntoIterator<Item = T>,
+) -> usize {
+    a.into_iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::highlight::Scope;
+
+    fn buffer(text: &str) -> Buffer {
+        let mut buffer = Buffer::default();
+        buffer.replace_text(text);
+        buffer
+    }
+
+    fn lines(buffer: &Buffer) -> Vec<String> {
+        (0..buffer.line_count())
+            .filter_map(|index| buffer.get(index).map(Cow::into_owned))
+            .collect()
+    }
+
+    #[test]
+    fn test_append_complete_lines() {
+        let mut buffer = buffer("one\n");
+        buffer.append("two\nthree\n");
+        assert_eq!(lines(&buffer), vec!["one", "two", "three"]);
+        assert!(buffer.text.ends_with_newline());
+    }
+
+    #[test]
+    fn test_append_continues_partial_line() {
+        let mut buffer = buffer("par");
+        buffer.append("tial\nnext");
+        assert_eq!(lines(&buffer), vec!["partial", "next"]);
+        assert!(!buffer.text.ends_with_newline());
+    }
+
+    #[test]
+    fn test_append_to_empty_buffer() {
+        let mut buffer = Buffer::default();
+        buffer.append("first\r\n");
+        assert_eq!(lines(&buffer), vec!["first"]);
+    }
+
+    #[test]
+    fn test_replace_text() {
+        let mut buffer = buffer("old\nlines\n");
+        buffer.replace_text("new\n");
+        assert_eq!(lines(&buffer), vec!["new"]);
+    }
+
+    #[test]
+    fn test_insert_and_delete_mark_modified() {
+        let mut buffer = buffer("héllo\nworld\n");
+        buffer.insert(0, 2, "X");
+        assert_eq!(buffer.get(0).as_deref(), Some("héXllo"));
+        assert!(buffer.modified);
+
+        // Past the end of the line, inserts are clamped to the line end.
+        buffer.insert(1, 99, "!");
+        assert_eq!(buffer.get(1).as_deref(), Some("world!"));
+    }
+
+    #[test]
+    fn test_delete_at_line_end_joins_lines() {
+        let mut buffer = buffer("ab\ncd\n");
+        assert!(buffer.delete(0, 2));
+        assert_eq!(lines(&buffer), vec!["abcd"]);
+        assert!(buffer.delete(0, 0));
+        assert_eq!(lines(&buffer), vec!["bcd"]);
+        // Only the final line break is left after the last line.
+        assert!(buffer.delete(0, 3));
+        assert!(!buffer.delete(0, 3));
+    }
+
+    #[test]
+    fn test_delete_lines() {
+        let mut buffer = buffer("a\r\nb\r\nc");
+        assert!(buffer.delete_lines(0..1));
+        assert_eq!(buffer.text(), "b\r\nc");
+        // The last line takes the line break before it along.
+        assert!(buffer.delete_lines(1..2));
+        assert_eq!(buffer.text(), "b");
+        assert!(!buffer.delete_lines(1..2));
+        assert!(buffer.delete_lines(0..1));
+        assert!(buffer.is_empty());
+
+        buffer.replace_text("a\nb\nc\nd\n");
+        assert!(buffer.delete_lines(1..9));
+        assert_eq!(buffer.text(), "a\n");
+        assert_eq!(buffer.slice((0, 0), (0, 1)), "a");
+        buffer.replace_text("one\ntwo");
+        assert_eq!(buffer.slice((0, 1), (1, 1)), "ne\nt");
+        buffer.delete_range((0, 1), (1, 1));
+        assert_eq!(buffer.text(), "owo");
+    }
+
+    #[test]
+    fn test_shift_lines() {
+        let mut buffer = buffer("a\n\n\tb\n   c\n");
+        buffer.shift_lines(0..9, false);
+        assert_eq!(buffer.text(), "\ta\n\n\t\tb\n\t   c\n");
+        buffer.shift_lines(0..9, true);
+        buffer.shift_lines(2..4, true);
+        assert_eq!(buffer.text(), "a\n\nb\nc\n");
+
+        buffer.set_settings(Settings {
+            indent: Indent::Spaces(2),
+            ..Settings::default()
+        });
+        buffer.shift_lines(0..1, false);
+        buffer.shift_lines(0..1, false);
+        buffer.shift_lines(0..1, true);
+        assert_eq!(buffer.get(0).as_deref(), Some("  a"));
+    }
+
+    #[test]
+    fn test_align_lines_up_a_delimiter() {
+        let mut assignments = buffer("x = 1\nlonger = 2\n// note\n  y=3\n");
+        assert_eq!(assignments.align(0..4, "="), 2);
+        assert_eq!(
+            assignments.text(),
+            "x      = 1\nlonger = 2\n// note\n  y    = 3\n"
+        );
+        assert_eq!(assignments.align(0..4, "="), 0);
+
+        let mut table = buffer("| a | bb |\n|---|---|\n| ccc | d |\n");
+        table.align(0..3, "|");
+        assert_eq!(
+            table.text(),
+            "| a   | bb  |\n| --- | --- |\n| ccc | d   |\n"
+        );
+
+        let mut list = buffer("1, 22, 3\n444, 5, 6\n");
+        list.align(0..2, ",");
+        assert_eq!(list.text(), "1,   22, 3\n444, 5,  6\n");
+    }
+
+    /// `count` lines made of a few letters, by a fixed pseudo-random
+    /// sequence starting from `seed`, so there are repeats.
+    fn random_lines(seed: u64, count: usize) -> Vec<String> {
+        let mut state = seed.max(1);
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        (0..count)
+            .map(|_| {
+                let length = next() % 3;
+                (0..length)
+                    .map(|_| char::from(b"aAb1"[usize::try_from(next() % 4).unwrap()]))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sorted_lines_keep_their_order_and_first_occurrence() {
+        for seed in 1..200 {
+            let original = random_lines(seed, usize::try_from(seed % 12).unwrap() + 1);
+            let text = format!("{}\n", original.join("\n"));
+            for options in [
+                SortOptions::default(),
+                SortOptions {
+                    unique: true,
+                    ..SortOptions::default()
+                },
+                SortOptions {
+                    ignore_case: true,
+                    unique: true,
+                    ..SortOptions::default()
+                },
+            ] {
+                let mut sorted = buffer(&text);
+                let dropped = sorted.sort_lines(0..original.len(), options);
+                let sorted = lines(&sorted);
+                assert_eq!(sorted.len() + dropped, original.len());
+                assert!(sorted
+                    .windows(2)
+                    .all(|pair| options.compare(&pair[0], &pair[1]) == Ordering::Less
+                        || !options.unique
+                            && options.compare(&pair[0], &pair[1]) == Ordering::Equal));
+                // Each line kept is the first of those that compare the
+                // same to it, and every line has one kept.
+                for line in &original {
+                    let first = original
+                        .iter()
+                        .find(|other| options.compare(other, line) == Ordering::Equal)
+                        .unwrap();
+                    if options.unique {
+                        assert!(sorted.contains(first));
+                    } else {
+                        assert!(sorted.contains(line));
+                    }
+                }
+            }
+
+            let mut deduped = buffer(&text);
+            let dropped = deduped.dedupe_lines(0..original.len());
+            let mut expected: Vec<String> = Vec::new();
+            for line in &original {
+                if !expected.contains(line) {
+                    expected.push(line.clone());
+                }
+            }
+            assert_eq!(lines(&deduped), expected);
+            assert_eq!(dropped, original.len() - expected.len());
+        }
+    }
+
+    #[test]
+    fn test_sort_lines_by_number_within_a_range() {
+        let mut list = buffer("keep\r\nitem 10\r\nitem -2\r\nnone\r\nitem 3\r\nitem 3\r\n");
+        assert_eq!(
+            list.sort_lines(
+                1..6,
+                SortOptions {
+                    numeric: true,
+                    unique: true,
+                    ..SortOptions::default()
+                }
+            ),
+            1
+        );
+        assert_eq!(
+            list.text(),
+            "keep\r\nnone\r\nitem -2\r\nitem 3\r\nitem 10\r\n"
+        );
+        assert_eq!(list.dedupe_lines(0..5), 0);
+        assert_eq!(first_number("v99999999999999999999"), Some(i64::MAX));
+    }
+
+    #[test]
+    fn test_retab_converts_the_indentation_as_one_edit() {
+        let mut buffer = buffer("\tone\n  \ttwo\nthree\n      four\n");
+        buffer.set_settings(Settings {
+            indent: Indent::Spaces(4),
+            ..Settings::default()
+        });
+        assert_eq!(buffer.retab(), 2);
+        assert_eq!(buffer.text(), "    one\n    two\nthree\n      four\n");
+        buffer.set_settings(Settings::default());
+        assert_eq!(buffer.retab(), 3);
+        assert_eq!(buffer.text(), "\tone\n\ttwo\nthree\n\t  four\n");
+        assert_eq!(buffer.retab(), 0);
+        buffer.undo();
+        buffer.undo();
+        assert_eq!(buffer.text(), "\tone\n  \ttwo\nthree\n      four\n");
+    }
+
+    #[test]
+    fn test_toggle_comment() {
+        let mut buffer = buffer("a\n\n\t// b\n");
+        buffer.toggle_comment(0..9, "//");
+        assert_eq!(buffer.text(), "// a\n\n\t// // b\n");
+        buffer.toggle_comment(0..3, "//");
+        assert_eq!(buffer.text(), "a\n\n\t// b\n");
+        buffer.toggle_comment(2..3, "//");
+        assert_eq!(buffer.text(), "a\n\n\tb\n");
+    }
+
+    #[test]
+    fn test_edits_recolor_the_lines_below() {
+        let mut buffer = buffer("let a;\nlet b;\nlet c;\n");
+        buffer.filetype = filetype::detect(Some(Path::new("a.rs")), None);
+        let scopes = |buffer: &Buffer, index| {
+            buffer
+                .highlight(index)
+                .into_iter()
+                .map(|token| token.scope)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(scopes(&buffer, 2), [Scope::Keyword]);
+        buffer.insert(0, 0, "/* ");
+        assert_eq!(scopes(&buffer, 2), [Scope::Comment]);
+        buffer.insert(1, 6, " */");
+        assert_eq!(scopes(&buffer, 1), [Scope::Comment]);
+        assert_eq!(scopes(&buffer, 2), [Scope::Keyword]);
+        buffer.delete_range((0, 0), (0, 3));
+        assert_eq!(scopes(&buffer, 1), [Scope::Keyword]);
+    }
+
+    #[test]
+    fn test_insert_newline_splits_line() {
+        let mut buffer = buffer("abcd");
+        buffer.insert(0, 2, "\n");
+        assert_eq!(lines(&buffer), vec!["ab", "cd"]);
+        assert_eq!(buffer.line_len(1), 2);
+    }
+
+    #[test]
+    fn test_delete_removes_whole_grapheme_and_crlf() {
+        let mut buffer = buffer("e\u{301}x\r\nnext\r\n");
+        buffer.delete(0, 0);
+        assert_eq!(buffer.get(0).as_deref(), Some("x"));
+        buffer.delete(0, 1);
+        assert_eq!(lines(&buffer), vec!["xnext"]);
+    }
+
+    #[test]
+    fn test_changing_tab_width_relays_lines() {
+        let mut buffer = buffer("a\tb\n");
+        assert_eq!(buffer.layout(0).width(), 5);
+        buffer.set_settings(Settings {
+            tab_width: 8,
+            ..Settings::default()
+        });
+        assert_eq!(buffer.layout(0).width(), 9);
+    }
+
+    #[test]
+    fn test_format_for_save() {
+        let settings = Settings {
+            end_of_line: Some(LineEnding::Crlf),
+            trim_trailing_whitespace: true,
+            insert_final_newline: true,
+            ..Settings::default()
+        };
+        assert_eq!(settings.format("a  \nb\t\r\nc"), "a\r\nb\r\nc\r\n");
+        assert_eq!(settings.format(""), "");
+
+        let keep = Settings {
+            insert_final_newline: true,
+            ..Settings::default()
+        };
+        assert_eq!(keep.format("a \r\nb"), "a \r\nb\r\n");
+        assert_eq!(Settings::default().format("a \r\nb"), "a \r\nb");
+    }
+
+    #[test]
+    fn test_save_applies_the_settings() {
+        let path = std::env::temp_dir().join(format!("hecto-buffer-format-{}", std::process::id()));
+        let mut buffer = Buffer::for_file(&path);
+        buffer.replace_text("trailing   \nno newline");
+        buffer.set_settings(Settings {
+            trim_trailing_whitespace: true,
+            insert_final_newline: true,
+            ..Settings::default()
+        });
+        buffer.save().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "trailing\nno newline\n"
+        );
+        assert_eq!(buffer.get(0).as_deref(), Some("trailing"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_layout_is_cached_until_the_line_changes() {
+        let mut buffer = buffer("one\ntwo\n");
+        let first = buffer.layout(0);
+        let second = buffer.layout(1);
+        assert!(Rc::ptr_eq(&first, &buffer.layout(0)));
+
+        buffer.insert(1, 0, "x");
+        assert!(Rc::ptr_eq(&first, &buffer.layout(0)));
+        assert!(!Rc::ptr_eq(&second, &buffer.layout(1)));
+        assert_eq!(buffer.line_len(1), 4);
+
+        buffer.insert(0, 0, "\n");
+        assert_eq!(buffer.line_len(0), 0);
+        assert_eq!(buffer.line_len(1), 3);
     }
 }
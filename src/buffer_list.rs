@@ -0,0 +1,114 @@
+//! # Buffer List
+//!
+//! The files open at once. The buffer being edited lives in the
+//! [`View`](crate::View); the others wait here, each with where its cursor
+//! was, so switching back to one picks up where editing left off. Every
+//! buffer keeps the number it was given when opened for as long as it is
+//! open; the one the editor starts with is number 0.
+
+use std::path::Path;
+
+use crate::{buffer::Buffer, operator::Pos};
+
+/// The number a buffer is known by while it is open.
+pub type BufferId = usize;
+
+/// A buffer that is open but not being edited.
+#[derive(Debug)]
+pub struct Parked {
+    pub id: BufferId,
+    pub buffer: Buffer,
+    /// Where the cursor was in it.
+    pub cursor: Pos,
+    /// The first line that was on screen.
+    pub scroll_offset: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct BufferList {
+    /// The parked buffers, in the order they were opened.
+    parked: Vec<Parked>,
+    /// The last number given out.
+    last_id: BufferId,
+}
+
+impl BufferList {
+    /// A number no buffer had before, for a buffer being opened.
+    pub fn new_id(&mut self) -> BufferId {
+        self.last_id = self.last_id.saturating_add(1);
+        self.last_id
+    }
+
+    /// Keeps `parked` until it is taken back.
+    pub fn park(&mut self, parked: Parked) {
+        let index = self.parked.partition_point(|other| other.id < parked.id);
+        self.parked.insert(index, parked);
+    }
+
+    /// Takes back the buffer numbered `id`, if it is parked.
+    pub fn take(&mut self, id: BufferId) -> Option<Parked> {
+        let index = self.parked.iter().position(|parked| parked.id == id)?;
+        Some(self.parked.remove(index))
+    }
+
+    /// The parked buffer numbered `id`.
+    #[must_use]
+    pub fn get(&self, id: BufferId) -> Option<&Parked> {
+        self.parked.iter().find(|parked| parked.id == id)
+    }
+
+    /// The parked buffers, in the order they were opened.
+    pub fn iter(&self) -> impl Iterator<Item = &Parked> {
+        self.parked.iter()
+    }
+
+    /// The parked buffers, to change.
+    pub fn buffers_mut(&mut self) -> impl Iterator<Item = &mut Buffer> {
+        self.iter_mut().map(|parked| &mut parked.buffer)
+    }
+
+    /// The parked buffers with their cursors, to change.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Parked> {
+        self.parked.iter_mut()
+    }
+
+    /// The number of the parked buffer for the file at `path`, if any.
+    #[must_use]
+    pub fn find(&self, path: &Path) -> Option<BufferId> {
+        self.parked
+            .iter()
+            .find(|parked| parked.buffer.file_name.as_deref() == Some(path))
+            .map(|parked| parked.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parked(id: BufferId, name: &str) -> Parked {
+        Parked {
+            id,
+            buffer: Buffer::for_file(Path::new(name)),
+            cursor: (0, 0),
+            scroll_offset: 0,
+        }
+    }
+
+    #[test]
+    fn test_parked_buffers_stay_in_opening_order() {
+        let mut list = BufferList::default();
+        let (first, second) = (list.new_id(), list.new_id());
+        assert_eq!((first, second), (1, 2));
+        list.park(parked(second, "b.txt"));
+        list.park(parked(0, "scratch"));
+        list.park(parked(first, "a.txt"));
+        let ids: Vec<BufferId> = list.iter().map(|parked| parked.id).collect();
+        assert_eq!(ids, vec![0, 1, 2]);
+
+        assert_eq!(list.find(Path::new("a.txt")), Some(first));
+        assert_eq!(list.take(first).map(|parked| parked.id), Some(first));
+        assert_eq!(list.take(first).map(|parked| parked.id), None);
+        assert_eq!(list.find(Path::new("a.txt")), None);
+    }
+}
@@ -0,0 +1,233 @@
+//! # Calculator
+//!
+//! Works out integer expressions, as `calculate` does with the selected
+//! text (or `calc EXPRESSION` on the command line), for bitmask math and
+//! the like while editing code. Numbers are decimal, or hexadecimal
+//! (`0xff`), binary (`0b1010`) or octal (`0o17`), with `_` between digits
+//! if wanted; the answer is written the way the first number was. The
+//! operators are C's, loosest first: `|`, `^`, `&`, `<<` and `>>`, `+`
+//! and `-`, `*`, `/` and `%`, then the unary `-`, `+` and `~`, with
+//! parentheses to group. Numbers are 64-bit and signed; an answer that
+//! does not fit, or a division by zero, is an error.
+
+/// How a number is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Radix {
+    Binary,
+    Octal,
+    Decimal,
+    Hexadecimal,
+}
+
+impl Radix {
+    fn base(self) -> u32 {
+        match self {
+            Self::Binary => 2,
+            Self::Octal => 8,
+            Self::Decimal => 10,
+            Self::Hexadecimal => 16,
+        }
+    }
+}
+
+/// The value of `expression`, written the way its first number is.
+///
+/// # Errors
+///
+/// Fails with what is wrong if `expression` is not one, or its value does
+/// not fit in 64 bits.
+pub fn calculate(expression: &str) -> Result<String, String> {
+    let mut parser = Parser {
+        text: expression,
+        at: 0,
+        radix: None,
+    };
+    let value = parser.expression(0)?;
+    parser.skip_whitespace();
+    if let Some(ch) = parser.peek() {
+        return Err(format!("Unexpected `{ch}` in the expression."));
+    }
+    let magnitude = value.unsigned_abs();
+    let sign = if value < 0 { "-" } else { "" };
+    Ok(match parser.radix.unwrap_or(Radix::Decimal) {
+        Radix::Binary => format!("{sign}0b{magnitude:b}"),
+        Radix::Octal => format!("{sign}0o{magnitude:o}"),
+        Radix::Decimal => value.to_string(),
+        Radix::Hexadecimal => format!("{sign}0x{magnitude:x}"),
+    })
+}
+
+/// The binary operators, each with how tightly it binds.
+const OPERATORS: &[(&str, u8)] = &[
+    ("|", 1),
+    ("^", 2),
+    ("&", 3),
+    ("<<", 4),
+    (">>", 4),
+    ("+", 5),
+    ("-", 5),
+    ("*", 6),
+    ("/", 6),
+    ("%", 6),
+];
+
+struct Parser<'a> {
+    text: &'a str,
+    /// The byte of `text` next to read.
+    at: usize,
+    /// How the first number was written.
+    radix: Option<Radix>,
+}
+
+impl Parser<'_> {
+    /// The expression at `at`, made of operators binding tighter than
+    /// `tighter_than`.
+    fn expression(&mut self, tighter_than: u8) -> Result<i64, String> {
+        let mut value = self.unary()?;
+        loop {
+            self.skip_whitespace();
+            let rest = self.text.get(self.at..).unwrap_or_default();
+            let Some(&(operator, precedence)) = OPERATORS.iter().find(|(operator, precedence)| {
+                *precedence > tighter_than && rest.starts_with(operator)
+            }) else {
+                return Ok(value);
+            };
+            self.at = self.at.saturating_add(operator.len());
+            let right = self.expression(precedence)?;
+            value = apply(operator, value, right)?;
+        }
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('-') => {
+                self.at = self.at.saturating_add(1);
+                self.unary()?.checked_neg().ok_or_else(overflow)
+            }
+            Some('+') => {
+                self.at = self.at.saturating_add(1);
+                self.unary()
+            }
+            Some('~') => {
+                self.at = self.at.saturating_add(1);
+                Ok(!self.unary()?)
+            }
+            Some('(') => {
+                self.at = self.at.saturating_add(1);
+                let value = self.expression(0)?;
+                self.skip_whitespace();
+                if self.peek() != Some(')') {
+                    return Err("Missing `)` in the expression.".to_string());
+                }
+                self.at = self.at.saturating_add(1);
+                Ok(value)
+            }
+            Some(ch) if ch.is_ascii_digit() => self.number(),
+            Some(ch) => Err(format!("Unexpected `{ch}` in the expression.")),
+            None => Err("The expression ends too soon.".to_string()),
+        }
+    }
+
+    fn number(&mut self) -> Result<i64, String> {
+        let rest = self.text.get(self.at..).unwrap_or_default();
+        let (radix, digits) = match rest.get(..2).map(str::to_ascii_lowercase).as_deref() {
+            Some("0x") => (Radix::Hexadecimal, &rest[2..]),
+            Some("0b") => (Radix::Binary, &rest[2..]),
+            Some("0o") => (Radix::Octal, &rest[2..]),
+            _ => (Radix::Decimal, rest),
+        };
+        let length = digits
+            .find(|ch: char| !ch.is_ascii_alphanumeric() && ch != '_')
+            .unwrap_or(digits.len());
+        let written = &rest[..rest
+            .len()
+            .saturating_sub(digits.len())
+            .saturating_add(length)];
+        let number: String = digits[..length].chars().filter(|&ch| ch != '_').collect();
+        let value = u64::from_str_radix(&number, radix.base())
+            .ok()
+            .and_then(|value| i64::try_from(value).ok())
+            .ok_or_else(|| format!("Not a number: {written}"))?;
+        self.at = self.at.saturating_add(written.len());
+        self.radix.get_or_insert(radix);
+        Ok(value)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text.get(self.at..)?.chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(ch) = self.peek().filter(|ch| ch.is_whitespace()) {
+            self.at = self.at.saturating_add(ch.len_utf8());
+        }
+    }
+}
+
+/// `left operator right`.
+fn apply(operator: &str, left: i64, right: i64) -> Result<i64, String> {
+    let shift = || u32::try_from(right).ok().filter(|&shift| shift < i64::BITS);
+    let value = match operator {
+        "|" => Some(left | right),
+        "^" => Some(left ^ right),
+        "&" => Some(left & right),
+        "<<" => shift().and_then(|shift| left.checked_shl(shift)),
+        ">>" => shift().and_then(|shift| left.checked_shr(shift)),
+        "+" => left.checked_add(right),
+        "-" => left.checked_sub(right),
+        "*" => left.checked_mul(right),
+        "/" | "%" if right == 0 => return Err("Division by zero.".to_string()),
+        "/" => left.checked_div(right),
+        "%" => left.checked_rem(right),
+        _ => None,
+    };
+    value.ok_or_else(overflow)
+}
+
+fn overflow() -> String {
+    "The answer does not fit in 64 bits.".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expressions_are_worked_out() {
+        assert_eq!(calculate("1 + 2 * 3").as_deref(), Ok("7"));
+        assert_eq!(calculate("(1 + 2) * 3 - 10").as_deref(), Ok("-1"));
+        assert_eq!(calculate("7 / 2 + 7 % 2").as_deref(), Ok("4"));
+        assert_eq!(calculate("-2 - -3").as_deref(), Ok("1"));
+        assert_eq!(calculate("10 - 4 - 3").as_deref(), Ok("3"));
+        assert_eq!(calculate("0xff & ~0x0f | 1 << 2").as_deref(), Ok("0xf4"));
+        assert_eq!(calculate("0b1010 ^ 0b0110").as_deref(), Ok("0b1100"));
+        assert_eq!(calculate("0o17 + 1").as_deref(), Ok("0o20"));
+        assert_eq!(calculate("1_000 * 0x10").as_deref(), Ok("16000"));
+        assert_eq!(calculate("0x10 - 0x20").as_deref(), Ok("-0x10"));
+        assert_eq!(calculate("1\u{a0}+\u{3000}2").as_deref(), Ok("3"));
+    }
+
+    #[test]
+    fn test_bad_expressions_are_errors() {
+        assert_eq!(calculate("1 / 0").unwrap_err(), "Division by zero.");
+        assert_eq!(
+            calculate("(1 + 2").unwrap_err(),
+            "Missing `)` in the expression."
+        );
+        assert_eq!(
+            calculate("1 + x").unwrap_err(),
+            "Unexpected `x` in the expression."
+        );
+        assert_eq!(calculate("0xfg").unwrap_err(), "Not a number: 0xfg");
+        assert_eq!(
+            calculate("2 *").unwrap_err(),
+            "The expression ends too soon."
+        );
+        assert_eq!(
+            calculate("0x7fffffffffffffff + 1").unwrap_err(),
+            "The answer does not fit in 64 bits."
+        );
+        assert!(calculate("1 << 64").is_err());
+    }
+}
@@ -0,0 +1,321 @@
+//! # Terminal Capabilities
+//!
+//! Works out which optional terminal features we may use. Querying the
+//! terminal directly would mean racing its reply against user input, so we
+//! rely on the same environment heuristics most TUI programs use
+//! (`TERM`, `TERM_PROGRAM`, ...), with an explicit override for each feature.
+
+use std::env;
+
+use crossterm::style::{Color, ContentStyle};
+
+/// Terminals known to implement synchronized output (DEC mode 2026).
+const SYNC_TERM_PROGRAMS: &[&str] = &["WezTerm", "iTerm.app", "vscode", "contour", "ghostty"];
+const SYNC_TERMS: &[&str] = &[
+    "kitty",
+    "foot",
+    "alacritty",
+    "wezterm",
+    "contour",
+    "ghostty",
+];
+
+/// Environment variable forcing synchronized output on (`1`) or off (`0`).
+const SYNC_OVERRIDE: &str = "HECTO_SYNC_OUTPUT";
+
+/// Terminals known to implement the kitty keyboard protocol, which tells
+/// apart keys legacy encodings merge (`Ctrl+I` and `Tab`, `Shift+Enter` and
+/// `Enter`, ...).
+const KEYBOARD_TERM_PROGRAMS: &[&str] = &["WezTerm", "ghostty"];
+const KEYBOARD_TERMS: &[&str] = &["kitty", "foot", "wezterm", "ghostty"];
+
+/// Environment variable forcing the keyboard protocol on (`1`) or off (`0`).
+const KEYBOARD_OVERRIDE: &str = "HECTO_KEYBOARD_PROTOCOL";
+
+/// Terminals known to render 24-bit colors even without `COLORTERM`.
+const TRUE_COLOR_TERM_PROGRAMS: &[&str] = &["iTerm.app", "WezTerm", "vscode", "ghostty"];
+
+/// Environment variable forcing the color depth: `truecolor`, `256` or `16`.
+const COLORS_OVERRIDE: &str = "HECTO_COLORS";
+
+/// The 16 basic colors, with the RGB values xterm uses for them.
+const BASIC_COLORS: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::DarkRed, (205, 0, 0)),
+    (Color::DarkGreen, (0, 205, 0)),
+    (Color::DarkYellow, (205, 205, 0)),
+    (Color::DarkBlue, (0, 0, 238)),
+    (Color::DarkMagenta, (205, 0, 205)),
+    (Color::DarkCyan, (0, 205, 205)),
+    (Color::Grey, (229, 229, 229)),
+    (Color::DarkGrey, (127, 127, 127)),
+    (Color::Red, (255, 0, 0)),
+    (Color::Green, (0, 255, 0)),
+    (Color::Yellow, (255, 255, 0)),
+    (Color::Blue, (92, 92, 255)),
+    (Color::Magenta, (255, 0, 255)),
+    (Color::Cyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// Intensities of the six steps of the 6×6×6 color cube (indices 16–231).
+const CUBE_STEPS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// How many colors the terminal can show.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSupport {
+    /// The 16 basic colors.
+    #[default]
+    Basic,
+    /// The xterm 256-color palette.
+    Ansi256,
+    /// 24-bit RGB.
+    TrueColor,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Frames can be wrapped in Begin/End Synchronized Update sequences, so
+    /// the terminal shows them atomically.
+    pub synchronized_output: bool,
+    /// The color depth; richer colors are converted down to it.
+    pub colors: ColorSupport,
+    /// Keys can be reported unambiguously with the kitty keyboard protocol.
+    pub keyboard_enhancement: bool,
+}
+
+impl Capabilities {
+    /// Detects the capabilities of the terminal we are running in.
+    #[must_use]
+    pub fn detect() -> Self {
+        Self::from_env(|name| env::var(name).ok())
+    }
+
+    /// Detects capabilities from an arbitrary variable lookup (so tests don't
+    /// have to touch the real environment).
+    pub fn from_env(var: impl Fn(&str) -> Option<String>) -> Self {
+        Self {
+            synchronized_output: detect_synchronized_output(&var),
+            colors: detect_colors(&var),
+            keyboard_enhancement: detect_keyboard_enhancement(&var),
+        }
+    }
+}
+
+impl ColorSupport {
+    /// Replaces `color` by the closest one the terminal can show.
+    #[must_use]
+    pub fn adapt(self, color: Color) -> Color {
+        match (self, color) {
+            (Self::Ansi256, Color::Rgb { r, g, b }) => Color::AnsiValue(nearest_ansi256((r, g, b))),
+            (Self::Basic, Color::Rgb { r, g, b }) => nearest_basic((r, g, b)),
+            (Self::Basic, Color::AnsiValue(index)) => nearest_basic(ansi256_rgb(index)),
+            _ => color,
+        }
+    }
+
+    /// Adapts both colors of `style`.
+    #[must_use]
+    pub fn adapt_style(self, style: ContentStyle) -> ContentStyle {
+        ContentStyle {
+            foreground_color: style.foreground_color.map(|color| self.adapt(color)),
+            background_color: style.background_color.map(|color| self.adapt(color)),
+            underline_color: style.underline_color.map(|color| self.adapt(color)),
+            ..style
+        }
+    }
+}
+
+fn detect_colors(var: &impl Fn(&str) -> Option<String>) -> ColorSupport {
+    match var(COLORS_OVERRIDE).as_deref() {
+        Some("truecolor" | "24bit") => return ColorSupport::TrueColor,
+        Some("256") => return ColorSupport::Ansi256,
+        Some("16") => return ColorSupport::Basic,
+        _ => {}
+    }
+    let colorterm = var("COLORTERM").unwrap_or_default();
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        return ColorSupport::TrueColor;
+    }
+    if var("TERM_PROGRAM")
+        .is_some_and(|program| TRUE_COLOR_TERM_PROGRAMS.contains(&program.as_str()))
+    {
+        return ColorSupport::TrueColor;
+    }
+    let term = var("TERM").unwrap_or_default();
+    if term.contains("direct") {
+        ColorSupport::TrueColor
+    } else if term.contains("256color") {
+        ColorSupport::Ansi256
+    } else {
+        ColorSupport::Basic
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    [(r1, r2), (g1, g2), (b1, b2)]
+        .into_iter()
+        .map(|(a, b)| u32::from(a.abs_diff(b)).pow(2))
+        .sum()
+}
+
+/// The RGB value of entry `index` of the xterm 256-color palette.
+fn ansi256_rgb(index: u8) -> (u8, u8, u8) {
+    if let Some(&(_, rgb)) = BASIC_COLORS.get(usize::from(index)) {
+        return rgb;
+    }
+    if let Some(gray) = index.checked_sub(232) {
+        let level = gray.saturating_mul(10).saturating_add(8);
+        return (level, level, level);
+    }
+    let cube = index.saturating_sub(16);
+    let step = |divisor: u8| {
+        let at = cube
+            .checked_div(divisor)
+            .unwrap_or(0)
+            .checked_rem(6)
+            .unwrap_or(0);
+        CUBE_STEPS.get(usize::from(at)).copied().unwrap_or(0)
+    };
+    (step(36), step(6), step(1))
+}
+
+/// The palette entry closest to `rgb`, looking at the color cube and the
+/// gray ramp (the first 16 entries vary between terminals).
+fn nearest_ansi256(rgb: (u8, u8, u8)) -> u8 {
+    (16..=255)
+        .min_by_key(|&index| distance(rgb, ansi256_rgb(index)))
+        .unwrap_or(16)
+}
+
+fn nearest_basic(rgb: (u8, u8, u8)) -> Color {
+    BASIC_COLORS
+        .iter()
+        .min_by_key(|(_, basic)| distance(rgb, *basic))
+        .map_or(Color::White, |&(color, _)| color)
+}
+
+fn detect_synchronized_output(var: &impl Fn(&str) -> Option<String>) -> bool {
+    detect_feature(var, SYNC_OVERRIDE, SYNC_TERM_PROGRAMS, SYNC_TERMS)
+}
+
+fn detect_keyboard_enhancement(var: &impl Fn(&str) -> Option<String>) -> bool {
+    detect_feature(
+        var,
+        KEYBOARD_OVERRIDE,
+        KEYBOARD_TERM_PROGRAMS,
+        KEYBOARD_TERMS,
+    )
+}
+
+/// Checks the `override_var` first, then whether `TERM_PROGRAM` is one of
+/// `programs` or `TERM` mentions one of `terms`.
+fn detect_feature(
+    var: &impl Fn(&str) -> Option<String>,
+    override_var: &str,
+    programs: &[&str],
+    terms: &[&str],
+) -> bool {
+    match var(override_var).as_deref() {
+        Some("1") => return true,
+        Some("0") => return false,
+        _ => {}
+    }
+    let program_supported =
+        var("TERM_PROGRAM").is_some_and(|program| programs.contains(&program.as_str()));
+    let term_supported = var("TERM").is_some_and(|term| {
+        terms
+            .iter()
+            .any(|supported| term.to_ascii_lowercase().contains(supported))
+    });
+    program_supported || term_supported
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn capabilities(vars: &[(&str, &str)]) -> Capabilities {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect();
+        Capabilities::from_env(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_unknown_terminal_has_no_synchronized_output() {
+        assert!(!capabilities(&[("TERM", "xterm-256color")]).synchronized_output);
+        assert!(!capabilities(&[]).synchronized_output);
+    }
+
+    #[test]
+    fn test_known_terminals_support_synchronized_output() {
+        assert!(capabilities(&[("TERM", "xterm-kitty")]).synchronized_output);
+        assert!(capabilities(&[("TERM_PROGRAM", "WezTerm")]).synchronized_output);
+    }
+
+    #[test]
+    fn test_color_detection() {
+        assert_eq!(capabilities(&[]).colors, ColorSupport::Basic);
+        assert_eq!(
+            capabilities(&[("TERM", "xterm-256color")]).colors,
+            ColorSupport::Ansi256
+        );
+        assert_eq!(
+            capabilities(&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor")]).colors,
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            capabilities(&[("COLORTERM", "truecolor"), (COLORS_OVERRIDE, "16")]).colors,
+            ColorSupport::Basic
+        );
+    }
+
+    #[test]
+    fn test_rgb_is_converted_to_the_nearest_palette_color() {
+        let orange = Color::Rgb {
+            r: 255,
+            g: 135,
+            b: 0,
+        };
+        assert_eq!(ColorSupport::TrueColor.adapt(orange), orange);
+        // 16 + 36 * 5 + 6 * 2 + 0
+        assert_eq!(ColorSupport::Ansi256.adapt(orange), Color::AnsiValue(208));
+        assert_eq!(ColorSupport::Basic.adapt(orange), Color::DarkYellow);
+
+        let gray = Color::Rgb {
+            r: 100,
+            g: 100,
+            b: 100,
+        };
+        // Gray ramp entry 241 is (98, 98, 98).
+        assert_eq!(ColorSupport::Ansi256.adapt(gray), Color::AnsiValue(241));
+    }
+
+    #[test]
+    fn test_palette_colors_fall_back_to_basic_colors() {
+        assert_eq!(ColorSupport::Basic.adapt(Color::AnsiValue(196)), Color::Red);
+        assert_eq!(
+            ColorSupport::Basic.adapt(Color::AnsiValue(4)),
+            Color::DarkBlue
+        );
+        assert_eq!(ColorSupport::Basic.adapt(Color::Blue), Color::Blue);
+    }
+
+    #[test]
+    fn test_keyboard_enhancement_detection() {
+        assert!(capabilities(&[("TERM", "xterm-kitty")]).keyboard_enhancement);
+        assert!(!capabilities(&[("TERM", "xterm-256color")]).keyboard_enhancement);
+        assert!(!capabilities(&[("TERM", "foot"), (KEYBOARD_OVERRIDE, "0")]).keyboard_enhancement);
+    }
+
+    #[test]
+    fn test_override_wins() {
+        assert!(
+            !capabilities(&[("TERM", "xterm-kitty"), (SYNC_OVERRIDE, "0")]).synchronized_output
+        );
+        assert!(capabilities(&[("TERM", "dumb"), (SYNC_OVERRIDE, "1")]).synchronized_output);
+    }
+}
@@ -0,0 +1,199 @@
+//! # System Clipboard
+//!
+//! Moves text between hecto and other programs through the clipboard tool
+//! the platform has: `wl-copy`/`wl-paste` under Wayland, `xclip` or `xsel`
+//! under X11, `pbcopy`/`pbpaste` on macOS, `clip.exe` and PowerShell on
+//! Windows (and WSL). The first one found on the `PATH` is used.
+//!
+//! Without one (over SSH, say), the editor falls back to copying through
+//! the terminal with OSC 52 when the `osc52` option is on; pasting then
+//! goes through the terminal too, as a bracketed paste (`Ctrl+Shift+V` in
+//! most terminals).
+
+use std::{
+    env,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::error::{Error, Result};
+
+/// A clipboard tool: the commands that copy from standard input and paste
+/// to standard output, and the environment variable that must be set for
+/// it to work (the display it talks to), if any.
+struct Tool {
+    copy: &'static [&'static str],
+    paste: &'static [&'static str],
+    needs: Option<&'static str>,
+}
+
+/// The tools tried, in order.
+const TOOLS: &[Tool] = &[
+    Tool {
+        copy: &["wl-copy"],
+        paste: &["wl-paste", "--no-newline"],
+        needs: Some("WAYLAND_DISPLAY"),
+    },
+    Tool {
+        copy: &["xclip", "-selection", "clipboard"],
+        paste: &["xclip", "-selection", "clipboard", "-o"],
+        needs: Some("DISPLAY"),
+    },
+    Tool {
+        copy: &["xsel", "--clipboard", "--input"],
+        paste: &["xsel", "--clipboard", "--output"],
+        needs: Some("DISPLAY"),
+    },
+    Tool {
+        copy: &["pbcopy"],
+        paste: &["pbpaste"],
+        needs: None,
+    },
+    Tool {
+        copy: &["clip.exe"],
+        paste: &["powershell.exe", "-NoProfile", "-Command", "Get-Clipboard"],
+        needs: None,
+    },
+];
+
+/// The system clipboard, reached through a clipboard tool. The default has
+/// no tool, and copies and pastes nothing.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Clipboard {
+    copy: Vec<String>,
+    paste: Vec<String>,
+}
+
+impl Clipboard {
+    /// The clipboard through the first tool that is installed and can work
+    /// here.
+    #[must_use]
+    pub fn detect() -> Self {
+        TOOLS
+            .iter()
+            .find(|tool| {
+                tool.needs.is_none_or(|name| env::var_os(name).is_some())
+                    && on_path(tool.copy[0])
+                    && on_path(tool.paste[0])
+            })
+            .map(|tool| Self::new(tool.copy, tool.paste))
+            .unwrap_or_default()
+    }
+
+    /// The clipboard through the commands `copy` (which reads the text from
+    /// standard input) and `paste` (which writes it to standard output).
+    #[must_use]
+    pub fn new(copy: &[&str], paste: &[&str]) -> Self {
+        let owned = |args: &[&str]| args.iter().map(ToString::to_string).collect();
+        Self {
+            copy: owned(copy),
+            paste: owned(paste),
+        }
+    }
+
+    /// Whether there is a tool to reach the clipboard with.
+    #[must_use]
+    pub fn is_available(&self) -> bool {
+        !self.copy.is_empty()
+    }
+
+    /// Puts `text` on the clipboard. Returns `false` if there is no tool.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the tool cannot be run or reports a failure.
+    pub fn copy(&self, text: &str) -> Result<bool> {
+        let Some((program, args)) = self.copy.split_first() else {
+            return Ok(false);
+        };
+        // Tools like `xclip` stay around to serve the text; their output
+        // must not reach the screen.
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        if child.wait()?.success() {
+            Ok(true)
+        } else {
+            Err(failed(program))
+        }
+    }
+
+    /// The text on the clipboard, or `None` if there is no tool.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the tool cannot be run or reports a failure.
+    pub fn paste(&self) -> Result<Option<String>> {
+        let Some((program, args)) = self.paste.split_first() else {
+            return Ok(None);
+        };
+        let output = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            return Err(failed(program));
+        }
+        Ok(Some(normalize_line_breaks(&String::from_utf8_lossy(
+            &output.stdout,
+        ))))
+    }
+}
+
+/// Turns `\r\n` and lone `\r` (as terminals send pasted line breaks, and
+/// Windows tools write them) into `\n`.
+#[must_use]
+pub fn normalize_line_breaks(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+fn failed(program: &str) -> Error {
+    Error::Io(std::io::Error::other(format!("{program} failed")))
+}
+
+/// Whether `program` is a file in one of the `PATH` directories.
+fn on_path(program: &str) -> bool {
+    env::var_os("PATH").is_some_and(|paths| {
+        env::split_paths(&paths).any(|dir| Path::new(&dir).join(program).is_file())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_and_paste_through_commands() {
+        let file = env::temp_dir().join(format!("hecto-clipboard-{}", std::process::id()));
+        let path = file.display().to_string();
+        let copy = format!("cat > {path}");
+        let paste = format!("printf 'one\\r\\ntwo' | cat - {path}");
+        let clipboard = Clipboard::new(&["sh", "-c", &copy], &["sh", "-c", &paste]);
+        assert!(clipboard.copy("\nthree").unwrap());
+        assert_eq!(
+            clipboard.paste().unwrap().as_deref(),
+            Some("one\ntwo\nthree")
+        );
+        std::fs::remove_file(file).unwrap();
+
+        let failing = Clipboard::new(&["false"], &["false"]);
+        assert_eq!(failing.copy("x").unwrap_err().to_string(), "false failed");
+        assert!(failing.paste().is_err());
+    }
+
+    #[test]
+    fn test_without_a_tool_nothing_happens() {
+        let clipboard = Clipboard::default();
+        assert!(!clipboard.is_available());
+        assert!(!clipboard.copy("text").unwrap());
+        assert_eq!(clipboard.paste().unwrap(), None);
+    }
+}
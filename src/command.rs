@@ -0,0 +1,581 @@
+//! # Editor Commands
+//!
+//! Everything the user can ask the editor to do, independent of the key
+//! that asks for it. Key events are first translated into an
+//! [`EditorCommand`] (see [`keymap`](crate::keymap)), which
+//! [`Editor::execute`](crate::Editor::execute) then carries out, so the two
+//! steps can be tested, and keys rebound, separately.
+
+use crate::operator::Operator;
+
+/// A cursor movement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Movement {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// To the start of the line.
+    Home,
+    /// To the end of the line.
+    End,
+    /// To the top of the screen.
+    PageUp,
+    /// To the bottom of the screen.
+    PageDown,
+    /// To the first line of the buffer.
+    FirstLine,
+    /// To the last line of the buffer.
+    LastLine,
+    /// To the start of the next word.
+    WordForward,
+    /// To the start of this word, or the previous one.
+    WordBackward,
+    /// To the end of this word, or the next one.
+    WordEnd,
+}
+
+/// Something to do with what [Git](crate::git) knows about the buffer's
+/// file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitCommand {
+    /// To the next hunk of lines changed since the last commit.
+    NextHunk,
+    /// To the previous hunk of lines changed since the last commit.
+    PreviousHunk,
+    /// Says who last changed the cursor's line, and when.
+    Blame,
+    /// Puts the hunk at the cursor in the index, once confirmed.
+    StageHunk,
+    /// Puts back the lines the hunk at the cursor replaced, once confirmed.
+    RevertHunk,
+}
+
+/// What replaces the selected text with something made of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    /// Replaces the selected expression with its value, or asks for one to
+    /// put the value of in (see [`calc`](crate::calc)).
+    Calculate,
+    /// Encodes the selected text as [base64](crate::base64).
+    Base64Encode,
+    /// Decodes the selected [base64](crate::base64) text.
+    Base64Decode,
+    /// [Percent-encodes](crate::url) the selected text.
+    UrlEncode,
+    /// Decodes the `%XX` escapes of the selected [URL](crate::url) text.
+    UrlDecode,
+    /// Lays the selected [JSON](crate::json) (or the whole buffer) out a
+    /// value to a line, indented.
+    JsonPretty,
+    /// Puts the selected [JSON](crate::json) (or the whole buffer) on one
+    /// line, without spaces.
+    JsonMinify,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorCommand {
+    MoveCursor(Movement),
+    /// Moves the cursor like [`EditorCommand::MoveCursor`], selecting the
+    /// text from where it was (or where selecting started) to where it goes.
+    Select(Movement),
+    InsertChar(char),
+    /// Splits the line at the cursor.
+    InsertNewline,
+    /// Types the next key as it is, without expanding an
+    /// [abbreviation](crate::abbreviation) before it.
+    InsertLiteral,
+    /// Puts in the date and time, as the `timestampformat` option lays it
+    /// out (see [`template`](crate::template)).
+    InsertTimestamp,
+    /// Deletes the character left of the cursor.
+    Backspace,
+    /// Deletes the character under the cursor.
+    Delete,
+    /// Deletes the line the cursor is on.
+    DeleteLine,
+    /// Comments out the cursor's line or the selected lines, or takes the
+    /// comments off them, as the buffer's [filetype](crate::filetype)
+    /// writes comments.
+    ToggleComment,
+    /// Indents the selected lines, or the cursor's line, by one level.
+    IndentLines,
+    /// Takes one level of indentation off the selected lines, or the
+    /// cursor's line.
+    OutdentLines,
+    /// Sorts the selected lines, or all of them.
+    SortLines,
+    /// Drops each of the selected lines, or of all of them, that repeats
+    /// one above it.
+    DedupeLines,
+    Transform(Transform),
+    /// Rewrites the indentation of every line with tabs or spaces, the way
+    /// the buffer is [indented](crate::indent).
+    Retab,
+    /// Adds an empty line below the cursor and starts typing there.
+    OpenLineBelow,
+    /// Adds an empty line above the cursor and starts typing there.
+    OpenLineAbove,
+    /// Switches to insert mode at the cursor.
+    InsertMode,
+    /// Switches to insert mode after the character under the cursor.
+    Append,
+    /// Switches to insert mode at the start of the line or, with a block
+    /// selected, before the block, typing on each of its lines at once.
+    InsertLineStart,
+    /// Switches to insert mode at the end of the line.
+    AppendLineEnd,
+    /// Switches to normal mode, when the `modal` option is on.
+    NormalMode,
+    /// Waits for the motion or text object to apply the operator to, or
+    /// applies it to the selection.
+    Operator(Operator),
+    /// Replaces the character under the cursor, or every selected one, with
+    /// the next one typed.
+    ReplaceChar,
+    /// Starts (or ends) selecting characters in visual mode.
+    VisualMode,
+    /// Starts (or ends) selecting whole lines in visual mode.
+    VisualLineMode,
+    /// Starts (or ends) selecting a block of columns in visual mode.
+    VisualBlockMode,
+    /// Moves the cursor to the other end of the selection.
+    SwapSelectionEnds,
+    /// Selects the whole buffer.
+    SelectAll,
+    /// Makes the last change again (Vim's `.`), in normal mode.
+    RepeatChange,
+    /// Takes back the last step of [edits](crate::undo).
+    Undo,
+    /// Makes the last step undone again.
+    Redo,
+    /// Takes the next key typed as the [register](crate::register) for the
+    /// following yank, delete or paste.
+    SelectRegister,
+    /// Pastes the last yanked or deleted text after the cursor (whole lines
+    /// below it).
+    PasteAfter,
+    /// Pastes the last yanked or deleted text before the cursor (whole
+    /// lines above it).
+    PasteBefore,
+    /// Like [`EditorCommand::PasteAfter`], but whole lines are indented
+    /// like the cursor's line instead of as they were.
+    PasteAfterReindent,
+    /// Like [`EditorCommand::PasteBefore`], reindenting whole lines.
+    PasteBeforeReindent,
+    Save,
+    /// Saves every modified buffer.
+    SaveAll,
+    /// Saves every modified buffer, then quits if that worked.
+    WriteQuitAll,
+    /// Quits, unless a buffer has changes that were not saved, as `:q`.
+    Quit,
+    /// Opens the recent-files picker.
+    OpenRecent,
+    /// Opens a picker of the files in the [project](crate::project).
+    FindFile,
+    /// Opens a picker of the open buffers, to switch to one.
+    ListBuffers,
+    /// Splits the focused [window](crate::window) in two, one above the
+    /// other.
+    SplitWindow,
+    /// Splits the focused window in two, side by side.
+    SplitWindowVertically,
+    /// Makes the focused window a row taller.
+    GrowWindow,
+    /// Makes the focused window a row shorter.
+    ShrinkWindow,
+    /// Makes the focused window a column wider.
+    WidenWindow,
+    /// Makes the focused window a column narrower.
+    NarrowWindow,
+    /// Gives every window the same share of the screen.
+    EqualizeWindows,
+    /// Moves the focus to the next window.
+    NextWindow,
+    /// Closes the focused window.
+    CloseWindow,
+    /// Jumps to the next place in the [quickfix list](crate::quickfix).
+    QuickfixNext,
+    /// Jumps to the previous place in the quickfix list.
+    QuickfixPrevious,
+    /// Shows or hides the pane listing the quickfix list.
+    ToggleQuickfix,
+    /// Opens the command line with a `/` typed, to [search](crate::search)
+    /// for the text typed after it.
+    Search,
+    /// Goes to the next place the last search found.
+    SearchNext,
+    /// Goes to the previous place the last search found.
+    SearchPrevious,
+    /// Asks the [language server](crate::lsp) where what is at the cursor
+    /// is defined, and goes there.
+    GoToDefinition,
+    /// Asks the language server where what is at the cursor is used, and
+    /// fills the quickfix list with the places.
+    FindReferences,
+    /// Opens the URL at the cursor with the system's handler, or the file
+    /// named there in the editor (see [`url`](crate::url)).
+    OpenAtCursor,
+    ToggleFollow,
+    /// Copies the current line to the system clipboard.
+    CopyLine,
+    /// Copies the selection, or else the current line, to the system
+    /// clipboard.
+    Copy,
+    /// Like [`EditorCommand::Copy`], then deletes what was copied.
+    Cut,
+    /// Inserts the text on the system clipboard at the cursor.
+    Paste,
+    /// Completes the word before the cursor with the next of the words in
+    /// the open buffers that start with it (see
+    /// [`completion`](crate::completion)).
+    CompleteNext,
+    /// Like [`EditorCommand::CompleteNext`], going back through the words.
+    CompletePrevious,
+    /// Asks the language server what could go at the cursor, and lists it
+    /// in a popup to pick from.
+    CompleteCode,
+    /// Shows what the language server says about what is at the cursor,
+    /// such as its type and documentation, until the next key.
+    Hover,
+    /// Runs the buffer through the [formatter](crate::format) for its
+    /// filetype.
+    Format,
+    /// Runs the [linter](crate::lint) for the buffer's filetype on its
+    /// file in the background.
+    Lint,
+    /// Shows how the buffer differs from its file, as a unified
+    /// [diff](crate::diff) in a read-only buffer in a new window.
+    DiffUnsaved,
+    /// Jumps to where the identifier at the cursor is defined, going by
+    /// the [tags](crate::tags) file.
+    GoToTag,
+    /// Jumps back to where the last jump to a tag was made from.
+    PopTag,
+    Git(GitCommand),
+    /// Jumps to the next [misspelled](crate::spell) word, going round past
+    /// the end of the buffer.
+    SpellNext,
+    /// Jumps to the previous misspelled word, going round past the start.
+    SpellPrevious,
+    /// Lists the words the one at the cursor may have been meant to be,
+    /// to put one in its place.
+    SpellSuggest,
+    /// Adds the word at the cursor to the user's word list.
+    SpellGood,
+    /// Inserts the newest entry of the [kill ring](crate::kill_ring) at
+    /// the cursor.
+    Yank,
+    /// Right after [`EditorCommand::Yank`], swaps the text it inserted for
+    /// the kill before it.
+    YankPop,
+    /// Stops the editor and returns to the shell.
+    Suspend,
+    /// Opens the command line, to type a command such as `set`.
+    CommandLine,
+}
+
+/// The names commands are bound by in the config file. Typing text has no
+/// name: it is what unbound keys do.
+const NAMES: &[(&str, EditorCommand)] = &[
+    ("move-up", EditorCommand::MoveCursor(Movement::Up)),
+    ("move-down", EditorCommand::MoveCursor(Movement::Down)),
+    ("move-left", EditorCommand::MoveCursor(Movement::Left)),
+    ("move-right", EditorCommand::MoveCursor(Movement::Right)),
+    ("move-home", EditorCommand::MoveCursor(Movement::Home)),
+    ("move-end", EditorCommand::MoveCursor(Movement::End)),
+    ("move-page-up", EditorCommand::MoveCursor(Movement::PageUp)),
+    (
+        "move-page-down",
+        EditorCommand::MoveCursor(Movement::PageDown),
+    ),
+    (
+        "move-first-line",
+        EditorCommand::MoveCursor(Movement::FirstLine),
+    ),
+    (
+        "move-last-line",
+        EditorCommand::MoveCursor(Movement::LastLine),
+    ),
+    (
+        "move-word-forward",
+        EditorCommand::MoveCursor(Movement::WordForward),
+    ),
+    (
+        "move-word-backward",
+        EditorCommand::MoveCursor(Movement::WordBackward),
+    ),
+    (
+        "move-word-end",
+        EditorCommand::MoveCursor(Movement::WordEnd),
+    ),
+    ("select-up", EditorCommand::Select(Movement::Up)),
+    ("select-down", EditorCommand::Select(Movement::Down)),
+    ("select-left", EditorCommand::Select(Movement::Left)),
+    ("select-right", EditorCommand::Select(Movement::Right)),
+    ("select-home", EditorCommand::Select(Movement::Home)),
+    ("select-end", EditorCommand::Select(Movement::End)),
+    ("select-page-up", EditorCommand::Select(Movement::PageUp)),
+    (
+        "select-page-down",
+        EditorCommand::Select(Movement::PageDown),
+    ),
+    ("newline", EditorCommand::InsertNewline),
+    ("insert-literal", EditorCommand::InsertLiteral),
+    ("insert-timestamp", EditorCommand::InsertTimestamp),
+    ("backspace", EditorCommand::Backspace),
+    ("delete", EditorCommand::Delete),
+    ("delete-line", EditorCommand::DeleteLine),
+    ("toggle-comment", EditorCommand::ToggleComment),
+    ("indent-lines", EditorCommand::IndentLines),
+    ("outdent-lines", EditorCommand::OutdentLines),
+    ("sort-lines", EditorCommand::SortLines),
+    ("dedupe-lines", EditorCommand::DedupeLines),
+    ("retab", EditorCommand::Retab),
+    ("calculate", EditorCommand::Transform(Transform::Calculate)),
+    (
+        "base64-encode",
+        EditorCommand::Transform(Transform::Base64Encode),
+    ),
+    (
+        "base64-decode",
+        EditorCommand::Transform(Transform::Base64Decode),
+    ),
+    ("url-encode", EditorCommand::Transform(Transform::UrlEncode)),
+    ("url-decode", EditorCommand::Transform(Transform::UrlDecode)),
+    (
+        "json-pretty",
+        EditorCommand::Transform(Transform::JsonPretty),
+    ),
+    (
+        "json-minify",
+        EditorCommand::Transform(Transform::JsonMinify),
+    ),
+    ("open-line-below", EditorCommand::OpenLineBelow),
+    ("open-line-above", EditorCommand::OpenLineAbove),
+    ("insert-mode", EditorCommand::InsertMode),
+    ("append", EditorCommand::Append),
+    ("insert-line-start", EditorCommand::InsertLineStart),
+    ("append-line-end", EditorCommand::AppendLineEnd),
+    ("normal-mode", EditorCommand::NormalMode),
+    ("delete-operator", EditorCommand::Operator(Operator::Delete)),
+    ("change-operator", EditorCommand::Operator(Operator::Change)),
+    ("yank-operator", EditorCommand::Operator(Operator::Yank)),
+    ("indent-operator", EditorCommand::Operator(Operator::Indent)),
+    (
+        "outdent-operator",
+        EditorCommand::Operator(Operator::Outdent),
+    ),
+    ("replace-char", EditorCommand::ReplaceChar),
+    ("visual-mode", EditorCommand::VisualMode),
+    ("visual-line-mode", EditorCommand::VisualLineMode),
+    ("visual-block-mode", EditorCommand::VisualBlockMode),
+    ("swap-selection-ends", EditorCommand::SwapSelectionEnds),
+    ("select-all", EditorCommand::SelectAll),
+    ("repeat-change", EditorCommand::RepeatChange),
+    ("undo", EditorCommand::Undo),
+    ("redo", EditorCommand::Redo),
+    ("select-register", EditorCommand::SelectRegister),
+    ("paste-after", EditorCommand::PasteAfter),
+    ("paste-before", EditorCommand::PasteBefore),
+    ("paste-after-reindent", EditorCommand::PasteAfterReindent),
+    ("paste-before-reindent", EditorCommand::PasteBeforeReindent),
+    ("save", EditorCommand::Save),
+    ("save-all", EditorCommand::SaveAll),
+    ("write-quit-all", EditorCommand::WriteQuitAll),
+    ("quit", EditorCommand::Quit),
+    ("open-recent", EditorCommand::OpenRecent),
+    ("find-file", EditorCommand::FindFile),
+    ("list-buffers", EditorCommand::ListBuffers),
+    ("split-window", EditorCommand::SplitWindow),
+    ("vsplit-window", EditorCommand::SplitWindowVertically),
+    ("grow-window", EditorCommand::GrowWindow),
+    ("shrink-window", EditorCommand::ShrinkWindow),
+    ("widen-window", EditorCommand::WidenWindow),
+    ("narrow-window", EditorCommand::NarrowWindow),
+    ("equalize-windows", EditorCommand::EqualizeWindows),
+    ("next-window", EditorCommand::NextWindow),
+    ("close-window", EditorCommand::CloseWindow),
+    ("quickfix-next", EditorCommand::QuickfixNext),
+    ("quickfix-previous", EditorCommand::QuickfixPrevious),
+    ("quickfix-toggle", EditorCommand::ToggleQuickfix),
+    ("search", EditorCommand::Search),
+    ("search-next", EditorCommand::SearchNext),
+    ("search-previous", EditorCommand::SearchPrevious),
+    ("go-to-definition", EditorCommand::GoToDefinition),
+    ("find-references", EditorCommand::FindReferences),
+    ("open-at-cursor", EditorCommand::OpenAtCursor),
+    ("toggle-follow", EditorCommand::ToggleFollow),
+    ("copy-line", EditorCommand::CopyLine),
+    ("copy", EditorCommand::Copy),
+    ("cut", EditorCommand::Cut),
+    ("paste", EditorCommand::Paste),
+    ("complete-next", EditorCommand::CompleteNext),
+    ("complete-previous", EditorCommand::CompletePrevious),
+    ("complete-code", EditorCommand::CompleteCode),
+    ("hover", EditorCommand::Hover),
+    ("format", EditorCommand::Format),
+    ("lint", EditorCommand::Lint),
+    ("diff-unsaved", EditorCommand::DiffUnsaved),
+    ("go-to-tag", EditorCommand::GoToTag),
+    ("pop-tag", EditorCommand::PopTag),
+    ("next-hunk", EditorCommand::Git(GitCommand::NextHunk)),
+    (
+        "previous-hunk",
+        EditorCommand::Git(GitCommand::PreviousHunk),
+    ),
+    ("blame", EditorCommand::Git(GitCommand::Blame)),
+    ("stage-hunk", EditorCommand::Git(GitCommand::StageHunk)),
+    ("revert-hunk", EditorCommand::Git(GitCommand::RevertHunk)),
+    ("spell-next", EditorCommand::SpellNext),
+    ("spell-previous", EditorCommand::SpellPrevious),
+    ("spell-suggest", EditorCommand::SpellSuggest),
+    ("spell-good", EditorCommand::SpellGood),
+    ("yank", EditorCommand::Yank),
+    ("yank-pop", EditorCommand::YankPop),
+    ("suspend", EditorCommand::Suspend),
+    ("command-line", EditorCommand::CommandLine),
+];
+
+impl EditorCommand {
+    /// The command called `name` in the config file, e.g. `save-all`.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        NAMES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, command)| *command)
+    }
+
+    /// The names of all commands that have one, in no particular order.
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        NAMES.iter().map(|(name, _)| *name)
+    }
+
+    /// The name the command is bound by, if it has one.
+    #[must_use]
+    pub fn name(self) -> Option<&'static str> {
+        NAMES
+            .iter()
+            .find(|(_, command)| *command == self)
+            .map(|(name, _)| *name)
+    }
+
+    /// Whether the command goes through word completions, which any other
+    /// command stops.
+    #[must_use]
+    pub fn completes_word(self) -> bool {
+        matches!(self, Self::CompleteNext | Self::CompletePrevious)
+    }
+
+    /// Whether the command modifies the buffer (which leaves follow mode).
+    #[must_use]
+    pub fn is_edit(self) -> bool {
+        matches!(
+            self,
+            Self::InsertChar(_)
+                | Self::InsertNewline
+                | Self::InsertLiteral
+                | Self::InsertTimestamp
+                | Self::Backspace
+                | Self::Delete
+                | Self::DeleteLine
+                | Self::ToggleComment
+                | Self::IndentLines
+                | Self::OutdentLines
+                | Self::SortLines
+                | Self::DedupeLines
+                | Self::Retab
+                | Self::Transform(_)
+                | Self::OpenLineBelow
+                | Self::OpenLineAbove
+                | Self::Operator(
+                    Operator::Delete | Operator::Change | Operator::Indent | Operator::Outdent
+                )
+                | Self::ReplaceChar
+                | Self::PasteAfter
+                | Self::PasteBefore
+                | Self::PasteAfterReindent
+                | Self::PasteBeforeReindent
+                | Self::Cut
+                | Self::Paste
+                | Self::Yank
+                | Self::YankPop
+                | Self::CompleteNext
+                | Self::CompletePrevious
+                | Self::Format
+                | Self::SpellSuggest
+                | Self::Git(GitCommand::RevertHunk)
+                | Self::Undo
+                | Self::Redo
+        )
+    }
+
+    /// Whether a count typed before the command in normal mode (`3j`)
+    /// repeats it.
+    #[must_use]
+    pub fn takes_count(self) -> bool {
+        matches!(
+            self,
+            Self::MoveCursor(_)
+                | Self::Select(_)
+                | Self::Backspace
+                | Self::Delete
+                | Self::DeleteLine
+                | Self::PasteAfter
+                | Self::PasteBefore
+                | Self::PasteAfterReindent
+                | Self::PasteBeforeReindent
+                | Self::Undo
+                | Self::Redo
+                | Self::GrowWindow
+                | Self::ShrinkWindow
+                | Self::WidenWindow
+                | Self::NarrowWindow
+        )
+    }
+
+    /// Whether the command edits, saves or follows the buffer, none of which
+    /// make sense before the whole file was loaded.
+    #[must_use]
+    pub fn needs_whole_file(self) -> bool {
+        self.is_edit()
+            || matches!(
+                self,
+                Self::Save
+                    | Self::SaveAll
+                    | Self::WriteQuitAll
+                    | Self::ToggleFollow
+                    | Self::Git(GitCommand::StageHunk)
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(
+            EditorCommand::from_name("save-all"),
+            Some(EditorCommand::SaveAll)
+        );
+        assert_eq!(
+            EditorCommand::from_name("move-page-down"),
+            Some(EditorCommand::MoveCursor(Movement::PageDown))
+        );
+        assert_eq!(EditorCommand::from_name("Save"), None);
+    }
+
+    #[test]
+    fn test_commands_blocked_while_loading() {
+        assert!(EditorCommand::InsertChar('x').needs_whole_file());
+        assert!(EditorCommand::SaveAll.needs_whole_file());
+        assert!(EditorCommand::ToggleFollow.needs_whole_file());
+        assert!(!EditorCommand::Quit.needs_whole_file());
+        assert!(!EditorCommand::MoveCursor(Movement::Down).needs_whole_file());
+        assert!(!EditorCommand::Save.is_edit());
+    }
+}
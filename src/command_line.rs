@@ -0,0 +1,184 @@
+//! # Command Line
+//!
+//! The line at the bottom of the screen where commands are typed by name
+//! (`Alt+X`, or `:` in normal mode), e.g. `set tabstop=8`. It replaces the
+//! status bar while open; `Enter` runs the command and `Esc` closes the
+//! line without running it. `Tab` completes a command name, or the path
+//! of a command that takes a file (`edit src/ma` `Tab`). The editing
+//! itself, history and the going through completions included, is the
+//! [`Prompt`]'s.
+
+use crate::{
+    keymap::KeyPress,
+    prompt::{History, Prompt, PromptAction},
+};
+
+/// Shown in front of the typed text.
+const PROMPT: &str = ":";
+
+/// The commands whose argument is a file.
+const FILE_COMMANDS: &[&str] = &["edit", "e", "split", "sp", "vsplit", "vs", "read", "r"];
+
+/// What the word being typed is, to complete it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Completion {
+    Command,
+    File,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandLine {
+    prompt: Prompt,
+}
+
+impl Default for CommandLine {
+    fn default() -> Self {
+        Self::new(History::default())
+    }
+}
+
+impl CommandLine {
+    /// An empty command line, going back through `history`.
+    #[must_use]
+    pub fn new(history: History) -> Self {
+        Self {
+            prompt: Prompt::new(PROMPT, history),
+        }
+    }
+
+    /// Replaces what is typed with `text`, the cursor at its end.
+    pub fn set_text(&mut self, text: &str) {
+        self.prompt.set_text(text);
+    }
+
+    /// Handles a key typed into the line.
+    pub fn handle_key(&mut self, key: KeyPress) -> PromptAction {
+        self.prompt.handle_key(key)
+    }
+
+    /// What the word before the cursor is, and what of it is typed; `None`
+    /// if it is not something that can be completed.
+    #[must_use]
+    pub fn completion(&self) -> Option<(Completion, &str)> {
+        let text = self.prompt.text();
+        if !self.prompt.at_end() {
+            return None;
+        }
+        match text.split_once(char::is_whitespace) {
+            None => Some((Completion::Command, text)),
+            Some((name, argument)) if FILE_COMMANDS.contains(&name) => {
+                let word = argument
+                    .rsplit(char::is_whitespace)
+                    .next()
+                    .unwrap_or_default();
+                Some((Completion::File, word))
+            }
+            Some(_) => None,
+        }
+    }
+
+    /// Completes the word before the cursor to the first of `matches` (all
+    /// of which start with what [`CommandLine::completion`] says is typed),
+    /// for `Tab` to go through. Returns `false` if there are none.
+    pub fn complete(&mut self, matches: Vec<String>) -> bool {
+        let Some((_, word)) = self.completion() else {
+            return false;
+        };
+        let text = self.prompt.text();
+        let start = text.chars().count().saturating_sub(word.chars().count());
+        self.prompt.complete(start, matches)
+    }
+
+    /// The ways to complete the word being completed, and which is shown,
+    /// if there is more than one.
+    #[must_use]
+    pub fn completions(&self) -> Option<(&[String], usize)> {
+        self.prompt.completions()
+    }
+
+    /// The row as shown on screen, at most `width` characters.
+    #[must_use]
+    pub fn render(&self, width: usize) -> String {
+        self.prompt.render(width)
+    }
+
+    /// The screen column of the cursor.
+    #[must_use]
+    pub fn cursor_column(&self) -> usize {
+        self.prompt.cursor_column()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    fn press(command_line: &mut CommandLine, code: KeyCode) -> PromptAction {
+        command_line.handle_key(KeyPress::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn test_typing_and_submitting() {
+        let mut command_line = CommandLine::default();
+        for ch in "set tsx".chars() {
+            press(&mut command_line, KeyCode::Char(ch));
+        }
+        press(&mut command_line, KeyCode::Backspace);
+        assert_eq!(command_line.render(80), ":set ts");
+        assert_eq!(command_line.cursor_column(), 7);
+        assert_eq!(command_line.render(4), ":set");
+
+        assert_eq!(
+            press(&mut command_line, KeyCode::Enter),
+            PromptAction::Accepted("set ts".to_string())
+        );
+    }
+
+    #[test]
+    fn test_tab_completes_command_names_and_files() {
+        let mut command_line = CommandLine::default();
+        press(&mut command_line, KeyCode::Char('s'));
+        assert_eq!(
+            press(&mut command_line, KeyCode::Tab),
+            PromptAction::Complete
+        );
+        assert_eq!(command_line.completion(), Some((Completion::Command, "s")));
+        let names = vec!["set".to_string(), "split".to_string()];
+        assert!(command_line.complete(names));
+        assert_eq!(command_line.render(80), ":set");
+        press(&mut command_line, KeyCode::Tab);
+        assert_eq!(command_line.render(80), ":split");
+
+        for ch in " a.txt sr".chars() {
+            press(&mut command_line, KeyCode::Char(ch));
+        }
+        assert_eq!(command_line.completion(), Some((Completion::File, "sr")));
+        assert!(command_line.complete(vec!["src/".to_string()]));
+        assert_eq!(command_line.render(80), ":split a.txt src/");
+        assert_eq!(command_line.completions(), None);
+
+        command_line = CommandLine::default();
+        for ch in "set ts".chars() {
+            press(&mut command_line, KeyCode::Char(ch));
+        }
+        assert_eq!(command_line.completion(), None);
+        assert!(!command_line.complete(vec!["tabstop".to_string()]));
+    }
+
+    #[test]
+    fn test_cancelling() {
+        let mut command_line = CommandLine::default();
+        press(&mut command_line, KeyCode::Char('x'));
+        assert_eq!(
+            press(&mut command_line, KeyCode::Esc),
+            PromptAction::Cancelled
+        );
+
+        let mut command_line = CommandLine::default();
+        assert_eq!(
+            press(&mut command_line, KeyCode::Backspace),
+            PromptAction::Cancelled
+        );
+    }
+}
@@ -0,0 +1,349 @@
+//! # Word Completion
+//!
+//! `Ctrl+N` completes the word before the cursor with a longer one found
+//! in the open buffers: the words of the buffer from the cursor's line on
+//! come first, going round past its end, then those of the other buffers.
+//! Pressing it again goes on to the next word, `Ctrl+P` back to the one
+//! before (or, to start with, the last), and going past either end brings
+//! back what was typed. The words are listed in a popup under the cursor's
+//! line; any other key keeps the word shown and closes it.
+//!
+//! With a [language server](crate::lsp) running, `Ctrl+Space`, or typing
+//! one of the characters the server says start a completion (`.` and
+//! `::` in Rust), asks it what could go at the cursor instead: a
+//! [`CodeCompletion`]. Its popup narrows down as the word is typed further,
+//! `Up` and `Down` go through it with what the item chosen is shown beside
+//! it, and `Enter` or `Tab` puts the item in, placeholders of a snippet and
+//! all. `Esc` or anything else that is not typing a word closes it.
+
+use std::{collections::HashSet, iter::Peekable, str::Chars};
+
+use crate::{lsp::CompletionItem, operator::Pos};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordCompletion {
+    /// Where the word being completed starts.
+    pub start: Pos,
+    /// What of it was typed.
+    typed: String,
+    /// The words it may be completed to, in order.
+    words: Vec<String>,
+    /// The word shown, if not what was typed.
+    chosen: Option<usize>,
+}
+
+impl WordCompletion {
+    /// Completion of `typed`, the word before the cursor that starts at
+    /// `start`, to the words of `texts`; `None` if there are none.
+    #[must_use]
+    pub fn new<'a>(
+        start: Pos,
+        typed: &str,
+        texts: impl IntoIterator<Item = &'a str>,
+    ) -> Option<Self> {
+        let mut seen = HashSet::new();
+        let words: Vec<String> = texts
+            .into_iter()
+            .flat_map(|text| text.split(|ch: char| !is_word_char(ch)))
+            .filter(|word| word.len() > typed.len() && word.starts_with(typed))
+            .filter(|word| seen.insert(*word))
+            .map(ToString::to_string)
+            .collect();
+        (!words.is_empty()).then(|| Self {
+            start,
+            typed: typed.to_string(),
+            words,
+            chosen: None,
+        })
+    }
+
+    /// Goes on to the next word, or back to what was typed after the last.
+    pub fn select_next(&mut self) {
+        self.chosen = match self.chosen {
+            None => Some(0),
+            Some(index) => Some(index.saturating_add(1)).filter(|&next| next < self.words.len()),
+        };
+    }
+
+    /// Goes back to the previous word, or to what was typed before the
+    /// first.
+    pub fn select_previous(&mut self) {
+        self.chosen = match self.chosen {
+            None => self.words.len().checked_sub(1),
+            Some(index) => index.checked_sub(1),
+        };
+    }
+
+    /// The word shown: the one chosen, or else what was typed.
+    #[must_use]
+    pub fn text(&self) -> &str {
+        self.chosen
+            .and_then(|index| self.words.get(index))
+            .unwrap_or(&self.typed)
+    }
+
+    #[must_use]
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    #[must_use]
+    pub fn chosen(&self) -> Option<usize> {
+        self.chosen
+    }
+}
+
+/// What a language server offered to complete the word at `start` with,
+/// narrowed down to the items that match what was typed of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeCompletion {
+    /// Where the word being completed starts.
+    pub start: Pos,
+    items: Vec<CompletionItem>,
+    /// The items that match what was typed, best first.
+    shown: Vec<usize>,
+    /// Which of the shown items is chosen.
+    chosen: usize,
+}
+
+impl CodeCompletion {
+    /// Completion of the word at `start` to `items`, of which those that
+    /// match `typed` are shown; `None` if none do.
+    #[must_use]
+    pub fn new(start: Pos, items: Vec<CompletionItem>, typed: &str) -> Option<Self> {
+        let mut completion = Self {
+            start,
+            items,
+            shown: Vec::new(),
+            chosen: 0,
+        };
+        completion.filter(typed).then_some(completion)
+    }
+
+    /// Shows the items that match `typed`: its characters in order, case
+    /// aside, those that start with it first. Returns whether any do.
+    pub fn filter(&mut self, typed: &str) -> bool {
+        let typed = typed.to_lowercase();
+        let (mut prefixed, mut others): (Vec<usize>, Vec<usize>) = (Vec::new(), Vec::new());
+        for (index, item) in self.items.iter().enumerate() {
+            let filter = item.filter.to_lowercase();
+            if filter.starts_with(&typed) {
+                prefixed.push(index);
+            } else if is_subsequence(&typed, &filter) {
+                others.push(index);
+            }
+        }
+        prefixed.append(&mut others);
+        self.shown = prefixed;
+        self.chosen = 0;
+        !self.shown.is_empty()
+    }
+
+    /// Chooses the next item, going round past the last.
+    pub fn select_next(&mut self) {
+        let next = self.chosen.saturating_add(1);
+        self.chosen = if next < self.shown.len() { next } else { 0 };
+    }
+
+    /// Chooses the previous item, going round past the first.
+    pub fn select_previous(&mut self) {
+        self.chosen = self
+            .chosen
+            .checked_sub(1)
+            .unwrap_or_else(|| self.shown.len().saturating_sub(1));
+    }
+
+    /// The item chosen.
+    #[must_use]
+    pub fn chosen(&self) -> Option<&CompletionItem> {
+        self.items.get(*self.shown.get(self.chosen)?)
+    }
+
+    /// The labels of the items shown, to list.
+    #[must_use]
+    pub fn labels(&self) -> Vec<String> {
+        self.shown
+            .iter()
+            .filter_map(|&index| Some(self.items.get(index)?.label.clone()))
+            .collect()
+    }
+
+    #[must_use]
+    pub fn chosen_index(&self) -> usize {
+        self.chosen
+    }
+}
+
+/// Whether the characters of `part` are all in `text`, in order.
+fn is_subsequence(part: &str, text: &str) -> bool {
+    let mut chars = text.chars();
+    part.chars().all(|wanted| chars.any(|ch| ch == wanted))
+}
+
+/// The text of an LSP snippet, with its tab stops and placeholders taken
+/// out (the placeholders' text stays), and the byte of it where the cursor
+/// goes: the first tab stop, else the final one (`$0`), else the end.
+#[must_use]
+pub fn expand_snippet(snippet: &str) -> (String, usize) {
+    expand_snippet_with(snippet, |_| None)
+}
+
+/// [`expand_snippet`], with each variable (`$TM_FILENAME` or
+/// `${TM_FILENAME:default}`) that `variable` has a value for replaced with
+/// it; the others are left empty, or with their default.
+#[must_use]
+pub fn expand_snippet_with(
+    snippet: &str,
+    variable: impl Fn(&str) -> Option<String>,
+) -> (String, usize) {
+    let mut text = String::new();
+    let mut stops: Vec<(u32, usize)> = Vec::new();
+    // Bytes of `text` where the placeholders still open started, so an
+    // outer one's text can hold inner ones.
+    let mut open = 0_usize;
+    let mut chars = snippet.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => text.extend(chars.next()),
+            '$' => {
+                let braced = chars.next_if_eq(&'{').is_some();
+                let mut number = String::new();
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    number.push(digit);
+                }
+                if let Ok(number) = number.parse() {
+                    stops.push((number, text.len()));
+                } else {
+                    // A variable such as `$TM_FILENAME`.
+                    let mut name = String::new();
+                    while let Some(ch) = chars.next_if(|ch| ch.is_alphanumeric() || *ch == '_') {
+                        name.push(ch);
+                    }
+                    if let Some(value) = variable(&name) {
+                        text.push_str(&value);
+                        if braced && chars.next_if_eq(&':').is_some() {
+                            skip_default(&mut chars);
+                        }
+                    }
+                }
+                if !braced {
+                    continue;
+                }
+                match chars.next() {
+                    Some(':') => open = open.saturating_add(1),
+                    // A choice: the first one goes in.
+                    Some('|') => {
+                        let choices: String = chars.by_ref().take_while(|&ch| ch != '|').collect();
+                        text.push_str(choices.split(',').next().unwrap_or_default());
+                        chars.next_if_eq(&'}');
+                    }
+                    _ => {}
+                }
+            }
+            '}' if open > 0 => open = open.saturating_sub(1),
+            _ => text.push(ch),
+        }
+    }
+    let cursor = stops
+        .iter()
+        .filter(|(number, _)| *number > 0)
+        .min_by_key(|(number, _)| *number)
+        .or_else(|| stops.iter().find(|(number, _)| *number == 0))
+        .map_or(text.len(), |(_, at)| *at);
+    (text, cursor)
+}
+
+/// Skips the default text of a variable that has a value, up to the `}`
+/// that closes it.
+fn skip_default(chars: &mut Peekable<Chars>) {
+    let mut depth = 0_usize;
+    while let Some(&ch) = chars.peek() {
+        match ch {
+            '}' if depth == 0 => return,
+            '}' => depth = depth.saturating_sub(1),
+            '{' => depth = depth.saturating_add(1),
+            '\\' => {
+                chars.next();
+            }
+            _ => {}
+        }
+        chars.next();
+    }
+}
+
+/// Whether `ch` can be part of a word that is completed.
+#[must_use]
+pub fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_goes_through_words_and_back_to_what_was_typed() {
+        let texts = ["let value = val;", "fn validate(valid: bool, value: u8)"];
+        let mut completion = WordCompletion::new((0, 12), "val", texts).unwrap();
+        assert_eq!(completion.words(), ["value", "validate", "valid"]);
+        assert_eq!(completion.text(), "val");
+        completion.select_next();
+        completion.select_next();
+        assert_eq!(completion.text(), "validate");
+        completion.select_next();
+        completion.select_next();
+        assert_eq!((completion.text(), completion.chosen()), ("val", None));
+        completion.select_previous();
+        assert_eq!(completion.text(), "valid");
+        assert_eq!(WordCompletion::new((0, 0), "zz", texts), None);
+    }
+
+    fn item(label: &str) -> CompletionItem {
+        CompletionItem {
+            label: label.to_string(),
+            text: label.to_string(),
+            snippet: false,
+            detail: None,
+            documentation: None,
+            filter: label.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_code_completion_narrows_as_typed() {
+        let items = ["into_iter", "iter", "is_some", "len"].map(item).to_vec();
+        let mut completion = CodeCompletion::new((0, 4), items, "").unwrap();
+        assert_eq!(completion.labels().len(), 4);
+        assert!(completion.filter("IT"));
+        assert_eq!(completion.labels(), ["iter", "into_iter"]);
+        completion.select_previous();
+        assert_eq!(completion.chosen().unwrap().label, "into_iter");
+        completion.select_next();
+        assert_eq!(completion.chosen_index(), 0);
+        assert!(!completion.filter("x"));
+        assert_eq!(CodeCompletion::new((0, 0), vec![item("len")], "z"), None);
+    }
+
+    #[test]
+    fn test_snippets_expand_to_their_placeholders() {
+        assert_eq!(
+            expand_snippet("push(${1:value})$0"),
+            ("push(value)".to_string(), 5)
+        );
+        assert_eq!(
+            expand_snippet("fn $2($1) {$0}"),
+            ("fn () {}".to_string(), 4)
+        );
+        assert_eq!(expand_snippet("if $0 {}"), ("if  {}".to_string(), 3));
+        assert_eq!(
+            expand_snippet("${1:Some(${2:x})} \\$ ${3|a,b|}$TM_FILENAME"),
+            ("Some(x) $ a".to_string(), 0)
+        );
+        assert_eq!(expand_snippet("plain"), ("plain".to_string(), 5));
+        let variable = |name: &str| (name == "AUTHOR").then(|| "Ada".to_string());
+        assert_eq!(
+            expand_snippet_with("// ${AUTHOR:x{}}, ${TM_FILENAME:none} $AUTHOR$0", variable),
+            ("// Ada, none Ada".to_string(), 16)
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! # Compressed Files
+//!
+//! Lets the buffer edit compressed files in place: the file is decompressed
+//! on load and recompressed on save. Like vim's `gzip` plugin, we delegate
+//! the actual (de)compression to the system `gzip` binary instead of linking
+//! a compression library.
+
+use std::{
+    fmt, fs,
+    io::{self, Read, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::error::{Error, Result};
+
+/// The two bytes every gzip stream starts with.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// How a file is stored on disk.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+}
+
+impl Compression {
+    /// Detects the compression from the file extension, falling back to the
+    /// gzip magic bytes for files that were renamed.
+    #[must_use]
+    pub fn detect(path: &Path) -> Self {
+        let has_gz_extension = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"));
+        if has_gz_extension || has_gzip_magic(path) {
+            Self::Gzip
+        } else {
+            Self::None
+        }
+    }
+
+    /// Reads `path`, decompressing it if needed.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be read, `gzip` fails, or the contents are
+    /// not valid UTF-8.
+    pub fn read(self, path: &Path) -> Result<String> {
+        match self {
+            Self::None => fs::read_to_string(path).map_err(|err| Error::at(path, err)),
+            Self::Gzip => {
+                let output = Command::new("gzip").arg("-dc").arg(path).output()?;
+                if !output.status.success() {
+                    return Err(gzip_failure(&output.stderr).into());
+                }
+                String::from_utf8(output.stdout).map_err(|_| Error::InvalidUtf8 {
+                    path: path.to_path_buf(),
+                })
+            }
+        }
+    }
+
+    /// Writes `contents` to `path`, compressing it if needed.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be written or `gzip` fails.
+    pub fn write(self, path: &Path, contents: &str) -> Result<()> {
+        match self {
+            Self::None => fs::write(path, contents).map_err(|err| Error::at(path, err)),
+            Self::Gzip => {
+                let mut child = Command::new("gzip")
+                    .arg("-c")
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()?;
+                // Feed stdin from a separate thread so a full stdout pipe
+                // can't deadlock us on large files.
+                let mut stdin = child.stdin.take().ok_or_else(|| gzip_failure(b""))?;
+                let input = contents.to_string();
+                let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+                let output = child.wait_with_output()?;
+                writer
+                    .join()
+                    .map_err(|_| gzip_failure(b"writer thread panicked"))??;
+                if !output.status.success() {
+                    return Err(gzip_failure(&output.stderr).into());
+                }
+                fs::write(path, output.stdout).map_err(|err| Error::at(path, err))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => Ok(()),
+            Self::Gzip => write!(f, "gzip"),
+        }
+    }
+}
+
+fn has_gzip_magic(path: &Path) -> bool {
+    let mut magic = [0; 2];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|()| magic == GZIP_MAGIC)
+}
+
+fn gzip_failure(stderr: &[u8]) -> io::Error {
+    io::Error::other(format!(
+        "gzip failed: {}",
+        String::from_utf8_lossy(stderr).trim()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        env::temp_dir().join(format!("hecto-compression-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn test_detect_by_extension() {
+        assert_eq!(
+            Compression::detect(Path::new("/nonexistent/app.log.gz")),
+            Compression::Gzip
+        );
+        assert_eq!(
+            Compression::detect(Path::new("/nonexistent/app.log")),
+            Compression::None
+        );
+    }
+
+    #[test]
+    fn test_gzip_round_trip() {
+        let path = temp_path("round-trip.gz");
+        let contents = "first line\nsecond line\n";
+
+        Compression::Gzip.write(&path, contents).unwrap();
+        let raw = fs::read(&path).unwrap();
+        assert_eq!(
+            raw.get(..2),
+            Some(&GZIP_MAGIC[..]),
+            "Expected gzip data on disk"
+        );
+
+        assert_eq!(Compression::detect(&path), Compression::Gzip);
+        assert_eq!(Compression::Gzip.read(&path).unwrap(), contents);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_detect_by_magic_bytes() {
+        let path = temp_path("renamed");
+        Compression::Gzip.write(&path, "hello").unwrap();
+        assert_eq!(Compression::detect(&path), Compression::Gzip);
+        let _ = fs::remove_file(path);
+    }
+}
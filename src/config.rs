@@ -0,0 +1,301 @@
+//! # Configuration File
+//!
+//! Reads the user's settings from `config.toml` in the XDG config directory
+//! (see [`dirs::config_dir()`]). Only the small part of TOML the settings
+//! need is understood: `[section]` headers, `key = value` pairs whose key
+//! and value may be bare or double-quoted, and `#` comments. For example:
+//!
+//! ```toml
+//! [keys]
+//! "ctrl-s" = "save"
+//! "ctrl-x ctrl-c" = "quit"
+//!
+//! [keys.normal]
+//! "ctrl-s" = "save-all"
+//!
+//! [options]
+//! tabstop = 8
+//! bell = "visual"
+//!
+//! [formatters]
+//! python = "ruff format -"
+//!
+//! [linters]
+//! python = "pylint --output-format=parseable %"
+//!
+//! [abbreviations.markdown]
+//! teh = "the"
+//! ```
+//!
+//! A broken line or an unknown section is reported as a problem and skipped;
+//! the rest of the file still applies. A `.hecto.toml` at the root of the
+//! [project](crate::project) is read the same way, after this file.
+
+use std::{fs, path::Path};
+
+use crate::{dirs, error::Error, keymap::Mode};
+
+/// Name of the configuration file inside [`dirs::config_dir()`].
+const FILE_NAME: &str = "config.toml";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// Key bindings from the `[keys]` (insert mode) and `[keys.MODE]`
+    /// sections: mode, key sequence, command name.
+    pub keys: Vec<(Mode, String, String)>,
+    /// Option values from the `[options]` section: name, value.
+    pub options: Vec<(String, String)>,
+    /// [Formatters](crate::format) from the `[formatters]` section:
+    /// filetype, command.
+    pub formatters: Vec<(String, String)>,
+    /// [Linters](crate::lint) from the `[linters]` section: filetype,
+    /// command.
+    pub linters: Vec<(String, String)>,
+    /// [Abbreviations](crate::abbreviation) from the `[abbreviations]`
+    /// (filetype `""`) and `[abbreviations.FILETYPE]` sections: filetype,
+    /// word, expansion.
+    pub abbreviations: Vec<(String, String, String)>,
+}
+
+impl Config {
+    /// Loads the default configuration file, if there is one. Returns the
+    /// configuration along with a description of each problem found in it.
+    #[must_use]
+    pub fn load_default() -> (Self, Vec<Error>) {
+        dirs::config_dir().map_or_else(
+            || (Self::default(), Vec::new()),
+            |dir| Self::load(&dir.join(FILE_NAME)),
+        )
+    }
+
+    /// Loads the configuration from `path`. A missing file is not a
+    /// problem: it just means nothing was configured.
+    #[must_use]
+    pub fn load(path: &Path) -> (Self, Vec<Error>) {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => (Self::default(), Vec::new()),
+            Err(err) => (Self::default(), vec![Error::at(path, err)]),
+        }
+    }
+
+    /// Parses the text of a configuration file.
+    #[must_use]
+    pub fn parse(text: &str) -> (Self, Vec<Error>) {
+        let mut config = Self::default();
+        let mut problems = Vec::new();
+        let mut section = String::new();
+        for (index, line) in text.lines().enumerate() {
+            let problem = |message: String| Error::ConfigParse {
+                line: index.saturating_add(1),
+                message,
+            };
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_string();
+                let known = ["options", "formatters", "linters"].contains(&section.as_str())
+                    || section_filetype(&section).is_some();
+                if !known && section_mode(&section).is_none() {
+                    problems.push(problem(format!("unknown section [{section}]")));
+                }
+                continue;
+            }
+            let Some((key, value)) = parse_pair(line) else {
+                problems.push(problem("expected `key = value`".to_string()));
+                continue;
+            };
+            match (section.as_str(), section_mode(&section)) {
+                (_, Some(mode)) => config.keys.push((mode, key, value)),
+                ("options", _) => config.options.push((key, value)),
+                ("formatters", _) => config.formatters.push((key, value)),
+                ("linters", _) => config.linters.push((key, value)),
+                _ if section_filetype(&section).is_some() => {
+                    let filetype = section_filetype(&section).unwrap_or_default();
+                    config
+                        .abbreviations
+                        .push((filetype.to_string(), key, value));
+                }
+                // Already reported at the section header.
+                _ if !section.is_empty() => {}
+                _ => problems.push(problem(format!("`{key}` is outside a section"))),
+            }
+        }
+        (config, problems)
+    }
+}
+
+/// The mode a key bindings section is for: `[keys]` binds keys in insert
+/// mode, `[keys.normal]` in normal mode.
+fn section_mode(section: &str) -> Option<Mode> {
+    match section.strip_prefix("keys") {
+        Some("") => Some(Mode::Insert),
+        Some(rest) => Mode::from_name(rest.strip_prefix('.')?),
+        None => None,
+    }
+}
+
+/// The filetype an abbreviations section is for: `""` for any in
+/// `[abbreviations]`, `rust` in `[abbreviations.rust]`.
+fn section_filetype(section: &str) -> Option<&str> {
+    match section.strip_prefix("abbreviations")? {
+        "" => Some(""),
+        rest => rest
+            .strip_prefix('.')
+            .filter(|filetype| !filetype.is_empty()),
+    }
+}
+
+/// Cuts a `#` comment off `line`, unless the `#` is inside quotes.
+pub fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (index, ch) in line.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted => return line.get(..index).unwrap_or(line),
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Splits `key = value`, unquoting both sides.
+#[must_use]
+pub fn parse_pair(line: &str) -> Option<(String, String)> {
+    let (key, rest) = if line.starts_with('"') {
+        parse_quoted(line)?
+    } else {
+        let end = line.find('=')?;
+        (line.get(..end)?.trim().to_string(), line.get(end..)?)
+    };
+    let value = rest.trim_start().strip_prefix('=')?.trim();
+    let value = if value.starts_with('"') {
+        let (value, tail) = parse_quoted(value)?;
+        if !tail.trim().is_empty() {
+            return None;
+        }
+        value
+    } else {
+        value.to_string()
+    };
+    (!key.is_empty()).then_some((key, value))
+}
+
+/// Reads the double-quoted string at the start of `text`, returning its
+/// contents and whatever follows the closing quote.
+fn parse_quoted(text: &str) -> Option<(String, &str)> {
+    let mut contents = String::new();
+    let mut chars = text.char_indices().skip(1);
+    while let Some((index, ch)) = chars.next() {
+        match ch {
+            '"' => return Some((contents, text.get(index.saturating_add(1)..)?)),
+            '\\' => match chars.next()?.1 {
+                'n' => contents.push('\n'),
+                't' => contents.push('\t'),
+                other => contents.push(other),
+            },
+            _ => contents.push(ch),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_keys_section() {
+        let (config, problems) = Config::parse(
+            "# my bindings\n\
+             [keys]\n\
+             \"ctrl-s\" = \"save\"  # comment\n\
+             ctrl-x = quit\n\
+             \"alt-#\" = \"save-all\"\n\
+             [keys.normal]\n\
+             q = quit\n\
+             [options]\n\
+             tabstop = 8\n\
+             [formatters]\n\
+             rust = \"rustfmt --edition 2024\"\n\
+             [linters]\n\
+             shell = \"\"\n\
+             [abbreviations]\n\
+             teh = the\n\
+             [abbreviations.rust]\n\
+             fnmain = \"fn main() {\\n}\"\n",
+        );
+        assert!(problems.is_empty(), "{problems:?}");
+        assert_eq!(
+            config.keys,
+            vec![
+                (Mode::Insert, "ctrl-s".to_string(), "save".to_string()),
+                (Mode::Insert, "ctrl-x".to_string(), "quit".to_string()),
+                (Mode::Insert, "alt-#".to_string(), "save-all".to_string()),
+                (Mode::Normal, "q".to_string(), "quit".to_string()),
+            ]
+        );
+        assert_eq!(
+            config.options,
+            vec![("tabstop".to_string(), "8".to_string())]
+        );
+        assert_eq!(
+            config.formatters,
+            vec![("rust".to_string(), "rustfmt --edition 2024".to_string())]
+        );
+        assert_eq!(config.linters, vec![("shell".to_string(), String::new())]);
+        assert_eq!(
+            config.abbreviations,
+            vec![
+                (String::new(), "teh".to_string(), "the".to_string()),
+                (
+                    "rust".to_string(),
+                    "fnmain".to_string(),
+                    "fn main() {\n}".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_problems_are_reported_and_skipped() {
+        let (config, problems) = Config::parse(
+            "stray = 1\n\
+             [keys]\n\
+             no equals sign\n\
+             \"unterminated = \"quit\"\n\
+             \"ctrl-q\" = \"quit\" trailing\n\
+             \"ctrl-s\" = \"save\"\n\
+             [colors]\n\
+             red = 1\n\
+             [keys.replace]\n",
+        );
+        assert_eq!(
+            config.keys,
+            vec![(Mode::Insert, "ctrl-s".to_string(), "save".to_string())]
+        );
+        assert_eq!(
+            problems.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![
+                "line 1: `stray` is outside a section",
+                "line 3: expected `key = value`",
+                "line 4: expected `key = value`",
+                "line 5: expected `key = value`",
+                "line 7: unknown section [colors]",
+                "line 9: unknown section [keys.replace]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_file_is_not_a_problem() {
+        let (config, problems) = Config::load(Path::new("/nonexistent-dir/config.toml"));
+        assert_eq!(config, Config::default());
+        assert!(problems.is_empty());
+    }
+}
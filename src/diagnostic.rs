@@ -0,0 +1,128 @@
+//! # Diagnostics
+//!
+//! Errors and warnings about a buffer's text that something other than the
+//! editor found: a [language server](crate::lsp) or a [linter](crate::lint).
+//! A buffer keeps the latest ones from each; their lines get a sign in the
+//! gutter, their text is underlined, and the one on the cursor's line is
+//! shown in the status bar while there is no other message.
+
+use std::ops::Range;
+
+use crate::notifications::Severity;
+
+/// What found a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    LanguageServer,
+    Linter,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub line: usize,
+    /// The columns it is about, in graphemes, end excluded.
+    pub cols: Range<usize>,
+    pub severity: Severity,
+    pub message: String,
+    pub source: Source,
+}
+
+impl Diagnostic {
+    /// The sign put in the gutter of its line.
+    #[must_use]
+    pub fn sign(&self) -> &'static str {
+        match self.severity {
+            Severity::Error => "E",
+            Severity::Warning => "W",
+            Severity::Info => "I",
+        }
+    }
+
+    /// The theme element its sign is colored with.
+    #[must_use]
+    pub fn sign_element(&self) -> &'static str {
+        match self.severity {
+            Severity::Error => "sign.error",
+            Severity::Warning => "sign.warning",
+            Severity::Info => "sign.info",
+        }
+    }
+
+    /// The theme element its text is marked with.
+    #[must_use]
+    pub fn element(&self) -> &'static str {
+        match self.severity {
+            Severity::Error => "diagnostic.error",
+            Severity::Warning => "diagnostic.warning",
+            Severity::Info => "diagnostic.info",
+        }
+    }
+}
+
+/// Replaces those of `diagnostics` that came from `source` with `found`,
+/// which it found last.
+pub fn replace(diagnostics: &mut Vec<Diagnostic>, source: Source, found: Vec<Diagnostic>) {
+    diagnostics.retain(|diagnostic| diagnostic.source != source);
+    diagnostics.extend(found);
+}
+
+/// The most serious of `diagnostics` on `line`, the first of them if
+/// several are as serious.
+#[must_use]
+pub fn worst_on_line(diagnostics: &[Diagnostic], line: usize) -> Option<&Diagnostic> {
+    let rank = |severity| match severity {
+        Severity::Error => 2,
+        Severity::Warning => 1,
+        Severity::Info => 0,
+    };
+    diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.line == line)
+        .rev()
+        .max_by_key(|diagnostic| rank(diagnostic.severity))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(line: usize, severity: Severity, message: &str) -> Diagnostic {
+        Diagnostic {
+            line,
+            cols: 0..1,
+            severity,
+            message: message.to_string(),
+            source: Source::LanguageServer,
+        }
+    }
+
+    #[test]
+    fn test_worst_on_line_prefers_errors_then_the_first() {
+        let diagnostics = [
+            diagnostic(1, Severity::Warning, "unused"),
+            diagnostic(1, Severity::Error, "first"),
+            diagnostic(1, Severity::Error, "second"),
+            diagnostic(2, Severity::Info, "hint"),
+        ];
+        let worst = worst_on_line(&diagnostics, 1).unwrap();
+        assert_eq!((worst.message.as_str(), worst.sign()), ("first", "E"));
+        assert_eq!(
+            worst_on_line(&diagnostics, 2).unwrap().element(),
+            "diagnostic.info"
+        );
+        assert_eq!(worst_on_line(&diagnostics, 0), None);
+
+        let mut diagnostics = diagnostics.to_vec();
+        let linted = Diagnostic {
+            source: Source::Linter,
+            ..diagnostic(0, Severity::Warning, "lint")
+        };
+        replace(
+            &mut diagnostics,
+            Source::LanguageServer,
+            vec![linted.clone()],
+        );
+        replace(&mut diagnostics, Source::Linter, vec![linted.clone()]);
+        assert_eq!(diagnostics, [linted]);
+    }
+}
@@ -0,0 +1,330 @@
+//! # Line Diffs
+//!
+//! Which lines changed between two versions of a text, worked out with
+//! Myers's algorithm, the one `diff` and Git use: the fewest lines deleted
+//! and inserted that turn the old version into the new one. The changes
+//! come as [`Hunk`]s, and lines outside them are the same in both.
+//!
+//! Lines the two share at the start and the end are set aside first, so
+//! the usual small change to a long text is quick to find. Past
+//! [`MAX_COST`] changes the search stops, and everything in between is
+//! taken as one hunk.
+//!
+//! [`unified`] prints the hunks the way `diff -u` does, for people to read.
+
+use std::{cmp::min, fmt::Write, ops::Range};
+
+/// The most lines deleted and inserted the search looks for before giving
+/// up; it takes memory in the square of it.
+const MAX_COST: usize = 1024;
+
+/// How many unchanged lines [`unified`] shows around each hunk.
+const CONTEXT: usize = 3;
+
+/// Lines of the old text replaced with lines of the new one. Either side
+/// may be empty, for lines only inserted or only deleted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// The hunks that turn `old` into `new`, in order.
+#[must_use]
+pub fn diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Hunk> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old
+        .iter()
+        .skip(prefix)
+        .rev()
+        .zip(new.iter().skip(prefix).rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = old
+        .get(prefix..old.len().saturating_sub(suffix))
+        .unwrap_or_default();
+    let new_middle = new
+        .get(prefix..new.len().saturating_sub(suffix))
+        .unwrap_or_default();
+    let Some(matches) = common_lines(old_middle, new_middle) else {
+        let hunk = Hunk {
+            old: prefix..prefix.saturating_add(old_middle.len()),
+            new: prefix..prefix.saturating_add(new_middle.len()),
+        };
+        return if hunk.old.is_empty() && hunk.new.is_empty() {
+            Vec::new()
+        } else {
+            vec![hunk]
+        };
+    };
+    // The hunks are the gaps between the lines kept.
+    let end = (old_middle.len(), new_middle.len());
+    let mut hunks = Vec::new();
+    let mut next = (0, 0);
+    for (a, b) in matches.into_iter().chain([end]) {
+        if a > next.0 || b > next.1 {
+            hunks.push(Hunk {
+                old: prefix.saturating_add(next.0)..prefix.saturating_add(a),
+                new: prefix.saturating_add(next.1)..prefix.saturating_add(b),
+            });
+        }
+        next = (a.saturating_add(1), b.saturating_add(1));
+    }
+    hunks
+}
+
+/// The hunks that turn the new text back into the old one, given the
+/// `hunks` that turn the old one into it.
+#[must_use]
+pub fn reverse(hunks: &[Hunk]) -> Vec<Hunk> {
+    hunks
+        .iter()
+        .map(|hunk| Hunk {
+            old: hunk.new.clone(),
+            new: hunk.old.clone(),
+        })
+        .collect()
+}
+
+/// Where line `line` of the old text is in the new one, given the `hunks`
+/// between them: moved along with the lines around it or, if it was
+/// changed, as far into what replaced it as it was into what it was.
+#[must_use]
+pub fn map_line(hunks: &[Hunk], line: usize) -> usize {
+    let mut last = Hunk {
+        old: 0..0,
+        new: 0..0,
+    };
+    for hunk in hunks {
+        if line < hunk.old.start {
+            break;
+        }
+        if line < hunk.old.end {
+            let into = min(
+                line.saturating_sub(hunk.old.start),
+                hunk.new.len().saturating_sub(1),
+            );
+            return hunk.new.start.saturating_add(into);
+        }
+        last = hunk.clone();
+    }
+    last.new
+        .end
+        .saturating_add(line.saturating_sub(last.old.end))
+}
+
+/// The hunks that turn `old` into `new` as a unified diff: a `---` and a
+/// `+++` line naming the two, then the hunks with up to [`CONTEXT`] lines
+/// around them, under `@@` lines saying where they are. Lines deleted
+/// start with `-`, lines inserted with `+` and the others with a space;
+/// hunks close enough to share context are shown together. Empty if the
+/// two are the same.
+#[must_use]
+pub fn unified(old: &[&str], new: &[&str], names: [&str; 2]) -> String {
+    let hunks = diff(old, new);
+    let mut text = String::new();
+    if hunks.is_empty() {
+        return text;
+    }
+    let [old_name, new_name] = names;
+    let _ = writeln!(text, "--- {old_name}\n+++ {new_name}");
+    let mut rest = hunks.as_slice();
+    while let Some(first) = rest.first() {
+        let together = rest
+            .windows(2)
+            .take_while(|pair| match pair {
+                [a, b] => b.old.start.saturating_sub(a.old.end) <= CONTEXT.saturating_mul(2),
+                _ => false,
+            })
+            .count()
+            .saturating_add(1);
+        let (group, after) = rest.split_at(min(together, rest.len()));
+        rest = after;
+        let Some(last) = group.last() else {
+            break;
+        };
+        let lead = min(first.old.start, CONTEXT);
+        let old_start = first.old.start.saturating_sub(lead);
+        let new_start = first.new.start.saturating_sub(lead);
+        let old_end = min(last.old.end.saturating_add(CONTEXT), old.len());
+        let new_end = last
+            .new
+            .end
+            .saturating_add(old_end.saturating_sub(last.old.end));
+        let _ = writeln!(
+            text,
+            "@@ -{} +{} @@",
+            lines(old_start, old_end.saturating_sub(old_start)),
+            lines(new_start, new_end.saturating_sub(new_start)),
+        );
+        let mut push = |mark: char, lines: &[&str]| {
+            for line in lines {
+                text.push(mark);
+                text.push_str(line);
+                text.push('\n');
+            }
+        };
+        let mut at = old_start;
+        for hunk in group {
+            push(' ', old.get(at..hunk.old.start).unwrap_or_default());
+            push('-', old.get(hunk.old.clone()).unwrap_or_default());
+            push('+', new.get(hunk.new.clone()).unwrap_or_default());
+            at = hunk.old.end;
+        }
+        push(' ', old.get(at..old_end).unwrap_or_default());
+    }
+    text
+}
+
+/// `count` lines from line `start` (from 0) as a unified diff numbers
+/// them: from 1, the count left out if it is 1, and none at all numbered
+/// by the line before them.
+fn lines(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{start},0"),
+        1 => start.saturating_add(1).to_string(),
+        _ => format!("{},{count}", start.saturating_add(1)),
+    }
+}
+
+/// The lines `old` and `new` have in common, as pairs of their indexes in
+/// each, in order; `None` if telling would take more than [`MAX_COST`]
+/// changes.
+fn common_lines<T: PartialEq>(old: &[T], new: &[T]) -> Option<Vec<(usize, usize)>> {
+    let (n, m) = (old.len(), new.len());
+    let limit = min(n.saturating_add(m), MAX_COST);
+    // How far along the old text each diagonal k (x - y) gets, at index
+    // k + offset.
+    let offset = limit.saturating_add(1);
+    let mut furthest = vec![0_usize; offset.saturating_mul(2).saturating_add(1)];
+    let mut trace = Vec::new();
+    let mut cost = None;
+    'search: for d in 0..=limit {
+        trace.push(furthest.clone());
+        for diagonal in (offset.saturating_sub(d)..=offset.saturating_add(d)).step_by(2) {
+            let mut x = next_x(&furthest, diagonal, d, offset);
+            let mut y = x.saturating_add(offset).saturating_sub(diagonal);
+            while x < n && y < m && old.get(x) == new.get(y) {
+                x = x.saturating_add(1);
+                y = y.saturating_add(1);
+            }
+            if let Some(slot) = furthest.get_mut(diagonal) {
+                *slot = x;
+            }
+            if x >= n && y >= m {
+                cost = Some(d);
+                break 'search;
+            }
+        }
+    }
+    cost?;
+    // Back from the end, one change at a time.
+    let mut matches = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, furthest) in trace.iter().enumerate().rev() {
+        let diagonal = x.saturating_add(offset).saturating_sub(y);
+        let previous = if went_down(furthest, diagonal, d, offset) {
+            diagonal.saturating_add(1)
+        } else {
+            diagonal.saturating_sub(1)
+        };
+        let previous_x = furthest.get(previous).copied().unwrap_or_default();
+        let previous_y = previous_x.saturating_add(offset).saturating_sub(previous);
+        while x > previous_x && y > previous_y {
+            x = x.saturating_sub(1);
+            y = y.saturating_sub(1);
+            matches.push((x, y));
+        }
+        if d > 0 {
+            (x, y) = (previous_x, previous_y);
+        }
+    }
+    matches.reverse();
+    Some(matches)
+}
+
+/// Whether the step onto `diagonal` after `d` changes is an insertion
+/// (down from the diagonal above) rather than a deletion.
+fn went_down(furthest: &[usize], diagonal: usize, d: usize, offset: usize) -> bool {
+    let at = |index: usize| furthest.get(index).copied().unwrap_or_default();
+    diagonal == offset.saturating_sub(d)
+        || (diagonal != offset.saturating_add(d)
+            && at(diagonal.saturating_sub(1)) < at(diagonal.saturating_add(1)))
+}
+
+/// How far along the old text a path onto `diagonal` with `d` changes
+/// starts, before the lines both texts share.
+fn next_x(furthest: &[usize], diagonal: usize, d: usize, offset: usize) -> usize {
+    let at = |index: usize| furthest.get(index).copied().unwrap_or_default();
+    if went_down(furthest, diagonal, d, offset) {
+        at(diagonal.saturating_add(1))
+    } else {
+        at(diagonal.saturating_sub(1)).saturating_add(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(old: Range<usize>, new: Range<usize>) -> Hunk {
+        Hunk { old, new }
+    }
+
+    #[test]
+    fn test_finds_the_fewest_changes() {
+        let old = ["a", "b", "c", "a", "b", "b", "a"];
+        let new = ["c", "b", "a", "b", "a", "c"];
+        let hunks = diff(&old, &new);
+        let cost: usize = hunks.iter().map(|h| h.old.len() + h.new.len()).sum();
+        assert_eq!(cost, 5);
+        // Applying the hunks gives the new text back.
+        let mut patched = Vec::new();
+        let mut at = 0;
+        for hunk in &hunks {
+            patched.extend_from_slice(&old[at..hunk.old.start]);
+            patched.extend_from_slice(&new[hunk.new.clone()]);
+            at = hunk.old.end;
+        }
+        patched.extend_from_slice(&old[at..]);
+        assert_eq!(patched, new);
+
+        assert_eq!(diff(&["x", "y"], &["x", "y"]), []);
+        assert_eq!(diff(&["x", "y", "z"], &["x", "z"]), [hunk(1..2, 1..1)]);
+        assert_eq!(diff::<&str>(&[], &["x"]), [hunk(0..0, 0..1)]);
+    }
+
+    #[test]
+    fn test_lines_are_mapped_past_the_hunks() {
+        // One line became three, and later two became none.
+        let hunks = [hunk(1..2, 1..4), hunk(5..7, 7..7)];
+        let mapped: Vec<usize> = (0..9).map(|line| map_line(&hunks, line)).collect();
+        assert_eq!(mapped, [0, 1, 4, 5, 6, 7, 7, 7, 8]);
+        let back: Vec<usize> = (0..9)
+            .map(|line| map_line(&reverse(&hunks), line))
+            .collect();
+        assert_eq!(back, [0, 1, 1, 1, 2, 3, 4, 7, 8]);
+    }
+
+    #[test]
+    fn test_unified_diff_shows_hunks_in_context() {
+        let old: Vec<String> = (1..=20).map(|n| n.to_string()).collect();
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let mut new = old.clone();
+        new[1] = "two";
+        new.remove(5);
+        new.insert(17, "new");
+        let names = ["a.txt", "a.txt (unsaved)"];
+        assert_eq!(
+            unified(&old, &new, names),
+            "--- a.txt\n+++ a.txt (unsaved)\n\
+             @@ -1,9 +1,8 @@\n 1\n-2\n+two\n 3\n 4\n 5\n-6\n 7\n 8\n 9\n\
+             @@ -16,5 +15,6 @@\n 16\n 17\n 18\n+new\n 19\n 20\n"
+        );
+        assert_eq!(
+            unified(&[], &["x"], names),
+            "--- a.txt\n+++ a.txt (unsaved)\n@@ -0,0 +1 @@\n+x\n"
+        );
+        assert_eq!(unified(&old, &old, names), "");
+    }
+}
@@ -0,0 +1,131 @@
+//! # Directory Listings
+//!
+//! Opening a directory (`hecto .`, `edit src`) shows what is in it, one
+//! entry per line, the way netrw does: `../` first, then the directories,
+//! each with a `/` after its name, then the files, each group sorted by
+//! name. `Enter` opens the entry under the cursor (a directory is listed
+//! in its place) and `-` goes up to the parent. `%` asks for the name of
+//! an entry to create (`create NAME`; a name ending in `/` makes a
+//! directory), `R` for a new name for the one under the cursor
+//! (`rename NAME`), and `D` deletes it once `y` confirms; `duplicate NAME`
+//! and `chmod MODE` work on it too (see [`files`](crate::files)). The
+//! listing is read-only, and read again after each of these.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::error::{Error, Result};
+
+/// The line that stands for the directory above.
+pub const PARENT: &str = "../";
+
+/// What is in `dir`, one entry per line.
+///
+/// # Errors
+///
+/// Fails if the directory cannot be read.
+pub fn list(dir: &Path) -> Result<String> {
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|err| Error::at(dir, err))? {
+        let entry = entry.map_err(|err| Error::at(dir, err))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.path().is_dir() {
+            dirs.push(format!("{name}/"));
+        } else {
+            files.push(name);
+        }
+    }
+    dirs.sort();
+    files.sort();
+    let mut text = format!("{PARENT}\n");
+    for name in dirs.iter().chain(&files) {
+        text.push_str(name);
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+/// The entry of `dir` that `line` of its listing names, if any.
+#[must_use]
+pub fn entry(dir: &Path, line: &str) -> Option<PathBuf> {
+    if line == PARENT {
+        return Some(dir.parent().unwrap_or(dir).to_path_buf());
+    }
+    let name = line.strip_suffix('/').unwrap_or(line);
+    (!name.is_empty()).then(|| dir.join(name))
+}
+
+/// The line of the listing of its directory that names `path`.
+#[must_use]
+pub fn line_for(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_string_lossy();
+    Some(if path.is_dir() {
+        format!("{name}/")
+    } else {
+        name.into_owned()
+    })
+}
+
+/// Makes `name` in `dir`: a directory (with any missing above it) if it
+/// ends in `/`, or else an empty file, which must not be there yet.
+///
+/// # Errors
+///
+/// Fails if it cannot be made, or the file is already there.
+pub fn create(dir: &Path, name: &str) -> Result<PathBuf> {
+    let path = dir.join(name);
+    let made = if name.ends_with('/') {
+        fs::create_dir_all(&path)
+    } else {
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map(drop)
+    };
+    made.map_err(|err| Error::at(&path, err))?;
+    Ok(path)
+}
+
+/// Deletes `path`: a file, or a directory and everything in it.
+///
+/// # Errors
+///
+/// Fails if anything in it cannot be deleted.
+pub fn delete(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+    .map_err(|err| Error::at(path, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directories_are_listed_before_files() {
+        let dir = std::env::temp_dir().join(format!("hecto-directory-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("b.txt"), "").unwrap();
+        fs::write(dir.join("a.txt"), "").unwrap();
+        assert_eq!(list(&dir).unwrap(), "../\nsrc/\na.txt\nb.txt\n");
+        assert_eq!(entry(&dir, "src/"), Some(dir.join("src")));
+        assert_eq!(entry(&dir, PARENT).as_deref(), dir.parent());
+        assert_eq!(entry(&dir, ""), None);
+        assert_eq!(line_for(&dir.join("src")).as_deref(), Some("src/"));
+
+        let made = create(&dir, "docs/").unwrap();
+        assert!(made.is_dir());
+        assert!(create(&dir, "a.txt").is_err());
+        delete(&dir.join("src")).unwrap();
+        delete(&dir.join("a.txt")).unwrap();
+        assert_eq!(list(&dir).unwrap(), "../\ndocs/\nb.txt\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,50 @@
+//! # XDG Base Directories
+//!
+//! Resolves the per-user directories hecto keeps its files in, following the
+//! [XDG Base Directory](https://specifications.freedesktop.org/basedir-spec/latest/)
+//! conventions: an explicit `XDG_*_HOME` variable wins, otherwise we fall back
+//! to the usual locations under `$HOME`.
+//!
+//! Every function returns `None` when neither variable is usable, in which
+//! case callers should simply skip persisting anything.
+
+use std::{env, path::PathBuf};
+
+/// The application sub-directory created inside each base directory.
+const APP_DIR: &str = "hecto";
+
+/// Directory for persistent data (e.g. the recent-files list).
+///
+/// `$XDG_DATA_HOME/hecto` or `~/.local/share/hecto`.
+#[must_use]
+pub fn data_dir() -> Option<PathBuf> {
+    base_dir("XDG_DATA_HOME", &[".local", "share"])
+}
+
+/// Directory for user configuration (e.g. `config.toml`).
+///
+/// `$XDG_CONFIG_HOME/hecto` or `~/.config/hecto`.
+#[must_use]
+pub fn config_dir() -> Option<PathBuf> {
+    base_dir("XDG_CONFIG_HOME", &[".config"])
+}
+
+/// Directory for state worth keeping but not backing up (e.g. the log).
+///
+/// `$XDG_STATE_HOME/hecto` or `~/.local/state/hecto`.
+#[must_use]
+pub fn state_dir() -> Option<PathBuf> {
+    base_dir("XDG_STATE_HOME", &[".local", "state"])
+}
+
+fn base_dir(var: &str, fallback: &[&str]) -> Option<PathBuf> {
+    let base = env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| {
+            let mut home = PathBuf::from(env::var_os("HOME")?);
+            home.extend(fallback);
+            Some(home)
+        })?;
+    Some(base.join(APP_DIR))
+}
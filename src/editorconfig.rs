@@ -0,0 +1,324 @@
+//! # `.editorconfig` Files
+//!
+//! Reads the [`.editorconfig`](https://editorconfig.org) files that apply to
+//! a file: the ones in its directory and every directory above it, up to
+//! and including one that says `root = true`. Closer files win over
+//! farther ones, and later sections over earlier ones.
+//!
+//! These properties are understood; the rest are ignored:
+//!
+//! - `indent_style` (`tab` or `space`) and `indent_size`
+//! - `tab_width`
+//! - `end_of_line` (`lf` or `crlf`)
+//! - `trim_trailing_whitespace` and `insert_final_newline`
+//!
+//! Section globs support `*`, `**`, `?`, `[abc]`, `[!abc]` and `{a,b}`
+//! (but not numeric ranges).
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::buffer::{Indent, LineEnding, Settings};
+
+const FILE_NAME: &str = ".editorconfig";
+
+/// The properties that apply to one file, as raw values. Values are kept
+/// as written (lowercased) until [`Properties::apply`], so a closer file
+/// can still `unset` them.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Properties {
+    values: HashMap<String, String>,
+}
+
+impl Properties {
+    /// Collects the properties for `path` from the `.editorconfig` files
+    /// above it. Unreadable files are skipped.
+    #[must_use]
+    pub fn for_file(path: &Path) -> Self {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut files: Vec<(PathBuf, String)> = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            let Ok(text) = fs::read_to_string(dir.join(FILE_NAME)) else {
+                continue;
+            };
+            let is_root = parse(&text).0;
+            files.push((dir.to_path_buf(), text));
+            if is_root {
+                break;
+            }
+        }
+        let mut properties = Self::default();
+        // Farthest first, so closer files override.
+        for (dir, text) in files.iter().rev() {
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            properties.add_file(text, &relative);
+        }
+        properties
+    }
+
+    /// Adds the sections of one `.editorconfig` file that match `relative`,
+    /// the path of the file relative to that `.editorconfig`.
+    fn add_file(&mut self, text: &str, relative: &str) {
+        for section in parse(text).1 {
+            if !section_matches(&section.glob, relative) {
+                continue;
+            }
+            for (key, value) in section.pairs {
+                if value == "unset" {
+                    self.values.remove(&key);
+                } else {
+                    self.values.insert(key, value);
+                }
+            }
+        }
+    }
+
+    fn number(&self, key: &str) -> Option<usize> {
+        self.values
+            .get(key)?
+            .parse()
+            .ok()
+            .filter(|number| *number > 0)
+    }
+
+    fn flag(&self, key: &str) -> Option<bool> {
+        match self.values.get(key)?.as_str() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Overrides `settings` with whatever the properties specify.
+    pub fn apply(&self, settings: &mut Settings) {
+        let indent_size = self.number("indent_size");
+        if let Some(tab_width) = self.number("tab_width").or(indent_size) {
+            settings.tab_width = tab_width;
+        }
+        match self.values.get("indent_style").map(String::as_str) {
+            Some("tab") => settings.indent = Indent::Tabs,
+            Some("space") => {
+                settings.indent = Indent::Spaces(indent_size.unwrap_or(settings.tab_width));
+            }
+            _ => {}
+        }
+        match self.values.get("end_of_line").map(String::as_str) {
+            Some("lf") => settings.end_of_line = Some(LineEnding::Lf),
+            Some("crlf") => settings.end_of_line = Some(LineEnding::Crlf),
+            _ => {}
+        }
+        if let Some(trim) = self.flag("trim_trailing_whitespace") {
+            settings.trim_trailing_whitespace = trim;
+        }
+        if let Some(insert) = self.flag("insert_final_newline") {
+            settings.insert_final_newline = insert;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Section {
+    glob: String,
+    pairs: Vec<(String, String)>,
+}
+
+/// Splits a file into whether it is a root file and its sections.
+fn parse(text: &str) -> (bool, Vec<Section>) {
+    let mut root = false;
+    let mut sections: Vec<Section> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(glob) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push(Section {
+                glob: glob.to_string(),
+                pairs: Vec::new(),
+            });
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim().to_ascii_lowercase();
+        match sections.last_mut() {
+            Some(section) => section.pairs.push((key, value)),
+            None if key == "root" => root = value == "true",
+            None => {}
+        }
+    }
+    (root, sections)
+}
+
+/// Whether a section glob applies to `relative`. Globs without a `/` match
+/// the file name in any directory; others are anchored to the directory of
+/// the `.editorconfig`.
+fn section_matches(glob: &str, relative: &str) -> bool {
+    let (glob, text) = if glob.contains('/') {
+        (glob.strip_prefix('/').unwrap_or(glob), relative)
+    } else {
+        (glob, relative.rsplit('/').next().unwrap_or(relative))
+    };
+    let text: Vec<char> = text.chars().collect();
+    expand_braces(glob)
+        .iter()
+        .any(|glob| glob_matches(&glob.chars().collect::<Vec<_>>(), &text))
+}
+
+/// Expands `{a,b}` alternatives into separate globs.
+fn expand_braces(glob: &str) -> Vec<String> {
+    let Some(open) = glob.find('{') else {
+        return vec![glob.to_string()];
+    };
+    let mut depth = 0_usize;
+    let mut close = None;
+    let mut splits = Vec::new();
+    for (index, ch) in glob.char_indices().skip_while(|(index, _)| *index < open) {
+        match ch {
+            '{' => depth = depth.saturating_add(1),
+            '}' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    close = Some(index);
+                    break;
+                }
+            }
+            ',' if depth == 1 => splits.push(index),
+            _ => {}
+        }
+    }
+    let Some(close) = close.filter(|_| !splits.is_empty()) else {
+        // Not an alternative (`{}`, `{a}` or unbalanced): keep it literal.
+        return vec![glob.to_string()];
+    };
+    let (prefix, suffix) = (&glob[..open], &glob[close.saturating_add(1)..]);
+    let mut bounds = vec![open];
+    bounds.extend(splits);
+    bounds.push(close);
+    bounds
+        .windows(2)
+        .flat_map(|pair| {
+            let alternative = &glob[pair[0].saturating_add(1)..pair[1]];
+            expand_braces(&format!("{prefix}{alternative}{suffix}"))
+        })
+        .collect()
+}
+
+fn glob_matches(glob: &[char], text: &[char]) -> bool {
+    match glob {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|skip| glob_matches(rest, &text[skip..])),
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|skip| *skip == 0 || text[skip.saturating_sub(1)] != '/')
+            .any(|skip| glob_matches(rest, &text[skip..])),
+        ['?', rest @ ..] => {
+            matches!(text, [ch, ..] if *ch != '/') && glob_matches(rest, &text[1..])
+        }
+        ['[', class @ ..] => {
+            let Some(end) = class.iter().position(|ch| *ch == ']') else {
+                return text.first() == Some(&'[') && glob_matches(class, &text[1..]);
+            };
+            let (negated, set) = match &class[..end] {
+                ['!', set @ ..] => (true, set),
+                set => (false, set),
+            };
+            matches!(text, [ch, ..] if set.contains(ch) != negated)
+                && glob_matches(&class[end.saturating_add(1)..], &text[1..])
+        }
+        ['\\', escaped, rest @ ..] | [escaped, rest @ ..] => {
+            text.first() == Some(escaped) && glob_matches(rest, &text[1..])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties(text: &str, relative: &str) -> Settings {
+        let mut properties = Properties::default();
+        properties.add_file(text, relative);
+        let mut settings = Settings::default();
+        properties.apply(&mut settings);
+        settings
+    }
+
+    #[test]
+    fn test_globs() {
+        assert!(section_matches("*", "src/main.rs"));
+        assert!(section_matches("*.rs", "src/main.rs"));
+        assert!(!section_matches("*.rs", "src/main.rsx"));
+        assert!(section_matches("*.{js,ts}", "web/app.ts"));
+        assert!(section_matches("Makefile", "sub/Makefile"));
+        assert!(section_matches("src/*.rs", "src/lib.rs"));
+        assert!(!section_matches("src/*.rs", "src/bin/main.rs"));
+        assert!(section_matches("/src/**.rs", "src/bin/main.rs"));
+        assert!(section_matches("file?.[ch]", "file1.h"));
+        assert!(!section_matches("file.[!ch]", "file.c"));
+        assert_eq!(expand_braces("a{b,{c,d}}e"), vec!["abe", "ace", "ade"]);
+    }
+
+    #[test]
+    fn test_properties() {
+        let settings = properties(
+            "root = true\n\
+             [*]\n\
+             indent_style = space\n\
+             indent_size = 2\n\
+             end_of_line = CRLF\n\
+             trim_trailing_whitespace = true\n\
+             insert_final_newline = true\n\
+             [Makefile]\n\
+             indent_style = tab\n\
+             tab_width = 8\n",
+            "main.c",
+        );
+        assert_eq!(settings.indent, Indent::Spaces(2));
+        assert_eq!(settings.tab_width, 2);
+        assert_eq!(settings.end_of_line, Some(LineEnding::Crlf));
+        assert!(settings.trim_trailing_whitespace);
+        assert!(settings.insert_final_newline);
+
+        let settings = properties(
+            "[*]\nindent_style = space\nindent_size = 2\n\
+             [Makefile]\nindent_style = tab\ntab_width = 8\nindent_size = unset\n",
+            "Makefile",
+        );
+        assert_eq!(settings.indent, Indent::Tabs);
+        assert_eq!(settings.tab_width, 8);
+    }
+
+    #[test]
+    fn test_closer_files_override_and_root_stops_the_search() {
+        let base = std::env::temp_dir().join(format!("hecto-editorconfig-{}", std::process::id()));
+        let sub = base.join("project").join("src");
+        fs::create_dir_all(&sub).unwrap();
+        fs::write(base.join(FILE_NAME), "[*]\nindent_size = 3\n").unwrap();
+        fs::write(
+            base.join("project").join(FILE_NAME),
+            "root = true\n[*]\nindent_style = space\nindent_size = 4\n",
+        )
+        .unwrap();
+        fs::write(sub.join(FILE_NAME), "[*.rs]\nindent_size = 2\n").unwrap();
+        let file = sub.join("main.rs");
+
+        let mut settings = Settings::default();
+        Properties::for_file(&file).apply(&mut settings);
+        assert_eq!(settings.indent, Indent::Spaces(2));
+
+        fs::remove_file(base.join("project").join(FILE_NAME)).unwrap();
+        let mut settings = Settings::default();
+        Properties::for_file(&file).apply(&mut settings);
+        assert_eq!(settings.indent, Indent::Tabs);
+        assert_eq!(settings.tab_width, 2);
+        fs::remove_dir_all(base).unwrap();
+    }
+}
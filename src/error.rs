@@ -1,11 +1,93 @@
+//! # Errors
+//!
+//! One error type for the whole crate. Failures on a particular file carry
+//! its path, so the message shown to the user says which file was at fault
+//! (`notes.txt: permission denied`) rather than just what went wrong; use
+//! [`Error::at`] to attach it.
+//!
+//! Most errors are not fatal: the editor shows them in the status bar and
+//! carries on. Only failing to read input or draw the screen ends it.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
 use derive_more::{derive::Display, From};
 
 #[derive(Debug, From, Display)]
 pub enum Error {
     #[from]
-    Io(std::io::Error),
+    Io(io::Error),
     #[from]
     TryFromInt(std::num::TryFromIntError),
+    #[display("{}: file not found", path.display())]
+    FileNotFound { path: PathBuf },
+    #[display("{}: permission denied", path.display())]
+    PermissionDenied { path: PathBuf },
+    #[display("{}: not valid UTF-8", path.display())]
+    InvalidUtf8 { path: PathBuf },
+    /// Any other I/O failure on a file.
+    #[display("{}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+    /// A line of a configuration file that could not be understood.
+    #[display("line {line}: {message}")]
+    ConfigParse { line: usize, message: String },
+    /// A [shell](crate::shell) command that failed.
+    #[display("{command}: {message}")]
+    Command { command: String, message: String },
+    /// A language there is no [spelling](crate::spell) dictionary for.
+    #[display("no dictionary for {lang}")]
+    NoDictionary { lang: String },
+    /// A command of a [script](crate::script) that failed.
+    #[display("{}:{line}: {message}", path.display())]
+    Script {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl Error {
+    /// Attaches `path` to an I/O error on that file, picking the specific
+    /// variant where there is one.
+    #[must_use]
+    pub fn at(path: &Path, err: io::Error) -> Self {
+        let path = path.to_path_buf();
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::FileNotFound { path },
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied { path },
+            // What `read_to_string` reports for bytes that are not UTF-8.
+            io::ErrorKind::InvalidData => Self::InvalidUtf8 { path },
+            _ => Self::File { path, source: err },
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_name_the_file() {
+        let path = Path::new("notes.txt");
+        let at = |kind| Error::at(path, io::Error::from(kind)).to_string();
+        assert_eq!(at(io::ErrorKind::NotFound), "notes.txt: file not found");
+        assert_eq!(
+            at(io::ErrorKind::PermissionDenied),
+            "notes.txt: permission denied"
+        );
+        assert_eq!(at(io::ErrorKind::InvalidData), "notes.txt: not valid UTF-8");
+        assert!(at(io::ErrorKind::WriteZero).starts_with("notes.txt: "));
+        assert_eq!(
+            Error::ConfigParse {
+                line: 3,
+                message: "expected `key = value`".to_string()
+            }
+            .to_string(),
+            "line 3: expected `key = value`"
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! # Event Sources
+//!
+//! Where the editor's main loop gets its input from. [`CrosstermEvents`]
+//! reads the real keyboard (and resize notifications); [`ScriptedEvents`]
+//! replays a fixed list of events, so the loop can run in tests or be driven
+//! by another program.
+
+use std::{collections::VecDeque, io, time::Duration};
+
+use crossterm::event::Event;
+
+use crate::error::Result;
+
+pub trait EventSource {
+    /// Waits for the next event and returns it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no event can be read.
+    fn read(&mut self) -> Result<Event>;
+
+    /// Waits at most `timeout` for an event, returning whether one is ready
+    /// to be [read](EventSource::read) without blocking.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the input cannot be checked.
+    fn poll(&mut self, timeout: Duration) -> Result<bool>;
+}
+
+/// Input from the terminal, through crossterm.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CrosstermEvents;
+
+impl EventSource for CrosstermEvents {
+    fn read(&mut self) -> Result<Event> {
+        crossterm::event::read().map_err(Into::into)
+    }
+
+    fn poll(&mut self, timeout: Duration) -> Result<bool> {
+        crossterm::event::poll(timeout).map_err(Into::into)
+    }
+}
+
+/// A fixed sequence of events, returned in order. Polling never waits: an
+/// event is either queued or it is not.
+#[derive(Debug, Default, Clone)]
+pub struct ScriptedEvents {
+    events: VecDeque<Event>,
+}
+
+impl ScriptedEvents {
+    #[must_use]
+    pub fn new(events: impl IntoIterator<Item = Event>) -> Self {
+        Self {
+            events: events.into_iter().collect(),
+        }
+    }
+
+    /// Queues another event after the ones already scripted.
+    pub fn push(&mut self, event: Event) {
+        self.events.push_back(event);
+    }
+
+    /// How many events have not been read yet.
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.events.len()
+    }
+}
+
+impl EventSource for ScriptedEvents {
+    /// Returns the next scripted event; running out is an error rather than
+    /// a hang, so a script that forgets to quit fails the test.
+    fn read(&mut self) -> Result<Event> {
+        self.events.pop_front().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::UnexpectedEof, "no more scripted events").into()
+        })
+    }
+
+    fn poll(&mut self, _timeout: Duration) -> Result<bool> {
+        Ok(!self.events.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyEvent};
+
+    #[test]
+    fn test_scripted_events_are_read_in_order() {
+        let a = Event::Key(KeyEvent::from(KeyCode::Char('a')));
+        let mut events = ScriptedEvents::new([a.clone()]);
+        events.push(Event::Resize(10, 5));
+
+        assert!(events.poll(Duration::ZERO).unwrap());
+        assert_eq!(events.read().unwrap(), a);
+        assert_eq!(events.remaining(), 1);
+        assert_eq!(events.read().unwrap(), Event::Resize(10, 5));
+
+        assert!(!events.poll(Duration::from_secs(1)).unwrap());
+        assert!(events.read().is_err());
+    }
+}
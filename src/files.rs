@@ -0,0 +1,236 @@
+//! # File Operations
+//!
+//! The commands that change the buffer's file on disk, or the entry under
+//! the cursor in a [directory listing](crate::directory):
+//!
+//! - `rename NAME` moves it to `NAME`, taken from the directory it is in;
+//!   the buffer then belongs to the new name.
+//! - `duplicate NAME` copies it, as last saved, to `NAME`.
+//! - `chmod MODE` changes its permissions, to an octal mode like `755` or
+//!   as `chmod` would, as in `u+x` or `go-w,a+r`.
+//! - `delete` asks, then moves it to the trash with the first of
+//!   `gio trash`, `trash-put` and `trash` that works, or deletes it for
+//!   good if none does (or the `trash` option is off). The buffer keeps
+//!   the text, but no longer has a file.
+//!
+//! None of them replaces a file that is already there.
+
+use std::{
+    fs, io,
+    path::{self, Path},
+    process::{Command, Stdio},
+};
+
+use crate::{
+    directory,
+    error::{Error, Result},
+};
+
+/// The programs that move files to the trash, tried in turn.
+const TRASHERS: &[&[&str]] = &[&["gio", "trash"], &["trash-put"], &["trash"]];
+
+/// Moves `from` to `to`, which must not be there yet.
+///
+/// # Errors
+///
+/// Fails if `to` is there, or `from` cannot be moved.
+pub fn rename(from: &Path, to: &Path) -> Result<()> {
+    refuse_existing(to)?;
+    fs::rename(from, to).map_err(|err| Error::at(from, err))
+}
+
+/// Copies the file `from` to `to`, which must not be there yet.
+///
+/// # Errors
+///
+/// Fails if `to` is there, or `from` cannot be copied.
+pub fn duplicate(from: &Path, to: &Path) -> Result<()> {
+    refuse_existing(to)?;
+    fs::copy(from, to).map_err(|err| Error::at(from, err))?;
+    Ok(())
+}
+
+/// Moves `path` to the trash if `trash` allows and a program for it works,
+/// or else [deletes](directory::delete) it. Returns whether it went to the
+/// trash.
+///
+/// # Errors
+///
+/// Fails if it cannot be deleted.
+pub fn delete(path: &Path, trash: bool) -> Result<bool> {
+    if trash {
+        // Absolute, so that no name starting with `-` is taken for an option.
+        let absolute = path::absolute(path).map_err(|err| Error::at(path, err))?;
+        for trasher in TRASHERS {
+            let Some((program, args)) = trasher.split_first() else {
+                continue;
+            };
+            let trashed = Command::new(program)
+                .args(args)
+                .arg(&absolute)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success());
+            if trashed && !absolute.exists() {
+                return Ok(true);
+            }
+        }
+    }
+    directory::delete(path)?;
+    Ok(false)
+}
+
+/// A permission change, as `chmod` takes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// The permission bits to set, as in `644`.
+    Octal(u32),
+    /// Changes to the bits there are, as in `u+x,go-w`: who they are for
+    /// (a mask of the bits concerned), the operator (`+`, `-` or `=`) and
+    /// the permissions, for the owner.
+    Symbolic(Vec<(u32, char, u32)>),
+}
+
+impl Mode {
+    /// Reads a mode like `755` or `u+x,go-w`; `None` if it is neither.
+    #[must_use]
+    pub fn parse(spec: &str) -> Option<Self> {
+        if !spec.is_empty() && spec.len() <= 4 && spec.chars().all(|ch| ch.is_digit(8)) {
+            return u32::from_str_radix(spec, 8).ok().map(Self::Octal);
+        }
+        spec.split(',')
+            .map(|clause| {
+                let at = clause.find(['+', '-', '='])?;
+                let (who, rest) = clause.split_at(at);
+                let mut rest = rest.chars();
+                let operator = rest.next()?;
+                let mut mask = 0;
+                for ch in who.chars() {
+                    mask |= match ch {
+                        'u' => 0o700,
+                        'g' => 0o070,
+                        'o' => 0o007,
+                        'a' => 0o777,
+                        _ => return None,
+                    };
+                }
+                let mut permissions = 0;
+                for ch in rest {
+                    permissions |= match ch {
+                        'r' => 0o4,
+                        'w' => 0o2,
+                        'x' => 0o1,
+                        _ => return None,
+                    };
+                }
+                Some((if mask == 0 { 0o777 } else { mask }, operator, permissions))
+            })
+            .collect::<Option<_>>()
+            .map(Self::Symbolic)
+    }
+
+    /// The permission bits `mode` becomes.
+    #[must_use]
+    pub fn apply(&self, mode: u32) -> u32 {
+        match self {
+            Self::Octal(bits) => (mode & !0o7777) | bits,
+            Self::Symbolic(clauses) => {
+                clauses.iter().fold(mode, |mode, &(mask, operator, bits)| {
+                    let bits = bits.wrapping_mul(0o111) & mask;
+                    match operator {
+                        '+' => mode | bits,
+                        '-' => mode & !bits,
+                        _ => (mode & !mask) | bits,
+                    }
+                })
+            }
+        }
+    }
+}
+
+/// Changes the permissions of `path` as `mode` says. Returns the bits it
+/// ends up with.
+///
+/// # Errors
+///
+/// Fails if they cannot be changed, or there are no such permissions here.
+#[cfg(unix)]
+pub fn chmod(path: &Path, mode: &Mode) -> Result<u32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let metadata = fs::metadata(path).map_err(|err| Error::at(path, err))?;
+    let bits = mode.apply(metadata.permissions().mode());
+    fs::set_permissions(path, fs::Permissions::from_mode(bits))
+        .map_err(|err| Error::at(path, err))?;
+    Ok(bits)
+}
+
+#[cfg(not(unix))]
+pub fn chmod(path: &Path, _mode: &Mode) -> Result<u32> {
+    Err(Error::at(
+        path,
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "permissions are not supported on this platform",
+        ),
+    ))
+}
+
+/// The permission bits of `mode` the way `ls -l` shows them.
+#[must_use]
+pub fn describe(mode: u32) -> String {
+    [
+        0o400, 0o200, 0o100, 0o040, 0o020, 0o010, 0o004, 0o002, 0o001,
+    ]
+    .into_iter()
+    .zip("rwxrwxrwx".chars())
+    .map(|(bit, ch)| if mode & bit == 0 { '-' } else { ch })
+    .collect()
+}
+
+fn refuse_existing(path: &Path) -> Result<()> {
+    if path.symlink_metadata().is_ok() {
+        return Err(Error::at(
+            path,
+            io::Error::from(io::ErrorKind::AlreadyExists),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modes_are_read_like_chmod() {
+        assert_eq!(Mode::parse("755"), Some(Mode::Octal(0o755)));
+        assert_eq!(Mode::parse("u+x").unwrap().apply(0o100_644), 0o100_744);
+        assert_eq!(Mode::parse("+x").unwrap().apply(0o644), 0o755);
+        assert_eq!(Mode::parse("go-w,a+r").unwrap().apply(0o622), 0o644);
+        assert_eq!(Mode::parse("o=r").unwrap().apply(0o777), 0o774);
+        assert_eq!(Mode::parse("644").unwrap().apply(0o100_755), 0o100_644);
+        assert_eq!(Mode::parse("u+z"), None);
+        assert_eq!(Mode::parse("9"), None);
+        assert_eq!(describe(0o754), "rwxr-xr--");
+    }
+
+    #[test]
+    fn test_files_are_not_replaced() {
+        let dir = std::env::temp_dir().join(format!("hecto-files-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        assert!(rename(&dir.join("a.txt"), &dir.join("b.txt")).is_err());
+        assert!(duplicate(&dir.join("a.txt"), &dir.join("b.txt")).is_err());
+        duplicate(&dir.join("a.txt"), &dir.join("c.txt")).unwrap();
+        rename(&dir.join("a.txt"), &dir.join("d.txt")).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("c.txt")).unwrap(), "a");
+        assert!(!dir.join("a.txt").exists());
+        assert!(!delete(&dir.join("d.txt"), false).unwrap());
+        assert!(!dir.join("d.txt").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
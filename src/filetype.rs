@@ -0,0 +1,243 @@
+//! # Filetypes
+//!
+//! What kind of file a buffer holds, worked out when it is opened: from the
+//! file's name (`Makefile`), else its extension (`.rs`), else the
+//! interpreter on a `#!` first line (`#!/usr/bin/env python3`). The
+//! filetype is shown in the status bar and decides how the buffer is
+//! [highlighted](crate::highlight), how it is indented unless the options
+//! or `.editorconfig` say otherwise, how what closes a block is
+//! [lined up](crate::indent) with its start, and what `toggle-comment`
+//! puts in front of lines. Anything not recognized is plain text.
+
+use std::path::Path;
+
+use crate::{
+    buffer::Indent,
+    highlight::{self, Language},
+    indent::Rule,
+};
+
+/// The rules of languages whose blocks are in brackets.
+const BRACKETS: &[Rule] = &[Rule::Bracket('}'), Rule::Bracket(')'), Rule::Bracket(']')];
+
+const PYTHON_RULES: &[Rule] = &[
+    Rule::Bracket('}'),
+    Rule::Bracket(')'),
+    Rule::Bracket(']'),
+    Rule::Word {
+        word: "else",
+        openers: &["if", "elif", "for", "while", "try", "except"],
+        closes: false,
+    },
+    Rule::Word {
+        word: "elif",
+        openers: &["if", "elif"],
+        closes: false,
+    },
+    Rule::Word {
+        word: "except",
+        openers: &["try", "except"],
+        closes: false,
+    },
+    Rule::Word {
+        word: "finally",
+        openers: &["try", "except", "else"],
+        closes: false,
+    },
+];
+
+const SHELL_RULES: &[Rule] = &[
+    Rule::Bracket('}'),
+    Rule::Word {
+        word: "fi",
+        openers: &["if"],
+        closes: true,
+    },
+    Rule::Word {
+        word: "else",
+        openers: &["if"],
+        closes: false,
+    },
+    Rule::Word {
+        word: "elif",
+        openers: &["if"],
+        closes: false,
+    },
+    Rule::Word {
+        word: "done",
+        openers: &["for", "while", "until", "select"],
+        closes: true,
+    },
+    Rule::Word {
+        word: "esac",
+        openers: &["case"],
+        closes: true,
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Filetype {
+    pub name: &'static str,
+    /// The file extensions it is picked for.
+    extensions: &'static [&'static str],
+    /// Whole file names it is picked for.
+    file_names: &'static [&'static str],
+    /// The interpreters a `#!` line may name.
+    interpreters: &'static [&'static str],
+    /// What starts a comment that runs to the end of the line.
+    pub comment: Option<&'static str>,
+    /// How its files are indented by convention, if it has one.
+    pub indent: Option<Indent>,
+    /// How lines that close a block are lined up with its start.
+    pub indent_rules: &'static [Rule],
+    pub language: &'static Language,
+}
+
+impl Default for Filetype {
+    fn default() -> Self {
+        TEXT
+    }
+}
+
+pub const TEXT: Filetype = Filetype {
+    name: "text",
+    extensions: &["txt"],
+    file_names: &[],
+    interpreters: &[],
+    comment: None,
+    indent: None,
+    indent_rules: &[],
+    language: &highlight::PLAIN_TEXT,
+};
+
+/// Diffs and patches, also given to the diffs the editor makes.
+pub const DIFF: Filetype = Filetype {
+    name: "diff",
+    extensions: &["diff", "patch"],
+    file_names: &[],
+    interpreters: &[],
+    comment: None,
+    indent: None,
+    indent_rules: &[],
+    language: &highlight::DIFF,
+};
+
+/// Every filetype there is, plain text last.
+const FILETYPES: &[Filetype] = &[
+    Filetype {
+        name: "rust",
+        extensions: &["rs"],
+        file_names: &[],
+        interpreters: &[],
+        comment: Some("//"),
+        indent: Some(Indent::Spaces(4)),
+        indent_rules: BRACKETS,
+        language: &highlight::RUST,
+    },
+    Filetype {
+        name: "python",
+        extensions: &["py", "pyw"],
+        file_names: &[],
+        interpreters: &["python", "python3"],
+        comment: Some("#"),
+        indent: Some(Indent::Spaces(4)),
+        indent_rules: PYTHON_RULES,
+        language: &highlight::PYTHON,
+    },
+    Filetype {
+        name: "shell",
+        extensions: &["sh", "bash", "zsh"],
+        file_names: &[".bashrc", ".profile", ".zshrc"],
+        interpreters: &["sh", "bash", "zsh", "dash"],
+        comment: Some("#"),
+        indent: None,
+        indent_rules: SHELL_RULES,
+        language: &highlight::SHELL,
+    },
+    Filetype {
+        name: "make",
+        extensions: &["mk"],
+        file_names: &["Makefile", "makefile", "GNUmakefile"],
+        interpreters: &["make"],
+        comment: Some("#"),
+        // Recipes must start with a tab.
+        indent: Some(Indent::Tabs),
+        indent_rules: &[],
+        language: &highlight::CONFIG,
+    },
+    Filetype {
+        name: "toml",
+        extensions: &["toml"],
+        file_names: &["Cargo.lock"],
+        interpreters: &[],
+        comment: Some("#"),
+        indent: None,
+        indent_rules: BRACKETS,
+        language: &highlight::CONFIG,
+    },
+    Filetype {
+        name: "markdown",
+        extensions: &["md", "markdown"],
+        file_names: &[],
+        interpreters: &[],
+        comment: None,
+        indent: None,
+        indent_rules: &[],
+        language: &highlight::PLAIN_TEXT,
+    },
+    DIFF,
+    TEXT,
+];
+
+/// The filetype of the file at `path`, whose first line is `first_line`.
+#[must_use]
+pub fn detect(path: Option<&Path>, first_line: Option<&str>) -> Filetype {
+    let name = path
+        .and_then(Path::file_name)
+        .and_then(|name| name.to_str());
+    let extension = path
+        .and_then(Path::extension)
+        .and_then(|extension| extension.to_str());
+    let interpreter = first_line.and_then(interpreter);
+    let by = |key: Option<&str>, keys: fn(&Filetype) -> &[&str]| {
+        FILETYPES
+            .iter()
+            .find(|filetype| key.is_some_and(|key| keys(filetype).contains(&key)))
+    };
+    by(name, |filetype| filetype.file_names)
+        .or_else(|| by(extension, |filetype| filetype.extensions))
+        .or_else(|| by(interpreter, |filetype| filetype.interpreters))
+        .copied()
+        .unwrap_or(TEXT)
+}
+
+/// The program a `#!` line runs the file with: `python3` for both
+/// `#!/usr/bin/python3` and `#!/usr/bin/env -S python3 -u`.
+fn interpreter(line: &str) -> Option<&str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let program = words.next()?.rsplit('/').next()?;
+    if program != "env" {
+        return Some(program);
+    }
+    words.find(|word| !word.starts_with('-') && !word.contains('='))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(path: &str, first_line: &str) -> &'static str {
+        detect(Some(Path::new(path)), Some(first_line)).name
+    }
+
+    #[test]
+    fn test_detect_by_name_extension_and_shebang() {
+        assert_eq!(name("src/main.rs", "fn main() {}"), "rust");
+        assert_eq!(name("build/Makefile", "all:"), "make");
+        assert_eq!(name("run", "#!/bin/bash"), "shell");
+        assert_eq!(name("tool", "#!/usr/bin/env -S python3 -u"), "python");
+        assert_eq!(name("notes", "#!not really"), "text");
+        assert_eq!(detect(None, None), TEXT);
+        assert_eq!(Filetype::default().comment, None);
+    }
+}
@@ -0,0 +1,145 @@
+//! # Follow Mode
+//!
+//! Watches a file that keeps growing (a log, typically) and hands back
+//! whatever was appended since the last check, like `tail -f`. The editor
+//! polls it between key presses and appends the text to the buffer.
+//!
+//! If the file shrinks (log rotation, `truncate`), we start over from the
+//! beginning of the new contents.
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone)]
+pub struct Follower {
+    path: PathBuf,
+    /// How many bytes of the file have been consumed so far.
+    offset: u64,
+}
+
+/// Text read from the followed file since the previous poll.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Growth {
+    /// New text was appended to the end of the file.
+    Appended(String),
+    /// The file was truncated; this is its whole new contents.
+    Replaced(String),
+}
+
+impl Follower {
+    /// Starts following `path` from its current end.
+    pub fn new(path: &Path) -> Result<Self> {
+        let offset = path.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            offset,
+        })
+    }
+
+    /// Returns what changed since the previous call, or `None` if the file
+    /// did not grow.
+    pub fn poll(&mut self) -> Result<Option<Growth>> {
+        let len = self.path.metadata()?.len();
+        if len == self.offset {
+            return Ok(None);
+        }
+        let truncated = len < self.offset;
+        if truncated {
+            self.offset = 0;
+        }
+
+        let mut file = File::open(&self.path).map_err(|err| Error::at(&self.path, err))?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        // Only consume complete UTF-8 sequences; a writer may be halfway
+        // through a multi-byte character.
+        let valid = match std::str::from_utf8(&bytes) {
+            Ok(_) => bytes.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(_) => {
+                return Err(Error::InvalidUtf8 {
+                    path: self.path.clone(),
+                })
+            }
+        };
+        bytes.truncate(valid);
+        self.offset = self.offset.saturating_add(u64::try_from(valid)?);
+        let text = String::from_utf8(bytes).map_err(|_| Error::InvalidUtf8 {
+            path: self.path.clone(),
+        })?;
+
+        Ok(Some(if truncated {
+            Growth::Replaced(text)
+        } else {
+            Growth::Appended(text)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, io::Write};
+
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("hecto-follow-{}-{name}", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_poll_returns_appended_text() {
+        let path = temp_file("append", "one\n");
+        let mut follower = Follower::new(&path).unwrap();
+        assert_eq!(follower.poll().unwrap(), None);
+
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"two\nthree\n").unwrap();
+
+        assert_eq!(
+            follower.poll().unwrap(),
+            Some(Growth::Appended("two\nthree\n".to_string()))
+        );
+        assert_eq!(follower.poll().unwrap(), None);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_poll_detects_truncation() {
+        let path = temp_file("truncate", "a long first version\n");
+        let mut follower = Follower::new(&path).unwrap();
+
+        fs::write(&path, "short\n").unwrap();
+        assert_eq!(
+            follower.poll().unwrap(),
+            Some(Growth::Replaced("short\n".to_string()))
+        );
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_poll_waits_for_complete_characters() {
+        let path = temp_file("utf8", "");
+        let mut follower = Follower::new(&path).unwrap();
+
+        // "é" is 0xC3 0xA9; write only the first byte.
+        fs::write(&path, [b'x', 0xC3]).unwrap();
+        assert_eq!(
+            follower.poll().unwrap(),
+            Some(Growth::Appended("x".to_string()))
+        );
+        fs::write(&path, [b'x', 0xC3, 0xA9]).unwrap();
+        assert_eq!(
+            follower.poll().unwrap(),
+            Some(Growth::Appended("é".to_string()))
+        );
+        let _ = fs::remove_file(path);
+    }
+}
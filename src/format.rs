@@ -0,0 +1,163 @@
+//! # Formatters
+//!
+//! `format` (`Alt+F`) runs the buffer's text through the formatter for its
+//! filetype, a shell command that reads the text on its standard input and
+//! prints it formatted: `rustfmt` for Rust, `black` for Python, `shfmt` for
+//! shell scripts, `taplo` for TOML and `prettier` for Markdown. The
+//! `[formatters]` section of the config file sets others, or turns one off
+//! with an empty command:
+//!
+//! ```toml
+//! [formatters]
+//! rust = "rustfmt --edition 2024"
+//! markdown = ""
+//! ```
+//!
+//! The buffer takes the output only if the command succeeds; otherwise it
+//! stays as it was and the error says why. With the `formatonsave` option
+//! on, buffers are formatted this way whenever they are saved.
+//!
+//! Formatting moves text around, so the cursor is moved with it: the
+//! lines that changed are found with a [diff](crate::diff), and in them
+//! the cursor goes to the same character, counting the characters that are
+//! not whitespace, which formatting keeps.
+
+use crate::{
+    buffer::Buffer,
+    diff::{self, Hunk},
+    error::{Error, Result},
+    layout::LineLayout,
+    operator::Pos,
+    shell,
+};
+
+/// The built-in formatter for each filetype.
+const FORMATTERS: &[(&str, &str)] = &[
+    ("rust", "rustfmt --edition 2021"),
+    ("python", "black --quiet -"),
+    ("shell", "shfmt"),
+    ("toml", "taplo format -"),
+    ("markdown", "prettier --parser markdown"),
+];
+
+/// The formatter for `filetype`: the one `configured` for it, or else the
+/// built-in one; `None` if it has none, or it was turned off.
+#[must_use]
+pub fn command<'a>(filetype: &str, configured: &'a [(String, String)]) -> Option<&'a str> {
+    configured
+        .iter()
+        .rev()
+        .find(|(name, _)| name == filetype)
+        .map(|(_, command)| command.as_str())
+        .or_else(|| {
+            FORMATTERS
+                .iter()
+                .find(|(name, _)| *name == filetype)
+                .map(|(_, command)| *command)
+        })
+        .filter(|command| !command.is_empty())
+}
+
+/// Runs `buffer`'s text through `command` and replaces it with the output,
+/// if that is different. Returns where `cursor` is moved to.
+///
+/// # Errors
+///
+/// Fails if the command fails, or prints nothing for a buffer with text;
+/// the buffer is left as it was.
+pub fn format(buffer: &mut Buffer, command: &str, cursor: Pos) -> Result<Pos> {
+    let text = buffer.text();
+    let formatted = shell::filter(command, &text)?;
+    if formatted.is_empty() && !text.is_empty() {
+        return Err(Error::Command {
+            command: command.to_string(),
+            message: "printed nothing".to_string(),
+        });
+    }
+    if formatted == text {
+        return Ok(cursor);
+    }
+    let old: Vec<&str> = text.lines().collect();
+    let new: Vec<&str> = formatted.lines().collect();
+    let cursor = remap(&old, &new, &diff::diff(&old, &new), cursor);
+    buffer.edit_text(&formatted);
+    Ok(cursor)
+}
+
+/// Where `cursor` in `old` goes in `new`, which `hunks` turn it into.
+fn remap(old: &[&str], new: &[&str], hunks: &[Hunk], (line, col): Pos) -> Pos {
+    let Some(hunk) = hunks.iter().find(|hunk| hunk.old.contains(&line)) else {
+        return (diff::map_line(hunks, line), col);
+    };
+    let is_kept = |ch: &char| !ch.is_whitespace();
+    let lines = |range: std::ops::Range<usize>, text: &[&str]| {
+        text.get(range)
+            .unwrap_or_default()
+            .iter()
+            .map(|line| line.chars().filter(is_kept).count())
+            .sum::<usize>()
+    };
+    let text = old.get(line).copied().unwrap_or_default();
+    let before = text
+        .get(..LineLayout::new(text).byte_offset(col))
+        .unwrap_or_default();
+    let mut count =
+        lines(hunk.old.start..line, old).saturating_add(before.chars().filter(is_kept).count());
+    for index in hunk.new.clone() {
+        let text = new.get(index).copied().unwrap_or_default();
+        for (byte, _) in text.char_indices().filter(|(_, ch)| is_kept(ch)) {
+            if count == 0 {
+                return (index, LineLayout::new(text).index_of_byte(byte));
+            }
+            count = count.saturating_sub(1);
+        }
+    }
+    // Past the last of them: the end of what replaced the cursor's line.
+    match hunk.new.end.checked_sub(1) {
+        Some(last) if !hunk.new.is_empty() => {
+            let text = new.get(last).copied().unwrap_or_default();
+            (last, LineLayout::new(text).len())
+        }
+        _ => (hunk.new.start, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatter_for_filetype() {
+        let configured = vec![
+            ("rust".to_string(), "rustfmt --edition 2024".to_string()),
+            ("markdown".to_string(), String::new()),
+        ];
+        assert_eq!(command("rust", &configured), Some("rustfmt --edition 2024"));
+        assert_eq!(command("python", &configured), Some("black --quiet -"));
+        assert_eq!(command("markdown", &configured), None);
+        assert_eq!(command("text", &[]), None);
+    }
+
+    #[test]
+    fn test_cursor_stays_on_the_same_character() {
+        let mut buffer = Buffer::default();
+        buffer.replace_text("fn f(a,b) {\n    x\n}\n");
+        // On `b`, which a space is put before.
+        let cursor = format(&mut buffer, "sed 's/,/, /'", (0, 7)).unwrap();
+        assert_eq!(buffer.text(), "fn f(a, b) {\n    x\n}\n");
+        assert_eq!(cursor, (0, 8));
+        assert!(buffer.modified);
+
+        // Lines joined and split: the cursor follows its character.
+        let old = ["call(", "    one,", "    two", ")", "end"];
+        let new = ["call(one, two)", "end"];
+        let hunks = diff::diff(&old, &new);
+        assert_eq!(remap(&old, &new, &hunks, (2, 5)), (0, 11));
+        assert_eq!(remap(&old, &new, &hunks, (4, 1)), (1, 1));
+        assert_eq!(remap(&new, &old, &diff::diff(&new, &old), (0, 10)), (2, 4));
+
+        assert!(format(&mut buffer, "exit 1", (0, 0)).is_err());
+        assert!(format(&mut buffer, "true", (0, 0)).is_err());
+        assert_eq!(buffer.text(), "fn f(a, b) {\n    x\n}\n");
+    }
+}
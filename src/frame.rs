@@ -0,0 +1,170 @@
+//! # Frame
+//!
+//! An in-memory picture of the whole screen: a grid of [`Cell`]s, each
+//! holding one character and its style. The editor paints a complete frame
+//! first and only then hands it to the [`Screen`](crate::screen::Screen),
+//! which turns the rows that changed into terminal output in one go. Nothing
+//! reaches the terminal while a frame is half-drawn.
+
+use std::{cmp::min, ops::Range};
+
+use crossterm::style::ContentStyle;
+
+use crate::{layout::char_width, terminal::Size};
+
+/// Fills the cell covered by the right half of a double-width character.
+/// It is never printed: the terminal advances past it on its own.
+pub const CONTINUATION: char = '\0';
+
+/// One character on screen, with its style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub symbol: char,
+    pub style: ContentStyle,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            symbol: ' ',
+            style: ContentStyle::default(),
+        }
+    }
+}
+
+/// A row of cells, exactly as wide as the frame.
+pub type Row = Vec<Cell>;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Frame {
+    rows: Vec<Row>,
+}
+
+impl Frame {
+    /// Creates a blank frame of the given size.
+    #[must_use]
+    pub fn new(size: Size) -> Self {
+        Self {
+            rows: vec![vec![Cell::default(); size.width]; size.height],
+        }
+    }
+
+    #[must_use]
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    /// Writes `text` into row `row` starting at column `col`, clipping
+    /// whatever falls outside the frame. Double-width characters take two
+    /// cells; zero-width ones (combining marks) have no cell of their own and
+    /// are dropped.
+    pub fn set_text(&mut self, row: usize, col: usize, text: &str, style: ContentStyle) {
+        let Some(cells) = self.rows.get_mut(row) else {
+            return;
+        };
+        let mut col = col;
+        for symbol in text.chars() {
+            let width = char_width(symbol);
+            if width == 0 {
+                continue;
+            }
+            let end = col.saturating_add(width);
+            if end > cells.len() {
+                break;
+            }
+            if let Some(span) = cells.get_mut(col..end) {
+                for (index, cell) in span.iter_mut().enumerate() {
+                    let symbol = if index == 0 { symbol } else { CONTINUATION };
+                    *cell = Cell { symbol, style };
+                }
+            }
+            col = end;
+        }
+    }
+
+    /// Lays `style` over the cells of `row` in the columns `cols`: the
+    /// colors and attributes it sets replace those of each cell, and the
+    /// rest stay as they were.
+    pub fn highlight(&mut self, row: usize, cols: Range<usize>, style: ContentStyle) {
+        let Some(cells) = self.rows.get_mut(row) else {
+            return;
+        };
+        let end = min(cols.end, cells.len());
+        for cell in cells
+            .get_mut(min(cols.start, end)..end)
+            .into_iter()
+            .flatten()
+        {
+            if let Some(color) = style.foreground_color {
+                cell.style.foreground_color = Some(color);
+            }
+            if let Some(color) = style.background_color {
+                cell.style.background_color = Some(color);
+            }
+            cell.style.attributes.extend(style.attributes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::style::{Attribute, Stylize};
+
+    fn frame() -> Frame {
+        Frame::new(Size {
+            width: 5,
+            height: 2,
+        })
+    }
+
+    #[test]
+    fn test_new_frame_is_blank() {
+        let frame = frame();
+        assert_eq!(frame.rows().len(), 2);
+        assert_eq!(frame.rows()[0], vec![Cell::default(); 5]);
+    }
+
+    #[test]
+    fn test_set_text_clips_to_width() {
+        let mut frame = frame();
+        frame.set_text(1, 2, "hello", ContentStyle::default());
+        let text: String = frame.rows()[1].iter().map(|cell| cell.symbol).collect();
+        assert_eq!(text, "  hel");
+        // Out-of-range rows are ignored.
+        frame.set_text(7, 0, "x", ContentStyle::default());
+    }
+
+    #[test]
+    fn test_wide_characters_take_two_cells() {
+        let mut frame = frame();
+        frame.set_text(0, 0, "日本語", ContentStyle::default());
+        let symbols: Vec<char> = frame.rows()[0].iter().map(|cell| cell.symbol).collect();
+        // The third character does not fit in the last column.
+        assert_eq!(symbols, vec!['日', CONTINUATION, '本', CONTINUATION, ' ']);
+    }
+
+    #[test]
+    fn test_set_text_applies_style() {
+        let mut frame = frame();
+        let bold = ContentStyle::new().attribute(Attribute::Bold);
+        frame.set_text(0, 1, "ab", bold);
+
+        let row = &frame.rows()[0];
+        assert_eq!(row[0], Cell::default());
+        assert_eq!(
+            row[1],
+            Cell {
+                symbol: 'a',
+                style: bold
+            }
+        );
+        assert_eq!(
+            row[2],
+            Cell {
+                symbol: 'b',
+                style: bold
+            }
+        );
+    }
+}
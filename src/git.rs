@@ -0,0 +1,721 @@
+//! # Git
+//!
+//! What Git knows about a buffer's file. With the `gitgutter` option on,
+//! as it is unless turned off, the lines that differ from the file's last
+//! commit get a sign in the gutter: `+` for lines added, `~` for lines
+//! changed, and `_` under the place lines were removed from (`‾` over the
+//! first line, if they were at the top). `]c` and `[c` in normal mode
+//! (`Alt+F3` and `Alt+Shift+F3`) go to the next and previous changed hunk.
+//! `stage-hunk` (`leader h s`) puts the hunk at the cursor in the index,
+//! ready to be committed, and `revert-hunk` (`leader h r`) puts back the
+//! lines it replaced; each shows the hunk first, and goes ahead on `y`.
+//!
+//! `blame` says who last changed the cursor's line, in which commit and
+//! how long ago; with the `blameline` option on, the status bar says so
+//! whenever it has nothing else to say.
+//!
+//! While the file is in a Git work tree, the status bar shows the branch
+//! checked out, with a `*` if anything in the repository is not committed
+//! and `↑`/`↓` with how many commits it is ahead of and behind the branch
+//! it tracks. `git status` is run for it when another file is opened and
+//! after a save, and otherwise at most every few seconds, when the editor
+//! is being used.
+//!
+//! The version in `HEAD` is read with `git show` and compared with the
+//! text ([`diff`](crate::diff)), and the text is blamed with `git blame
+//! --incremental`, on threads of their own, once the text has stayed the
+//! same for a moment, so typing never waits for them. Files outside a
+//! repository, or not committed yet, get no signs.
+
+use std::{
+    fmt,
+    ops::Range,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    diff::{self, Hunk},
+    error::{Error, Result},
+    shell,
+};
+
+/// Units of time an age is told in, longest first, in seconds.
+const AGE_UNITS: &[(u64, &str)] = &[
+    (31_536_000, "year"),
+    (2_592_000, "month"),
+    (604_800, "week"),
+    (86_400, "day"),
+    (3_600, "hour"),
+    (60, "minute"),
+];
+
+/// How a line differs from the last commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Modified,
+    /// Lines after it were removed.
+    Removed,
+    /// Lines before it, the first, were removed.
+    RemovedAbove,
+}
+
+impl Change {
+    /// The sign put in the gutter of its line.
+    #[must_use]
+    pub fn sign(self) -> &'static str {
+        match self {
+            Self::Added => "+",
+            Self::Modified => "~",
+            Self::Removed => "_",
+            Self::RemovedAbove => "‾",
+        }
+    }
+
+    /// The theme element its sign is colored with.
+    #[must_use]
+    pub fn element(self) -> &'static str {
+        match self {
+            Self::Added => "sign.added",
+            Self::Modified => "sign.modified",
+            Self::Removed | Self::RemovedAbove => "sign.removed",
+        }
+    }
+}
+
+/// Who last changed a line, and when.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Commit {
+    /// Its hash; all zeros for lines not committed yet.
+    pub id: String,
+    pub author: String,
+    /// When it was written, in seconds since the Unix epoch.
+    pub time: u64,
+    /// The first line of its message.
+    pub summary: String,
+}
+
+impl Commit {
+    #[must_use]
+    pub fn is_committed(&self) -> bool {
+        self.id.chars().any(|ch| ch != '0')
+    }
+
+    /// Who made it, when and why, like `1a2b3c4d Ann, 3 days ago: Fix
+    /// it`, `now` being the time in seconds since the Unix epoch.
+    #[must_use]
+    pub fn describe(&self, now: u64) -> String {
+        if !self.is_committed() {
+            return "Not committed yet".to_string();
+        }
+        let id = self.id.get(..8).unwrap_or(&self.id);
+        let age = age(now.saturating_sub(self.time));
+        format!("{id} {}, {age}: {}", self.author, self.summary)
+    }
+}
+
+/// `seconds` told in the longest unit that fits, like `3 days ago`.
+fn age(seconds: u64) -> String {
+    AGE_UNITS
+        .iter()
+        .find_map(|&(length, unit)| {
+            let count = seconds.checked_div(length).filter(|&count| count > 0)?;
+            let plural = if count == 1 { "" } else { "s" };
+            Some(format!("{count} {unit}{plural} ago"))
+        })
+        .unwrap_or_else(|| "just now".to_string())
+}
+
+/// The time now, in seconds since the Unix epoch.
+#[must_use]
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Who last changed each line of a text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Blame {
+    commits: Vec<Commit>,
+    /// The index in `commits` of each line's.
+    lines: Vec<Option<usize>>,
+}
+
+impl Blame {
+    /// Reads what `git blame --incremental` printed: for each run of lines
+    /// from one commit, a line with the commit's hash, the line numbers it
+    /// had there and has now, and how many lines there are; then, the
+    /// first time the commit comes up, what it is, a field per line; and
+    /// last its `filename`.
+    #[must_use]
+    pub fn parse(output: &str) -> Self {
+        let mut blame = Self::default();
+        let mut run = None;
+        for line in output.lines() {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let Some(index) = run.map(|(index, _, _)| index) else {
+                run = blame.start_run(key, value);
+                continue;
+            };
+            let Some(commit) = blame.commits.get_mut(index) else {
+                continue;
+            };
+            match key {
+                "author" => commit.author = value.to_string(),
+                "author-time" => commit.time = value.parse().unwrap_or_default(),
+                "summary" => commit.summary = value.to_string(),
+                "filename" => {
+                    if let Some((index, start, count)) = run.take() {
+                        let end = start.saturating_add(count);
+                        if blame.lines.len() < end {
+                            blame.lines.resize(end, None);
+                        }
+                        for slot in blame.lines.iter_mut().take(end).skip(start) {
+                            *slot = Some(index);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        blame
+    }
+
+    /// Starts a run of lines from the commit `id`, given the rest of the
+    /// line that names it: its commit's index, its first line from 0, and
+    /// how many lines it has.
+    fn start_run(&mut self, id: &str, rest: &str) -> Option<(usize, usize, usize)> {
+        let numbers: Vec<usize> = rest
+            .split(' ')
+            .map(str::parse)
+            .collect::<std::result::Result<_, _>>()
+            .ok()?;
+        let [_, line, count] = numbers.as_slice() else {
+            return None;
+        };
+        if id.len() < 40 || !id.chars().all(|ch| ch.is_ascii_hexdigit()) {
+            return None;
+        }
+        let index = self
+            .commits
+            .iter()
+            .position(|commit| commit.id == id)
+            .unwrap_or_else(|| {
+                self.commits.push(Commit {
+                    id: id.to_string(),
+                    ..Commit::default()
+                });
+                self.commits.len().saturating_sub(1)
+            });
+        Some((index, line.saturating_sub(1), *count))
+    }
+
+    /// The commit that last changed line `line`, from 0.
+    #[must_use]
+    pub fn line(&self, line: usize) -> Option<&Commit> {
+        let index = (*self.lines.get(line)?)?;
+        self.commits.get(index)
+    }
+}
+
+/// The directory `path` is in, and its name there.
+fn split(path: &Path) -> Option<(&Path, String)> {
+    let name = path.file_name()?.to_string_lossy().into_owned();
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    Some((dir, name))
+}
+
+/// Who last changed each line of `text`, the buffer of `path`; lines
+/// changed since the last commit are not committed yet.
+///
+/// # Errors
+///
+/// Fails if Git cannot be run, or cannot blame the file: it is not in a
+/// repository, or was never committed.
+pub fn blame(path: &Path, text: &str) -> Result<Blame> {
+    let (dir, name) = split(path).ok_or_else(|| Error::Command {
+        command: "git blame".to_string(),
+        message: "not a file".to_string(),
+    })?;
+    let command = format!(
+        "git -C {} blame --incremental --contents - -- {}",
+        shell::quote(&dir.to_string_lossy()),
+        shell::quote(&name)
+    );
+    shell::filter(&command, text).map(|output| Blame::parse(&output))
+}
+
+/// The text of `path` as of the last commit; `None` if it is not in a
+/// repository, was never committed, or Git cannot be run.
+#[must_use]
+pub fn head(path: &Path) -> Option<String> {
+    show(path, "HEAD")
+}
+
+/// The text of `path` as it is staged; `None` if it is not in the index.
+fn staged(path: &Path) -> Option<String> {
+    show(path, "")
+}
+
+/// The text of `path` in `revision`, or the index if that is empty.
+fn show(path: &Path, revision: &str) -> Option<String> {
+    let (dir, name) = split(path)?;
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        .arg(format!("{revision}:./{name}"))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Where the repository a file is in stands.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Status {
+    /// The branch checked out or, detached, the start of the commit's hash
+    /// in brackets.
+    pub branch: String,
+    /// Whether anything is not committed, untracked files included.
+    pub dirty: bool,
+    /// How many commits it has that the branch it tracks has not.
+    pub ahead: usize,
+    /// How many commits the branch it tracks has that it has not.
+    pub behind: usize,
+}
+
+impl Status {
+    /// Reads what `git status --porcelain=v2 --branch` printed: headers
+    /// starting with `#`, then a line for each file that is not committed.
+    #[must_use]
+    pub fn parse(output: &str) -> Self {
+        let mut status = Self::default();
+        let mut commit = "";
+        for line in output.lines() {
+            let Some(header) = line.strip_prefix("# ") else {
+                status.dirty = true;
+                continue;
+            };
+            let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+            match key {
+                "branch.oid" => commit = value,
+                "branch.head" => status.branch = value.to_string(),
+                "branch.ab" => {
+                    for count in value.split(' ') {
+                        let number = |digits: &str| digits.parse().unwrap_or_default();
+                        if let Some(digits) = count.strip_prefix('+') {
+                            status.ahead = number(digits);
+                        } else if let Some(digits) = count.strip_prefix('-') {
+                            status.behind = number(digits);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if status.branch == "(detached)" {
+            status.branch = format!("({})", commit.get(..7).unwrap_or(commit));
+        }
+        status
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.branch)?;
+        if self.dirty {
+            f.write_str("*")?;
+        }
+        if self.ahead > 0 {
+            write!(f, " ↑{}", self.ahead)?;
+        }
+        if self.behind > 0 {
+            write!(f, " ↓{}", self.behind)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where the repository `path` is in stands; `None` if it is not in a
+/// work tree, or Git cannot be run.
+#[must_use]
+pub fn status(path: &Path) -> Option<Status> {
+    let (dir, _) = split(path)?;
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["status", "--porcelain=v2", "--branch"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| Status::parse(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// A hunk, with the lines it replaced in the last commit and the lines
+/// that replaced them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    pub hunk: Hunk,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+impl Patch {
+    /// The lines removed, after a `-`, then those added, after a `+`.
+    #[must_use]
+    pub fn preview(&self) -> Vec<String> {
+        let removed = self.old.iter().map(|line| format!("-{line}"));
+        let added = self.new.iter().map(|line| format!("+{line}"));
+        removed.chain(added).collect()
+    }
+
+    /// The patch as `git apply` takes it, without lines of context, for
+    /// the file at `path` in the repository.
+    #[must_use]
+    pub fn unified(&self, path: &str) -> String {
+        // An empty side is numbered from the line before it.
+        let start = |lines: &[String]| {
+            if lines.is_empty() {
+                self.hunk.old.start
+            } else {
+                self.hunk.old.start.saturating_add(1)
+            }
+        };
+        let mut unified = format!(
+            "diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n\
+             @@ -{},{} +{},{} @@\n",
+            start(&self.old),
+            self.old.len(),
+            start(&self.new),
+            self.new.len()
+        );
+        for line in self.preview() {
+            unified.push_str(&line);
+            unified.push('\n');
+        }
+        unified
+    }
+}
+
+/// The hunk at line `line` of `text`, the buffer of `path`, as a patch on
+/// the last commit; `None` if the line is as it was committed.
+#[must_use]
+pub fn patch_at(path: &Path, text: &str, line: usize) -> Option<Patch> {
+    let head = head(path)?;
+    let hunk = hunks(&head, text)
+        .into_iter()
+        .find(|hunk| is_at(hunk, line))?;
+    Some(patch(&head, text, hunk))
+}
+
+/// `hunk`, one of those that turn `base` into `text`, as a patch.
+fn patch(base: &str, text: &str, hunk: Hunk) -> Patch {
+    let lines = |text: &str, range: &Range<usize>| -> Vec<String> {
+        let count = range.len();
+        let lines = text.lines().skip(range.start).take(count);
+        lines.map(ToString::to_string).collect()
+    };
+    Patch {
+        old: lines(base, &hunk.old),
+        new: lines(text, &hunk.new),
+        hunk,
+    }
+}
+
+/// Puts `change`, a hunk between the last commit of `path` and `text`, its
+/// buffer's, in the index. What is staged already may have moved the lines
+/// it covers, so what goes in is numbered from the index instead: the
+/// hunks between it and `text` on the same lines of `text`.
+///
+/// # Errors
+///
+/// Fails if Git cannot be run, or the lines are staged already.
+pub fn stage(path: &Path, text: &str, change: &Patch) -> Result<()> {
+    let failed = |message: &str| Error::Command {
+        command: "git apply".to_string(),
+        message: message.to_string(),
+    };
+    let (dir, name) = split(path).ok_or_else(|| failed("not a file"))?;
+    let index = staged(path).ok_or_else(|| failed("not in the index"))?;
+    let patches: Vec<Patch> = hunks(&index, text)
+        .into_iter()
+        .filter(|hunk| overlaps(&hunk.new, &change.hunk.new))
+        .map(|hunk| patch(&index, text, hunk))
+        .collect();
+    if patches.is_empty() {
+        return Err(failed("the hunk is staged already"));
+    }
+    let dir = shell::quote(&dir.to_string_lossy());
+    // Paths in a patch are from the top of the repository.
+    let prefix = shell::run(&format!("git -C {dir} rev-parse --show-prefix"))?;
+    let name = format!("{}{name}", prefix.trim_end());
+    let command = format!("git -C {dir} apply --cached --unidiff-zero -");
+    // From the last, so the lines of those before stay where they are.
+    for patch in patches.iter().rev() {
+        shell::filter(&command, &patch.unified(&name))?;
+    }
+    Ok(())
+}
+
+/// Whether the lines `a` and `b` cover meet; an empty range, lines
+/// removed, meets those on either side of where they were.
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    if a.is_empty() || b.is_empty() {
+        a.start <= b.end && b.start <= a.end
+    } else {
+        a.start < b.end && b.start < a.end
+    }
+}
+
+/// The hunks that turn the last commit of `path` into `text`, its
+/// buffer's; none if there is no such commit.
+#[must_use]
+pub fn changes(path: &Path, text: &str) -> Vec<Hunk> {
+    head(path).map_or_else(Vec::new, |head| hunks(&head, text))
+}
+
+/// The hunks that turn `head` into `text`, line by line.
+#[must_use]
+pub fn hunks(head: &str, text: &str) -> Vec<Hunk> {
+    let old: Vec<&str> = head.lines().collect();
+    let new: Vec<&str> = text.lines().collect();
+    diff::diff(&old, &new)
+}
+
+/// The line of the text `hunk` is shown on: its first, or the one above
+/// where lines were removed.
+fn line_of(hunk: &Hunk) -> usize {
+    if hunk.new.is_empty() {
+        hunk.new.start.saturating_sub(1)
+    } else {
+        hunk.new.start
+    }
+}
+
+/// Whether `hunk` is shown on line `line`: it has the line, or lines
+/// were removed next to it.
+fn is_at(hunk: &Hunk, line: usize) -> bool {
+    hunk.new.contains(&line) || (hunk.new.is_empty() && line_of(hunk) == line)
+}
+
+/// How line `line` of the text differs from the last commit, given the
+/// `hunks` between them.
+#[must_use]
+pub fn change_at(hunks: &[Hunk], line: usize) -> Option<Change> {
+    let hunk = hunks.iter().find(|hunk| is_at(hunk, line))?;
+    Some(if !hunk.new.is_empty() && hunk.old.is_empty() {
+        Change::Added
+    } else if !hunk.new.is_empty() {
+        Change::Modified
+    } else if hunk.new.start == 0 {
+        Change::RemovedAbove
+    } else {
+        Change::Removed
+    })
+}
+
+/// The line the next of `hunks` after line `line` is shown on, or the
+/// previous one before it if not `forward`.
+#[must_use]
+pub fn next_hunk(hunks: &[Hunk], line: usize, forward: bool) -> Option<usize> {
+    let mut lines = hunks.iter().map(line_of);
+    if forward {
+        lines.find(|&start| start > line)
+    } else {
+        lines.rev().find(|&start| start < line)
+    }
+}
+
+/// Work on revision `revision` of the buffer of `path` going on in the
+/// background, like [`changes`] or [`blame`].
+#[derive(Debug)]
+pub struct Run<T> {
+    path: PathBuf,
+    revision: usize,
+    receiver: Receiver<Result<T>>,
+}
+
+impl<T: Send + 'static> Run<T> {
+    /// Starts `work` on a worker thread.
+    #[must_use]
+    pub fn spawn(
+        path: &Path,
+        revision: usize,
+        work: impl FnOnce() -> Result<T> + Send + 'static,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(work());
+        });
+        Self {
+            path: path.to_path_buf(),
+            revision,
+            receiver,
+        }
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[must_use]
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+
+    /// What the work came to, once it is done; `None` until then.
+    pub fn poll(&mut self) -> Option<Result<T>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(Error::Command {
+                command: "git".to_string(),
+                message: "stopped without a result".to_string(),
+            })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_lines_get_signs() {
+        let head = "a\nb\nc\nd\ne\n";
+        let text = "a\nB\nnew\nc\ne\n";
+        let hunks = hunks(head, text);
+        let signs: Vec<_> = (0..5)
+            .map(|line| change_at(&hunks, line).map(Change::sign))
+            .collect();
+        assert_eq!(signs, [None, Some("~"), Some("~"), Some("_"), None]);
+
+        let hunks = super::hunks(head, "c\nd\ne\nf\n");
+        assert_eq!(change_at(&hunks, 0), Some(Change::RemovedAbove));
+        assert_eq!(change_at(&hunks, 3), Some(Change::Added));
+        assert_eq!(change_at(&hunks, 3).unwrap().element(), "sign.added");
+    }
+
+    #[test]
+    fn test_hunks_are_found_in_either_direction() {
+        let hunks = hunks("a\nb\nc\nd\ne\nf\n", "a\nB\nc\nd\nf\n");
+        assert_eq!(next_hunk(&hunks, 0, true), Some(1));
+        assert_eq!(next_hunk(&hunks, 1, true), Some(3));
+        assert_eq!(next_hunk(&hunks, 3, true), None);
+        assert_eq!(next_hunk(&hunks, 4, false), Some(3));
+        assert_eq!(next_hunk(&hunks, 1, false), None);
+    }
+
+    #[test]
+    fn test_patches_number_lines_from_the_last_commit() {
+        let patch = Patch {
+            hunk: Hunk {
+                old: 2..3,
+                new: 2..4,
+            },
+            old: vec!["c".to_string()],
+            new: vec!["C".to_string(), "D".to_string()],
+        };
+        assert_eq!(patch.preview(), ["-c", "+C", "+D"]);
+        assert_eq!(
+            patch.unified("src/a.txt"),
+            "diff --git a/src/a.txt b/src/a.txt\n--- a/src/a.txt\n+++ b/src/a.txt\n\
+             @@ -3,1 +3,2 @@\n-c\n+C\n+D\n"
+        );
+        let removed = Patch {
+            hunk: Hunk {
+                old: 2..3,
+                new: 2..2,
+            },
+            new: Vec::new(),
+            ..patch
+        };
+        assert!(removed.unified("a").contains("@@ -3,1 +2,0 @@\n-c\n"));
+    }
+
+    #[test]
+    fn test_status_tells_the_branch_and_how_it_stands() {
+        let output = "# branch.oid 0123456789abcdef0123456789abcdef01234567\n\
+                      # branch.head main\n\
+                      # branch.upstream origin/main\n\
+                      # branch.ab +2 -1\n\
+                      1 .M N... 100644 100644 100644 0123 4567 src/a.rs\n";
+        let status = Status::parse(output);
+        assert_eq!(status.to_string(), "main* ↑2 ↓1");
+        let output = "# branch.oid 0123456789abcdef0123456789abcdef01234567\n\
+                      # branch.head (detached)\n";
+        assert_eq!(Status::parse(output).to_string(), "(0123456)");
+    }
+
+    #[test]
+    fn test_blame_is_read_line_by_line() {
+        let output = "0000000000000000000000000000000000000000 2 2 1\n\
+                      author Not Committed Yet\n\
+                      author-time 1700000000\n\
+                      summary Version of f from standard input\n\
+                      filename f\n\
+                      a033b464020b9d5a8e697c50a70b542f45f97e14 1 1 1\n\
+                      author Ann\n\
+                      author-time 1700000000\n\
+                      summary First one\n\
+                      boundary\n\
+                      filename f\n\
+                      a033b464020b9d5a8e697c50a70b542f45f97e14 3 3 1\n\
+                      filename f\n";
+        let blame = Blame::parse(output);
+        let day = 86_400;
+        let first = blame.line(0).unwrap();
+        assert_eq!(
+            first.describe(1_700_000_000 + 3 * day),
+            "a033b464 Ann, 3 days ago: First one"
+        );
+        assert_eq!(blame.line(2), Some(first));
+        assert_eq!(blame.line(1).unwrap().describe(0), "Not committed yet");
+        assert_eq!(blame.line(3), None);
+        assert_eq!(
+            first.describe(1_700_000_030),
+            "a033b464 Ann, just now: First one"
+        );
+        assert_eq!(age(3_600), "1 hour ago");
+    }
+
+    #[test]
+    fn test_files_outside_a_repository_have_no_head() {
+        let dir = std::env::temp_dir().join(format!("hecto-git-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.txt");
+        std::fs::write(&path, "a\n").unwrap();
+        assert_eq!(head(&path), None);
+        let file = path.clone();
+        let mut run = Run::spawn(&path, 3, move || Ok(changes(&file, "b\n")));
+        let hunks = loop {
+            if let Some(hunks) = run.poll() {
+                break hunks.unwrap();
+            }
+            thread::sleep(std::time::Duration::from_millis(5));
+        };
+        assert!(hunks.is_empty());
+        assert!(blame(&path, "a\n").is_err());
+        assert_eq!((run.path(), run.revision()), (path.as_path(), 3));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
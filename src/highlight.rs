@@ -0,0 +1,719 @@
+//! # Syntax Highlighting
+//!
+//! Colors text by what it is: keywords, strings, comments and numbers, each
+//! drawn in the theme's style for it (`syntax.keyword`, `syntax.string`,
+//! ...; see [`theme`](crate::theme)). The rules come from the buffer's
+//! [`Language`], which its [filetype](crate::filetype) picks; only the
+//! lines on screen are colored. Plain text is shown as it is, and in
+//! diffs whole lines are colored by what they start with: `+` for those
+//! inserted, `-` for those deleted.
+//!
+//! Block comments, strings (Rust's raw ones, Python's in triple quotes) and
+//! the shell's here-documents may go on over line breaks, so each line is
+//! colored knowing the [`State`] the line above left open. A [`StateCache`]
+//! keeps those states, worked out once from the top down; an edit that
+//! changes how a line ends has the ones below it worked out again.
+//!
+//! Words such as `TODO` and `FIXME` (the `todowords` option) stand out in
+//! comments, in the theme's `syntax.comment.todo` style; see [`todos`].
+
+use std::{
+    cell::{Cell, RefCell},
+    ops::Range,
+};
+
+/// What a piece of text is, which decides its color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Keyword,
+    String,
+    Comment,
+    Number,
+    /// A line a diff inserts.
+    Inserted,
+    /// A line a diff deletes.
+    Deleted,
+}
+
+impl Scope {
+    /// The theme element it is drawn with.
+    #[must_use]
+    pub fn element(self) -> &'static str {
+        match self {
+            Self::Keyword => "syntax.keyword",
+            Self::String => "syntax.string",
+            Self::Comment => "syntax.comment",
+            Self::Number => "syntax.number",
+            Self::Inserted => "syntax.inserted",
+            Self::Deleted => "syntax.deleted",
+        }
+    }
+}
+
+/// A highlighted piece of a line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    /// Where it is in the line, in bytes.
+    pub bytes: Range<usize>,
+    pub scope: Scope,
+}
+
+/// What a line leaves open for the next one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum State {
+    /// Nothing: the next line starts afresh.
+    #[default]
+    Normal,
+    /// A block comment.
+    Comment,
+    /// A string that ends at `end` (a quote, `"#` for a raw string opened
+    /// with `r#"`, or `"""`); backslashes escape nothing in a `raw` one.
+    Quoted { end: String, raw: bool },
+    /// A here-document, which ends at a line holding only `end`.
+    HereDoc { end: String },
+}
+
+/// The kinds of string a language has besides quotes closed on the same
+/// line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Strings {
+    None,
+    /// `'c'` characters, strings that go on over line breaks, and raw
+    /// strings (`r#"..."#`).
+    Rust,
+    /// Strings in triple quotes, which may span lines.
+    Python,
+    /// Strings that go on over line breaks, and here-documents (`<<EOF`).
+    Shell,
+}
+
+/// How to highlight the files of one language.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Language {
+    pub name: &'static str,
+    keywords: &'static [&'static str],
+    /// What starts a comment that runs to the end of the line.
+    line_comment: Option<&'static str>,
+    /// What starts and ends a comment that may stop before the line does.
+    block_comment: Option<(&'static str, &'static str)>,
+    /// The characters strings are quoted with.
+    quotes: &'static [char],
+    strings: Strings,
+    /// What starts lines that are colored whole, first match first.
+    line_starts: &'static [(&'static str, Scope)],
+}
+
+/// Text with nothing to highlight.
+pub const PLAIN_TEXT: Language = Language {
+    name: "text",
+    keywords: &[],
+    line_comment: None,
+    block_comment: None,
+    quotes: &[],
+    strings: Strings::None,
+    line_starts: &[],
+};
+
+pub const RUST: Language = Language {
+    name: "rust",
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+        "true", "type", "unsafe", "use", "where", "while",
+    ],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    strings: Strings::Rust,
+    line_starts: &[],
+};
+
+pub const PYTHON: Language = Language {
+    name: "python",
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+        "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "True",
+        "try", "while", "with", "yield",
+    ],
+    line_comment: Some("#"),
+    block_comment: None,
+    quotes: &['"', '\''],
+    strings: Strings::Python,
+    line_starts: &[],
+};
+
+pub const SHELL: Language = Language {
+    name: "shell",
+    keywords: &[
+        "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+        "in", "local", "return", "then", "until", "while",
+    ],
+    line_comment: Some("#"),
+    block_comment: None,
+    quotes: &['"', '\''],
+    strings: Strings::Shell,
+    line_starts: &[],
+};
+
+/// Files of settings, like TOML and Makefiles: comments and strings only.
+pub const CONFIG: Language = Language {
+    name: "config",
+    keywords: &[],
+    line_comment: Some("#"),
+    block_comment: None,
+    quotes: &['"', '\''],
+    strings: Strings::None,
+    line_starts: &[],
+};
+
+/// Unified diffs, as `diff -u` and `git diff` print them.
+pub const DIFF: Language = Language {
+    name: "diff",
+    keywords: &[],
+    line_comment: None,
+    block_comment: None,
+    quotes: &[],
+    strings: Strings::None,
+    line_starts: &[
+        ("+++", Scope::Keyword),
+        ("---", Scope::Keyword),
+        ("diff ", Scope::Keyword),
+        ("@@", Scope::Number),
+        ("+", Scope::Inserted),
+        ("-", Scope::Deleted),
+    ],
+};
+
+/// A piece a line starts with from some point: how long it is in bytes,
+/// what it is if it is colored, and what it leaves open at the end of the
+/// line, if anything.
+type Piece = (usize, Option<Scope>, Option<State>);
+
+impl Language {
+    /// The highlighted pieces of `line`, taken on its own, in order; the
+    /// text between them is plain.
+    #[must_use]
+    pub fn highlight(&self, line: &str) -> Vec<Token> {
+        self.highlight_line(line, &State::Normal).0
+    }
+
+    /// The highlighted pieces of `line` when the line above left `state`
+    /// open, and the state it leaves for the line below.
+    #[must_use]
+    pub fn highlight_line(&self, line: &str, state: &State) -> (Vec<Token>, State) {
+        if let Some(&(_, scope)) = self
+            .line_starts
+            .iter()
+            .find(|(start, _)| line.starts_with(start))
+        {
+            let token = Token {
+                bytes: 0..line.len(),
+                scope,
+            };
+            return (vec![token], State::Normal);
+        }
+        let mut tokens = Vec::new();
+        let (mut start, scope, open) = self.go_on(line, state);
+        if let Some(scope) = scope.filter(|_| start > 0) {
+            tokens.push(Token {
+                bytes: 0..start,
+                scope,
+            });
+        }
+        let mut next = open.unwrap_or_default();
+        while let Some(rest) = line.get(start..) {
+            let Some(ch) = rest.chars().next() else {
+                break;
+            };
+            let (len, scope, open) = self.token_at(rest, ch);
+            let end = start.saturating_add(len.max(ch.len_utf8()));
+            if let Some(scope) = scope {
+                tokens.push(Token {
+                    bytes: start..end,
+                    scope,
+                });
+            }
+            if let Some(open) = open {
+                next = open;
+            }
+            start = end;
+        }
+        (tokens, next)
+    }
+
+    /// How much of `line` the construct the line above left open in
+    /// `state` takes: its length in bytes, what it is, and the state left at
+    /// the end of the line if it does not end on it.
+    fn go_on(&self, line: &str, state: &State) -> Piece {
+        let (len, scope) = match state {
+            State::Normal => return (0, None, None),
+            State::Comment => {
+                let end = self.block_comment.map_or("*/", |(_, end)| end);
+                (
+                    line.find(end).map(|at| at.saturating_add(end.len())),
+                    Scope::Comment,
+                )
+            }
+            State::Quoted { end, raw } => (find_end(line, end, *raw), Scope::String),
+            State::HereDoc { end } => (
+                (line.trim() == end.as_str()).then_some(line.len()),
+                Scope::String,
+            ),
+        };
+        match len {
+            Some(len) => (len, Some(scope), None),
+            None => (line.len(), Some(scope), Some(state.clone())),
+        }
+    }
+
+    /// Whether anything can be left open at the end of a line, so lines
+    /// must be looked at from the top.
+    #[must_use]
+    pub fn spans_lines(&self) -> bool {
+        self.block_comment.is_some() || self.strings != Strings::None
+    }
+
+    /// The piece `rest` starts with.
+    fn token_at(&self, rest: &str, ch: char) -> Piece {
+        if self
+            .line_comment
+            .is_some_and(|start| rest.starts_with(start))
+        {
+            return (rest.len(), Some(Scope::Comment), None);
+        }
+        if let Some((start, end)) = self
+            .block_comment
+            .filter(|(start, _)| rest.starts_with(start))
+        {
+            return match rest.get(start.len()..).and_then(|inside| inside.find(end)) {
+                Some(at) => {
+                    let len = at.saturating_add(start.len()).saturating_add(end.len());
+                    (len, Some(Scope::Comment), None)
+                }
+                None => (rest.len(), Some(Scope::Comment), Some(State::Comment)),
+            };
+        }
+        if let Some(piece) = self.string_at(rest, ch) {
+            return piece;
+        }
+        if self.quotes.contains(&ch) {
+            let spans = matches!(self.strings, Strings::Rust | Strings::Shell);
+            return string(rest, ch.len_utf8(), ch.to_string(), false, spans);
+        }
+        if ch.is_ascii_digit() {
+            return (number_len(rest), Some(Scope::Number), None);
+        }
+        if is_identifier_start(ch) {
+            let len = identifier_len(rest);
+            let word = rest.get(..len).unwrap_or_default();
+            let scope = self.keywords.contains(&word).then_some(Scope::Keyword);
+            return (len, scope, None);
+        }
+        (ch.len_utf8(), None, None)
+    }
+
+    /// The piece `rest` starts with if it is one of the language's own
+    /// kinds of string.
+    fn string_at(&self, rest: &str, ch: char) -> Option<Piece> {
+        match self.strings {
+            Strings::Rust if ch == '\'' => Some(if let Some(len) = char_literal_len(rest) {
+                (len, Some(Scope::String), None)
+            } else {
+                // The start of a lifetime.
+                let name = identifier_len(rest.get(1..).unwrap_or_default());
+                (name.saturating_add(1), None, None)
+            }),
+            Strings::Rust => {
+                let raw = rest.strip_prefix('b').unwrap_or(rest).strip_prefix('r')?;
+                let inside = raw.trim_start_matches('#');
+                let hashes = raw.len().saturating_sub(inside.len());
+                inside.starts_with('"').then(|| {
+                    let open = rest.len().saturating_sub(inside.len()).saturating_add(1);
+                    let end = format!("\"{}", "#".repeat(hashes));
+                    string(rest, open, end, true, true)
+                })
+            }
+            Strings::Python => ["\"\"\"", "'''"]
+                .into_iter()
+                .find(|quotes| rest.starts_with(quotes))
+                .map(|quotes| string(rest, quotes.len(), quotes.to_string(), false, true)),
+            // A here-string, which is not a here-document.
+            Strings::Shell if rest.starts_with("<<<") => Some((3, None, None)),
+            Strings::Shell => {
+                let operator = rest.strip_prefix("<<")?;
+                let word = operator.strip_prefix('-').unwrap_or(operator);
+                let word = word.trim_start_matches([' ', '\'', '"']);
+                if !word.starts_with(is_identifier_start) {
+                    return None;
+                }
+                let len = identifier_len(word);
+                let after = word.get(len..)?.trim_start_matches(['\'', '"']);
+                let end = word.get(..len)?.to_string();
+                let taken = rest.len().saturating_sub(after.len());
+                Some((taken, None, Some(State::HereDoc { end })))
+            }
+            Strings::None => None,
+        }
+    }
+}
+
+/// Where the `words` are in the comments of `line`, whose highlighted
+/// pieces are `tokens`, in bytes. Only whole words count: `TODOS` is not
+/// `TODO`.
+#[must_use]
+pub fn todos(line: &str, tokens: &[Token], words: &[String]) -> Vec<Range<usize>> {
+    let is_word = |at: usize, len: usize| {
+        let before = line.get(..at).and_then(|text| text.chars().next_back());
+        let after = line
+            .get(at.saturating_add(len)..)
+            .and_then(|text| text.chars().next());
+        ![before, after]
+            .into_iter()
+            .flatten()
+            .any(|ch| ch.is_alphanumeric() || ch == '_')
+    };
+    let mut found: Vec<Range<usize>> = tokens
+        .iter()
+        .filter(|token| token.scope == Scope::Comment)
+        .flat_map(|token| {
+            let comment = line.get(token.bytes.clone()).unwrap_or_default();
+            words.iter().flat_map(move |word| {
+                comment
+                    .match_indices(word.as_str())
+                    .map(move |(at, _)| at.saturating_add(token.bytes.start))
+                    .filter(move |&at| is_word(at, word.len()))
+                    .map(move |at| at..at.saturating_add(word.len()))
+            })
+        })
+        .collect();
+    found.sort_by_key(|bytes| bytes.start);
+    found
+}
+
+/// The string `text` starts with, whose opening quotes take `open` bytes
+/// and which ends at `end`. Unless it `spans` lines, one that is not closed
+/// ends with the line.
+fn string(text: &str, open: usize, end: String, raw: bool, spans: bool) -> Piece {
+    let inside = text.get(open..).unwrap_or_default();
+    if let Some(len) = find_end(inside, &end, raw) {
+        (open.saturating_add(len), Some(Scope::String), None)
+    } else {
+        let state = spans.then_some(State::Quoted { end, raw });
+        (text.len(), Some(Scope::String), state)
+    }
+}
+
+/// How far into `text` the string ending at `end` goes, `end` included;
+/// backslashes escape the character after them unless the string is `raw`.
+fn find_end(text: &str, end: &str, raw: bool) -> Option<usize> {
+    let mut escaped = false;
+    for (at, ch) in text.char_indices() {
+        if !escaped && text.get(at..)?.starts_with(end) {
+            return Some(at.saturating_add(end.len()));
+        }
+        escaped = !raw && ch == '\\' && !escaped;
+    }
+    None
+}
+
+fn is_identifier_start(ch: char) -> bool {
+    ch.is_alphabetic() || ch == '_'
+}
+
+/// The length of the identifier `text` starts with, in bytes.
+fn identifier_len(text: &str) -> usize {
+    text.find(|ch: char| !ch.is_alphanumeric() && ch != '_')
+        .unwrap_or(text.len())
+}
+
+/// The length of the number `text` starts with: digits, letters for
+/// suffixes and bases (`0x1f`, `2u8`), and a point followed by a digit.
+fn number_len(text: &str) -> usize {
+    let mut len = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((at, ch)) = chars.next() {
+        let fraction = ch == '.' && chars.peek().is_some_and(|(_, next)| next.is_ascii_digit());
+        if !ch.is_alphanumeric() && ch != '_' && !fraction {
+            break;
+        }
+        len = at.saturating_add(ch.len_utf8());
+    }
+    len
+}
+
+/// The length of the Rust character literal `text` starts with (`'a'`,
+/// `'\n'`, `'\u{1F600}'`), if it starts with one rather than a lifetime.
+fn char_literal_len(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices().skip(1);
+    let (_, first) = chars.next()?;
+    if first == '\\' {
+        chars.next()?;
+        return chars
+            .take(10)
+            .find(|(_, ch)| *ch == '\'')
+            .map(|(at, _)| at.saturating_add(1));
+    }
+    let (at, second) = chars.next()?;
+    (second == '\'').then_some(at.saturating_add(1))
+}
+
+/// The [`State`] each line of a buffer ends in, worked out on first use
+/// from the top down.
+///
+/// Lookups take `&self`, as rendering only borrows the buffer. An edit
+/// within one line is only noted: if the line still ends as it did, the
+/// states below it stay.
+#[derive(Debug, Default, Clone)]
+pub struct StateCache {
+    /// What the first lines end in, in order.
+    ends: RefCell<Vec<State>>,
+    /// The line edited since its state was worked out, if any.
+    edited: Cell<Option<usize>>,
+    /// The language the states were worked out for.
+    language: Cell<&'static str>,
+}
+
+impl StateCache {
+    /// The state line `index` starts in, in a buffer of `language` whose
+    /// lines `line` gives.
+    pub fn state_before(
+        &self,
+        index: usize,
+        language: &Language,
+        line: impl Fn(usize) -> Option<String>,
+    ) -> State {
+        if !language.spans_lines() {
+            return State::Normal;
+        }
+        let mut ends = self.ends.borrow_mut();
+        if self.language.replace(language.name) != language.name {
+            ends.clear();
+            self.edited.set(None);
+        }
+        let end_of = |ends: &[State], index: usize| {
+            let start = index
+                .checked_sub(1)
+                .and_then(|above| ends.get(above))
+                .cloned()
+                .unwrap_or_default();
+            line(index).map(|text| language.highlight_line(&text, &start).1)
+        };
+        if let Some(edited) = self.edited.take().filter(|&edited| edited < ends.len()) {
+            if end_of(&ends, edited).as_ref() != ends.get(edited) {
+                ends.truncate(edited);
+            }
+        }
+        while ends.len() < index {
+            let Some(end) = end_of(&ends, ends.len()) else {
+                break;
+            };
+            ends.push(end);
+        }
+        index
+            .checked_sub(1)
+            .and_then(|above| ends.get(above))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Notes an edit within line `line`.
+    pub fn invalidate(&mut self, line: usize) {
+        match self.edited.get() {
+            Some(edited) if edited != line => self.invalidate_from(edited.min(line)),
+            _ => self.edited.set(Some(line)),
+        }
+    }
+
+    /// Forgets the states of line `line` and everything below it, after an
+    /// edit that added or removed line breaks.
+    pub fn invalidate_from(&mut self, line: usize) {
+        let from = self.edited.take().map_or(line, |edited| edited.min(line));
+        self.ends.get_mut().truncate(from);
+    }
+
+    pub fn clear(&mut self) {
+        self.ends.get_mut().clear();
+        self.edited.set(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each highlighted piece of `line` with its scope.
+    fn pieces<'a>(language: &Language, line: &'a str) -> Vec<(&'a str, Scope)> {
+        language
+            .highlight(line)
+            .into_iter()
+            .map(|token| (&line[token.bytes], token.scope))
+            .collect()
+    }
+
+    #[test]
+    fn test_rust_lines() {
+        assert_eq!(
+            pieces(&RUST, "pub fn main() { let x = 0x1f + 2.5; } // done"),
+            [
+                ("pub", Scope::Keyword),
+                ("fn", Scope::Keyword),
+                ("let", Scope::Keyword),
+                ("0x1f", Scope::Number),
+                ("2.5", Scope::Number),
+                ("// done", Scope::Comment),
+            ]
+        );
+        assert_eq!(
+            pieces(&RUST, r#"f("a \" // b", '\'', x /* c */, 'a', 1..2) // é"#),
+            [
+                (r#""a \" // b""#, Scope::String),
+                (r"'\''", Scope::String),
+                ("/* c */", Scope::Comment),
+                ("'a'", Scope::String),
+                ("1", Scope::Number),
+                ("2", Scope::Number),
+                ("// é", Scope::Comment),
+            ]
+        );
+        // A lifetime is not a character literal, nor are names with
+        // keywords in them keywords.
+        assert_eq!(
+            pieces(&RUST, "fn f<'a>(format: &'a str)"),
+            [("fn", Scope::Keyword)]
+        );
+        assert_eq!(pieces(&RUST, "\"unclosed"), [("\"unclosed", Scope::String)]);
+    }
+
+    #[test]
+    fn test_other_languages() {
+        assert!(PLAIN_TEXT.highlight("fn main() // x").is_empty());
+        assert_eq!(
+            pieces(&PYTHON, "def f(): return 'it''s' # 2"),
+            [
+                ("def", Scope::Keyword),
+                ("return", Scope::Keyword),
+                ("'it'", Scope::String),
+                ("'s'", Scope::String),
+                ("# 2", Scope::Comment),
+            ]
+        );
+        assert_eq!(
+            pieces(&CONFIG, "name = \"x\" # if"),
+            [("\"x\"", Scope::String), ("# if", Scope::Comment)]
+        );
+        assert_eq!(
+            pieces_of(
+                &DIFF,
+                &["--- a.rs", "@@ -1 +1 @@", "-fn a()", "+fn b()", " // x"]
+            ),
+            [
+                vec![("--- a.rs", Scope::Keyword)],
+                vec![("@@ -1 +1 @@", Scope::Number)],
+                vec![("-fn a()", Scope::Deleted)],
+                vec![("+fn b()", Scope::Inserted)],
+                vec![],
+            ]
+        );
+    }
+
+    /// Each highlighted piece of each of `lines`, colored from the top.
+    fn pieces_of<'a>(language: &Language, lines: &[&'a str]) -> Vec<Vec<(&'a str, Scope)>> {
+        let mut state = State::Normal;
+        lines
+            .iter()
+            .map(|line| {
+                let (tokens, next) = language.highlight_line(line, &state);
+                state = next;
+                tokens
+                    .into_iter()
+                    .map(|token| (&line[token.bytes], token.scope))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_todos_in_comments() {
+        let words = ["TODO", "FIXME"].map(ToString::to_string);
+        let line = "let todo = \"TODO\"; // FIXME: TODOS, then TODO";
+        let found: Vec<&str> = todos(line, &RUST.highlight(line), &words)
+            .into_iter()
+            .map(|bytes| &line[bytes])
+            .collect();
+        assert_eq!(found, ["FIXME", "TODO"]);
+        assert!(todos(line, &PLAIN_TEXT.highlight(line), &words).is_empty());
+    }
+
+    #[test]
+    fn test_constructs_spanning_lines() {
+        assert_eq!(
+            pieces_of(
+                &RUST,
+                &["let x = 1; /* a", "fn */ fn", "r#\"raw \\\"", "\"# 2"]
+            ),
+            [
+                vec![
+                    ("let", Scope::Keyword),
+                    ("1", Scope::Number),
+                    ("/* a", Scope::Comment)
+                ],
+                vec![("fn */", Scope::Comment), ("fn", Scope::Keyword)],
+                vec![("r#\"raw \\\"", Scope::String)],
+                vec![("\"#", Scope::String), ("2", Scope::Number)],
+            ]
+        );
+        assert_eq!(
+            pieces_of(&PYTHON, &["x = '''if", "else''' if"]),
+            [
+                vec![("'''if", Scope::String)],
+                vec![("else'''", Scope::String), ("if", Scope::Keyword)],
+            ]
+        );
+        assert_eq!(
+            pieces_of(&SHELL, &["cat <<-'EOF' | if", "  fi", "\tEOF", "fi"]),
+            [
+                vec![("if", Scope::Keyword)],
+                vec![("  fi", Scope::String)],
+                vec![("\tEOF", Scope::String)],
+                vec![("fi", Scope::Keyword)],
+            ]
+        );
+        // Here-strings and shifts start nothing.
+        assert_eq!(
+            SHELL
+                .highlight_line("cat <<< x; echo $((1 << 2))", &State::Normal)
+                .1,
+            State::Normal
+        );
+    }
+
+    #[test]
+    fn test_state_cache_follows_edits() {
+        let mut lines = vec!["a", "/* b", "c */", "d"];
+        let cache = StateCache::default();
+        let state = |cache: &StateCache, lines: &[&str], index| {
+            cache.state_before(index, &RUST, |i| lines.get(i).map(ToString::to_string))
+        };
+        assert_eq!(state(&cache, &lines, 2), State::Comment);
+        assert_eq!(state(&cache, &lines, 3), State::Normal);
+
+        // An edit that leaves the line ending as it did keeps what is below.
+        let mut cache = cache;
+        lines[1] = "/* bb";
+        cache.invalidate(1);
+        assert_eq!(cache.ends.borrow().len(), 3);
+        assert_eq!(state(&cache, &lines, 3), State::Normal);
+        assert_eq!(cache.ends.borrow().len(), 3);
+
+        lines[1] = "b";
+        cache.invalidate(1);
+        assert_eq!(state(&cache, &lines, 2), State::Normal);
+        assert_eq!(cache.ends.borrow().len(), 2);
+        assert_eq!(
+            cache.state_before(9, &PLAIN_TEXT, |_| Some("/*".to_string())),
+            State::Normal
+        );
+    }
+}
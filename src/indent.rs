@@ -0,0 +1,188 @@
+//! # Indentation
+//!
+//! Works out how a file is indented when it is opened, so typing in it
+//! keeps to its style: with tabs if more of its indented lines start with
+//! a tab than with spaces, or else with spaces, as many per level as its
+//! indentation most often grows by from one line to the next. Only the
+//! first lines are looked at, and the lines of a `/* ... */` comment that
+//! start with `*` are skipped, as their one extra space is no level.
+//!
+//! What is found wins over the indentation of the file's
+//! [filetype](crate::filetype), and is shown in the status bar (as in
+//! `[spaces:2]`). `set indent=tabs` or `set indent=4` (spaces) decides it
+//! for every file instead, and `set indent=auto` goes back to guessing;
+//! the file's `.editorconfig` wins over both. `retab` then rewrites the
+//! indentation of every line that way, as one change to undo: leading
+//! tabs become spaces, or spaces a tab stop wide become tabs.
+//!
+//! With `smartindent` on (the default), typing what closes a block first
+//! on a line lines it up with where the block opened, by the [`Rule`]s of
+//! the file's filetype: a `}` with the line of its `{`, Python's `else:`
+//! with its `if`, or the shell's `fi` (once a space, `;` or line break
+//! follows it) with its `if`.
+
+use std::cmp::Reverse;
+
+use crate::{
+    buffer::{Buffer, Indent},
+    operator::{self, Pos},
+};
+
+/// How many lines [`detect`] looks at.
+const MAX_LINES: usize = 1000;
+
+/// The widest indentation level [`detect`] finds.
+const MAX_WIDTH: usize = 8;
+
+/// What lines a line up with the one that opened the block it is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    /// A closing bracket typed first on a line, lined up with the line of
+    /// the bracket it closes.
+    Bracket(char),
+    /// A line starting with `word`, lined up, once the word is typed out,
+    /// with the nearest line above starting with one of `openers` that
+    /// a line starting with a word that `closes` them has not closed yet.
+    Word {
+        word: &'static str,
+        openers: &'static [&'static str],
+        closes: bool,
+    },
+}
+
+/// The indentation line `line` of `buffer` takes, by `rules`, now that
+/// `typed` was typed at `col` of it (or with `'\n'`, is about to be), if
+/// that changes it.
+#[must_use]
+pub fn reindent(buffer: &Buffer, rules: &[Rule], (line, col): Pos, typed: char) -> Option<String> {
+    let before = buffer.slice((line, 0), (line, col));
+    let indentation = rules.iter().find_map(|rule| match *rule {
+        Rule::Bracket(close) if typed == close && before.trim().is_empty() => {
+            let (open, _) = operator::matching_bracket(buffer, (line, col))?;
+            Some(leading(buffer, open))
+        }
+        Rule::Word { word, openers, .. } if !is_word(typed) && before.trim_start() == word => {
+            opening_line(buffer, rules, line, openers).map(|open| leading(buffer, open))
+        }
+        _ => None,
+    })?;
+    (indentation != leading(buffer, line)).then_some(indentation)
+}
+
+/// The nearest line above `line` starting with one of `openers` that is
+/// not closed yet.
+fn opening_line(buffer: &Buffer, rules: &[Rule], line: usize, openers: &[&str]) -> Option<usize> {
+    let mut depth = 0_usize;
+    for above in (0..line).rev() {
+        let text = buffer.get(above)?;
+        let first = text
+            .trim_start()
+            .split(|ch| !is_word(ch))
+            .next()
+            .unwrap_or_default();
+        let closer = rules.iter().any(|rule| {
+            matches!(rule, Rule::Word { word, openers: closed, closes: true }
+                if *word == first && *closed == openers)
+        });
+        if closer {
+            depth = depth.saturating_add(1);
+        } else if openers.contains(&first) {
+            match depth.checked_sub(1) {
+                Some(outer) => depth = outer,
+                None => return Some(above),
+            }
+        }
+    }
+    None
+}
+
+/// The spaces and tabs line `line` of `buffer` starts with.
+fn leading(buffer: &Buffer, line: usize) -> String {
+    buffer
+        .get(line)
+        .map(|text| {
+            text.chars()
+                .take_while(|ch| matches!(ch, ' ' | '\t'))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn is_word(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// How `text` is indented, if it has indented lines to tell by.
+#[must_use]
+pub fn detect(text: &str) -> Option<Indent> {
+    let (mut tabs, mut spaces) = (0_usize, 0_usize);
+    // How often the indentation grows by each number of spaces.
+    let mut steps = [0_usize; MAX_WIDTH.saturating_add(1)];
+    let mut previous = 0;
+    for line in text.lines().take(MAX_LINES) {
+        let body = line.trim_start_matches([' ', '\t']);
+        if body.is_empty() || body.starts_with('*') {
+            continue;
+        }
+        let leading = &line[..line.len().saturating_sub(body.len())];
+        if leading.starts_with('\t') {
+            tabs = tabs.saturating_add(1);
+            continue;
+        }
+        if leading.contains('\t') {
+            continue;
+        }
+        let width = leading.len();
+        if width > 0 {
+            spaces = spaces.saturating_add(1);
+        }
+        if let Some(step) = steps.get_mut(width.saturating_sub(previous)) {
+            *step = step.saturating_add(1);
+        }
+        previous = width;
+    }
+    if tabs > spaces {
+        return Some(Indent::Tabs);
+    }
+    (1..=MAX_WIDTH)
+        .filter(|&width| steps[width] > 0)
+        .max_by_key(|&width| (steps[width], Reverse(width)))
+        .map(Indent::Spaces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_indentation_is_detected() {
+        assert_eq!(
+            detect("fn main() {\n\tlet x;\n\tif x {\n\t\ty();\n\t}\n}\n"),
+            Some(Indent::Tabs)
+        );
+        assert_eq!(
+            detect("a:\n  b:\n    c: 1\n    d: 2\n  e:\n    f: 3\n"),
+            Some(Indent::Spaces(2))
+        );
+        assert_eq!(
+            detect("/**\n * Doc.\n */\nclass A {\n    void f() {\n        g();\n    }\n}\n"),
+            Some(Indent::Spaces(4))
+        );
+        assert_eq!(detect("flat\ntext\n"), None);
+    }
+
+    #[test]
+    fn test_what_closes_a_block_lines_up_with_its_start() {
+        let rules = crate::filetype::detect(Some(std::path::Path::new("a.sh")), None).indent_rules;
+        let mut buffer = Buffer::default();
+        buffer.replace_text("if a; then\n  if b; then\n    x\n  fi\n    fi\n  {\n      }\n");
+        assert_eq!(reindent(&buffer, rules, (4, 6), '\n'), Some(String::new()));
+        assert_eq!(reindent(&buffer, rules, (3, 4), ';'), None);
+        assert_eq!(reindent(&buffer, rules, (3, 3), ';'), None);
+        assert_eq!(
+            reindent(&buffer, rules, (6, 6), '}'),
+            Some("  ".to_string())
+        );
+        assert_eq!(reindent(&buffer, rules, (2, 5), '\n'), None);
+    }
+}
@@ -0,0 +1,477 @@
+//! # JSON
+//!
+//! Just enough JSON for the messages of the [language server
+//! client](crate::lsp): a [`Value`] parsed from text and written back out
+//! compactly. Objects keep their keys in the order they came in, and
+//! numbers are `f64`, as in JavaScript.
+//!
+//! [`reformat`] lays JSON text out again, as `json-pretty` and
+//! `json-minify` do to the selection, keeping its strings and numbers
+//! exactly as they were written.
+
+use std::fmt::{self, Write};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Parses `text`, which must hold one value and nothing but whitespace
+    /// around it.
+    ///
+    /// # Errors
+    ///
+    /// Fails with what was wrong and at which line and column, if `text` is
+    /// not JSON.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = Parser { text, at: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.at < text.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    /// An object of `pairs`.
+    #[must_use]
+    pub fn object<'a>(pairs: impl IntoIterator<Item = (&'a str, Value)>) -> Self {
+        Self::Object(
+            pairs
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// The value at `key`, if this is an object that has it.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Object(pairs) => pairs
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// The value at the end of `path`, a key for each object on the way.
+    #[must_use]
+    pub fn at(&self, path: &[&str]) -> Option<&Value> {
+        path.iter().try_fold(self, |value, key| value.get(key))
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    /// The value as a count or index, if it is a whole number that fits.
+    #[must_use]
+    pub fn as_usize(&self) -> Option<usize> {
+        let number = self.as_f64()?;
+        let whole = number.fract() == 0.0 && (0.0..9.0e15).contains(&number);
+        // A whole number below 2^53 is exact both ways.
+        #[allow(
+            clippy::as_conversions,
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss
+        )]
+        whole.then_some(number as usize)
+    }
+
+    #[must_use]
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Self::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Self::String(text.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Self::String(text)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(number: usize) -> Self {
+        // Counts and positions fit easily; anything past u32 is clamped.
+        Self::Number(f64::from(u32::try_from(number).unwrap_or(u32::MAX)))
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool(value) => write!(f, "{value}"),
+            Self::Number(number) if number.is_finite() => write!(f, "{number}"),
+            // JSON has no infinity or NaN.
+            Self::Null | Self::Number(_) => f.write_str("null"),
+            Self::String(text) => write_string(f, text),
+            Self::Array(values) => {
+                f.write_char('[')?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_char(']')
+            }
+            Self::Object(pairs) => {
+                f.write_char('{')?;
+                for (index, (key, value)) in pairs.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+/// Writes `text` quoted, with what JSON requires escaped.
+fn write_string(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for ch in text.chars() {
+        match ch {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            _ if ch.is_control() => write!(f, "\\u{:04x}", u32::from(ch))?,
+            _ => f.write_char(ch)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// The JSON `text` laid out again: a value to a line, nested ones indented
+/// by `indent` more, or all on one line without spaces if `indent` is
+/// `None`.
+///
+/// # Errors
+///
+/// Fails as [`Value::parse`] does if `text` is not JSON.
+pub fn reformat(text: &str, indent: Option<&str>) -> Result<String, String> {
+    Value::parse(text)?;
+    let mut laid_out = String::with_capacity(text.len());
+    let mut depth = 0_usize;
+    let new_line = |laid_out: &mut String, depth: usize| {
+        if let Some(indent) = indent {
+            laid_out.push('\n');
+            laid_out.push_str(&indent.repeat(depth));
+        }
+    };
+    let mut chars = text.char_indices().peekable();
+    while let Some((at, ch)) = chars.next() {
+        match ch {
+            '"' => {
+                // Strings go over as they are, escapes and all.
+                let mut end = text.len();
+                let mut escaped = false;
+                for (at, ch) in chars.by_ref() {
+                    match ch {
+                        _ if escaped => escaped = false,
+                        '\\' => escaped = true,
+                        '"' => {
+                            end = at.saturating_add(1);
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                laid_out.push_str(text.get(at..end).unwrap_or_default());
+            }
+            '{' | '[' => {
+                laid_out.push(ch);
+                while chars.next_if(|(_, ch)| ch.is_ascii_whitespace()).is_some() {}
+                if chars
+                    .peek()
+                    .is_some_and(|&(_, next)| matches!(next, '}' | ']'))
+                {
+                    continue;
+                }
+                depth = depth.saturating_add(1);
+                new_line(&mut laid_out, depth);
+            }
+            '}' | ']' => {
+                if !laid_out.ends_with(['{', '[']) {
+                    depth = depth.saturating_sub(1);
+                    new_line(&mut laid_out, depth);
+                }
+                laid_out.push(ch);
+            }
+            ',' => {
+                laid_out.push(ch);
+                new_line(&mut laid_out, depth);
+            }
+            ':' => laid_out.push_str(if indent.is_some() { ": " } else { ":" }),
+            _ if ch.is_ascii_whitespace() => {}
+            _ => laid_out.push(ch),
+        }
+    }
+    Ok(laid_out)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    /// The byte looked at next.
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, problem: &str) -> String {
+        let before = self.text.get(..self.at).unwrap_or(self.text);
+        let line = before.matches('\n').count().saturating_add(1);
+        let column = before
+            .rsplit('\n')
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .count()
+            .saturating_add(1);
+        format!("{problem} at line {line}, column {column}")
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.at).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|byte| byte.is_ascii_whitespace()) {
+            self.at = self.at.saturating_add(1);
+        }
+    }
+
+    /// Skips `word` if the text goes on with it.
+    fn eat(&mut self, word: &str) -> bool {
+        let found = self
+            .text
+            .get(self.at..)
+            .is_some_and(|rest| rest.starts_with(word));
+        if found {
+            self.at = self.at.saturating_add(word.len());
+        }
+        found
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ if self.eat("true") => Ok(Value::Bool(true)),
+            _ if self.eat("false") => Ok(Value::Bool(false)),
+            _ if self.eat("null") => Ok(Value::Null),
+            None => Err(self.error("unexpected end")),
+            Some(_) => Err(self.error("unexpected character")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.eat("{");
+        let mut pairs = Vec::new();
+        self.skip_whitespace();
+        if self.eat("}") {
+            return Ok(Value::Object(pairs));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            if !self.eat(":") {
+                return Err(self.error("expected ':'"));
+            }
+            pairs.push((key, self.value()?));
+            self.skip_whitespace();
+            if self.eat("}") {
+                return Ok(Value::Object(pairs));
+            }
+            if !self.eat(",") {
+                return Err(self.error("expected ',' or '}'"));
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.eat("[");
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.eat("]") {
+            return Ok(Value::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            if self.eat("]") {
+                return Ok(Value::Array(values));
+            }
+            if !self.eat(",") {
+                return Err(self.error("expected ',' or ']'"));
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.eat("\"");
+        let mut text = String::new();
+        loop {
+            let rest = self.text.get(self.at..).unwrap_or_default();
+            let Some(ch) = rest.chars().next() else {
+                return Err(self.error("unterminated string"));
+            };
+            self.at = self.at.saturating_add(ch.len_utf8());
+            match ch {
+                '"' => return Ok(text),
+                '\\' => text.push(self.escape()?),
+                _ => text.push(ch),
+            }
+        }
+    }
+
+    /// The character an escape stands for, after its backslash.
+    fn escape(&mut self) -> Result<char, String> {
+        let Some(byte) = self.peek() else {
+            return Err(self.error("unterminated string"));
+        };
+        self.at = self.at.saturating_add(1);
+        Ok(match byte {
+            b'"' => '"',
+            b'\\' => '\\',
+            b'/' => '/',
+            b'b' => '\u{8}',
+            b'f' => '\u{c}',
+            b'n' => '\n',
+            b'r' => '\r',
+            b't' => '\t',
+            b'u' => {
+                let high = self.hex()?;
+                // Characters past U+FFFF come as a pair of surrogates.
+                let code = if (0xd800..0xdc00).contains(&high) && self.eat("\\u") {
+                    let low = self.hex()?;
+                    0x10000_u32
+                        .saturating_add((high.saturating_sub(0xd800)) << 10)
+                        .saturating_add(low.saturating_sub(0xdc00))
+                } else {
+                    high
+                };
+                char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+            }
+            _ => return Err(self.error("unknown escape")),
+        })
+    }
+
+    /// The four hex digits of a `\u` escape.
+    fn hex(&mut self) -> Result<u32, String> {
+        let digits = self
+            .text
+            .get(self.at..self.at.saturating_add(4))
+            .ok_or_else(|| self.error("short \\u escape"))?;
+        let code = u32::from_str_radix(digits, 16).map_err(|_| self.error("bad \\u escape"))?;
+        self.at = self.at.saturating_add(4);
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.at;
+        while self
+            .peek()
+            .is_some_and(|byte| byte.is_ascii_digit() || b"+-.eE".contains(&byte))
+        {
+            self.at = self.at.saturating_add(1);
+        }
+        self.text
+            .get(start..self.at)
+            .and_then(|digits| digits.parse().ok())
+            .map(Value::Number)
+            .ok_or_else(|| self.error("bad number"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_write_back() {
+        let text = r#"{"id":1,"params":{"uri":"file:///a b","ok":[true,false,null]},"x":-2.5}"#;
+        let value = Value::parse(text).unwrap();
+        assert_eq!(value.to_string(), text);
+        assert_eq!(value.get("id").and_then(Value::as_usize), Some(1));
+        assert_eq!(
+            value.at(&["params", "uri"]).and_then(Value::as_str),
+            Some("file:///a b")
+        );
+        assert_eq!(value.get("x").and_then(Value::as_usize), None);
+
+        let escaped = Value::parse(r#" "a\"\n\u00e9\ud83d\ude00" "#).unwrap();
+        assert_eq!(escaped, Value::from("a\"\né😀"));
+        assert_eq!(escaped.to_string(), "\"a\\\"\\né😀\"");
+
+        assert!(Value::parse("{\"a\" 1}").is_err());
+        assert!(Value::parse("[1,]").is_err());
+        assert!(Value::parse("\"open").is_err());
+        assert!(Value::parse("1 2").is_err());
+    }
+
+    #[test]
+    fn test_reformat_pretty_and_minified() {
+        let text = "{ \"a\" : [1, 2.50, {}], \"b\":{\"c d\":\"x, \\\"y\\\"\"}, \"e\": [ ] }";
+        let pretty = reformat(text, Some("  ")).unwrap();
+        assert_eq!(
+            pretty,
+            "{\n  \"a\": [\n    1,\n    2.50,\n    {}\n  ],\n  \"b\": {\n    \"c d\": \"x, \\\"y\\\"\"\n  },\n  \"e\": []\n}"
+        );
+        assert_eq!(
+            reformat(&pretty, None).unwrap(),
+            "{\"a\":[1,2.50,{}],\"b\":{\"c d\":\"x, \\\"y\\\"\"},\"e\":[]}"
+        );
+        assert_eq!(reformat(" 1e3 ", None).unwrap(), "1e3");
+        assert_eq!(
+            reformat("{\n  \"a\": 1,\n  \"b\" 2\n}", None).unwrap_err(),
+            "expected ':' at line 3, column 7"
+        );
+    }
+}
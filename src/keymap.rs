@@ -0,0 +1,838 @@
+//! # Key Bindings
+//!
+//! Maps key sequences to [`EditorCommand`]s. A binding is one key
+//! (`ctrl-s`) or a chord of several pressed in turn (`ctrl-x ctrl-s`); while
+//! the keys typed so far are the start of a chord, the editor waits for the
+//! rest.
+//!
+//! The built-in bindings are compiled in; the `[keys]` section of the config
+//! file adds to or replaces them, and `none` removes one:
+//!
+//! ```toml
+//! [keys]
+//! "ctrl-x ctrl-s" = "save"
+//! "ctrl-t" = "none"
+//! ```
+//!
+//! With the `modal` option on, keys mean something different in each
+//! [`Mode`], as in Vim: in normal mode they move around and edit (`hjkl`,
+//! `dd`, `o`, ...), and `i` switches to insert mode, where they type text
+//! until `Esc` switches back. Each mode has its own bindings. `[keys]` (or
+//! `[keys.insert]`) changes those of insert mode, the only mode without the
+//! option, `[keys.normal]` those of normal mode and `[keys.visual]` those of
+//! the visual modes, where the cursor selects text (`v`, `V`, `ctrl-v`).
+//!
+//! `hecto --pager`, or text piped in, starts in [`Mode::Pager`] whatever
+//! the option says: keys only move around, as in `less` (`space` and `b`
+//! page, `/` searches, `q` quits), and `[keys.pager]` changes them.
+//!
+//! Keys are written as optional `ctrl-`, `alt-` and `shift-` modifiers
+//! followed by a character or a key name (`enter`, `tab`, `space`, `up`,
+//! `pagedown`, `f5`, ...). `leader` stands for the key set by the `leader`
+//! option (`space` unless changed), so chords can start with a key of the
+//! user's choosing: normal mode binds `leader w` to save, and
+//! `"leader f g" = "..."` adds more. A chord not finished within
+//! `timeoutlen` milliseconds is dropped.
+
+use std::{collections::HashMap, fmt};
+
+use crossterm::event::{KeyCode, KeyModifiers};
+
+use crate::command::EditorCommand;
+
+/// The bindings every keymap starts with.
+const DEFAULT_BINDINGS: &[(&str, &str)] = &[
+    ("ctrl-a", "select-all"),
+    ("ctrl-b", "list-buffers"),
+    ("ctrl-n", "complete-next"),
+    ("ctrl-o", "find-file"),
+    ("ctrl-p", "complete-previous"),
+    ("ctrl-space", "complete-code"),
+    ("ctrl-q", "quit"),
+    ("ctrl-r", "open-recent"),
+    ("ctrl-s", "save"),
+    ("ctrl-t", "toggle-follow"),
+    ("ctrl-c", "copy"),
+    ("ctrl-x", "cut"),
+    ("ctrl-v", "paste"),
+    ("ctrl-y", "yank"),
+    ("ctrl-z", "suspend"),
+    ("ctrl-w s", "split-window"),
+    ("ctrl-w v", "vsplit-window"),
+    ("ctrl-w w", "next-window"),
+    ("ctrl-w +", "grow-window"),
+    ("ctrl-w -", "shrink-window"),
+    ("ctrl-w >", "widen-window"),
+    ("ctrl-w <", "narrow-window"),
+    ("ctrl-w =", "equalize-windows"),
+    ("ctrl-w c", "close-window"),
+    ("f8", "quickfix-next"),
+    ("shift-f8", "quickfix-previous"),
+    ("f12", "go-to-definition"),
+    ("shift-f12", "find-references"),
+    ("ctrl-k ctrl-i", "hover"),
+    ("alt-s", "save-all"),
+    ("alt-q", "write-quit-all"),
+    ("alt-x", "command-line"),
+    ("alt-f", "format"),
+    ("alt-.", "go-to-tag"),
+    ("alt-,", "pop-tag"),
+    ("alt-f3", "next-hunk"),
+    ("alt-shift-f3", "previous-hunk"),
+    ("alt-y", "yank-pop"),
+    ("alt-;", "toggle-comment"),
+    ("shift-tab", "outdent-lines"),
+    ("alt-v", "insert-literal"),
+    ("alt-z", "undo"),
+    ("alt-Z", "redo"),
+    ("up", "move-up"),
+    ("down", "move-down"),
+    ("left", "move-left"),
+    ("right", "move-right"),
+    ("home", "move-home"),
+    ("end", "move-end"),
+    ("pageup", "move-page-up"),
+    ("pagedown", "move-page-down"),
+    ("shift-up", "select-up"),
+    ("shift-down", "select-down"),
+    ("shift-left", "select-left"),
+    ("shift-right", "select-right"),
+    ("shift-home", "select-home"),
+    ("shift-end", "select-end"),
+    ("shift-pageup", "select-page-up"),
+    ("shift-pagedown", "select-page-down"),
+    ("enter", "newline"),
+    ("backspace", "backspace"),
+    ("delete", "delete"),
+    ("esc", "normal-mode"),
+];
+
+/// The bindings normal mode has on top of [`DEFAULT_BINDINGS`].
+const NORMAL_BINDINGS: &[(&str, &str)] = &[
+    ("h", "move-left"),
+    ("j", "move-down"),
+    ("k", "move-up"),
+    ("l", "move-right"),
+    ("0", "move-home"),
+    ("$", "move-end"),
+    ("g g", "move-first-line"),
+    ("G", "move-last-line"),
+    ("enter", "move-down"),
+    ("backspace", "move-left"),
+    ("w", "move-word-forward"),
+    ("b", "move-word-backward"),
+    ("e", "move-word-end"),
+    ("x", "delete"),
+    ("u", "undo"),
+    ("ctrl-r", "redo"),
+    ("d", "delete-operator"),
+    ("c", "change-operator"),
+    ("y", "yank-operator"),
+    (">", "indent-operator"),
+    ("<", "outdent-operator"),
+    ("r", "replace-char"),
+    ("p", "paste-after"),
+    ("P", "paste-before"),
+    ("] p", "paste-after-reindent"),
+    ("[ p", "paste-before-reindent"),
+    ("] q", "quickfix-next"),
+    ("g c c", "toggle-comment"),
+    ("g d", "go-to-definition"),
+    ("g r", "find-references"),
+    ("g x", "open-at-cursor"),
+    ("g f", "open-at-cursor"),
+    ("K", "hover"),
+    ("ctrl-]", "go-to-tag"),
+    ("ctrl-t", "pop-tag"),
+    ("[ q", "quickfix-previous"),
+    ("] c", "next-hunk"),
+    ("[ c", "previous-hunk"),
+    ("] s", "spell-next"),
+    ("[ s", "spell-previous"),
+    ("z =", "spell-suggest"),
+    ("z g", "spell-good"),
+    ("/", "search"),
+    ("n", "search-next"),
+    ("N", "search-previous"),
+    (".", "repeat-change"),
+    ("\"", "select-register"),
+    ("v", "visual-mode"),
+    ("V", "visual-line-mode"),
+    ("ctrl-v", "visual-block-mode"),
+    ("o", "open-line-below"),
+    ("O", "open-line-above"),
+    ("i", "insert-mode"),
+    ("a", "append"),
+    ("I", "insert-line-start"),
+    ("A", "append-line-end"),
+    (":", "command-line"),
+    ("leader w", "save"),
+    ("leader a", "save-all"),
+    ("leader q", "quit"),
+    ("leader x", "write-quit-all"),
+    ("leader r", "open-recent"),
+    ("leader b", "list-buffers"),
+    ("leader h s", "stage-hunk"),
+    ("leader h r", "revert-hunk"),
+];
+
+/// The command name that removes a binding.
+const UNBIND: &str = "none";
+
+/// The bindings visual mode has on top of [`DEFAULT_BINDINGS`].
+const VISUAL_BINDINGS: &[(&str, &str)] = &[
+    ("h", "move-left"),
+    ("j", "move-down"),
+    ("k", "move-up"),
+    ("l", "move-right"),
+    ("w", "move-word-forward"),
+    ("b", "move-word-backward"),
+    ("e", "move-word-end"),
+    ("0", "move-home"),
+    ("$", "move-end"),
+    ("g g", "move-first-line"),
+    ("G", "move-last-line"),
+    ("enter", "move-down"),
+    ("backspace", "move-left"),
+    ("d", "delete-operator"),
+    ("x", "delete-operator"),
+    ("delete", "delete-operator"),
+    ("c", "change-operator"),
+    ("y", "yank-operator"),
+    (">", "indent-operator"),
+    ("<", "outdent-operator"),
+    ("r", "replace-char"),
+    ("\"", "select-register"),
+    ("o", "swap-selection-ends"),
+    ("I", "insert-line-start"),
+    ("v", "visual-mode"),
+    ("V", "visual-line-mode"),
+    ("ctrl-v", "visual-block-mode"),
+    ("g c", "toggle-comment"),
+];
+
+/// The bindings of pager mode, the only ones it has.
+const PAGER_BINDINGS: &[(&str, &str)] = &[
+    ("space", "move-page-down"),
+    ("f", "move-page-down"),
+    ("ctrl-f", "move-page-down"),
+    ("pagedown", "move-page-down"),
+    ("b", "move-page-up"),
+    ("ctrl-b", "move-page-up"),
+    ("pageup", "move-page-up"),
+    ("j", "move-down"),
+    ("e", "move-down"),
+    ("enter", "move-down"),
+    ("down", "move-down"),
+    ("k", "move-up"),
+    ("y", "move-up"),
+    ("up", "move-up"),
+    ("h", "move-left"),
+    ("left", "move-left"),
+    ("l", "move-right"),
+    ("right", "move-right"),
+    ("g", "move-first-line"),
+    ("<", "move-first-line"),
+    ("home", "move-first-line"),
+    ("G", "move-last-line"),
+    (">", "move-last-line"),
+    ("end", "move-last-line"),
+    ("/", "search"),
+    ("n", "search-next"),
+    ("N", "search-previous"),
+    ("F", "toggle-follow"),
+    (":", "command-line"),
+    ("q", "quit"),
+    ("Q", "quit"),
+    ("ctrl-c", "quit"),
+];
+
+/// Which set of bindings is in use.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// Keys type text. The only mode unless the `modal` option is on.
+    #[default]
+    Insert,
+    /// Keys move the cursor and edit.
+    Normal,
+    /// Keys move the cursor to select characters, and act on them.
+    Visual,
+    /// Like [`Mode::Visual`], but whole lines are selected.
+    VisualLine,
+    /// Like [`Mode::Visual`], but the same columns of each line are
+    /// selected, as a rectangle.
+    VisualBlock,
+    /// Keys page through text that is only read, as in `less`.
+    Pager,
+}
+
+impl Mode {
+    pub const ALL: [Self; 6] = [
+        Self::Insert,
+        Self::Normal,
+        Self::Visual,
+        Self::VisualLine,
+        Self::VisualBlock,
+        Self::Pager,
+    ];
+
+    /// The mode a `[keys.NAME]` section of the config file binds keys in.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "insert" => Some(Self::Insert),
+            "normal" => Some(Self::Normal),
+            "visual" => Some(Self::Visual),
+            "pager" => Some(Self::Pager),
+            _ => None,
+        }
+    }
+
+    /// The mode whose bindings this one uses: the visual modes share them.
+    fn bindings(self) -> Self {
+        match self {
+            Self::VisualLine | Self::VisualBlock => Self::Visual,
+            mode => mode,
+        }
+    }
+
+    /// Whether text is being selected.
+    #[must_use]
+    pub fn is_visual(self) -> bool {
+        self.bindings() == Self::Visual
+    }
+}
+
+/// The name shown in the status bar.
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Insert => "INSERT",
+            Self::Normal => "NORMAL",
+            Self::Visual => "VISUAL",
+            Self::VisualLine => "VISUAL LINE",
+            Self::VisualBlock => "VISUAL BLOCK",
+            Self::Pager => "PAGER",
+        })
+    }
+}
+
+/// Stands for the leader key in bound sequences, whichever key that is.
+pub const LEADER: KeyPress = KeyPress {
+    code: KeyCode::Null,
+    modifiers: KeyModifiers::NONE,
+};
+
+/// A single key press: a key and the modifiers held with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyPress {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyPress {
+    /// Only `Ctrl`, `Alt` and `Shift` are kept. `Shift` is dropped from
+    /// characters, which already come in the shifted form (`A`, `?`).
+    #[must_use]
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers =
+            modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        if matches!(code, KeyCode::Char(_)) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        // `Shift+Tab` comes as `BackTab`, with or without `Shift`.
+        let code = match code {
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            _ => code,
+        };
+        if code == KeyCode::BackTab {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        // Terminals send `Ctrl+]` as the byte crossterm reads as `Ctrl+5`.
+        let code = match code {
+            KeyCode::Char('5') if modifiers == KeyModifiers::CONTROL => KeyCode::Char(']'),
+            _ => code,
+        };
+        Self { code, modifiers }
+    }
+
+    /// Parses a key such as `ctrl-x`, `alt-shift-up` or `-`.
+    #[must_use]
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        // A lone `-` (or `ctrl--`) is the minus key, not a separator.
+        while let Some((prefix, tail)) = rest.split_once('-').filter(|(_, tail)| !tail.is_empty()) {
+            modifiers |= match prefix.to_ascii_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+            rest = tail;
+        }
+        Some(Self::new(parse_code(rest)?, modifiers))
+    }
+
+    /// The character typing this key inserts, if it is plain text.
+    #[must_use]
+    pub fn text(self) -> Option<char> {
+        if self
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        {
+            return None;
+        }
+        match self.code {
+            KeyCode::Char(ch) => Some(ch),
+            KeyCode::Tab => Some('\t'),
+            _ => None,
+        }
+    }
+}
+
+fn parse_code(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(ch));
+    }
+    let code = match name.to_ascii_lowercase().as_str() {
+        "space" => KeyCode::Char(' '),
+        "enter" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "esc" | "escape" => KeyCode::Esc,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "leader" => KeyCode::Null,
+        other => KeyCode::F(other.strip_prefix('f')?.parse().ok()?),
+    };
+    Some(code)
+}
+
+/// Writes the key the way it is written in the config file.
+impl fmt::Display for KeyPress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl-"),
+            (KeyModifiers::ALT, "alt-"),
+            (KeyModifiers::SHIFT, "shift-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("space"),
+            KeyCode::Char(ch) => write!(f, "{ch}"),
+            KeyCode::F(number) => write!(f, "f{number}"),
+            KeyCode::PageUp => f.write_str("pageup"),
+            KeyCode::PageDown => f.write_str("pagedown"),
+            KeyCode::Null => f.write_str("leader"),
+            code => f.write_str(&format!("{code:?}").to_ascii_lowercase()),
+        }
+    }
+}
+
+/// Writes a key sequence, e.g. `ctrl-x ctrl-s`.
+#[must_use]
+pub fn describe(keys: &[KeyPress]) -> String {
+    keys.iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// What a sequence of keys means.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookup {
+    Command(EditorCommand),
+    /// The keys start a chord; more are needed.
+    Prefix,
+    Unbound,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    /// The bindings of each mode.
+    bindings: HashMap<Mode, HashMap<Vec<KeyPress>, EditorCommand>>,
+    /// The key that [`LEADER`] stands for.
+    leader: KeyPress,
+}
+
+/// The built-in bindings.
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Self {
+            bindings: HashMap::new(),
+            leader: KeyPress::new(KeyCode::Char(' '), KeyModifiers::NONE),
+        };
+        for (mode, defaults) in [
+            (Mode::Insert, DEFAULT_BINDINGS),
+            (Mode::Normal, DEFAULT_BINDINGS),
+            (Mode::Normal, NORMAL_BINDINGS),
+            (Mode::Visual, DEFAULT_BINDINGS),
+            (Mode::Visual, VISUAL_BINDINGS),
+            (Mode::Pager, PAGER_BINDINGS),
+        ] {
+            for (keys, name) in defaults {
+                if let (Some(keys), Some(command)) =
+                    (parse_keys(keys), EditorCommand::from_name(name))
+                {
+                    keymap.bind(mode, keys, Some(command));
+                }
+            }
+        }
+        keymap
+    }
+}
+
+impl Keymap {
+    /// The built-in bindings with `bindings` (mode, key sequence, command
+    /// name) applied on top, in order. Returns the keymap along with a
+    /// description of each binding that could not be applied, or that
+    /// replaced an earlier one from `bindings`.
+    #[must_use]
+    pub fn with_bindings(bindings: &[(Mode, String, String)]) -> (Self, Vec<String>) {
+        let mut keymap = Self::default();
+        let mut problems = Vec::new();
+        let mut configured: Vec<(Mode, Vec<KeyPress>)> = Vec::new();
+        for (mode, keys_text, name) in bindings {
+            let Some(keys) = parse_keys(keys_text) else {
+                problems.push(format!("unknown key `{keys_text}`"));
+                continue;
+            };
+            let command = if name == UNBIND {
+                None
+            } else if let Some(command) = EditorCommand::from_name(name) {
+                Some(command)
+            } else {
+                problems.push(format!("unknown command `{name}` for `{keys_text}`"));
+                continue;
+            };
+            for replaced in keymap.bind(*mode, keys.clone(), command) {
+                if configured.contains(&(*mode, replaced.clone())) && replaced != keys {
+                    problems.push(format!("`{keys_text}` replaces `{}`", describe(&replaced)));
+                }
+            }
+            configured.push((*mode, keys));
+        }
+        (keymap, problems)
+    }
+
+    /// Binds `keys` to `command` (or unbinds them) in `mode`, first
+    /// removing any binding that is the same as, a prefix of, or prefixed by
+    /// `keys`, since those could never be reached. Returns the removed
+    /// sequences.
+    fn bind(
+        &mut self,
+        mode: Mode,
+        keys: Vec<KeyPress>,
+        command: Option<EditorCommand>,
+    ) -> Vec<Vec<KeyPress>> {
+        let bindings = self.bindings.entry(mode.bindings()).or_default();
+        let replaced: Vec<Vec<KeyPress>> = bindings
+            .keys()
+            .filter(|bound| bound.starts_with(&keys) || keys.starts_with(bound))
+            .cloned()
+            .collect();
+        for bound in &replaced {
+            bindings.remove(bound);
+        }
+        if let Some(command) = command {
+            bindings.insert(keys, command);
+        }
+        replaced
+    }
+
+    /// Makes `key` the one that [`LEADER`] stands for.
+    pub fn set_leader(&mut self, key: KeyPress) {
+        self.leader = key;
+    }
+
+    /// What the keys typed so far mean in `mode`. Keys bound as typed win
+    /// over the leader key standing for [`LEADER`].
+    #[must_use]
+    pub fn lookup(&self, mode: Mode, keys: &[KeyPress]) -> Lookup {
+        let Some(bindings) = self.bindings.get(&mode.bindings()) else {
+            return Lookup::Unbound;
+        };
+        match lookup_in(bindings, keys) {
+            Lookup::Unbound if keys.contains(&self.leader) => {
+                lookup_in(bindings, &self.with_leader(keys))
+            }
+            found => found,
+        }
+    }
+
+    /// The keys that can follow `keys` in `mode` to finish a chord, with
+    /// the command each runs, in order.
+    #[must_use]
+    pub fn continuations(&self, mode: Mode, keys: &[KeyPress]) -> Vec<(String, EditorCommand)> {
+        let Some(bindings) = self.bindings.get(&mode.bindings()) else {
+            return Vec::new();
+        };
+        let typed = if lookup_in(bindings, keys) == Lookup::Prefix {
+            keys.to_vec()
+        } else {
+            self.with_leader(keys)
+        };
+        let mut found: Vec<(String, EditorCommand)> = bindings
+            .iter()
+            .filter_map(|(bound, command)| {
+                let rest = bound.strip_prefix(typed.as_slice())?;
+                (!rest.is_empty()).then(|| (describe(rest), *command))
+            })
+            .collect();
+        found.sort_by(|(a, _), (b, _)| a.cmp(b));
+        found
+    }
+
+    /// `keys` with [`LEADER`] in place of the leader key.
+    fn with_leader(&self, keys: &[KeyPress]) -> Vec<KeyPress> {
+        keys.iter()
+            .map(|&key| if key == self.leader { LEADER } else { key })
+            .collect()
+    }
+}
+
+/// What `keys` mean in one mode's `bindings`.
+fn lookup_in(bindings: &HashMap<Vec<KeyPress>, EditorCommand>, keys: &[KeyPress]) -> Lookup {
+    if let Some(command) = bindings.get(keys) {
+        return Lookup::Command(*command);
+    }
+    if !keys.is_empty() && bindings.keys().any(|bound| bound.starts_with(keys)) {
+        Lookup::Prefix
+    } else {
+        Lookup::Unbound
+    }
+}
+
+/// Parses a space-separated key sequence.
+fn parse_keys(text: &str) -> Option<Vec<KeyPress>> {
+    let keys = text
+        .split_whitespace()
+        .map(KeyPress::parse)
+        .collect::<Option<Vec<_>>>()?;
+    (!keys.is_empty()).then_some(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{command::Movement, operator::Operator};
+
+    fn key(text: &str) -> KeyPress {
+        KeyPress::parse(text).unwrap()
+    }
+
+    /// Insert-mode bindings, as in the `[keys]` section.
+    fn bindings(pairs: &[(&str, &str)]) -> Vec<(Mode, String, String)> {
+        pairs
+            .iter()
+            .map(|(keys, name)| (Mode::Insert, (*keys).to_string(), (*name).to_string()))
+            .collect()
+    }
+
+    fn lookup(keymap: &Keymap, keys: &[KeyPress]) -> Lookup {
+        keymap.lookup(Mode::Insert, keys)
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(
+            key("ctrl-s"),
+            KeyPress::new(KeyCode::Char('s'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            key("Alt-Shift-PageUp"),
+            KeyPress::new(KeyCode::PageUp, KeyModifiers::ALT | KeyModifiers::SHIFT)
+        );
+        assert_eq!(
+            key("-"),
+            KeyPress::new(KeyCode::Char('-'), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            key("ctrl--"),
+            KeyPress::new(KeyCode::Char('-'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(key("f12").code, KeyCode::F(12));
+        assert_eq!(key("space").code, KeyCode::Char(' '));
+        assert_eq!(KeyPress::parse("hyper-x"), None);
+        assert_eq!(KeyPress::parse("pgdn"), None);
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        for text in ["ctrl-x", "alt-shift-up", "f5", "space", "ctrl--", "enter"] {
+            assert_eq!(key(text).to_string(), text);
+        }
+        assert_eq!(describe(&[key("ctrl-x"), key("s")]), "ctrl-x s");
+    }
+
+    #[test]
+    fn test_shift_is_dropped_from_characters() {
+        let typed = KeyPress::new(KeyCode::Char('S'), KeyModifiers::SHIFT);
+        assert_eq!(typed.text(), Some('S'));
+        assert_eq!(
+            KeyPress::new(KeyCode::Tab, KeyModifiers::NONE).text(),
+            Some('\t')
+        );
+        assert_eq!(key("ctrl-s").text(), None);
+        assert_eq!(
+            KeyPress::new(KeyCode::Char('5'), KeyModifiers::CONTROL),
+            key("ctrl-]")
+        );
+        assert_eq!(
+            KeyPress::new(KeyCode::BackTab, KeyModifiers::SHIFT),
+            key("shift-tab")
+        );
+    }
+
+    #[test]
+    fn test_default_bindings_are_all_valid() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.bindings[&Mode::Insert].len(), DEFAULT_BINDINGS.len());
+        assert_eq!(
+            lookup(&keymap, &[key("pagedown")]),
+            Lookup::Command(EditorCommand::MoveCursor(Movement::PageDown))
+        );
+    }
+
+    #[test]
+    fn test_each_mode_has_its_own_bindings() {
+        let (keymap, problems) =
+            Keymap::with_bindings(&[(Mode::Normal, "ctrl-s".to_string(), "quit".to_string())]);
+        assert!(problems.is_empty(), "{problems:?}");
+        assert_eq!(lookup(&keymap, &[key("j")]), Lookup::Unbound);
+        assert_eq!(
+            keymap.lookup(Mode::Normal, &[key("j")]),
+            Lookup::Command(EditorCommand::MoveCursor(Movement::Down))
+        );
+        assert_eq!(
+            keymap.lookup(Mode::Normal, &[key("d")]),
+            Lookup::Command(EditorCommand::Operator(Operator::Delete))
+        );
+        assert_eq!(keymap.lookup(Mode::Normal, &[key("g")]), Lookup::Prefix);
+        assert_eq!(
+            keymap.lookup(Mode::Normal, &[key("ctrl-s")]),
+            Lookup::Command(EditorCommand::Quit)
+        );
+        assert_eq!(
+            lookup(&keymap, &[key("ctrl-s")]),
+            Lookup::Command(EditorCommand::Save)
+        );
+        assert_eq!(
+            keymap.lookup(Mode::Pager, &[key("space")]),
+            Lookup::Command(EditorCommand::MoveCursor(Movement::PageDown))
+        );
+        assert_eq!(
+            keymap.lookup(Mode::Pager, &[key("g")]),
+            Lookup::Command(EditorCommand::MoveCursor(Movement::FirstLine))
+        );
+        assert_eq!(
+            keymap.lookup(Mode::Pager, &[key("ctrl-s")]),
+            Lookup::Unbound
+        );
+        assert_eq!(Mode::from_name("pager"), Some(Mode::Pager));
+    }
+
+    #[test]
+    fn test_chords() {
+        let (keymap, problems) = Keymap::with_bindings(&bindings(&[
+            ("ctrl-x ctrl-s", "save"),
+            ("ctrl-x k", "quit"),
+        ]));
+        assert!(problems.is_empty(), "{problems:?}");
+        assert_eq!(lookup(&keymap, &[key("ctrl-x")]), Lookup::Prefix);
+        assert_eq!(
+            lookup(&keymap, &[key("ctrl-x"), key("ctrl-s")]),
+            Lookup::Command(EditorCommand::Save)
+        );
+        assert_eq!(lookup(&keymap, &[key("ctrl-x"), key("z")]), Lookup::Unbound);
+        // The default binding is still there.
+        assert_eq!(
+            lookup(&keymap, &[key("ctrl-s")]),
+            Lookup::Command(EditorCommand::Save)
+        );
+    }
+
+    #[test]
+    fn test_leader_chords() {
+        let (mut keymap, problems) = Keymap::with_bindings(&bindings(&[
+            ("leader f g", "open-recent"),
+            ("space", "quit"),
+        ]));
+        assert!(problems.is_empty(), "{problems:?}");
+        // A key bound as typed wins over the leader standing for it.
+        assert_eq!(
+            lookup(&keymap, &[key("space")]),
+            Lookup::Command(EditorCommand::Quit)
+        );
+        keymap.set_leader(key(","));
+        assert_eq!(lookup(&keymap, &[key(","), key("f")]), Lookup::Prefix);
+        assert_eq!(
+            lookup(&keymap, &[key(","), key("f"), key("g")]),
+            Lookup::Command(EditorCommand::OpenRecent)
+        );
+        assert_eq!(
+            keymap.continuations(Mode::Insert, &[key(",")]),
+            vec![("f g".to_string(), EditorCommand::OpenRecent)]
+        );
+        assert_eq!(
+            keymap
+                .continuations(Mode::Normal, &[key(",")])
+                .first()
+                .map(|(keys, _)| keys.as_str()),
+            Some("a")
+        );
+        assert_eq!(key("leader").to_string(), "leader");
+    }
+
+    #[test]
+    fn test_user_bindings_replace_and_remove_defaults() {
+        let (keymap, problems) = Keymap::with_bindings(&bindings(&[
+            ("ctrl-s ctrl-s", "save-all"),
+            ("ctrl-t", "none"),
+            ("alt-q", "quit"),
+        ]));
+        assert!(problems.is_empty(), "{problems:?}");
+        assert_eq!(lookup(&keymap, &[key("ctrl-s")]), Lookup::Prefix);
+        assert_eq!(lookup(&keymap, &[key("ctrl-t")]), Lookup::Unbound);
+        assert_eq!(
+            lookup(&keymap, &[key("alt-q")]),
+            Lookup::Command(EditorCommand::Quit)
+        );
+    }
+
+    #[test]
+    fn test_invalid_bindings_are_reported() {
+        let (keymap, problems) = Keymap::with_bindings(&bindings(&[
+            ("ctrl-q", "explode"),
+            ("hyper-q", "quit"),
+            ("ctrl-x", "save"),
+            ("ctrl-x ctrl-c", "quit"),
+        ]));
+        assert_eq!(
+            problems,
+            vec![
+                "unknown command `explode` for `ctrl-q`",
+                "unknown key `hyper-q`",
+                "`ctrl-x ctrl-c` replaces `ctrl-x`",
+            ]
+        );
+        assert_eq!(
+            lookup(&keymap, &[key("ctrl-q")]),
+            Lookup::Command(EditorCommand::Quit)
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! # Kill Ring
+//!
+//! Emacs's memory of recent kills: every cut, delete and copy adds its text
+//! here, newest first, whatever the system clipboard holds. `Ctrl+Y` yanks
+//! (inserts) the newest; `Alt+Y` right after swaps what was yanked for the
+//! kill before it, and so on round the ring.
+
+use std::collections::VecDeque;
+
+/// How many kills are kept; older ones are dropped.
+const CAPACITY: usize = 60;
+
+#[derive(Debug, Default, Clone)]
+pub struct KillRing {
+    /// The kills, newest first.
+    entries: VecDeque<String>,
+}
+
+impl KillRing {
+    /// Adds `text` as the newest kill. Empty text, or the same text as the
+    /// newest kill, adds nothing.
+    pub fn push(&mut self, text: &str) {
+        if text.is_empty() || self.entries.front().is_some_and(|newest| newest == text) {
+            return;
+        }
+        self.entries.push_front(text.to_string());
+        self.entries.truncate(CAPACITY);
+    }
+
+    /// The kill `index` entries older than the newest, going round the
+    /// ring past the oldest; `None` if there are none.
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&str> {
+        let index = index.checked_rem(self.entries.len())?;
+        self.entries.get(index).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kills_go_round_newest_first() {
+        let mut ring = KillRing::default();
+        assert_eq!(ring.get(0), None);
+        for text in ["one", "", "two", "two"] {
+            ring.push(text);
+        }
+        assert_eq!(ring.get(0), Some("two"));
+        assert_eq!(ring.get(1), Some("one"));
+        assert_eq!(ring.get(2), Some("two"));
+
+        for n in 0..=CAPACITY {
+            ring.push(&n.to_string());
+        }
+        assert_eq!(ring.get(CAPACITY.saturating_sub(1)), Some("1"));
+        assert_eq!(ring.get(CAPACITY), Some(CAPACITY.to_string().as_str()));
+    }
+}
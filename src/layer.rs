@@ -0,0 +1,143 @@
+//! # Highlight Layers
+//!
+//! What is colored over a window's text comes in layers: the syntax
+//! highlighting (with the `todowords` in comments), the words
+//! [misspelled](crate::spell), the text [diagnostics](crate::diagnostic)
+//! are about, the lines that differ from the file a buffer is
+//! [compared](crate::diff) with, the bracket under the cursor and the one
+//! matching it, the
+//! places the last [search](crate::search) found, and the selection. Each
+//! gives [`Span`]s of the lines on screen, and they are laid over the text
+//! in the order of [`Layer::ALL`], so where two overlap the later one's
+//! colors win and the colors it does not set show through from below.
+//! Coloring something new is a matter of a new layer and the spans it
+//! gives; nothing that draws has to change.
+
+use std::ops::Range;
+
+use crate::{
+    buffer::Buffer,
+    highlight,
+    spell::{self, Dictionary},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    Syntax,
+    Differences,
+    Spelling,
+    Diagnostics,
+    Brackets,
+    Search,
+    Selection,
+}
+
+impl Layer {
+    /// Every layer, bottom first.
+    pub const ALL: [Self; 7] = [
+        Self::Syntax,
+        Self::Differences,
+        Self::Spelling,
+        Self::Diagnostics,
+        Self::Brackets,
+        Self::Search,
+        Self::Selection,
+    ];
+}
+
+/// Part of a line colored by a layer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    /// The columns colored, in graphemes, end excluded.
+    pub cols: Range<usize>,
+    /// The theme element it is colored with.
+    pub element: &'static str,
+}
+
+/// The spans `buffer`'s [highlighting](crate::highlight) colors in `lines`,
+/// the `todo_words` in its comments last.
+#[must_use]
+pub fn syntax(buffer: &Buffer, lines: Range<usize>, todo_words: &[String]) -> Vec<Span> {
+    let mut spans = Vec::new();
+    for line in lines {
+        let (Some(text), layout) = (buffer.get(line), buffer.layout(line)) else {
+            continue;
+        };
+        let tokens = buffer.highlight(line);
+        let todos = highlight::todos(&text, &tokens, todo_words);
+        let scoped = tokens
+            .into_iter()
+            .map(|token| (token.bytes, token.scope.element()))
+            .chain(todos.into_iter().map(|bytes| (bytes, TODO)));
+        spans.extend(scoped.map(|(bytes, element)| Span {
+            line,
+            cols: layout.index_of_byte(bytes.start)..layout.index_of_byte(bytes.end),
+            element,
+        }));
+    }
+    spans
+}
+
+/// The spans of the words in `lines` of `buffer` that `dictionary` says
+/// are [misspelled](crate::spell).
+#[must_use]
+pub fn spelling(buffer: &Buffer, lines: Range<usize>, dictionary: &Dictionary) -> Vec<Span> {
+    lines
+        .flat_map(|line| {
+            spell::misspelled(buffer, line, dictionary)
+                .into_iter()
+                .map(move |cols| Span {
+                    line,
+                    cols,
+                    element: MISSPELLED,
+                })
+        })
+        .collect()
+}
+
+/// The lines in `lines` of `buffer` that its `changes` say differ from the
+/// text it is compared with, colored across the window: those only it
+/// has, and those that replaced others.
+#[must_use]
+pub fn differences(buffer: &Buffer, lines: &Range<usize>) -> Vec<Span> {
+    buffer
+        .changes
+        .iter()
+        .flat_map(|hunk| {
+            let element = if hunk.old.is_empty() { ADDED } else { CHANGED };
+            let shown = hunk.new.start.max(lines.start)..hunk.new.end.min(lines.end);
+            shown.map(move |line| Span {
+                line,
+                cols: 0..usize::MAX,
+                element,
+            })
+        })
+        .collect()
+}
+
+/// The spans of `buffer`'s [diagnostics](crate::diagnostic) in `lines`.
+#[must_use]
+pub fn diagnostics(buffer: &Buffer, lines: Range<usize>) -> Vec<Span> {
+    buffer
+        .diagnostics
+        .iter()
+        .filter(|diagnostic| lines.contains(&diagnostic.line))
+        .map(|diagnostic| Span {
+            line: diagnostic.line,
+            cols: diagnostic.cols.clone(),
+            element: diagnostic.element(),
+        })
+        .collect()
+}
+
+/// The theme element `todowords` in comments are colored with.
+const TODO: &str = "syntax.comment.todo";
+
+/// The theme elements of lines only one of two compared texts has, and
+/// of those that replaced others.
+const ADDED: &str = "diff.added";
+const CHANGED: &str = "diff.changed";
+
+/// The theme element misspelled words are colored with.
+const MISSPELLED: &str = "spell.bad";
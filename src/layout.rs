@@ -0,0 +1,327 @@
+//! # Line Layout
+//!
+//! Works out how a line of text occupies the screen: where each grapheme
+//! (user-perceived character) starts in the line, how many columns it
+//! takes, and where tabs land. Rendering and cursor placement both need
+//! this, so [`Buffer`](crate::buffer::Buffer) caches one [`LineLayout`] per
+//! line and throws it away when the line is edited.
+//!
+//! We don't depend on the full Unicode tables; the ranges below cover
+//! combining marks, joiners, and the common double-width blocks (CJK,
+//! Hangul, fullwidth forms, emoji), which is what shows up in practice.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+/// Columns between tab stops, unless the buffer is set up otherwise.
+pub const TAB_WIDTH: usize = 4;
+
+/// Shown in place of control characters, which have no glyph of their own.
+const CONTROL_PLACEHOLDER: char = '▯';
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+/// Characters that attach to the preceding one instead of starting a new
+/// grapheme (combining marks, variation selectors, ...).
+const EXTENDING: &[(u32, u32)] = &[
+    (0x0300, 0x036F),
+    (0x0483, 0x0489),
+    (0x0591, 0x05BD),
+    (0x0610, 0x061A),
+    (0x064B, 0x065F),
+    (0x0E31, 0x0E31),
+    (0x0E34, 0x0E3A),
+    (0x1AB0, 0x1AFF),
+    (0x1DC0, 0x1DFF),
+    (0x200C, 0x200D),
+    (0x20D0, 0x20FF),
+    (0xFE00, 0xFE0F),
+    (0xFE20, 0xFE2F),
+    (0x1F3FB, 0x1F3FF),
+    (0xE0020, 0xE007F),
+    (0xE0100, 0xE01EF),
+];
+
+/// Characters taking two columns.
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115F),
+    (0x2E80, 0x303E),
+    (0x3041, 0x33FF),
+    (0x3400, 0x4DBF),
+    (0x4E00, 0x9FFF),
+    (0xA000, 0xA4CF),
+    (0xAC00, 0xD7A3),
+    (0xF900, 0xFAFF),
+    (0xFE30, 0xFE4F),
+    (0xFF00, 0xFF60),
+    (0xFFE0, 0xFFE6),
+    (0x1F300, 0x1F64F),
+    (0x1F900, 0x1F9FF),
+    (0x20000, 0x3FFFD),
+];
+
+fn in_ranges(ch: char, ranges: &[(u32, u32)]) -> bool {
+    let code = u32::from(ch);
+    ranges
+        .iter()
+        .any(|&(start, end)| (start..=end).contains(&code))
+}
+
+fn is_extending(ch: char) -> bool {
+    in_ranges(ch, EXTENDING)
+}
+
+/// Display width of a single character (ignoring tabs, which depend on the
+/// column they are in).
+#[must_use]
+pub fn char_width(ch: char) -> usize {
+    if is_extending(ch) {
+        0
+    } else if in_ranges(ch, WIDE) {
+        2
+    } else {
+        1
+    }
+}
+
+/// One grapheme of a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grapheme {
+    /// Byte offset in the line.
+    pub start: usize,
+    /// Length in bytes.
+    pub len: usize,
+    /// Screen column where the grapheme starts.
+    pub col: usize,
+    /// Number of screen columns it takes (tabs included).
+    pub width: usize,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LineLayout {
+    graphemes: Vec<Grapheme>,
+    /// Length of the line in bytes.
+    byte_len: usize,
+}
+
+impl LineLayout {
+    /// Lays out `line` with tab stops every [`TAB_WIDTH`] columns.
+    #[must_use]
+    pub fn new(line: &str) -> Self {
+        Self::with_tab_width(line, TAB_WIDTH)
+    }
+
+    /// Lays out `line` with tab stops every `tab_width` columns.
+    #[must_use]
+    pub fn with_tab_width(line: &str, tab_width: usize) -> Self {
+        let mut graphemes: Vec<Grapheme> = Vec::new();
+        let mut col = 0_usize;
+        let mut previous = None;
+        for (start, ch) in line.char_indices() {
+            let joins_previous = is_extending(ch) || previous == Some(ZERO_WIDTH_JOINER);
+            previous = Some(ch);
+            if let Some(last) = graphemes.last_mut().filter(|_| joins_previous) {
+                last.len = last.len.saturating_add(ch.len_utf8());
+                continue;
+            }
+            let width = if ch == '\t' {
+                tab_width.saturating_sub(col.checked_rem(tab_width).unwrap_or(0))
+            } else {
+                char_width(ch)
+            };
+            graphemes.push(Grapheme {
+                start,
+                len: ch.len_utf8(),
+                col,
+                width,
+            });
+            col = col.saturating_add(width);
+        }
+        Self {
+            graphemes,
+            byte_len: line.len(),
+        }
+    }
+
+    /// Number of graphemes in the line.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.graphemes.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.graphemes.is_empty()
+    }
+
+    /// Total display width of the line.
+    #[must_use]
+    pub fn width(&self) -> usize {
+        self.graphemes
+            .last()
+            .map_or(0, |last| last.col.saturating_add(last.width))
+    }
+
+    /// Byte offset where grapheme `index` starts (the line length past the
+    /// last grapheme).
+    #[must_use]
+    pub fn byte_offset(&self, index: usize) -> usize {
+        self.graphemes
+            .get(index)
+            .map_or(self.byte_len, |grapheme| grapheme.start)
+    }
+
+    /// Screen column where grapheme `index` starts (the line width past the
+    /// last grapheme).
+    #[must_use]
+    pub fn column(&self, index: usize) -> usize {
+        self.graphemes
+            .get(index)
+            .map_or_else(|| self.width(), |grapheme| grapheme.col)
+    }
+
+    /// Index of the grapheme byte offset `byte` falls in (the number of
+    /// graphemes past the end of the line).
+    #[must_use]
+    pub fn index_of_byte(&self, byte: usize) -> usize {
+        self.graphemes
+            .partition_point(|grapheme| grapheme.start.saturating_add(grapheme.len) <= byte)
+    }
+
+    /// Index of the grapheme shown at screen column `column` (the number of
+    /// graphemes past the end of the line).
+    #[must_use]
+    pub fn index_at(&self, column: usize) -> usize {
+        self.graphemes
+            .partition_point(|grapheme| grapheme.col.saturating_add(grapheme.width) <= column)
+    }
+
+    /// Renders `line` (which must be the line this layout was built from)
+    /// as it appears on screen in `width` columns: tabs expanded to spaces,
+    /// control characters replaced, and a wide character that would straddle
+    /// the right edge replaced by a space.
+    #[must_use]
+    pub fn render(&self, line: &str, width: usize) -> String {
+        let mut rendered = String::new();
+        for grapheme in &self.graphemes {
+            let end = grapheme.col.saturating_add(grapheme.width);
+            if grapheme.col >= width {
+                break;
+            }
+            let text = line
+                .get(grapheme.start..grapheme.start.saturating_add(grapheme.len))
+                .unwrap_or_default();
+            if end > width {
+                rendered.push_str(&" ".repeat(width.saturating_sub(grapheme.col)));
+                break;
+            }
+            match text.chars().next() {
+                Some('\t') => rendered.push_str(&" ".repeat(grapheme.width)),
+                Some(ch) if ch.is_control() => rendered.push(CONTROL_PLACEHOLDER),
+                _ => rendered.push_str(text),
+            }
+        }
+        rendered
+    }
+}
+
+/// Layouts of the lines of one buffer, computed on first use.
+///
+/// Lookups go through `&self` because rendering only borrows the buffer;
+/// invalidation needs `&mut self`, which every edit has anyway.
+#[derive(Debug, Default, Clone)]
+pub struct LayoutCache {
+    lines: RefCell<HashMap<usize, Rc<LineLayout>>>,
+}
+
+impl LayoutCache {
+    /// The cached layout of line `line`, computing it with `layout` if it is
+    /// not cached yet.
+    pub fn get_or_insert_with(
+        &self,
+        line: usize,
+        layout: impl FnOnce() -> LineLayout,
+    ) -> Rc<LineLayout> {
+        Rc::clone(
+            self.lines
+                .borrow_mut()
+                .entry(line)
+                .or_insert_with(|| Rc::new(layout())),
+        )
+    }
+
+    /// Forgets the layout of line `line`, after an edit within it.
+    pub fn invalidate(&mut self, line: usize) {
+        self.lines.get_mut().remove(&line);
+    }
+
+    /// Forgets the layouts of line `line` and everything below it, after an
+    /// edit that added or removed line breaks (and so renumbered the lines).
+    pub fn invalidate_from(&mut self, line: usize) {
+        self.lines.get_mut().retain(|&cached, _| cached < line);
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.get_mut().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii_layout() {
+        let layout = LineLayout::new("abc");
+        assert_eq!(layout.len(), 3);
+        assert_eq!(layout.width(), 3);
+        assert_eq!(layout.byte_offset(1), 1);
+        assert_eq!(layout.byte_offset(99), 3);
+        assert_eq!(layout.column(3), 3);
+    }
+
+    #[test]
+    fn test_combining_marks_join_previous_grapheme() {
+        // "e" + COMBINING ACUTE ACCENT, then "x"
+        let line = "e\u{301}x";
+        let layout = LineLayout::new(line);
+        assert_eq!(layout.len(), 2);
+        assert_eq!(layout.width(), 2);
+        assert_eq!(layout.byte_offset(1), 3);
+    }
+
+    #[test]
+    fn test_wide_characters_take_two_columns() {
+        let line = "日本a";
+        let layout = LineLayout::new(line);
+        assert_eq!(layout.width(), 5);
+        assert_eq!(layout.column(2), 4);
+        assert_eq!(layout.index_at(3), 1);
+        assert_eq!(layout.index_at(9), 3);
+        // The second wide character would straddle column 3.
+        assert_eq!(layout.render(line, 3), "日 ");
+    }
+
+    #[test]
+    fn test_tabs_expand_to_next_stop() {
+        let line = "a\tb";
+        let layout = LineLayout::new(line);
+        assert_eq!(layout.column(2), TAB_WIDTH);
+        assert_eq!(layout.render(line, 80), format!("a{}b", " ".repeat(3)));
+
+        let layout = LineLayout::with_tab_width(line, 8);
+        assert_eq!(layout.column(2), 8);
+    }
+
+    #[test]
+    fn test_zwj_sequences_form_one_grapheme() {
+        // MAN + ZWJ + LAPTOP
+        let layout = LineLayout::new("👨\u{200D}💻!");
+        assert_eq!(layout.len(), 2);
+    }
+
+    #[test]
+    fn test_control_characters_are_replaced() {
+        let line = "a\u{7}b";
+        assert_eq!(LineLayout::new(line).render(line, 80), "a▯b");
+    }
+}
@@ -0,0 +1,93 @@
+#![warn(
+    clippy::all,
+    clippy::pedantic,
+    clippy::print_stdout,
+    clippy::arithmetic_side_effects,
+    clippy::as_conversions,
+    clippy::integer_division
+)]
+
+//! # hecto
+//!
+//! A small terminal text editor. The binary only calls [`Editor::run`]; the
+//! pieces are exposed here so other tools can embed the editor, drive it
+//! with their own events, or reuse its text storage (see `benches/`).
+//!
+//! - [`Editor`]: the event loop and everything the user can do.
+//! - [`View`]: renders a [`Buffer`] into screen rows.
+//! - [`Buffer`]: the text of a file, stored in a piece table.
+//! - [`terminal`]: the thin layer over crossterm everything is drawn through.
+//! - [`events`]: where the main loop reads its input from.
+
+pub mod abbreviation;
+pub mod ansi;
+mod base64;
+pub mod buffer;
+pub mod buffer_list;
+pub mod calc;
+mod capabilities;
+pub mod clipboard;
+pub mod command;
+mod command_line;
+pub mod completion;
+pub mod compression;
+mod config;
+pub mod diagnostic;
+pub mod diff;
+pub mod directory;
+mod dirs;
+pub mod editor;
+pub mod editorconfig;
+pub mod error;
+pub mod events;
+pub mod files;
+pub mod filetype;
+mod follow;
+pub mod format;
+mod frame;
+pub mod git;
+pub mod highlight;
+pub mod indent;
+pub mod json;
+pub mod keymap;
+pub mod kill_ring;
+pub mod layer;
+pub mod layout;
+pub mod lint;
+mod loader;
+pub mod logging;
+pub mod lsp;
+pub mod markdown;
+pub mod notifications;
+pub mod operator;
+pub mod options;
+mod picker;
+pub mod piece_table;
+pub mod plugin;
+mod positions;
+mod project;
+mod prompt;
+pub mod quickfix;
+mod recent;
+pub mod register;
+mod scheduler;
+mod screen;
+pub mod script;
+pub mod search;
+pub mod selection;
+pub mod shell;
+pub mod spell;
+mod statusbar;
+pub mod tags;
+pub mod template;
+pub mod terminal;
+pub mod theme;
+pub mod undo;
+pub mod url;
+pub mod viewer;
+mod which_key;
+pub mod window;
+
+pub use buffer::Buffer;
+pub use editor::Editor;
+pub use viewer::View;
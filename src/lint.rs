@@ -0,0 +1,229 @@
+//! # Linters
+//!
+//! `lint` runs the linter for the buffer's filetype, a shell command whose
+//! complaints come out the way compilers print errors (see
+//! [`quickfix::parse`]): `cargo clippy` for Rust, `ruff` for Python and
+//! `shellcheck` for shell scripts. A `%` in the command stands for the
+//! buffer's file. The `[linters]` section of the config file sets others,
+//! or turns one off with an empty command:
+//!
+//! ```toml
+//! [linters]
+//! python = "pylint --output-format=parseable %"
+//! shell = ""
+//! ```
+//!
+//! With the `lintonsave` option on, the linter runs whenever a buffer is
+//! saved. It runs on a thread of its own, so the editor can be used while
+//! it works; once it is done, what it found fills the quickfix list, and
+//! each buffer of a file it complained about gets [diagnostics] on the
+//! lines concerned, in place of those of the run before. A run started
+//! while another is going replaces it.
+
+use std::{
+    path::{self, Path},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use crate::{
+    buffer::Buffer,
+    completion::is_word_char,
+    diagnostic::{Diagnostic, Source},
+    error::{Error, Result},
+    notifications::Severity,
+    quickfix::{self, Entry},
+    shell::{self, Output},
+};
+
+/// The built-in linter for each filetype.
+const LINTERS: &[(&str, &str)] = &[
+    ("rust", "cargo clippy --quiet --message-format=short"),
+    ("python", "ruff check --output-format=concise %"),
+    ("shell", "shellcheck --format=gcc %"),
+];
+
+/// The linter for `filetype`, to check `path`: the one `configured` for
+/// it, or else the built-in one, with `%` replaced by `path`; `None` if it
+/// has none, or it was turned off.
+#[must_use]
+pub fn command(filetype: &str, configured: &[(String, String)], path: &Path) -> Option<String> {
+    let command = configured
+        .iter()
+        .rev()
+        .find(|(name, _)| name == filetype)
+        .map(|(_, command)| command.as_str())
+        .or_else(|| {
+            LINTERS
+                .iter()
+                .find(|(name, _)| *name == filetype)
+                .map(|(_, command)| *command)
+        })
+        .filter(|command| !command.is_empty())?;
+    Some(command.replace('%', &shell::quote(&path.to_string_lossy())))
+}
+
+/// A linter running in the background.
+#[derive(Debug)]
+pub struct Run {
+    command: String,
+    receiver: Receiver<Result<Output>>,
+}
+
+impl Run {
+    /// Starts `command` on a worker thread.
+    #[must_use]
+    pub fn spawn(command: &str) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let line = command.to_string();
+        thread::spawn(move || {
+            let _ = sender.send(shell::capture(&line));
+        });
+        Self {
+            command: command.to_string(),
+            receiver,
+        }
+    }
+
+    #[must_use]
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// What the linter printed, once it is done; `None` while it runs.
+    pub fn poll(&mut self) -> Option<Result<Output>> {
+        match self.receiver.try_recv() {
+            Ok(output) => Some(output),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(Error::Command {
+                command: self.command.clone(),
+                message: "stopped without a result".to_string(),
+            })),
+        }
+    }
+}
+
+/// The places the linter complained about in `output`.
+#[must_use]
+pub fn entries(output: &Output) -> Vec<Entry> {
+    quickfix::parse(&format!("{}{}", output.stdout, output.stderr))
+}
+
+/// The diagnostics `entries` make for `buffer`: those about its file, each
+/// marking the word at its column (or the character, if there is none).
+#[must_use]
+pub fn diagnostics(entries: &[Entry], buffer: &Buffer) -> Vec<Diagnostic> {
+    let Some(file) = buffer.file_name.as_deref().and_then(absolute) else {
+        return Vec::new();
+    };
+    entries
+        .iter()
+        .filter(|entry| absolute(&entry.path).is_some_and(|path| path == file))
+        .map(|entry| {
+            let text = buffer.get(entry.line).unwrap_or_default();
+            // Linters count characters, the buffer graphemes.
+            let byte = |chars: usize| {
+                text.char_indices()
+                    .nth(chars)
+                    .map_or(text.len(), |(byte, _)| byte)
+            };
+            let word = text
+                .chars()
+                .skip(entry.col)
+                .take_while(|&ch| is_word_char(ch))
+                .count();
+            let layout = buffer.layout(entry.line);
+            let start = layout.index_of_byte(byte(entry.col));
+            let end = layout.index_of_byte(byte(entry.col.saturating_add(word)));
+            Diagnostic {
+                line: entry.line,
+                cols: start..end.max(start.saturating_add(1)),
+                severity: severity(&entry.message),
+                message: entry.message.clone(),
+                source: Source::Linter,
+            }
+        })
+        .collect()
+}
+
+/// `path` made absolute, so that paths written differently compare equal.
+fn absolute(path: &Path) -> Option<std::path::PathBuf> {
+    path::absolute(path).ok()
+}
+
+/// How serious a complaint is, going by the word it starts with, as in
+/// `error: ...` or `note: ...`; a warning if it says nothing.
+fn severity(message: &str) -> Severity {
+    let word: String = message
+        .chars()
+        .take_while(char::is_ascii_alphabetic)
+        .collect::<String>()
+        .to_lowercase();
+    match word.as_str() {
+        "error" | "fatal" => Severity::Error,
+        "note" | "info" | "help" | "style" => Severity::Info,
+        _ => Severity::Warning,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_linter_for_filetype() {
+        let configured = vec![("shell".to_string(), String::new())];
+        let path = Path::new("it's.py");
+        assert_eq!(
+            command("python", &configured, path).unwrap(),
+            r"ruff check --output-format=concise 'it'\''s.py'"
+        );
+        assert_eq!(command("shell", &configured, path), None);
+        assert_eq!(command("text", &[], path), None);
+    }
+
+    #[test]
+    fn test_complaints_become_diagnostics_of_their_file() {
+        let mut buffer = Buffer::default();
+        buffer.replace_text("let é = value;\n");
+        buffer.file_name = Some(PathBuf::from("src/main.rs"));
+        let output = Output {
+            stdout: String::new(),
+            stderr: "./src/main.rs:1:9: error: cannot find value\n\
+                     src/main.rs:1:5: note: unused\n\
+                     src/lib.rs:1:1: warning: elsewhere\n"
+                .to_string(),
+            success: false,
+            status: "exit status: 101".to_string(),
+        };
+        let entries = entries(&output);
+        assert_eq!(entries.len(), 3);
+        let diagnostics = diagnostics(&entries, &buffer);
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.cols.clone(), diagnostic.severity))
+            .collect();
+        assert_eq!(found, [(8..13, Severity::Error), (4..5, Severity::Info)]);
+        assert!(diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.source == Source::Linter));
+
+        buffer.file_name = None;
+        assert!(super::diagnostics(&entries, &buffer).is_empty());
+    }
+
+    #[test]
+    fn test_runs_in_the_background() {
+        let mut run = Run::spawn("echo 'a.sh:2:3: warning: quote this'");
+        let output = loop {
+            if let Some(output) = run.poll() {
+                break output.unwrap();
+            }
+            thread::sleep(std::time::Duration::from_millis(5));
+        };
+        assert_eq!(entries(&output)[0].line, 1);
+        assert_eq!(run.command(), "echo 'a.sh:2:3: warning: quote this'");
+    }
+}
@@ -0,0 +1,302 @@
+//! # Background Loading
+//!
+//! Reading a multi-gigabyte file in one go would freeze the editor until it
+//! is done. Large files are instead read on a worker thread, which sends the
+//! text over a channel in chunks; the editor appends whatever has arrived
+//! between key presses, so the first screens can be browsed while the rest
+//! is still loading.
+//!
+//! Text piped in is read the same way, however little of it there is: it
+//! may be the output of a command that takes its time, or never ends
+//! (`tail -f log | hecto`). Bytes in it that are not UTF-8 are shown as
+//! `�` rather than failing the whole input.
+//!
+//! Compressed files are not handled here: `gzip` output is only available
+//! once the whole stream was decompressed anyway.
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread,
+};
+
+use crate::error::{Error, Result};
+
+/// Files at least this large (in bytes) are loaded in the background.
+pub const BACKGROUND_THRESHOLD: u64 = 1024 * 1024;
+
+/// How much the worker reads before handing text to the editor.
+const CHUNK_SIZE: usize = 256 * 1024;
+
+#[derive(Debug)]
+enum Message {
+    Chunk(String),
+    Failed(Error),
+}
+
+/// What arrived since the previous call to [`Loader::poll`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Update {
+    /// More text was read; the file is not finished yet.
+    Loading(String),
+    /// The rest of the file. The loader is done.
+    Finished(String),
+}
+
+#[derive(Debug)]
+pub struct Loader {
+    receiver: Receiver<Message>,
+    /// Size of the file when loading started; `None` for what is piped in.
+    total: Option<u64>,
+    /// Bytes received so far.
+    loaded: u64,
+}
+
+impl Loader {
+    /// Whether `path` is big enough to be worth loading in the background.
+    #[must_use]
+    pub fn is_large(path: &Path) -> bool {
+        path.metadata()
+            .is_ok_and(|metadata| metadata.len() >= BACKGROUND_THRESHOLD)
+    }
+
+    /// Starts reading `path` on a worker thread.
+    pub fn spawn(path: &Path) -> Result<Self> {
+        Self::with_chunk_size(path, CHUNK_SIZE)
+    }
+
+    fn with_chunk_size(path: &Path, chunk_size: usize) -> Result<Self> {
+        let file = File::open(path).map_err(|err| Error::at(path, err))?;
+        let total = file.metadata()?.len();
+        let source = Source {
+            path: path.to_path_buf(),
+            lossy: false,
+        };
+        Ok(Self::read(file, source, Some(total), chunk_size))
+    }
+
+    /// Starts reading standard input on a worker thread, until it is
+    /// closed.
+    #[must_use]
+    pub fn stdin() -> Self {
+        let source = Source {
+            path: PathBuf::from("standard input"),
+            lossy: true,
+        };
+        Self::read(io::stdin(), source, None, CHUNK_SIZE)
+    }
+
+    fn read(
+        reader: impl Read + Send + 'static,
+        source: Source,
+        total: Option<u64>,
+        chunk_size: usize,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || read_chunks(reader, &source, chunk_size, &sender));
+        Self {
+            receiver,
+            total,
+            loaded: 0,
+        }
+    }
+
+    /// Collects the text that arrived so far, without waiting for more.
+    pub fn poll(&mut self) -> Result<Update> {
+        let mut text = String::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(Message::Chunk(chunk)) => {
+                    self.loaded = self.loaded.saturating_add(u64::try_from(chunk.len())?);
+                    text.push_str(&chunk);
+                }
+                Ok(Message::Failed(err)) => return Err(err),
+                Err(TryRecvError::Empty) => return Ok(Update::Loading(text)),
+                Err(TryRecvError::Disconnected) => return Ok(Update::Finished(text)),
+            }
+        }
+    }
+
+    /// How much of the file was loaded, in percent; `None` if its size is
+    /// not known.
+    #[must_use]
+    pub fn percent(&self) -> Option<u64> {
+        let total = self.total?;
+        let percent = self.loaded.saturating_mul(100).checked_div(total);
+        Some(percent.unwrap_or(100).min(100))
+    }
+}
+
+/// Where the text comes from, for the worker.
+struct Source {
+    /// What errors name.
+    path: PathBuf,
+    /// Whether bytes that are not UTF-8 are replaced instead of failing.
+    lossy: bool,
+}
+
+/// Reads `reader` to the end, sending it as text chunks. Multi-byte
+/// characters split across reads are carried over to the next chunk. The
+/// worker stops early if the editor dropped its [`Loader`].
+fn read_chunks(
+    mut reader: impl Read,
+    source: &Source,
+    chunk_size: usize,
+    sender: &Sender<Message>,
+) {
+    let invalid = || {
+        Message::Failed(Error::InvalidUtf8 {
+            path: source.path.clone(),
+        })
+    };
+    let mut pending = Vec::new();
+    let mut buf = vec![0; chunk_size];
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => {
+                let _ = sender.send(Message::Failed(Error::at(&source.path, err)));
+                return;
+            }
+        };
+        pending.extend_from_slice(buf.get(..read).unwrap_or_default());
+        let (whole, is_utf8) = whole_characters(&pending);
+        if !is_utf8 && !source.lossy {
+            let _ = sender.send(invalid());
+            return;
+        }
+        let rest = pending.split_off(whole);
+        let chunk = String::from_utf8_lossy(&std::mem::replace(&mut pending, rest)).into_owned();
+        if sender.send(Message::Chunk(chunk)).is_err() {
+            return;
+        }
+    }
+    if pending.is_empty() {
+        return;
+    }
+    let _ = sender.send(if source.lossy {
+        Message::Chunk(String::from_utf8_lossy(&pending).into_owned())
+    } else {
+        invalid()
+    });
+}
+
+/// How many bytes at the start of `bytes` are whole characters (or bytes
+/// that are not UTF-8), leaving out one cut short at the end, and whether
+/// they are all UTF-8.
+fn whole_characters(bytes: &[u8]) -> (usize, bool) {
+    let mut whole = 0;
+    let mut is_utf8 = true;
+    loop {
+        match std::str::from_utf8(bytes.get(whole..).unwrap_or_default()) {
+            Ok(rest) => return (whole.saturating_add(rest.len()), is_utf8),
+            Err(err) => {
+                whole = whole.saturating_add(err.valid_up_to());
+                let Some(length) = err.error_len() else {
+                    return (whole, is_utf8);
+                };
+                is_utf8 = false;
+                whole = whole.saturating_add(length);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, path::PathBuf};
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("hecto-loader-{}-{name}", std::process::id()))
+    }
+
+    /// Polls until the loader is done, returning all the text it produced.
+    fn load_all(loader: &mut Loader) -> Result<String> {
+        let mut text = String::new();
+        loop {
+            match loader.poll()? {
+                Update::Loading(chunk) => text.push_str(&chunk),
+                Update::Finished(chunk) => {
+                    text.push_str(&chunk);
+                    return Ok(text);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_loads_whole_file_across_chunks() {
+        let path = temp_path("chunks.txt");
+        // Small chunks split the multi-byte characters.
+        let contents = "héllo wörld\n日本語\n".repeat(50);
+        fs::write(&path, &contents).unwrap();
+
+        let mut loader = Loader::with_chunk_size(&path, 7).unwrap();
+        assert_eq!(load_all(&mut loader).unwrap(), contents);
+        assert_eq!(loader.percent(), Some(100));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_utf8_fails() {
+        let path = temp_path("invalid.txt");
+        fs::write(&path, b"ok\n\xff\xfe\n").unwrap();
+
+        let mut loader = Loader::spawn(&path).unwrap();
+        assert!(load_all(&mut loader).is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_piped_text_is_read_as_it_comes() {
+        let source = Source {
+            path: PathBuf::from("standard input"),
+            lossy: true,
+        };
+        let (writer, reader) = mpsc::channel::<Vec<u8>>();
+        let mut loader = Loader::read(Pipe(reader, Vec::new()), source, None, 7);
+        writer.send("héllo\n".as_bytes().to_vec()).unwrap();
+        let mut text = String::new();
+        while text.is_empty() {
+            match loader.poll().unwrap() {
+                Update::Loading(chunk) => text.push_str(&chunk),
+                Update::Finished(_) => panic!("the pipe is still open"),
+            }
+        }
+        assert_eq!(text, "héllo\n");
+        assert_eq!(loader.percent(), None);
+
+        writer.send(b"b\xffd\n\xc3".to_vec()).unwrap();
+        drop(writer);
+        assert_eq!(load_all(&mut loader).unwrap(), "b\u{fffd}d\n\u{fffd}");
+    }
+
+    /// A reader giving what is sent to it, as a pipe does what is written
+    /// to it, until the sender is dropped.
+    struct Pipe(Receiver<Vec<u8>>, Vec<u8>);
+
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.1.is_empty() {
+                self.1 = self.0.recv().unwrap_or_default();
+            }
+            let length = self.1.len().min(buf.len());
+            buf[..length].copy_from_slice(&self.1[..length]);
+            self.1.drain(..length);
+            Ok(length)
+        }
+    }
+
+    #[test]
+    fn test_missing_file_fails_to_spawn() {
+        assert!(Loader::spawn(&temp_path("missing.txt")).is_err());
+        assert!(!Loader::is_large(&temp_path("missing.txt")));
+    }
+}
@@ -0,0 +1,153 @@
+//! # Logging
+//!
+//! While the editor runs, the terminal belongs to it, so nothing can be
+//! printed for debugging. Instead, the [`log`] macros write to
+//! `hecto.log` in the XDG state directory (see [`dirs::state_dir()`]),
+//! once logging is turned on with `--log-level=LEVEL` or the `HECTO_LOG`
+//! environment variable. The level is one of `error`, `warn`, `info`,
+//! `debug` or `trace`; it is off by default.
+//!
+//! Each line is a record in [logfmt](https://brandur.org/logfmt) form, so
+//! it can be filtered with ordinary tools:
+//!
+//! ```text
+//! ts=1760518800.123 level=debug target=hecto::editor event=command command=Save
+//! ```
+//!
+//! Messages are expected to be `key=value` pairs themselves, with values
+//! that may contain spaces passed through [`quote`].
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::dirs;
+
+/// Environment variable holding the log level, if `--log-level` is not
+/// given.
+pub const ENV_VAR: &str = "HECTO_LOG";
+
+/// Name of the log file inside [`dirs::state_dir()`].
+const FILE_NAME: &str = "hecto.log";
+
+struct FileLogger {
+    level: LevelFilter,
+    file: Mutex<File>,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format_record(SystemTime::now(), record);
+        if let Ok(mut file) = self.file.lock() {
+            // A failing log must never take the editor down with it.
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Starts logging at `level` to the log file. Returns the path of the file
+/// (`None` if logging is off).
+///
+/// # Errors
+///
+/// Fails with a message if the level is unknown, there is nowhere to put
+/// the file, it cannot be opened, or logging was already started.
+pub fn init(level: &str) -> Result<Option<PathBuf>, String> {
+    let level: LevelFilter = level
+        .parse()
+        .map_err(|_| format!("unknown log level: {level}"))?;
+    if level == LevelFilter::Off {
+        return Ok(None);
+    }
+    let dir = dirs::state_dir().ok_or("no state directory (is HOME set?)")?;
+    let path = dir.join(FILE_NAME);
+    let file = fs::create_dir_all(&dir)
+        .and_then(|()| OpenOptions::new().create(true).append(true).open(&path))
+        .map_err(|err| format!("cannot open {}: {err}", path.display()))?;
+    let logger = Box::leak(Box::new(FileLogger {
+        level,
+        file: Mutex::new(file),
+    }));
+    log::set_logger(logger).map_err(|err| err.to_string())?;
+    log::set_max_level(level);
+    log::info!("event=start version={}", env!("CARGO_PKG_VERSION"));
+    Ok(Some(path))
+}
+
+/// Formats a value for a logfmt record, quoting it if it has spaces,
+/// quotes or `=` in it (or is empty).
+#[must_use]
+pub fn quote(value: &str) -> String {
+    if !value.is_empty() && !value.contains([' ', '"', '=', '\n', '\t']) {
+        return value.to_string();
+    }
+    format!("{value:?}")
+}
+
+fn format_record(now: SystemTime, record: &Record) -> String {
+    let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!(
+        "ts={}.{:03} level={} target={} {}\n",
+        since_epoch.as_secs(),
+        since_epoch.subsec_millis(),
+        record.level().as_str().to_ascii_lowercase(),
+        record.target(),
+        record.args()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_record() {
+        let now = UNIX_EPOCH + Duration::from_millis(1_760_518_800_123);
+        let line = format_record(
+            now,
+            &Record::builder()
+                .level(log::Level::Debug)
+                .target("hecto::editor")
+                .args(format_args!("event=command command={}", quote("Save")))
+                .build(),
+        );
+        assert_eq!(
+            line,
+            "ts=1760518800.123 level=debug target=hecto::editor event=command command=Save\n"
+        );
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("ctrl-s"), "ctrl-s");
+        assert_eq!(quote("InsertChar(' ')"), "\"InsertChar(' ')\"");
+        assert_eq!(quote("a=\"b\""), "\"a=\\\"b\\\"\"");
+        assert_eq!(quote(""), "\"\"");
+    }
+
+    #[test]
+    fn test_unknown_levels_are_rejected() {
+        assert_eq!(init("loud"), Err("unknown log level: loud".to_string()));
+        assert_eq!(init("off"), Ok(None));
+    }
+}
@@ -0,0 +1,831 @@
+//! # Language Servers
+//!
+//! With the `lsp` option on, a buffer whose filetype has a language server
+//! (`rust-analyzer` for Rust, `pylsp` for Python) gets one started for it,
+//! one per filetype, in the directory the editor runs in. The client speaks
+//! JSON-RPC to the server over its stdin and stdout: it opens each buffer's
+//! file with the server and sends the whole text again whenever it changed.
+//! A thread reads what the server sends, so the editor never waits on it;
+//! it picks the messages up between key presses. The diagnostics the
+//! server publishes for a file replace its buffer's
+//! [diagnostics](crate::diagnostic).
+//!
+//! The editor can also ask the server about the text at the cursor: where
+//! what is there is defined, where it is used, what could be typed there,
+//! and what it is. The answers come in among the other messages, each with the
+//! [`Request`] it answers.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread,
+};
+
+use crate::{
+    buffer::Buffer,
+    diagnostic::{Diagnostic, Source},
+    error::{Error, Result},
+    json::Value,
+    layout::LineLayout,
+    notifications::Severity,
+    operator::Pos,
+    url,
+};
+
+/// The command that starts the language server for each filetype.
+const SERVERS: &[(&str, &[&str])] = &[("rust", &["rust-analyzer"]), ("python", &["pylsp"])];
+
+/// The command that starts the language server for `filetype`, if it has
+/// one.
+#[must_use]
+pub fn server(filetype: &str) -> Option<&'static [&'static str]> {
+    SERVERS
+        .iter()
+        .find(|(name, _)| *name == filetype)
+        .map(|(_, command)| *command)
+}
+
+/// What the editor can ask a server about the text at a place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    /// Where what is there is defined.
+    Definition,
+    /// Everywhere it is used, its definition included.
+    References,
+    /// What could be typed there.
+    Completion,
+    /// What is there: its type or signature, and its documentation.
+    Hover,
+}
+
+impl Request {
+    fn method(self) -> &'static str {
+        match self {
+            Self::Definition => "textDocument/definition",
+            Self::References => "textDocument/references",
+            Self::Completion => "textDocument/completion",
+            Self::Hover => "textDocument/hover",
+        }
+    }
+}
+
+/// What a server sent that the editor has to act on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Diagnostics(Published),
+    /// The result of a request, or the error the server answered it with.
+    Answer(Request, std::result::Result<Value, String>),
+}
+
+/// A place in a file, with its column in UTF-16 code units.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// The file, as an absolute path.
+    pub path: PathBuf,
+    pub line: usize,
+    pub character: usize,
+}
+
+/// The places in the result of a definition or references request: a
+/// location, a list of them, or a list of links to them.
+#[must_use]
+pub fn locations(result: &Value) -> Vec<Location> {
+    let location = |value: &Value| {
+        let (uri, range) = match value.get("targetUri") {
+            Some(uri) => (uri, value.get("targetSelectionRange")?),
+            None => (value.get("uri")?, value.get("range")?),
+        };
+        let start = range.get("start")?;
+        Some(Location {
+            path: path_of(uri.as_str()?)?,
+            line: start.get("line")?.as_usize()?,
+            character: start.get("character")?.as_usize()?,
+        })
+    };
+    match result {
+        Value::Array(values) => values.iter().filter_map(location).collect(),
+        _ => location(result).into_iter().collect(),
+    }
+}
+
+/// Something a server offers to complete the text at a place with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionItem {
+    pub label: String,
+    /// What is inserted when it is picked.
+    pub text: String,
+    /// Whether `text` is a snippet, with `$1` and the like in it (see
+    /// [`completion::expand_snippet`](crate::completion::expand_snippet)).
+    pub snippet: bool,
+    /// What it is, such as a function's signature.
+    pub detail: Option<String>,
+    pub documentation: Option<String>,
+    /// What the text typed is matched against.
+    pub filter: String,
+}
+
+/// The items in the result of a completion request, a list of them or an
+/// object with one, in the order the server sorts them in.
+#[must_use]
+pub fn completion_items(result: &Value) -> Vec<CompletionItem> {
+    let items = result
+        .as_array()
+        .or_else(|| result.get("items")?.as_array())
+        .unwrap_or_default();
+    let mut sorted: Vec<(String, CompletionItem)> = items
+        .iter()
+        .filter_map(|item| {
+            let text = |key| Some(item.get(key)?.as_str()?.to_string());
+            let label = text("label")?;
+            let documentation = item.get("documentation").and_then(|documentation| {
+                documentation
+                    .as_str()
+                    .or_else(|| documentation.get("value")?.as_str())
+                    .map(ToString::to_string)
+            });
+            let completion = CompletionItem {
+                text: item
+                    .at(&["textEdit", "newText"])
+                    .and_then(Value::as_str)
+                    .map(ToString::to_string)
+                    .or_else(|| text("insertText"))
+                    .unwrap_or_else(|| label.clone()),
+                snippet: item.get("insertTextFormat").and_then(Value::as_usize) == Some(2),
+                detail: text("detail"),
+                documentation,
+                filter: text("filterText").unwrap_or_else(|| label.clone()),
+                label,
+            };
+            Some((text("sortText").unwrap_or_default(), completion))
+        })
+        .collect();
+    // Stable, so items without a sort text stay in the order they came in.
+    sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
+    sorted.into_iter().map(|(_, item)| item).collect()
+}
+
+/// The Markdown in the result of a hover request. Its contents are markup,
+/// plain text, code in a language, or a list of them.
+#[must_use]
+pub fn hover_markdown(result: &Value) -> String {
+    let part = |value: &Value| {
+        if let Some(text) = value.as_str() {
+            return Some(text.to_string());
+        }
+        let text = value.get("value")?.as_str()?;
+        Some(match value.get("language").and_then(Value::as_str) {
+            Some(language) => format!("```{language}\n{text}\n```"),
+            None => text.to_string(),
+        })
+    };
+    let Some(contents) = result.get("contents") else {
+        return String::new();
+    };
+    match contents.as_array() {
+        Some(parts) => parts
+            .iter()
+            .filter_map(part)
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        None => part(contents).unwrap_or_default(),
+    }
+}
+
+/// The grapheme of `text` that `units` UTF-16 code units in are.
+#[must_use]
+pub fn column_of_utf16(text: &str, units: usize) -> usize {
+    LineLayout::new(text.get(..byte_of_utf16(text, units)).unwrap_or_default()).len()
+}
+
+/// How many UTF-16 code units into line `line` of `buffer` grapheme `col`
+/// is.
+fn utf16_of_column(buffer: &Buffer, line: usize, col: usize) -> usize {
+    let text = buffer.get(line).unwrap_or_default();
+    let byte = buffer.layout(line).byte_offset(col);
+    text.get(..byte)
+        .unwrap_or_default()
+        .chars()
+        .map(char::len_utf16)
+        .sum()
+}
+
+/// The diagnostics a server published for a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Published {
+    /// The file, as an absolute path.
+    pub path: PathBuf,
+    reported: Vec<Reported>,
+}
+
+/// One diagnostic as the server put it, with its columns in UTF-16 code
+/// units.
+#[derive(Debug, Clone, PartialEq)]
+struct Reported {
+    start: (usize, usize),
+    end: (usize, usize),
+    severity: Severity,
+    message: String,
+}
+
+impl Published {
+    /// Reads the parameters of a `textDocument/publishDiagnostics`
+    /// notification.
+    fn parse(params: &Value) -> Option<Self> {
+        let path = path_of(params.get("uri")?.as_str()?)?;
+        let reported = params
+            .get("diagnostics")?
+            .as_array()?
+            .iter()
+            .filter_map(|diagnostic| {
+                let position = |key| {
+                    let position = diagnostic.at(&["range", key])?;
+                    Some((
+                        position.get("line")?.as_usize()?,
+                        position.get("character")?.as_usize()?,
+                    ))
+                };
+                Some(Reported {
+                    start: position("start")?,
+                    end: position("end")?,
+                    // 1 is an error, 2 a warning, 3 and 4 information and
+                    // hints; without one, it is up to the client.
+                    severity: match diagnostic.get("severity").and_then(Value::as_usize) {
+                        Some(1) | None => Severity::Error,
+                        Some(2) => Severity::Warning,
+                        Some(_) => Severity::Info,
+                    },
+                    message: diagnostic.get("message")?.as_str()?.to_string(),
+                })
+            })
+            .collect();
+        Some(Self { path, reported })
+    }
+
+    /// The diagnostics as they apply to `buffer`, which holds the file. One
+    /// that goes on past its first line is marked to the end of that line.
+    #[must_use]
+    pub fn diagnostics(&self, buffer: &Buffer) -> Vec<Diagnostic> {
+        self.reported
+            .iter()
+            .map(|reported| {
+                let (line, start) = reported.start;
+                let column = |units| {
+                    let text = buffer.get(line).unwrap_or_default();
+                    buffer
+                        .layout(line)
+                        .index_of_byte(byte_of_utf16(&text, units))
+                };
+                let end = if reported.end.0 == line {
+                    column(reported.end.1)
+                } else {
+                    buffer.line_len(line)
+                };
+                let start = column(start);
+                Diagnostic {
+                    line,
+                    // Even an empty range marks the character it is at.
+                    cols: start..end.max(start.saturating_add(1)),
+                    severity: reported.severity,
+                    message: reported.message.clone(),
+                    source: Source::LanguageServer,
+                }
+            })
+            .collect()
+    }
+}
+
+/// The byte of `text` that `units` UTF-16 code units in are.
+fn byte_of_utf16(text: &str, units: usize) -> usize {
+    let mut counted = 0;
+    for (at, ch) in text.char_indices() {
+        if counted >= units {
+            return at;
+        }
+        counted = counted.saturating_add(ch.len_utf16());
+    }
+    text.len()
+}
+
+/// What the client knows about a file it opened with the server.
+#[derive(Debug, Clone, Copy)]
+struct Document {
+    /// The version of the text the server was sent last.
+    version: usize,
+    /// The buffer's revision then.
+    revision: usize,
+}
+
+#[derive(Debug)]
+pub struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    receiver: Receiver<Value>,
+    /// The id of the next request.
+    next_id: usize,
+    /// Whether the server answered `initialize`; until then, messages wait
+    /// in `queued`.
+    ready: bool,
+    queued: Vec<Value>,
+    /// Whether the server is still there to talk to.
+    running: bool,
+    documents: HashMap<PathBuf, Document>,
+    /// What the requests waiting for an answer asked, by id.
+    pending: HashMap<usize, Request>,
+    /// The characters that start a completion as they are typed, as the
+    /// server said when it initialized.
+    trigger_characters: Vec<char>,
+}
+
+impl Client {
+    /// Starts the server `command` and asks it to initialize for the
+    /// project in `root`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the server cannot be started.
+    pub fn spawn(command: &[&str], root: &Path) -> Result<Self> {
+        let (program, args) = command.split_first().unwrap_or((&"", &[]));
+        let failed = |err: io::Error| Error::Command {
+            command: (*program).to_string(),
+            message: if err.kind() == io::ErrorKind::NotFound {
+                "not found".to_string()
+            } else {
+                err.to_string()
+            },
+        };
+        let mut child = Command::new(program)
+            .args(args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(failed)?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(failed(io::Error::other("no pipes")));
+        };
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || read_messages(stdout, &sender));
+        let mut client = Self {
+            child,
+            stdin,
+            receiver,
+            next_id: 0,
+            ready: false,
+            queued: Vec::new(),
+            running: true,
+            documents: HashMap::new(),
+            pending: HashMap::new(),
+            trigger_characters: Vec::new(),
+        };
+        let params = Value::object([
+            (
+                "processId",
+                Value::from(usize::try_from(std::process::id()).unwrap_or_default()),
+            ),
+            ("rootUri", Value::from(uri(root))),
+            (
+                "capabilities",
+                Value::object([(
+                    "textDocument",
+                    Value::object([
+                        ("publishDiagnostics", Value::object([])),
+                        (
+                            "completion",
+                            Value::object([(
+                                "completionItem",
+                                Value::object([("snippetSupport", Value::from(true))]),
+                            )]),
+                        ),
+                        (
+                            "hover",
+                            Value::object([(
+                                "contentFormat",
+                                Value::Array(vec![
+                                    Value::from("markdown"),
+                                    Value::from("plaintext"),
+                                ]),
+                            )]),
+                        ),
+                    ]),
+                )]),
+            ),
+        ]);
+        let request = client.request("initialize", params);
+        client.write(&request);
+        Ok(client)
+    }
+
+    /// Whether the server is still there to talk to.
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Whether typing `ch` starts a completion.
+    #[must_use]
+    pub fn is_trigger(&self, ch: char) -> bool {
+        self.trigger_characters.contains(&ch)
+    }
+
+    /// Brings the server up to date with `buffer`, which holds the file at
+    /// `path`: opens it the first time, sends its text again once it
+    /// changed.
+    pub fn sync(&mut self, path: &Path, buffer: &Buffer) {
+        let revision = buffer.revision();
+        let uri = Value::from(uri(path));
+        let (method, document, version) = match self.documents.get(path).copied() {
+            None => (
+                "textDocument/didOpen",
+                Value::object([
+                    ("uri", uri),
+                    ("languageId", Value::from(buffer.filetype.name)),
+                    ("version", Value::from(0_usize)),
+                    ("text", Value::from(buffer.text())),
+                ]),
+                0,
+            ),
+            Some(document) if document.revision == revision => return,
+            Some(document) => {
+                let version = document.version.saturating_add(1);
+                self.send(notification(
+                    "textDocument/didChange",
+                    Value::object([
+                        (
+                            "textDocument",
+                            Value::object([("uri", uri), ("version", Value::from(version))]),
+                        ),
+                        (
+                            "contentChanges",
+                            Value::Array(vec![Value::object([(
+                                "text",
+                                Value::from(buffer.text()),
+                            )])]),
+                        ),
+                    ]),
+                ));
+                self.documents
+                    .insert(path.to_path_buf(), Document { version, revision });
+                return;
+            }
+        };
+        self.send(notification(
+            method,
+            Value::object([("textDocument", document)]),
+        ));
+        self.documents
+            .insert(path.to_path_buf(), Document { version, revision });
+    }
+
+    /// Asks the server `request` about the place `pos` in `buffer`, which
+    /// holds the file at `path`; the answer comes from
+    /// [`Client::poll`] later.
+    pub fn ask(&mut self, request: Request, path: &Path, buffer: &Buffer, pos: Pos) {
+        self.sync(path, buffer);
+        let (line, col) = pos;
+        let mut params = vec![
+            (
+                "textDocument",
+                Value::object([("uri", Value::from(uri(path)))]),
+            ),
+            (
+                "position",
+                Value::object([
+                    ("line", Value::from(line)),
+                    ("character", Value::from(utf16_of_column(buffer, line, col))),
+                ]),
+            ),
+        ];
+        if request == Request::References {
+            params.push((
+                "context",
+                Value::object([("includeDeclaration", Value::from(true))]),
+            ));
+        }
+        let message = self.request(request.method(), Value::object(params));
+        if let Some(id) = message.get("id").and_then(Value::as_usize) {
+            self.pending.insert(id, request);
+        }
+        self.send(message);
+    }
+
+    /// Handles what the server sent since the last call, without waiting
+    /// for more, and returns what the editor has to act on.
+    pub fn poll(&mut self) -> Vec<Message> {
+        let mut messages = Vec::new();
+        loop {
+            let message = match self.receiver.try_recv() {
+                Ok(message) => message,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.running = false;
+                    break;
+                }
+            };
+            let id = message.get("id");
+            match message.get("method").and_then(Value::as_str) {
+                Some("textDocument/publishDiagnostics") => {
+                    messages.extend(
+                        message
+                            .get("params")
+                            .and_then(Published::parse)
+                            .map(Message::Diagnostics),
+                    );
+                }
+                // A request from the server, such as one to register a
+                // capability: it waits for an answer, which need not say
+                // more than that it arrived.
+                Some(_) => {
+                    if let Some(id) = id {
+                        let answer = Value::object([
+                            ("jsonrpc", Value::from("2.0")),
+                            ("id", id.clone()),
+                            ("result", Value::Null),
+                        ]);
+                        self.write(&answer);
+                    }
+                }
+                None if !self.ready => {
+                    self.ready = true;
+                    self.trigger_characters = message
+                        .at(&[
+                            "result",
+                            "capabilities",
+                            "completionProvider",
+                            "triggerCharacters",
+                        ])
+                        .and_then(Value::as_array)
+                        .unwrap_or_default()
+                        .iter()
+                        .filter_map(|trigger| trigger.as_str()?.chars().next())
+                        .collect();
+                    self.write(&notification("initialized", Value::object([])));
+                    for message in std::mem::take(&mut self.queued) {
+                        self.write(&message);
+                    }
+                }
+                None => {
+                    let request = id
+                        .and_then(Value::as_usize)
+                        .and_then(|id| self.pending.remove(&id));
+                    if let Some(request) = request {
+                        messages.push(Message::Answer(request, answer(&message)));
+                    }
+                }
+            }
+        }
+        messages
+    }
+
+    /// A request with the next id.
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id = self.next_id.saturating_add(1);
+        Value::object([
+            ("jsonrpc", Value::from("2.0")),
+            ("id", Value::from(id)),
+            ("method", Value::from(method)),
+            ("params", params),
+        ])
+    }
+
+    /// Sends `message` once the server is initialized.
+    fn send(&mut self, message: Value) {
+        if self.ready {
+            self.write(&message);
+        } else {
+            self.queued.push(message);
+        }
+    }
+
+    fn write(&mut self, message: &Value) {
+        if self.stdin.write_all(frame(message).as_bytes()).is_err() || self.stdin.flush().is_err() {
+            self.running = false;
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.write(&notification("exit", Value::Null));
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The result a response carries, or the error message in its place.
+fn answer(response: &Value) -> std::result::Result<Value, String> {
+    match response.get("error") {
+        Some(error) => Err(error
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("request failed")
+            .to_string()),
+        None => Ok(response.get("result").cloned().unwrap_or(Value::Null)),
+    }
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    Value::object([
+        ("jsonrpc", Value::from("2.0")),
+        ("method", Value::from(method)),
+        ("params", params),
+    ])
+}
+
+/// `message` with the header that tells the other side how long it is.
+#[must_use]
+pub fn frame(message: &Value) -> String {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+/// Reads the messages the server writes to `stdout` and sends them to the
+/// editor, until the server exits or the editor is gone.
+fn read_messages(stdout: ChildStdout, sender: &Sender<Value>) {
+    let mut reader = BufReader::new(stdout);
+    while let Some(message) = read_message(&mut reader) {
+        // Something that is not JSON is skipped; the next one may be.
+        if let Ok(message) = Value::parse(&message) {
+            if sender.send(message).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// The body of the next message from `reader`; `None` at the end.
+fn read_message(reader: &mut impl BufRead) -> Option<String> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+    let mut body = vec![0; length?];
+    reader.read_exact(&mut body).ok()?;
+    String::from_utf8(body).ok()
+}
+
+/// The `file:` URI of `path`, made absolute.
+#[must_use]
+pub fn uri(path: &Path) -> String {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    format!(
+        "file://{}",
+        url::encode_keeping(&path.to_string_lossy(), b"/")
+    )
+}
+
+/// The path a `file:` URI names.
+fn path_of(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut index = 0;
+    while let Some(&byte) = encoded.get(index) {
+        let escaped = (byte == b'%')
+            .then(|| encoded.get(index.saturating_add(1)..index.saturating_add(3)))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        if let Some(escaped) = escaped {
+            bytes.push(escaped);
+            index = index.saturating_add(3);
+        } else {
+            bytes.push(byte);
+            index = index.saturating_add(1);
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_are_framed_and_read_back() {
+        let message = notification("initialized", Value::object([]));
+        let framed = frame(&message);
+        assert_eq!(
+            framed,
+            "Content-Length: 52\r\n\r\n{\"jsonrpc\":\"2.0\",\"method\":\"initialized\",\"params\":{}}"
+        );
+        let mut input = format!("{framed}{framed}").into_bytes();
+        input.extend_from_slice(b"Content-Length: 4\r\n\r\nnu");
+        let mut reader = io::Cursor::new(input);
+        assert_eq!(read_message(&mut reader), Some(message.to_string()));
+        assert_eq!(read_message(&mut reader), Some(message.to_string()));
+        assert_eq!(read_message(&mut reader), None);
+    }
+
+    #[test]
+    fn test_locations_of_each_shape() {
+        let result = Value::parse(
+            r#"[{"uri":"file:///a.rs","range":{"start":{"line":3,"character":4},"end":{"line":3,"character":5}}},
+                {"targetUri":"file:///b.rs","targetRange":{},
+                 "targetSelectionRange":{"start":{"line":1,"character":2},"end":{"line":1,"character":3}}}]"#,
+        )
+        .unwrap();
+        let found = locations(&result);
+        assert_eq!(
+            found,
+            [
+                Location {
+                    path: PathBuf::from("/a.rs"),
+                    line: 3,
+                    character: 4
+                },
+                Location {
+                    path: PathBuf::from("/b.rs"),
+                    line: 1,
+                    character: 2
+                }
+            ]
+        );
+        let single = result.as_array().unwrap()[0].clone();
+        assert_eq!(locations(&single).len(), 1);
+        assert_eq!(locations(&Value::Null), []);
+        assert_eq!(column_of_utf16("\"😀\".len()", 4), 3);
+    }
+
+    #[test]
+    fn test_completion_items_are_sorted() {
+        let result = Value::parse(
+            r#"{"isIncomplete":false,"items":[
+                {"label":"push","sortText":"2","insertText":"push(${1:value})","insertTextFormat":2,
+                 "documentation":{"kind":"markdown","value":"Appends."}},
+                {"label":"pop","sortText":"1","detail":"fn(&mut self)","documentation":"Removes."},
+                {"label":"len","textEdit":{"newText":"len()"},"filterText":"len"}]}"#,
+        )
+        .unwrap();
+        let items = completion_items(&result);
+        let labels: Vec<&str> = items.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, ["len", "pop", "push"]);
+        assert_eq!(items[0].text, "len()");
+        assert_eq!(items[1].detail.as_deref(), Some("fn(&mut self)"));
+        assert_eq!(items[1].documentation.as_deref(), Some("Removes."));
+        assert!(items[2].snippet && !items[1].snippet);
+        assert_eq!(items[2].documentation.as_deref(), Some("Appends."));
+        assert_eq!(completion_items(&Value::Null), []);
+    }
+
+    #[test]
+    fn test_hover_contents_of_each_shape() {
+        let hover = |json| hover_markdown(&Value::parse(json).unwrap());
+        assert_eq!(
+            hover(r#"{"contents":{"kind":"markdown","value":"**x**"}}"#),
+            "**x**"
+        );
+        assert_eq!(
+            hover(r#"{"contents":["Docs.",{"language":"rust","value":"fn f()"}]}"#),
+            "Docs.\n\n```rust\nfn f()\n```"
+        );
+        assert_eq!(hover("null"), "");
+    }
+
+    #[test]
+    fn test_uris_round_trip() {
+        let path = Path::new("/src/my file%.rs");
+        assert_eq!(uri(path), "file:///src/my%20file%25.rs");
+        assert_eq!(path_of(&uri(path)), Some(path.to_path_buf()));
+        assert_eq!(path_of("http://example.com"), None);
+    }
+
+    #[test]
+    fn test_published_diagnostics_apply_to_the_buffer() {
+        let params = Value::parse(
+            r#"{"uri":"file:///src/main.rs","diagnostics":[
+                {"range":{"start":{"line":0,"character":7},"end":{"line":0,"character":8}},
+                 "severity":2,"message":"unused"},
+                {"range":{"start":{"line":1,"character":0},"end":{"line":3,"character":0}},
+                 "message":"mismatched types"}]}"#,
+        )
+        .unwrap();
+        let published = Published::parse(&params).unwrap();
+        assert_eq!(published.path, Path::new("/src/main.rs"));
+        let mut buffer = Buffer::default();
+        // "😀" is two UTF-16 code units but one grapheme.
+        buffer.insert(0, 0, "s = \"😀x\";\nlet y = 1;\n");
+        let diagnostics = published.diagnostics(&buffer);
+        assert_eq!(diagnostics[0].cols, 6..7);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(
+            (diagnostics[1].line, diagnostics[1].cols.clone()),
+            (1, 0..10)
+        );
+        assert_eq!(diagnostics[1].severity, Severity::Error);
+    }
+}
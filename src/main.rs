@@ -7,16 +7,21 @@
     clippy::integer_division
 )]
 
-use editor::Editor;
-use error::Result;
+use std::{env, path::Path, process::ExitCode};
 
-mod buffer;
-mod editor;
-mod error;
-pub mod io_provider;
-mod terminal;
-mod viewer;
+use hecto::{events::CrosstermEvents, script, Editor};
 
-fn main() -> Result<()> {
-    Editor::default().run()
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.as_slice() {
+        [flag, path, files @ ..] if flag == "--script" => script::run_file(Path::new(path), files),
+        _ => Editor::default().run(&mut CrosstermEvents),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("hecto: {err}");
+            ExitCode::FAILURE
+        }
+    }
 }
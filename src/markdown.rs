@@ -0,0 +1,289 @@
+//! # Markdown
+//!
+//! Language servers write their documentation in Markdown. It is rendered
+//! lightly, to be read in a popup: the markup is taken out and what it
+//! marks is colored instead, in the theme's `markdown.*` elements. That
+//! covers headings, code (fenced or inline), strong and emphasized text,
+//! and links, of which the text is kept and the address dropped. List
+//! bullets become `•`, rules a line across, escapes the character escaped,
+//! and runs of blank lines a single one (none around a rule). Anything else stays as written.
+
+use std::ops::Range;
+
+use crate::{layer::Span, layout::LineLayout};
+
+const HEADING: &str = "markdown.heading";
+const CODE: &str = "markdown.code";
+const STRONG: &str = "markdown.strong";
+const EMPHASIS: &str = "markdown.emphasis";
+const LINK: &str = "markdown.link";
+
+/// Text rendered from Markdown.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rendered {
+    pub lines: Vec<String>,
+    /// What is colored; a span's `line` is an index into `lines`.
+    pub spans: Vec<Span>,
+}
+
+impl Rendered {
+    /// How many columns the widest line takes.
+    #[must_use]
+    pub fn width(&self) -> usize {
+        self.lines
+            .iter()
+            .map(|line| LineLayout::new(line).width())
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Adds `line`, with the bytes of it in `marks` colored.
+    fn push(&mut self, line: String, marks: Vec<(Range<usize>, &'static str)>) {
+        let index = self.lines.len();
+        let layout = LineLayout::new(&line);
+        self.spans
+            .extend(marks.into_iter().map(|(bytes, element)| Span {
+                line: index,
+                cols: layout.index_of_byte(bytes.start)..layout.index_of_byte(bytes.end),
+                element,
+            }));
+        self.lines.push(line);
+    }
+}
+
+/// Renders `markdown`.
+#[must_use]
+pub fn render(markdown: &str) -> Rendered {
+    let mut rendered = Rendered::default();
+    let mut rules = Vec::new();
+    let mut fenced = false;
+    for line in markdown.lines() {
+        let line = line.replace('\t', "    ");
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fenced = !fenced;
+            continue;
+        }
+        if fenced {
+            let whole = 0..line.len();
+            rendered.push(line, vec![(whole, CODE)]);
+            continue;
+        }
+        if trimmed.is_empty() {
+            if rendered.lines.last().is_some_and(|last| !last.is_empty()) {
+                rendered.push(String::new(), Vec::new());
+            }
+            continue;
+        }
+        if is_rule(trimmed) {
+            // A rule separates enough without blank lines around it.
+            if rendered.lines.last().is_some_and(String::is_empty) {
+                rendered.lines.pop();
+            }
+            // Drawn once the width is known.
+            rules.push(rendered.lines.len());
+            rendered.push(String::new(), Vec::new());
+            continue;
+        }
+        let mut text = String::new();
+        let mut marks = Vec::new();
+        let level = trimmed.bytes().take_while(|&byte| byte == b'#').count();
+        let indent = line.len().saturating_sub(trimmed.len());
+        if let Some(heading) = trimmed
+            .get(level..)
+            .filter(|rest| (1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' ')))
+        {
+            inline(heading.trim(), &mut text, &mut marks);
+            marks.insert(0, (0..text.len(), HEADING));
+        } else if let Some(item) = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|bullet| trimmed.strip_prefix(bullet))
+        {
+            text.push_str(line.get(..indent).unwrap_or_default());
+            text.push_str("• ");
+            inline(item, &mut text, &mut marks);
+        } else {
+            text.push_str(line.get(..indent).unwrap_or_default());
+            inline(trimmed, &mut text, &mut marks);
+        }
+        rendered.push(text, marks);
+    }
+    while rendered.lines.last().is_some_and(String::is_empty) {
+        rendered.lines.pop();
+    }
+    let width = rendered.width();
+    for index in rules {
+        if let Some(line) = rendered.lines.get_mut(index) {
+            *line = "─".repeat(width);
+        }
+    }
+    rendered
+}
+
+/// Whether `line` is a rule: three or more of `-`, `*` or `_`, and nothing
+/// else but spaces.
+fn is_rule(line: &str) -> bool {
+    ['-', '*', '_'].iter().any(|&mark| {
+        line.chars().all(|ch| ch == mark || ch == ' ')
+            && line.chars().filter(|&ch| ch == mark).count() >= 3
+    })
+}
+
+/// Appends `text` to `out` with its inline markup taken out, and the bytes
+/// of `out` that markup marked to `marks`.
+fn inline(text: &str, out: &mut String, marks: &mut Vec<(Range<usize>, &'static str)>) {
+    let mut rest = text;
+    while let Some(ch) = rest.chars().next() {
+        let after = rest.get(ch.len_utf8()..).unwrap_or_default();
+        let marked = match ch {
+            '\\' => after
+                .chars()
+                .next()
+                .filter(char::is_ascii_punctuation)
+                .map(|escaped| {
+                    out.push(escaped);
+                    after.get(escaped.len_utf8()..).unwrap_or_default()
+                }),
+            '`' => code(rest, out, marks),
+            '*' | '_' => emphasis(rest, out, marks),
+            '[' => link(rest, out, marks),
+            _ => None,
+        };
+        rest = marked.unwrap_or_else(|| {
+            out.push(ch);
+            after
+        });
+    }
+}
+
+/// Appends the code span `rest` starts with, if it is closed; returns what
+/// comes after it.
+fn code<'a>(
+    rest: &'a str,
+    out: &mut String,
+    marks: &mut Vec<(Range<usize>, &'static str)>,
+) -> Option<&'a str> {
+    let ticks = rest.bytes().take_while(|&byte| byte == b'`').count();
+    let (fence, body) = rest.split_at(ticks);
+    let end = body.find(fence)?;
+    let content = body.get(..end)?;
+    // A space on each side lets code start or end with a backtick.
+    let content = content
+        .strip_prefix(' ')
+        .and_then(|content| content.strip_suffix(' '))
+        .filter(|inner| !inner.trim().is_empty())
+        .unwrap_or(content);
+    let start = out.len();
+    out.push_str(content);
+    marks.push((start..out.len(), CODE));
+    body.get(end.saturating_add(ticks)..)
+}
+
+/// Appends the strong (`**`, `__`) or emphasized (`*`, `_`) text `rest`
+/// starts with, if it is closed; returns what comes after it. An
+/// underscore inside a word, as in `snake_case`, marks nothing.
+fn emphasis<'a>(
+    rest: &'a str,
+    out: &mut String,
+    marks: &mut Vec<(Range<usize>, &'static str)>,
+) -> Option<&'a str> {
+    let underscore = rest.starts_with('_');
+    if underscore && out.chars().next_back().is_some_and(char::is_alphanumeric) {
+        return None;
+    }
+    let (delimiter, element) = if rest.starts_with("**") || rest.starts_with("__") {
+        (rest.get(..2)?, STRONG)
+    } else {
+        (rest.get(..1)?, EMPHASIS)
+    };
+    let body = rest.get(delimiter.len()..)?;
+    if body.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let (end, _) = body.match_indices(delimiter).find(|&(at, _)| {
+        let before = body.get(..at).unwrap_or_default();
+        let after = body
+            .get(at.saturating_add(delimiter.len())..)
+            .unwrap_or_default();
+        let closes = !before.is_empty() && !before.ends_with(char::is_whitespace);
+        closes && !(underscore && after.starts_with(char::is_alphanumeric))
+    })?;
+    let start = out.len();
+    inline(body.get(..end)?, out, marks);
+    marks.push((start..out.len(), element));
+    body.get(end.saturating_add(delimiter.len())..)
+}
+
+/// Appends the text of the link `rest` starts with, `[text](address)`, if
+/// it is one; returns what comes after it.
+fn link<'a>(
+    rest: &'a str,
+    out: &mut String,
+    marks: &mut Vec<(Range<usize>, &'static str)>,
+) -> Option<&'a str> {
+    let mut depth = 0_usize;
+    let close = rest.char_indices().find_map(|(at, ch)| {
+        match ch {
+            '[' => depth = depth.saturating_add(1),
+            ']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        (depth == 0).then_some(at)
+    })?;
+    let address = rest.get(close.saturating_add(1)..)?.strip_prefix('(')?;
+    let end = address.find(')')?;
+    let start = out.len();
+    inline(rest.get(1..close)?, out, marks);
+    marks.push((start..out.len(), LINK));
+    address.get(end.saturating_add(1)..)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marked(rendered: &Rendered, element: &str) -> Vec<String> {
+        rendered
+            .spans
+            .iter()
+            .filter(|span| span.element == element)
+            .map(|span| {
+                rendered.lines[span.line]
+                    .chars()
+                    .skip(span.cols.start)
+                    .take(span.cols.len())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_markup_is_taken_out_and_colored() {
+        let rendered = render(
+            "```rust\nfn len(&self) -> usize\n```\n\n---\n\n\n# Returns the `len`\n\
+             Counts **all** the *items*, see [`Vec`](https://doc.rust-lang.org/Vec).\n\
+             - my_snake_case \\* 2\n\n",
+        );
+        assert_eq!(
+            rendered.lines,
+            [
+                "fn len(&self) -> usize",
+                &"─".repeat(30),
+                "Returns the len",
+                "Counts all the items, see Vec.",
+                "• my_snake_case * 2",
+            ]
+        );
+        assert_eq!(
+            marked(&rendered, CODE),
+            ["fn len(&self) -> usize", "len", "Vec"]
+        );
+        assert_eq!(marked(&rendered, HEADING), ["Returns the len"]);
+        assert_eq!(marked(&rendered, STRONG), ["all"]);
+        assert_eq!(marked(&rendered, EMPHASIS), ["items"]);
+        assert_eq!(marked(&rendered, LINK), ["Vec"]);
+        assert_eq!(rendered.width(), 30);
+
+        assert_eq!(render("a `b and **c").lines, ["a `b and **c"]);
+    }
+}
@@ -0,0 +1,171 @@
+//! # Notifications
+//!
+//! Messages for the user, from anywhere in the editor. The editor itself
+//! shows them in the status bar right away; other parts, possibly on other
+//! threads, post them through a [`Notifier`] and they appear on the next
+//! refresh. Either way they are kept in a history, listed by the `messages`
+//! command.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+/// How many notifications the history keeps; older ones are dropped.
+const HISTORY_LIMIT: usize = 200;
+
+/// How serious a notification is, which decides its color.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub severity: Severity,
+    pub text: String,
+}
+
+impl Notification {
+    #[must_use]
+    pub fn new(severity: Severity, text: impl Into<String>) -> Self {
+        Self {
+            severity,
+            text: text.into(),
+        }
+    }
+}
+
+/// Posts notifications to the editor. Cheap to clone and can be sent to
+/// other threads; posting after the editor is gone does nothing.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    sender: Sender<Notification>,
+}
+
+impl Notifier {
+    pub fn post(&self, severity: Severity, text: impl Into<String>) {
+        let _ = self.sender.send(Notification::new(severity, text));
+    }
+
+    pub fn info(&self, text: impl Into<String>) {
+        self.post(Severity::Info, text);
+    }
+
+    pub fn warning(&self, text: impl Into<String>) {
+        self.post(Severity::Warning, text);
+    }
+
+    pub fn error(&self, text: impl Into<String>) {
+        self.post(Severity::Error, text);
+    }
+}
+
+/// The queue of posted notifications and the history of shown ones.
+#[derive(Debug)]
+pub struct Notifications {
+    sender: Sender<Notification>,
+    receiver: Receiver<Notification>,
+    history: VecDeque<Notification>,
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver,
+            history: VecDeque::new(),
+        }
+    }
+}
+
+impl Notifications {
+    /// A handle for posting to this queue.
+    #[must_use]
+    pub fn notifier(&self) -> Notifier {
+        Notifier {
+            sender: self.sender.clone(),
+        }
+    }
+
+    /// Records a notification that is being shown in the history.
+    pub fn record(&mut self, notification: Notification) {
+        if self.history.len() >= HISTORY_LIMIT {
+            self.history.pop_front();
+        }
+        self.history.push_back(notification);
+    }
+
+    /// Moves everything posted since the last call into the history and
+    /// returns the most recent one, which is the one to show.
+    pub fn drain(&mut self) -> Option<Notification> {
+        let mut latest = None;
+        while let Ok(notification) = self.receiver.try_recv() {
+            self.record(notification.clone());
+            latest = Some(notification);
+        }
+        latest
+    }
+
+    /// The shown notifications, oldest first.
+    #[must_use]
+    pub fn history(&self) -> impl DoubleEndedIterator<Item = &Notification> {
+        self.history.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_posted_notifications_are_drained_in_order() {
+        let mut notifications = Notifications::default();
+        let notifier = notifications.notifier();
+        assert_eq!(notifications.drain(), None);
+
+        std::thread::spawn(move || {
+            notifier.info("Indexed.");
+            notifier.error("Lint failed.");
+        })
+        .join()
+        .unwrap();
+        assert_eq!(
+            notifications.drain(),
+            Some(Notification::new(Severity::Error, "Lint failed."))
+        );
+        let texts: Vec<&str> = notifications
+            .history()
+            .map(|notification| notification.text.as_str())
+            .collect();
+        assert_eq!(texts, vec!["Indexed.", "Lint failed."]);
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut notifications = Notifications::default();
+        for index in 0..HISTORY_LIMIT.saturating_add(5) {
+            notifications.record(Notification::new(Severity::Info, index.to_string()));
+        }
+        assert_eq!(notifications.history().count(), HISTORY_LIMIT);
+        assert_eq!(
+            notifications.history().next().map(|n| n.text.as_str()),
+            Some("5")
+        );
+    }
+}
@@ -0,0 +1,751 @@
+//! # Operators and Motions
+//!
+//! The editing grammar of normal mode, as in Vim: an [`Operator`] (`d`elete,
+//! `c`hange, `y`ank, `>` indent, `<` outdent) followed by what it applies
+//! to. That is a [`Motion`]
+//! (`dw`, `d$`, `dgg`), a text [`Object`] (`diw`, `ci"`, `dap`), or the
+//! operator's own key again for whole lines (`dd`). A count may come in
+//! between (`d3w`).
+//!
+//! The keys typed after the operator are [`parse`]d as they arrive, so the
+//! editor knows whether to wait for more; adding a motion or an object is
+//! a matter of adding it to the tables below. [`span`] then works out which
+//! text the result covers.
+//!
+//! Positions are `(line, column)` pairs, with columns counted in graphemes
+//! as everywhere in the [`Buffer`].
+
+use std::cmp::{max, min};
+
+use crate::buffer::Buffer;
+
+/// A place in the buffer: line and column.
+pub type Pos = (usize, usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    /// Deletes, then switches to insert mode.
+    Change,
+    /// Copies without deleting.
+    Yank,
+    /// Indents the lines by one level.
+    Indent,
+    /// Removes one level of indentation from the lines.
+    Outdent,
+}
+
+impl Operator {
+    /// The key that starts the operator; typed again, it applies the
+    /// operator to whole lines.
+    #[must_use]
+    pub fn key(self) -> char {
+        match self {
+            Self::Delete => 'd',
+            Self::Change => 'c',
+            Self::Yank => 'y',
+            Self::Indent => '>',
+            Self::Outdent => '<',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    /// To the start of the next word.
+    WordForward,
+    /// To the start of this word, or the previous one.
+    WordBackward,
+    /// To the end of this word, or the next one.
+    WordEnd,
+    LineStart,
+    LineEnd,
+    FirstLine,
+    LastLine,
+}
+
+/// The keys of each motion.
+const MOTIONS: &[(&str, Motion)] = &[
+    ("h", Motion::Left),
+    ("l", Motion::Right),
+    ("k", Motion::Up),
+    ("j", Motion::Down),
+    ("w", Motion::WordForward),
+    ("b", Motion::WordBackward),
+    ("e", Motion::WordEnd),
+    ("0", Motion::LineStart),
+    ("$", Motion::LineEnd),
+    ("gg", Motion::FirstLine),
+    ("G", Motion::LastLine),
+];
+
+impl Motion {
+    /// Whether an operator applies to every line the motion passes, rather
+    /// than the text between the two positions.
+    fn is_linewise(self) -> bool {
+        matches!(
+            self,
+            Self::Up | Self::Down | Self::FirstLine | Self::LastLine
+        )
+    }
+
+    /// Whether an operator includes the character the motion lands on.
+    fn is_inclusive(self) -> bool {
+        matches!(self, Self::WordEnd | Self::LineEnd)
+    }
+}
+
+/// A piece of text around the cursor, selected by `i` (inner) or `a`
+/// (around) and a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Object {
+    /// A run of word characters, punctuation or spaces; around also takes
+    /// the spaces after it.
+    Word,
+    /// Text between two of the same quote on the line; around takes the
+    /// quotes too.
+    Quote(char),
+    /// Text between an open bracket and the one that matches it, possibly
+    /// over several lines; around takes the brackets too.
+    Block(char, char),
+    /// A run of non-blank lines; around also takes the blank lines after it.
+    Paragraph,
+}
+
+/// The key after `i` or `a` for each object.
+const OBJECTS: &[(char, Object)] = &[
+    ('w', Object::Word),
+    ('"', Object::Quote('"')),
+    ('\'', Object::Quote('\'')),
+    ('`', Object::Quote('`')),
+    ('(', Object::Block('(', ')')),
+    (')', Object::Block('(', ')')),
+    ('b', Object::Block('(', ')')),
+    ('[', Object::Block('[', ']')),
+    (']', Object::Block('[', ']')),
+    ('{', Object::Block('{', '}')),
+    ('}', Object::Block('{', '}')),
+    ('B', Object::Block('{', '}')),
+    ('<', Object::Block('<', '>')),
+    ('>', Object::Block('<', '>')),
+    ('p', Object::Paragraph),
+];
+
+/// What an operator applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// `count` whole lines, starting at the cursor.
+    Lines,
+    Motion(Motion),
+    Object {
+        object: Object,
+        inner: bool,
+    },
+}
+
+/// A complete operator command, e.g. `d3w`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Action {
+    pub operator: Operator,
+    pub count: usize,
+    pub target: Target,
+}
+
+/// Text a yank or delete took, to be pasted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Yank {
+    pub text: String,
+    /// Whether `text` is whole lines, which are pasted as lines of their own.
+    pub linewise: bool,
+}
+
+impl Yank {
+    /// The same lines with the leading whitespace they share swapped for
+    /// `indent`, to line up with where they are pasted; blank lines are
+    /// left empty. Text that is not whole lines is kept as it is.
+    #[must_use]
+    pub fn reindented(&self, indent: &str) -> Self {
+        if !self.linewise {
+            return self.clone();
+        }
+        let unindented = |line: &str| line.trim_start_matches([' ', '\t']).len();
+        let common = self
+            .text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len().saturating_sub(unindented(line)))
+            .min()
+            .unwrap_or_default();
+        let text = self
+            .text
+            .split_inclusive('\n')
+            .fold(String::new(), |mut text, line| {
+                if line.trim().is_empty() {
+                    text.push_str(line.trim_start_matches([' ', '\t']));
+                } else {
+                    text.push_str(indent);
+                    text.push_str(line.get(common..).unwrap_or(line));
+                }
+                text
+            });
+        Self {
+            text,
+            linewise: true,
+        }
+    }
+}
+
+/// What the keys typed after an operator amount to so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parse {
+    /// They may still become a command.
+    Pending,
+    Done(Action),
+    /// They never will.
+    Invalid,
+}
+
+/// Parses the keys typed after `operator`: an optional count, then a
+/// motion, a text object or the operator key again.
+#[must_use]
+pub fn parse(operator: Operator, keys: &str) -> Parse {
+    // A leading `0` is the motion, not a count.
+    let digits = if keys.starts_with('0') {
+        0
+    } else {
+        keys.find(|ch: char| !ch.is_ascii_digit())
+            .unwrap_or(keys.len())
+    };
+    let (count, rest) = keys.split_at(digits);
+    let count = if count.is_empty() {
+        1
+    } else {
+        count.parse().unwrap_or(usize::MAX)
+    };
+    let done = |target| {
+        Parse::Done(Action {
+            operator,
+            count,
+            target,
+        })
+    };
+    let mut chars = rest.chars();
+    let target = match (chars.next(), chars.next(), chars.next()) {
+        (None, ..) => return Parse::Pending,
+        (Some(key), None, _) if key == operator.key() => Target::Lines,
+        (Some(scope @ ('i' | 'a')), object, None) => {
+            let Some(key) = object else {
+                return Parse::Pending;
+            };
+            return OBJECTS
+                .iter()
+                .find(|(candidate, _)| *candidate == key)
+                .map_or(Parse::Invalid, |(_, object)| {
+                    done(Target::Object {
+                        object: *object,
+                        inner: scope == 'i',
+                    })
+                });
+        }
+        _ => {
+            if let Some((_, motion)) = MOTIONS.iter().find(|(keys, _)| *keys == rest) {
+                Target::Motion(*motion)
+            } else if MOTIONS.iter().any(|(keys, _)| keys.starts_with(rest)) {
+                return Parse::Pending;
+            } else {
+                return Parse::Invalid;
+            }
+        }
+    };
+    done(target)
+}
+
+/// The text an action covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: Pos,
+    /// Just past the last character; for a linewise span, a position on
+    /// the last line.
+    pub end: Pos,
+    /// Whether the span is the whole lines from `start` to `end`.
+    pub linewise: bool,
+}
+
+impl Span {
+    fn lines(first: usize, last: usize) -> Self {
+        Self {
+            start: (min(first, last), 0),
+            end: (max(first, last), 0),
+            linewise: true,
+        }
+    }
+
+    /// The covered text. Whole lines end with a line break each.
+    #[must_use]
+    pub fn text(&self, buffer: &Buffer) -> String {
+        if !self.linewise {
+            return buffer.slice(self.start, self.end);
+        }
+        let mut text = String::new();
+        for line in (self.start.0..=self.end.0).filter_map(|line| buffer.get(line)) {
+            text.push_str(&line);
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Deletes the covered text.
+    pub fn delete(&self, buffer: &mut Buffer) {
+        if self.linewise {
+            buffer.delete_lines(self.start.0..self.end.0.saturating_add(1));
+        } else {
+            buffer.delete_range(self.start, self.end);
+        }
+    }
+}
+
+/// The text `action` covers with the cursor at `cursor`, if any.
+#[must_use]
+pub fn span(buffer: &Buffer, cursor: Pos, action: Action) -> Option<Span> {
+    let last_line = buffer.line_count().checked_sub(1)?;
+    let count = max(action.count, 1);
+    match action.target {
+        Target::Lines => {
+            let last = min(cursor.0.saturating_add(count).saturating_sub(1), last_line);
+            Some(Span::lines(cursor.0, last))
+        }
+        Target::Motion(motion) => {
+            // `cw` changes just the word, like `ce`.
+            let on_space = char_at(buffer, cursor).is_none_or(char::is_whitespace);
+            let motion = match motion {
+                Motion::WordForward if action.operator == Operator::Change && !on_space => {
+                    Motion::WordEnd
+                }
+                motion => motion,
+            };
+            let to = destination(buffer, cursor, motion, count)?;
+            if motion.is_linewise() {
+                return Some(Span::lines(cursor.0, to.0));
+            }
+            let (start, mut end) = (min(cursor, to), max(cursor, to));
+            if motion.is_inclusive() {
+                end.1 = min(end.1.saturating_add(1), buffer.line_len(end.0));
+            }
+            // A word motion stops at the end of the line it started on
+            // rather than taking the line break along.
+            if motion == Motion::WordForward && end.0 > start.0 {
+                let line = end.0.saturating_sub(1);
+                end = (line, buffer.line_len(line));
+            }
+            (start != end).then_some(Span {
+                start,
+                end,
+                linewise: false,
+            })
+        }
+        Target::Object { object, inner } => object_span(buffer, cursor, object, inner),
+    }
+}
+
+/// Where `motion` moves the cursor from `from`, `count` times over; `None`
+/// if it cannot move at all.
+#[must_use]
+pub fn destination(buffer: &Buffer, from: Pos, motion: Motion, count: usize) -> Option<Pos> {
+    let (line, col) = from;
+    let last_line = buffer.line_count().saturating_sub(1);
+    let repeat = |step: fn(&Buffer, Pos) -> Option<Pos>| {
+        let mut pos = from;
+        for _ in 0..count {
+            match step(buffer, pos) {
+                Some(next) => pos = next,
+                None => break,
+            }
+        }
+        (pos != from).then_some(pos)
+    };
+    match motion {
+        Motion::Left => (col > 0).then(|| (line, col.saturating_sub(count))),
+        Motion::Right => {
+            let len = buffer.line_len(line);
+            (col < len).then(|| (line, min(col.saturating_add(count), len)))
+        }
+        Motion::Up => (line > 0).then(|| (line.saturating_sub(count), col)),
+        Motion::Down => {
+            (line < last_line).then(|| (min(line.saturating_add(count), last_line), col))
+        }
+        Motion::WordForward => repeat(next_word_start),
+        Motion::WordBackward => repeat(previous_word_start),
+        Motion::WordEnd => repeat(word_end),
+        Motion::LineStart => Some((line, 0)),
+        Motion::LineEnd => Some((line, buffer.line_len(line).saturating_sub(1))),
+        Motion::FirstLine => Some((0, 0)),
+        Motion::LastLine => Some((last_line, 0)),
+    }
+}
+
+/// The kinds of character a word is made of: a word runs until the kind
+/// changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Space,
+    Word,
+    Punctuation,
+}
+
+fn class(ch: Option<char>) -> Class {
+    match ch {
+        None => Class::Space,
+        Some(ch) if ch.is_whitespace() => Class::Space,
+        Some(ch) if ch.is_alphanumeric() || ch == '_' => Class::Word,
+        Some(_) => Class::Punctuation,
+    }
+}
+
+/// The character at `pos`: the first of its grapheme, or `None` at the end
+/// of a line.
+fn char_at(buffer: &Buffer, (line, col): Pos) -> Option<char> {
+    let text = buffer.get(line)?;
+    let offset = buffer.layout(line).byte_offset(col);
+    text.get(offset..)?.chars().next()
+}
+
+/// The position after `pos`, going on to the next line past the end of
+/// one.
+fn next_pos(buffer: &Buffer, (line, col): Pos) -> Option<Pos> {
+    if col < buffer.line_len(line) {
+        Some((line, col.saturating_add(1)))
+    } else if line.saturating_add(1) < buffer.line_count() {
+        Some((line.saturating_add(1), 0))
+    } else {
+        None
+    }
+}
+
+/// The position before `pos`, going back to the end of the previous line
+/// from the start of one.
+fn previous_pos(buffer: &Buffer, (line, col): Pos) -> Option<Pos> {
+    if let Some(col) = col.checked_sub(1) {
+        Some((line, col))
+    } else {
+        let line = line.checked_sub(1)?;
+        Some((line, buffer.line_len(line)))
+    }
+}
+
+fn next_word_start(buffer: &Buffer, from: Pos) -> Option<Pos> {
+    let start = class(char_at(buffer, from));
+    let mut pos = next_pos(buffer, from)?;
+    while start != Class::Space && class(char_at(buffer, pos)) == start {
+        pos = next_pos(buffer, pos)?;
+    }
+    while class(char_at(buffer, pos)) == Class::Space {
+        match next_pos(buffer, pos) {
+            Some(next) => pos = next,
+            None => break,
+        }
+    }
+    Some(pos)
+}
+
+fn previous_word_start(buffer: &Buffer, from: Pos) -> Option<Pos> {
+    let mut pos = previous_pos(buffer, from)?;
+    while class(char_at(buffer, pos)) == Class::Space {
+        pos = previous_pos(buffer, pos)?;
+    }
+    let word = class(char_at(buffer, pos));
+    while let Some(previous) = previous_pos(buffer, pos) {
+        if class(char_at(buffer, previous)) != word {
+            break;
+        }
+        pos = previous;
+    }
+    Some(pos)
+}
+
+fn word_end(buffer: &Buffer, from: Pos) -> Option<Pos> {
+    let mut pos = next_pos(buffer, from)?;
+    while class(char_at(buffer, pos)) == Class::Space {
+        pos = next_pos(buffer, pos)?;
+    }
+    let word = class(char_at(buffer, pos));
+    while let Some(next) = next_pos(buffer, pos) {
+        if class(char_at(buffer, next)) != word {
+            break;
+        }
+        pos = next;
+    }
+    Some(pos)
+}
+
+fn object_span(buffer: &Buffer, cursor: Pos, object: Object, inner: bool) -> Option<Span> {
+    let (line, col) = cursor;
+    let charwise = |start, end| Span {
+        start,
+        end,
+        linewise: false,
+    };
+    match object {
+        Object::Word => {
+            let len = buffer.line_len(line);
+            let col = min(col, len.checked_sub(1)?);
+            let same = |col, kind| class(char_at(buffer, (line, col))) == kind;
+            let kind = class(char_at(buffer, (line, col)));
+            let mut start = col;
+            while start > 0 && same(start.saturating_sub(1), kind) {
+                start = start.saturating_sub(1);
+            }
+            let mut end = col.saturating_add(1);
+            while end < len && same(end, kind) {
+                end = end.saturating_add(1);
+            }
+            if !inner && kind != Class::Space {
+                if end < len && same(end, Class::Space) {
+                    while end < len && same(end, Class::Space) {
+                        end = end.saturating_add(1);
+                    }
+                } else {
+                    while start > 0 && same(start.saturating_sub(1), Class::Space) {
+                        start = start.saturating_sub(1);
+                    }
+                }
+            }
+            Some(charwise((line, start), (line, end)))
+        }
+        Object::Quote(quote) => {
+            let quotes: Vec<usize> = (0..buffer.line_len(line))
+                .filter(|&col| char_at(buffer, (line, col)) == Some(quote))
+                .collect();
+            let (open, close) = quotes
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1]))
+                .find(|&(_, close)| col <= close)?;
+            Some(if inner {
+                charwise((line, open.saturating_add(1)), (line, close))
+            } else {
+                charwise((line, open), (line, close.saturating_add(1)))
+            })
+        }
+        Object::Block(open, close) => {
+            let start = find_open(buffer, cursor, open, close)?;
+            let end = find_close(buffer, start, open, close)?;
+            Some(if inner {
+                charwise(next_pos(buffer, start)?, end)
+            } else {
+                charwise(start, next_pos(buffer, end)?)
+            })
+        }
+        Object::Paragraph => {
+            let blank = |line| buffer.get(line).is_none_or(|text| text.trim().is_empty());
+            let kind = blank(line);
+            let last_line = buffer.line_count().saturating_sub(1);
+            let mut first = line;
+            while first > 0 && blank(first.saturating_sub(1)) == kind {
+                first = first.saturating_sub(1);
+            }
+            let mut last = line;
+            while last < last_line && blank(last.saturating_add(1)) == kind {
+                last = last.saturating_add(1);
+            }
+            if !inner {
+                if last < last_line {
+                    last = last.saturating_add(1);
+                    while last < last_line && blank(last.saturating_add(1)) != kind {
+                        last = last.saturating_add(1);
+                    }
+                } else {
+                    while first > 0 && blank(first.saturating_sub(1)) != kind {
+                        first = first.saturating_sub(1);
+                    }
+                }
+            }
+            Some(Span::lines(first, last))
+        }
+    }
+}
+
+/// Where the bracket matching the one at `pos` is, if there is a bracket
+/// there and it has a match.
+#[must_use]
+pub fn matching_bracket(buffer: &Buffer, pos: Pos) -> Option<Pos> {
+    let ch = char_at(buffer, pos)?;
+    let (open, close) = [('(', ')'), ('[', ']'), ('{', '}')]
+        .into_iter()
+        .find(|&(open, close)| ch == open || ch == close)?;
+    if ch == open {
+        find_close(buffer, pos, open, close)
+    } else {
+        find_open(buffer, pos, open, close)
+    }
+}
+
+/// The unmatched `open` bracket at or before `from`.
+fn find_open(buffer: &Buffer, from: Pos, open: char, close: char) -> Option<Pos> {
+    let mut depth = 0_usize;
+    let mut pos = from;
+    loop {
+        match char_at(buffer, pos) {
+            Some(ch) if ch == open => match depth.checked_sub(1) {
+                Some(outer) => depth = outer,
+                None => return Some(pos),
+            },
+            Some(ch) if ch == close && pos != from => depth = depth.saturating_add(1),
+            _ => {}
+        }
+        pos = previous_pos(buffer, pos)?;
+    }
+}
+
+/// The `close` bracket matching the `open` one at `start`.
+fn find_close(buffer: &Buffer, start: Pos, open: char, close: char) -> Option<Pos> {
+    let mut depth = 0_usize;
+    let mut pos = start;
+    loop {
+        pos = next_pos(buffer, pos)?;
+        match char_at(buffer, pos) {
+            Some(ch) if ch == open => depth = depth.saturating_add(1),
+            Some(ch) if ch == close => match depth.checked_sub(1) {
+                Some(outer) => depth = outer,
+                None => return Some(pos),
+            },
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(text: &str) -> Buffer {
+        let mut buffer = Buffer::default();
+        buffer.replace_text(text);
+        buffer
+    }
+
+    /// The text `keys` (after a `d`) would delete with the cursor at
+    /// `cursor`.
+    fn deleted(text: &str, cursor: Pos, keys: &str) -> Option<String> {
+        let buffer = buffer(text);
+        let Parse::Done(action) = parse(Operator::Delete, keys) else {
+            panic!("{keys} did not parse");
+        };
+        span(&buffer, cursor, action).map(|span| span.text(&buffer))
+    }
+
+    #[test]
+    fn test_parse() {
+        let action = |count, target| {
+            Parse::Done(Action {
+                operator: Operator::Delete,
+                count,
+                target,
+            })
+        };
+        assert_eq!(parse(Operator::Delete, ""), Parse::Pending);
+        assert_eq!(parse(Operator::Delete, "d"), action(1, Target::Lines));
+        assert_eq!(
+            parse(Operator::Delete, "12w"),
+            action(12, Target::Motion(Motion::WordForward))
+        );
+        assert_eq!(
+            parse(Operator::Delete, "0"),
+            action(1, Target::Motion(Motion::LineStart))
+        );
+        assert_eq!(parse(Operator::Delete, "3g"), Parse::Pending);
+        assert_eq!(
+            parse(Operator::Delete, "gg"),
+            action(1, Target::Motion(Motion::FirstLine))
+        );
+        assert_eq!(parse(Operator::Delete, "i"), Parse::Pending);
+        assert_eq!(
+            parse(Operator::Delete, "a\""),
+            action(
+                1,
+                Target::Object {
+                    object: Object::Quote('"'),
+                    inner: false
+                }
+            )
+        );
+        assert_eq!(parse(Operator::Delete, "iz"), Parse::Invalid);
+        assert_eq!(parse(Operator::Delete, "c"), Parse::Invalid);
+        assert_eq!(parse(Operator::Delete, "gx"), Parse::Invalid);
+    }
+
+    #[test]
+    fn test_motions() {
+        let text = "let x = foo(bar);\nnext line\n";
+        assert_eq!(deleted(text, (0, 0), "w").as_deref(), Some("let "));
+        assert_eq!(deleted(text, (0, 0), "3w").as_deref(), Some("let x = "));
+        assert_eq!(deleted(text, (0, 8), "e").as_deref(), Some("foo"));
+        assert_eq!(deleted(text, (0, 11), "b").as_deref(), Some("foo"));
+        assert_eq!(deleted(text, (0, 8), "$").as_deref(), Some("foo(bar);"));
+        assert_eq!(deleted(text, (0, 8), "0").as_deref(), Some("let x = "));
+        // The last word stops at the end of the line.
+        assert_eq!(deleted(text, (0, 16), "w").as_deref(), Some(";"));
+        assert_eq!(deleted(text, (1, 0), "k").as_deref(), Some(text));
+        assert_eq!(deleted(text, (0, 0), "2d").as_deref(), Some(text));
+        assert_eq!(deleted(text, (0, 0), "h"), None);
+    }
+
+    #[test]
+    fn test_change_word_keeps_the_space() {
+        let buffer = buffer("one two");
+        let action = Action {
+            operator: Operator::Change,
+            count: 1,
+            target: Target::Motion(Motion::WordForward),
+        };
+        let span = span(&buffer, (0, 0), action).unwrap();
+        assert_eq!(span.text(&buffer), "one");
+    }
+
+    #[test]
+    fn test_text_objects() {
+        let text = "call(\"a b\", [1, (2)]) end";
+        assert_eq!(deleted(text, (0, 6), "iw").as_deref(), Some("a"));
+        assert_eq!(deleted(text, (0, 22), "aw").as_deref(), Some(" end"));
+        assert_eq!(deleted(text, (0, 7), "i\"").as_deref(), Some("a b"));
+        assert_eq!(deleted(text, (0, 0), "a\"").as_deref(), Some("\"a b\""));
+        assert_eq!(deleted(text, (0, 14), "i[").as_deref(), Some("1, (2)"));
+        assert_eq!(deleted(text, (0, 17), "ib").as_deref(), Some("2"));
+        assert_eq!(
+            deleted(text, (0, 4), "a(").as_deref(),
+            Some("(\"a b\", [1, (2)])")
+        );
+        assert_eq!(
+            deleted("fn {\n  x\n}", (1, 2), "iB").as_deref(),
+            Some("\n  x\n")
+        );
+        assert_eq!(deleted(text, (0, 0), "i{"), None);
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        let text = "a\nb\n\nc\n\n\nd\n";
+        assert_eq!(deleted(text, (0, 0), "ip").as_deref(), Some("a\nb\n"));
+        assert_eq!(deleted(text, (3, 0), "ap").as_deref(), Some("c\n\n\n"));
+        // The last paragraph takes the blank lines before it instead.
+        assert_eq!(deleted(text, (6, 0), "ap").as_deref(), Some("\n\nd\n"));
+    }
+
+    #[test]
+    fn test_reindented_lines_keep_their_shape() {
+        let yank = |text: &str, linewise| Yank {
+            text: text.to_string(),
+            linewise,
+        };
+        assert_eq!(
+            yank("        if x {\n  \n            y\n        }\n", true).reindented("\t"),
+            yank("\tif x {\n\n\t    y\n\t}\n", true)
+        );
+        assert_eq!(
+            yank("  words", false).reindented("\t"),
+            yank("  words", false)
+        );
+    }
+}
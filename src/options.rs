@@ -0,0 +1,590 @@
+//! # Options
+//!
+//! The settings that can be changed while the editor runs, with the
+//! `set` command (`Alt+X` opens the command line), or at startup in the
+//! `[options]` section of the config file. The syntax follows Vim's `:set`:
+//!
+//! - `set tabstop=8`: give an option a value.
+//! - `set osc52`, `set noosc52`, `set osc52!`: turn a switch on, off, or
+//!   toggle it.
+//! - `set tabstop?` (or `set tabstop` for options that are not switches):
+//!   show the current value.
+//!
+//! A space in a value is escaped with a backslash, as in Vim:
+//! `set makeprg=cargo\ build`.
+//!
+//! A file's `.editorconfig` (see [`editorconfig`](crate::editorconfig)) wins
+//! over these for that file.
+//!
+//! | Option            | Short | Value                                   | Default   |
+//! |-------------------|-------|-----------------------------------------|-----------|
+//! | `tabstop`         | `ts`  | columns between tab stops, 1–16         | 4         |
+//! | `bell`            |       | `audible`, `visual` or `none`           | `audible` |
+//! | `osc52`           |       | copy to the clipboard (a switch)        | off       |
+//! | `theme`           |       | a [theme](crate::theme) name            | `dark`    |
+//! | `modal`           |       | Vim-style modes (a switch)              | off       |
+//! | `leader`          |       | the key [`leader`](crate::keymap) means | `space`   |
+//! | `timeoutlen`      | `tm`  | milliseconds to finish a chord, 0: wait | 1000      |
+//! | `makeprg`         | `mp`  | the command `make` runs                 | `make`    |
+//! | `todowords`       |       | words marked in comments, by commas     | see below |
+//! | `lsp`             |       | run [language servers](crate::lsp)      | off       |
+//! | `formatonsave`    | `fos` | [format](crate::format) when saving     | off       |
+//! | `lintonsave`      | `los` | [lint](crate::lint) after saving        | off       |
+//! | `spell`           |       | [check spelling](crate::spell)          | off       |
+//! | `spelllang`       | `spl` | the language of the dictionary          | `en_US`   |
+//! | `gitgutter`       |       | mark lines [changed](crate::git) in Git | on        |
+//! | `blameline`       |       | show the cursor's line's last commit    | off       |
+//! | `trash`           |       | `delete` to the [trash](crate::files)   | on        |
+//! | `restorecursor`   |       | open files where the cursor was left    | on        |
+//! | `undofile`        |       | keep [undo](crate::undo) across runs    | off       |
+//! | `indent`          |       | `tabs`, spaces per level, or `auto`     | `auto`    |
+//! | `smartindent`     |       | line up what closes a block with it     | on        |
+//! | `timestampformat` |       | how `insert-timestamp` writes the time  | see below |
+//! | `author`          |       | who [templates](crate::template) name   | login     |
+//! | `ansi`            |       | show [ANSI colors](crate::ansi)         | off       |
+//!
+//! `todowords` starts as `TODO,FIXME,XXX,NOTE`, and `timestampformat` as
+//! `%Y-%m-%d %H:%M`. With `indent=auto`, each
+//! file is indented the way it was [found](crate::indent) to be.
+
+use std::time::Duration;
+
+use crossterm::event::{KeyCode, KeyModifiers};
+
+use crate::{
+    buffer::Indent,
+    keymap::KeyPress,
+    layout::TAB_WIDTH,
+    terminal::Bell,
+    theme::{Theme, DEFAULT_THEME},
+};
+
+/// The widest tab stop `tabstop` accepts.
+const MAX_TAB_WIDTH: usize = 16;
+
+// The switches are independent settings, not states of one thing.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    /// Columns between tab stops.
+    pub tab_width: usize,
+    /// How to signal a command that could not be carried out.
+    pub bell: Bell,
+    /// Whether copied text is sent to the system clipboard with OSC 52.
+    pub osc52: bool,
+    /// Name of the color theme.
+    pub theme: String,
+    /// Whether keys work in modes, starting in normal mode.
+    pub modal: bool,
+    /// The key that starts leader chords.
+    pub leader: KeyPress,
+    /// How long a half-typed chord waits for the rest, if not forever.
+    pub chord_timeout: Option<Duration>,
+    /// The build command `make` runs.
+    pub make_program: String,
+    /// The words highlighted in comments, which `todo` lists.
+    pub todo_words: Vec<String>,
+    /// Whether language servers are started for the files that have one.
+    pub lsp: bool,
+    /// Whether buffers are run through their formatter when saved.
+    pub format_on_save: bool,
+    /// Whether the linter runs on a buffer's file once it is saved.
+    pub lint_on_save: bool,
+    /// Whether misspelled words are marked.
+    pub spell: bool,
+    /// The language spelling is checked in, like `en_US`.
+    pub spell_lang: String,
+    /// Whether lines changed since the last commit are marked.
+    pub git_gutter: bool,
+    /// Whether the status bar says who last changed the cursor's line.
+    pub blame_line: bool,
+    /// Whether `delete` moves files to the trash rather than deleting them.
+    pub trash: bool,
+    /// Whether files open with the cursor where it was last left.
+    pub restore_cursor: bool,
+    /// Whether what can be undone in a file is kept when it is saved, to
+    /// be undone after it is opened again.
+    pub undo_file: bool,
+    /// How every file is indented; `None` goes by how each already is.
+    pub indent: Option<Indent>,
+    /// Whether typing what closes a block lines it up with the block's
+    /// start.
+    pub smart_indent: bool,
+    /// How `insert-timestamp` lays out the time, `strftime` style.
+    pub timestamp_format: String,
+    /// Who snippets name as the author; the login name if empty.
+    pub author: String,
+    /// Whether files with ANSI color escapes are shown in their colors,
+    /// read-only.
+    pub ansi: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            tab_width: TAB_WIDTH,
+            bell: Bell::default(),
+            osc52: false,
+            theme: DEFAULT_THEME.to_string(),
+            modal: false,
+            leader: KeyPress::new(KeyCode::Char(' '), KeyModifiers::NONE),
+            chord_timeout: Some(Duration::from_secs(1)),
+            make_program: "make".to_string(),
+            todo_words: ["TODO", "FIXME", "XXX", "NOTE"]
+                .map(ToString::to_string)
+                .to_vec(),
+            lsp: false,
+            format_on_save: false,
+            lint_on_save: false,
+            spell: false,
+            spell_lang: "en_US".to_string(),
+            git_gutter: true,
+            blame_line: false,
+            trash: true,
+            restore_cursor: true,
+            undo_file: false,
+            indent: None,
+            smart_indent: true,
+            timestamp_format: "%Y-%m-%d %H:%M".to_string(),
+            author: String::new(),
+            ansi: false,
+        }
+    }
+}
+
+impl Options {
+    /// Applies one `set` argument. Returns the text to show the user: the
+    /// value for a query, nothing for a change, or why the argument was
+    /// rejected.
+    ///
+    /// # Errors
+    ///
+    /// Fails with a message if the option does not exist or the value does
+    /// not suit it.
+    pub fn set(&mut self, argument: &str) -> Result<Option<String>, String> {
+        let argument = argument.trim();
+        if let Some(name) = argument.strip_suffix('?') {
+            return self.query(name).map(Some);
+        }
+        if let Some((name, value)) = argument.split_once('=') {
+            return self.assign(name.trim(), value.trim()).map(|()| None);
+        }
+        if let Some(name) = argument.strip_suffix('!') {
+            let value = self.switch(name)?;
+            *value = !*value;
+            return Ok(None);
+        }
+        if let Ok(value) = self.switch(argument) {
+            *value = true;
+            return Ok(None);
+        }
+        if let Some(value) = argument
+            .strip_prefix("no")
+            .and_then(|name| self.switch(name).ok())
+        {
+            *value = false;
+            return Ok(None);
+        }
+        self.query(argument).map(Some)
+    }
+
+    /// Shows an option as `name=value`.
+    fn query(&self, name: &str) -> Result<String, String> {
+        let value = match canonical_name(name)? {
+            "tabstop" => self.tab_width.to_string(),
+            "bell" => bell_name(self.bell).to_string(),
+            "osc52" => return Ok(if self.osc52 { "osc52" } else { "noosc52" }.to_string()),
+            "theme" => self.theme.clone(),
+            "modal" => return Ok(if self.modal { "modal" } else { "nomodal" }.to_string()),
+            "leader" => self.leader.to_string(),
+            "timeoutlen" => self
+                .chord_timeout
+                .map_or(0, |timeout| timeout.as_millis())
+                .to_string(),
+            "makeprg" => self.make_program.clone(),
+            "todowords" => self.todo_words.join(","),
+            "lsp" => return Ok(if self.lsp { "lsp" } else { "nolsp" }.to_string()),
+            "formatonsave" => {
+                let value = if self.format_on_save { "" } else { "no" };
+                return Ok(format!("{value}formatonsave"));
+            }
+            "lintonsave" => {
+                let value = if self.lint_on_save { "" } else { "no" };
+                return Ok(format!("{value}lintonsave"));
+            }
+            "spell" => return Ok(if self.spell { "spell" } else { "nospell" }.to_string()),
+            "spelllang" => self.spell_lang.clone(),
+            "gitgutter" => {
+                let value = if self.git_gutter { "" } else { "no" };
+                return Ok(format!("{value}gitgutter"));
+            }
+            "blameline" => {
+                let value = if self.blame_line { "" } else { "no" };
+                return Ok(format!("{value}blameline"));
+            }
+            "trash" => return Ok(if self.trash { "trash" } else { "notrash" }.to_string()),
+            "restorecursor" => {
+                let value = if self.restore_cursor { "" } else { "no" };
+                return Ok(format!("{value}restorecursor"));
+            }
+            "undofile" => {
+                let value = if self.undo_file { "" } else { "no" };
+                return Ok(format!("{value}undofile"));
+            }
+            "smartindent" => {
+                let value = if self.smart_indent { "" } else { "no" };
+                return Ok(format!("{value}smartindent"));
+            }
+            "ansi" => return Ok(if self.ansi { "ansi" } else { "noansi" }.to_string()),
+            "timestampformat" => self.timestamp_format.clone(),
+            "author" => self.author.clone(),
+            "indent" => match self.indent {
+                None => "auto".to_string(),
+                Some(Indent::Tabs) => "tabs".to_string(),
+                Some(Indent::Spaces(size)) => size.to_string(),
+            },
+            _ => return Err(unknown(name)),
+        };
+        Ok(format!("{}={value}", canonical_name(name)?))
+    }
+
+    fn assign(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match canonical_name(name)? {
+            "tabstop" => {
+                self.tab_width = value
+                    .parse()
+                    .ok()
+                    .filter(|width| (1..=MAX_TAB_WIDTH).contains(width))
+                    .ok_or_else(|| {
+                        format!("tabstop must be a number from 1 to {MAX_TAB_WIDTH}: {value}")
+                    })?;
+            }
+            "bell" => {
+                self.bell = parse_bell(value)
+                    .ok_or_else(|| format!("bell must be audible, visual or none: {value}"))?;
+            }
+            "osc52" | "modal" | "lsp" | "formatonsave" | "lintonsave" | "spell" | "gitgutter"
+            | "blameline" | "trash" | "restorecursor" | "undofile" | "smartindent" | "ansi" => {
+                *self.switch(name)? = match value {
+                    "true" | "on" => true,
+                    "false" | "off" => false,
+                    _ => return Err(format!("{name} must be on or off: {value}")),
+                };
+            }
+            "theme" => {
+                Theme::load(value).map_err(|problem| {
+                    format!("{problem} (themes: {})", Theme::available().join(", "))
+                })?;
+                self.theme = value.to_string();
+            }
+            "leader" => {
+                self.leader = KeyPress::parse(value)
+                    .ok_or_else(|| format!("leader must be a key: {value}"))?;
+            }
+            "timeoutlen" => {
+                let millis: u64 = value
+                    .parse()
+                    .map_err(|_| format!("timeoutlen must be a number: {value}"))?;
+                self.chord_timeout = (millis > 0).then(|| Duration::from_millis(millis));
+            }
+            "makeprg" => {
+                if value.is_empty() {
+                    return Err("makeprg must be a command".to_string());
+                }
+                self.make_program = value.to_string();
+            }
+            "indent" => {
+                self.indent = match value {
+                    "auto" => None,
+                    "tabs" => Some(Indent::Tabs),
+                    _ => Some(Indent::Spaces(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|size| (1..=MAX_TAB_WIDTH).contains(size))
+                            .ok_or_else(|| {
+                                format!(
+                                    "indent must be auto, tabs or a number from 1 to \
+                                     {MAX_TAB_WIDTH}: {value}"
+                                )
+                            })?,
+                    )),
+                };
+            }
+            "timestampformat" => {
+                if value.is_empty() {
+                    return Err("timestampformat must be a format, like %Y-%m-%d".to_string());
+                }
+                self.timestamp_format = value.to_string();
+            }
+            "author" => self.author = value.to_string(),
+            "spelllang" => {
+                if value.is_empty() || value.contains(['/', '\\']) {
+                    return Err(format!("spelllang must be a language, like en_US: {value}"));
+                }
+                self.spell_lang = value.to_string();
+            }
+            "todowords" => {
+                let words: Vec<String> = value
+                    .split(',')
+                    .filter(|word| !word.is_empty())
+                    .map(ToString::to_string)
+                    .collect();
+                let is_word =
+                    |word: &String| word.chars().all(|ch| ch.is_alphanumeric() || ch == '_');
+                if !words.iter().all(is_word) {
+                    return Err(format!(
+                        "todowords must be words separated by commas: {value}"
+                    ));
+                }
+                self.todo_words = words;
+            }
+            _ => return Err(unknown(name)),
+        }
+        Ok(())
+    }
+
+    /// The value of a switch (on/off option).
+    fn switch(&mut self, name: &str) -> Result<&mut bool, String> {
+        match canonical_name(name)? {
+            "osc52" => Ok(&mut self.osc52),
+            "modal" => Ok(&mut self.modal),
+            "lsp" => Ok(&mut self.lsp),
+            "formatonsave" => Ok(&mut self.format_on_save),
+            "lintonsave" => Ok(&mut self.lint_on_save),
+            "spell" => Ok(&mut self.spell),
+            "gitgutter" => Ok(&mut self.git_gutter),
+            "blameline" => Ok(&mut self.blame_line),
+            "trash" => Ok(&mut self.trash),
+            "restorecursor" => Ok(&mut self.restore_cursor),
+            "undofile" => Ok(&mut self.undo_file),
+            "smartindent" => Ok(&mut self.smart_indent),
+            "ansi" => Ok(&mut self.ansi),
+            _ => Err(format!("{name} is not a switch")),
+        }
+    }
+}
+
+/// Resolves short names (`ts`) to the full option name.
+fn canonical_name(name: &str) -> Result<&'static str, String> {
+    match name {
+        "tabstop" | "ts" => Ok("tabstop"),
+        "bell" => Ok("bell"),
+        "osc52" => Ok("osc52"),
+        "theme" => Ok("theme"),
+        "modal" => Ok("modal"),
+        "leader" => Ok("leader"),
+        "timeoutlen" | "tm" => Ok("timeoutlen"),
+        "makeprg" | "mp" => Ok("makeprg"),
+        "todowords" => Ok("todowords"),
+        "lsp" => Ok("lsp"),
+        "formatonsave" | "fos" => Ok("formatonsave"),
+        "lintonsave" | "los" => Ok("lintonsave"),
+        "spell" => Ok("spell"),
+        "spelllang" | "spl" => Ok("spelllang"),
+        "gitgutter" => Ok("gitgutter"),
+        "blameline" => Ok("blameline"),
+        "trash" => Ok("trash"),
+        "restorecursor" => Ok("restorecursor"),
+        "undofile" => Ok("undofile"),
+        "indent" => Ok("indent"),
+        "smartindent" => Ok("smartindent"),
+        "timestampformat" => Ok("timestampformat"),
+        "author" => Ok("author"),
+        "ansi" => Ok("ansi"),
+        _ => Err(unknown(name)),
+    }
+}
+
+/// The arguments of a `set` command line, split at whitespace except
+/// where a backslash escapes it.
+#[must_use]
+pub fn split_arguments(line: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut argument = String::new();
+    let mut chars = line.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some(next) if next.is_whitespace() => argument.push(next),
+                next => {
+                    // Any other backslash stays, as in a Windows path.
+                    argument.push(ch);
+                    argument.extend(next);
+                }
+            },
+            _ if ch.is_whitespace() => {
+                if !argument.is_empty() {
+                    arguments.push(std::mem::take(&mut argument));
+                }
+            }
+            _ => argument.push(ch),
+        }
+    }
+    if !argument.is_empty() {
+        arguments.push(argument);
+    }
+    arguments
+}
+
+fn unknown(name: &str) -> String {
+    format!("Unknown option: {name}")
+}
+
+#[must_use]
+pub fn parse_bell(name: &str) -> Option<Bell> {
+    match name {
+        "audible" => Some(Bell::Audible),
+        "visual" => Some(Bell::Visual),
+        "none" => Some(Bell::None),
+        _ => None,
+    }
+}
+
+fn bell_name(bell: Bell) -> &'static str {
+    match bell {
+        Bell::Audible => "audible",
+        Bell::Visual => "visual",
+        Bell::None => "none",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assign_and_query() {
+        let mut options = Options::default();
+        assert_eq!(options.set("tabstop?"), Ok(Some("tabstop=4".to_string())));
+        assert_eq!(options.set("ts=8"), Ok(None));
+        assert_eq!(options.tab_width, 8);
+        assert_eq!(options.set("tabstop"), Ok(Some("tabstop=8".to_string())));
+
+        assert_eq!(options.set("bell=visual"), Ok(None));
+        assert_eq!(options.bell, Bell::Visual);
+        assert_eq!(options.set("bell?"), Ok(Some("bell=visual".to_string())));
+
+        assert_eq!(options.set("theme=light"), Ok(None));
+        assert_eq!(options.set("theme"), Ok(Some("theme=light".to_string())));
+
+        assert_eq!(options.set("leader"), Ok(Some("leader=space".to_string())));
+        assert_eq!(options.set("leader=,"), Ok(None));
+        assert_eq!(options.leader, KeyPress::parse(",").unwrap());
+        assert_eq!(options.set("tm=0"), Ok(None));
+        assert_eq!(options.chord_timeout, None);
+        assert_eq!(
+            options.set("timeoutlen"),
+            Ok(Some("timeoutlen=0".to_string()))
+        );
+
+        let arguments = split_arguments(r"mp=cargo\ build\ -q  ts=2 path=C:\src");
+        assert_eq!(arguments, ["mp=cargo build -q", "ts=2", r"path=C:\src"]);
+        assert_eq!(options.set(&arguments[0]), Ok(None));
+        assert_eq!(
+            options.set("makeprg"),
+            Ok(Some("makeprg=cargo build -q".to_string()))
+        );
+
+        assert_eq!(options.set("todowords=TODO,HACK"), Ok(None));
+        assert_eq!(options.todo_words, ["TODO", "HACK"]);
+        assert!(options.set("todowords=a|b").is_err());
+
+        assert_eq!(options.set("timestampformat=%d/%m %H:%M"), Ok(None));
+        assert_eq!(options.timestamp_format, "%d/%m %H:%M");
+        assert_eq!(options.set("author=Ada"), Ok(None));
+        assert_eq!(options.set("author"), Ok(Some("author=Ada".to_string())));
+    }
+
+    #[test]
+    fn test_switches() {
+        let mut options = Options::default();
+        options.set("osc52").unwrap();
+        assert!(options.osc52);
+        options.set("noosc52").unwrap();
+        assert!(!options.osc52);
+        options.set("osc52!").unwrap();
+        assert!(options.osc52);
+        assert_eq!(options.set("osc52?"), Ok(Some("osc52".to_string())));
+        options.set("osc52=off").unwrap();
+        assert!(!options.osc52);
+        options.set("modal=true").unwrap();
+        assert!(options.modal);
+        assert_eq!(options.set("modal?"), Ok(Some("modal".to_string())));
+        assert_eq!(options.set("lsp?"), Ok(Some("nolsp".to_string())));
+        options.set("lsp").unwrap();
+        assert!(options.lsp);
+        options.set("fos").unwrap();
+        assert_eq!(
+            options.set("formatonsave?"),
+            Ok(Some("formatonsave".to_string()))
+        );
+        options.set("los").unwrap();
+        assert!(options.lint_on_save);
+        options.set("spell").unwrap();
+        assert_eq!(options.set("spell?"), Ok(Some("spell".to_string())));
+        options.set("spl=de_DE").unwrap();
+        assert_eq!(
+            options.set("spelllang"),
+            Ok(Some("spelllang=de_DE".to_string()))
+        );
+        assert!(options.set("spelllang=../x").is_err());
+        options.set("nogitgutter").unwrap();
+        assert!(!options.git_gutter);
+        assert_eq!(
+            options.set("blameline?"),
+            Ok(Some("noblameline".to_string()))
+        );
+        options.set("notrash").unwrap();
+        assert!(!options.trash);
+        assert_eq!(
+            options.set("restorecursor?"),
+            Ok(Some("restorecursor".to_string()))
+        );
+        options.set("undofile").unwrap();
+        assert!(options.undo_file);
+        assert_eq!(options.set("indent"), Ok(Some("indent=auto".to_string())));
+        options.set("indent=2").unwrap();
+        assert_eq!(options.indent, Some(Indent::Spaces(2)));
+        options.set("indent=tabs").unwrap();
+        assert_eq!(options.indent, Some(Indent::Tabs));
+        assert!(options.set("indent=spaces").is_err());
+        options.set("nosmartindent").unwrap();
+        assert!(!options.smart_indent);
+        assert_eq!(options.set("ansi?"), Ok(Some("noansi".to_string())));
+        options.set("ansi").unwrap();
+        assert!(options.ansi);
+    }
+
+    #[test]
+    fn test_invalid_settings_are_rejected() {
+        let mut options = Options::default();
+        assert_eq!(
+            options.set("tabstop=0"),
+            Err("tabstop must be a number from 1 to 16: 0".to_string())
+        );
+        assert_eq!(
+            options.set("bell=loud"),
+            Err("bell must be audible, visual or none: loud".to_string())
+        );
+        assert_eq!(options.set("wrap"), Err("Unknown option: wrap".to_string()));
+        assert!(options
+            .set("theme=neon")
+            .is_err_and(|problem| problem.starts_with("Unknown theme: neon (themes: ")));
+        assert_eq!(
+            options.set("tabstop!"),
+            Err("tabstop is not a switch".to_string())
+        );
+        assert_eq!(
+            options.set("leader=ctrl-nope"),
+            Err("leader must be a key: ctrl-nope".to_string())
+        );
+        assert_eq!(options, Options::default());
+    }
+
+    #[test]
+    fn test_parse_bell() {
+        assert_eq!(parse_bell("visual"), Some(Bell::Visual));
+        assert_eq!(parse_bell("loud"), None);
+    }
+}
@@ -0,0 +1,216 @@
+//! # Picker
+//!
+//! A full-screen list the user can move through with the arrow keys and pick
+//! an entry from with `Enter` (or dismiss with `Esc`). Typing narrows the
+//! list to the entries with the typed characters in that order, not
+//! necessarily next to each other (`bfr` finds `buffer.rs`). It knows
+//! nothing about what the entries mean: callers hand it labels and get
+//! back the index of the chosen one.
+
+use crate::{screen::fit_to_width, terminal::Size};
+
+/// The outcome of feeding a key to the picker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickerAction {
+    /// The picker is still open.
+    Pending,
+    /// The user chose the entry at this index.
+    Picked(usize),
+    /// The user dismissed the picker.
+    Cancelled,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Picker {
+    title: String,
+    items: Vec<String>,
+    /// What was typed to narrow the list.
+    query: String,
+    /// The indices of the items that match the query.
+    matches: Vec<usize>,
+    /// Which of the matches is selected.
+    selected: usize,
+}
+
+impl Picker {
+    #[must_use]
+    pub fn new(title: &str, items: Vec<String>) -> Self {
+        Self {
+            title: title.to_string(),
+            matches: (0..items.len()).collect(),
+            items,
+            query: String::new(),
+            selected: 0,
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self) {
+        let last = self.matches.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add(1).min(last);
+    }
+
+    /// Adds `ch` to the query, keeping only the items that still match.
+    pub fn type_char(&mut self, ch: char) {
+        self.query.push(ch);
+        self.filter();
+    }
+
+    /// Takes the last character off the query, if there is one.
+    pub fn backspace(&mut self) {
+        if self.query.pop().is_some() {
+            self.filter();
+        }
+    }
+
+    /// Confirms the current selection; an empty picker can only be cancelled.
+    #[must_use]
+    pub fn accept(&self) -> PickerAction {
+        match self.matches.get(self.selected) {
+            Some(&index) => PickerAction::Picked(index),
+            None => PickerAction::Cancelled,
+        }
+    }
+
+    fn filter(&mut self) {
+        self.matches = (0..self.items.len())
+            .filter(|&index| {
+                self.items
+                    .get(index)
+                    .is_some_and(|item| fuzzy_match(&self.query, item))
+            })
+            .collect();
+        self.selected = 0;
+    }
+
+    /// Builds the title row followed by one row per item, marking the
+    /// selection with a `>`. Items that do not fit are scrolled so the
+    /// selection stays visible.
+    #[must_use]
+    pub fn render(&self, Size { height, width }: Size) -> Vec<String> {
+        let visible = height.saturating_sub(1);
+        let first = self.selected.saturating_sub(visible.saturating_sub(1));
+
+        (0..height)
+            .map(|row| {
+                let mut line = if row == 0 && self.query.is_empty() {
+                    format!("{} ({})", self.title, self.matches.len())
+                } else if row == 0 {
+                    format!("{} ({}): {}", self.title, self.matches.len(), self.query)
+                } else {
+                    let index = first.saturating_add(row).saturating_sub(1);
+                    let item = self
+                        .matches
+                        .get(index)
+                        .and_then(|&item| self.items.get(item));
+                    match item {
+                        Some(item) if index == self.selected => format!("> {item}"),
+                        Some(item) => format!("  {item}"),
+                        None => "~".to_string(),
+                    }
+                };
+                fit_to_width(&mut line, width);
+                line
+            })
+            .collect()
+    }
+
+    /// The [theme](crate::theme) element that row `row` of
+    /// [`Picker::render`] is drawn as.
+    #[must_use]
+    pub fn element(&self, row: usize, Size { height, .. }: Size) -> &'static str {
+        let visible = height.saturating_sub(1);
+        let first = self.selected.saturating_sub(visible.saturating_sub(1));
+        let index = first.saturating_add(row).saturating_sub(1);
+        if row == 0 {
+            "status_bar"
+        } else if index == self.selected {
+            "selection"
+        } else if index >= self.matches.len() {
+            "empty_line"
+        } else {
+            "text"
+        }
+    }
+}
+
+/// Whether the characters of `query` appear in `item` in order, ignoring
+/// case.
+fn fuzzy_match(query: &str, item: &str) -> bool {
+    let mut chars = item.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|wanted| chars.any(|ch| ch == wanted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picker() -> Picker {
+        Picker::new("Recent files", vec!["a".into(), "b".into(), "c".into()])
+    }
+
+    #[test]
+    fn test_selection_is_clamped() {
+        let mut picker = picker();
+        picker.select_previous();
+        assert_eq!(picker.accept(), PickerAction::Picked(0));
+
+        for _ in 0..10 {
+            picker.select_next();
+        }
+        assert_eq!(picker.accept(), PickerAction::Picked(2));
+    }
+
+    #[test]
+    fn test_empty_picker_cancels() {
+        let picker = Picker::new("Nothing", vec![]);
+        assert_eq!(picker.accept(), PickerAction::Cancelled);
+    }
+
+    #[test]
+    fn test_render_marks_selection() {
+        let mut picker = picker();
+        picker.select_next();
+        let rows = picker.render(Size {
+            width: 80,
+            height: 5,
+        });
+        assert_eq!(rows, vec!["Recent files (3)", "  a", "> b", "  c", "~"]);
+    }
+
+    #[test]
+    fn test_typing_narrows_the_list() {
+        let mut picker = Picker::new(
+            "Buffers",
+            vec![
+                "src/buffer.rs".into(),
+                "README.md".into(),
+                "src/lib.rs".into(),
+            ],
+        );
+        picker.type_char('S');
+        picker.type_char('r');
+        picker.type_char('s');
+        let size = Size {
+            width: 80,
+            height: 4,
+        };
+        assert_eq!(
+            picker.render(size),
+            vec!["Buffers (2): Srs", "> src/buffer.rs", "  src/lib.rs", "~"]
+        );
+        picker.select_next();
+        assert_eq!(picker.accept(), PickerAction::Picked(2));
+
+        picker.type_char('x');
+        assert_eq!(picker.accept(), PickerAction::Cancelled);
+        picker.backspace();
+        assert_eq!(picker.accept(), PickerAction::Picked(0));
+    }
+}
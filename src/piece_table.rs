@@ -0,0 +1,413 @@
+//! # Piece Table
+//!
+//! The text storage behind [`Buffer`](crate::buffer::Buffer). The file
+//! contents are kept, untouched, in an *original* string; everything typed
+//! afterwards is appended to an *added* string. The document is described by
+//! a list of pieces, each pointing at a span of one of the two strings.
+//!
+//! Inserting or deleting only splits or trims pieces, so the cost of an edit
+//! depends on how many edits came before it, not on the size of the file or
+//! on where in the file it happens. Both strings also keep an index of their
+//! line breaks, which makes finding the start of a line a matter of walking
+//! the pieces and binary-searching that index.
+
+use std::{borrow::Cow, ops::Range};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Original,
+    Added,
+}
+
+/// A span of one of the two backing strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Piece {
+    source: Source,
+    start: usize,
+    len: usize,
+    /// Number of line breaks in the span.
+    newlines: usize,
+}
+
+impl Piece {
+    fn end(&self) -> usize {
+        self.start.saturating_add(self.len)
+    }
+}
+
+/// A string that remembers where its line breaks are.
+#[derive(Debug, Default, Clone)]
+struct Store {
+    text: String,
+    newlines: Vec<usize>,
+}
+
+impl Store {
+    fn new(text: String) -> Self {
+        let newlines = newline_offsets(&text, 0);
+        Self { text, newlines }
+    }
+
+    fn push(&mut self, text: &str) -> usize {
+        let start = self.text.len();
+        self.newlines.extend(newline_offsets(text, start));
+        self.text.push_str(text);
+        start
+    }
+
+    fn newlines_in(&self, range: Range<usize>) -> usize {
+        let first = self.newlines.partition_point(|&at| at < range.start);
+        let last = self.newlines.partition_point(|&at| at < range.end);
+        last.saturating_sub(first)
+    }
+
+    /// Offset of the `nth` (0-based) line break at or after `start`.
+    fn nth_newline_from(&self, start: usize, nth: usize) -> Option<usize> {
+        let first = self.newlines.partition_point(|&at| at < start);
+        self.newlines.get(first.checked_add(nth)?).copied()
+    }
+}
+
+fn newline_offsets(text: &str, base: usize) -> Vec<usize> {
+    text.bytes()
+        .enumerate()
+        .filter(|&(_, byte)| byte == b'\n')
+        .map(|(at, _)| base.saturating_add(at))
+        .collect()
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct PieceTable {
+    original: Store,
+    added: Store,
+    pieces: Vec<Piece>,
+    /// Total length in bytes.
+    len: usize,
+}
+
+impl PieceTable {
+    #[must_use]
+    pub fn new(text: String) -> Self {
+        let original = Store::new(text);
+        let len = original.text.len();
+        let pieces = if len == 0 {
+            Vec::new()
+        } else {
+            vec![Piece {
+                source: Source::Original,
+                start: 0,
+                len,
+                newlines: original.newlines.len(),
+            }]
+        };
+        Self {
+            original,
+            added: Store::default(),
+            pieces,
+            len,
+        }
+    }
+
+    /// Length of the text in bytes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn store(&self, source: Source) -> &Store {
+        match source {
+            Source::Original => &self.original,
+            Source::Added => &self.added,
+        }
+    }
+
+    fn piece_text(&self, piece: &Piece) -> &str {
+        self.store(piece.source)
+            .text
+            .get(piece.start..piece.end())
+            .unwrap_or_default()
+    }
+
+    /// Number of line breaks in the text.
+    #[must_use]
+    pub fn newline_count(&self) -> usize {
+        self.pieces.iter().map(|piece| piece.newlines).sum()
+    }
+
+    /// Number of lines, counted like [`str::lines`]: a trailing line break
+    /// does not start another line.
+    #[must_use]
+    pub fn line_count(&self) -> usize {
+        let newlines = self.newline_count();
+        if self.is_empty() || self.ends_with_newline() {
+            newlines
+        } else {
+            newlines.saturating_add(1)
+        }
+    }
+
+    #[must_use]
+    pub fn ends_with_newline(&self) -> bool {
+        self.pieces
+            .last()
+            .is_some_and(|piece| self.piece_text(piece).ends_with('\n'))
+    }
+
+    /// Byte offset where line `line` starts. The line after the last line
+    /// break starts at the end of the text.
+    #[must_use]
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        if line == 0 {
+            return Some(0);
+        }
+        // We are looking for the `line`-th line break (1-based).
+        let mut remaining = line;
+        let mut offset = 0_usize;
+        for piece in &self.pieces {
+            if remaining <= piece.newlines {
+                let newline = self
+                    .store(piece.source)
+                    .nth_newline_from(piece.start, remaining.saturating_sub(1))?;
+                let within = newline.saturating_sub(piece.start);
+                return Some(offset.saturating_add(within).saturating_add(1));
+            }
+            remaining = remaining.saturating_sub(piece.newlines);
+            offset = offset.saturating_add(piece.len);
+        }
+        None
+    }
+
+    /// The byte range of line `line`, excluding its line break.
+    #[must_use]
+    pub fn line_range(&self, line: usize) -> Option<Range<usize>> {
+        if line >= self.line_count() {
+            return None;
+        }
+        let start = self.line_start(line)?;
+        let end = self
+            .line_start(line.saturating_add(1))
+            .map_or(self.len, |next| next.saturating_sub(1));
+        Some(start..end)
+    }
+
+    /// The text of line `line`, without its line break. Borrowed when the
+    /// line lies within a single piece, which is the common case.
+    #[must_use]
+    pub fn line(&self, line: usize) -> Option<Cow<'_, str>> {
+        self.line_range(line).map(|range| self.slice(range))
+    }
+
+    /// The text in the byte range `range`.
+    #[must_use]
+    pub fn slice(&self, range: Range<usize>) -> Cow<'_, str> {
+        let mut parts = Vec::new();
+        let mut offset = 0_usize;
+        for piece in &self.pieces {
+            let piece_end = offset.saturating_add(piece.len);
+            if piece_end > range.start && offset < range.end {
+                let from = range.start.saturating_sub(offset);
+                let to = range.end.min(piece_end).saturating_sub(offset);
+                parts.push(self.piece_text(piece).get(from..to).unwrap_or_default());
+            }
+            if piece_end >= range.end {
+                break;
+            }
+            offset = piece_end;
+        }
+        match parts.as_slice() {
+            [] => Cow::Borrowed(""),
+            [single] => Cow::Borrowed(single),
+            _ => Cow::Owned(parts.concat()),
+        }
+    }
+
+    /// The whole text.
+    #[must_use]
+    pub fn text(&self) -> String {
+        self.pieces
+            .iter()
+            .map(|piece| self.piece_text(piece))
+            .collect()
+    }
+
+    /// Inserts `text` at byte offset `at` (clamped to the end of the text).
+    pub fn insert(&mut self, at: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let at = at.min(self.len);
+        let start = self.added.push(text);
+        let piece = Piece {
+            source: Source::Added,
+            start,
+            len: text.len(),
+            newlines: self
+                .added
+                .newlines_in(start..start.saturating_add(text.len())),
+        };
+        let index = self.split_at(at);
+        self.pieces.insert(index, piece);
+        self.len = self.len.saturating_add(text.len());
+        self.merge_with_previous(index);
+    }
+
+    /// Removes the bytes in `range` (clamped to the text).
+    pub fn delete(&mut self, range: Range<usize>) {
+        let end = range.end.min(self.len);
+        let start = range.start.min(end);
+        if start == end {
+            return;
+        }
+        let first = self.split_at(start);
+        let last = self.split_at(end);
+        self.pieces.drain(first..last);
+        self.len = self.len.saturating_sub(end.saturating_sub(start));
+    }
+
+    /// Makes sure a piece boundary falls at byte offset `at` and returns the
+    /// index of the piece starting there.
+    fn split_at(&mut self, at: usize) -> usize {
+        let mut offset = 0_usize;
+        for index in 0..self.pieces.len() {
+            let Some(piece) = self.pieces.get(index).copied() else {
+                break;
+            };
+            if offset == at {
+                return index;
+            }
+            let piece_end = offset.saturating_add(piece.len);
+            if at < piece_end {
+                let store = self.store(piece.source);
+                let split = piece.start.saturating_add(at.saturating_sub(offset));
+                let left = Piece {
+                    len: split.saturating_sub(piece.start),
+                    newlines: store.newlines_in(piece.start..split),
+                    ..piece
+                };
+                let right = Piece {
+                    start: split,
+                    len: piece.end().saturating_sub(split),
+                    newlines: store.newlines_in(split..piece.end()),
+                    ..piece
+                };
+                self.pieces.splice(index..=index, [left, right]);
+                return index.saturating_add(1);
+            }
+            offset = piece_end;
+        }
+        self.pieces.len()
+    }
+
+    /// Typing appends to the added store, so consecutive keystrokes produce
+    /// adjacent spans; fold them into one piece to keep the list short.
+    fn merge_with_previous(&mut self, index: usize) {
+        let Some(previous_index) = index.checked_sub(1) else {
+            return;
+        };
+        let (Some(previous), Some(current)) = (
+            self.pieces.get(previous_index).copied(),
+            self.pieces.get(index).copied(),
+        ) else {
+            return;
+        };
+        if previous.source == current.source && previous.end() == current.start {
+            let merged = Piece {
+                len: previous.len.saturating_add(current.len),
+                newlines: previous.newlines.saturating_add(current.newlines),
+                ..previous
+            };
+            self.pieces.splice(previous_index..=index, [merged]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(table: &PieceTable) -> Vec<String> {
+        (0..table.line_count())
+            .filter_map(|line| table.line(line).map(Cow::into_owned))
+            .collect()
+    }
+
+    #[test]
+    fn test_lines_match_str_lines() {
+        for text in ["", "a", "a\n", "a\nb", "a\nb\n", "\n\n", "x\n\ny"] {
+            let table = PieceTable::new(text.to_string());
+            let expected: Vec<String> = text.lines().map(str::to_string).collect();
+            assert_eq!(lines(&table), expected, "for {text:?}");
+        }
+    }
+
+    #[test]
+    fn test_insert_in_the_middle() {
+        let mut table = PieceTable::new("hello world\nsecond\n".to_string());
+        table.insert(5, ",");
+        table.insert(0, ">> ");
+        table.insert(table.len(), "third");
+        assert_eq!(table.text(), ">> hello, world\nsecond\nthird");
+        assert_eq!(lines(&table), vec![">> hello, world", "second", "third"]);
+    }
+
+    #[test]
+    fn test_insert_line_breaks() {
+        let mut table = PieceTable::new("onetwo".to_string());
+        table.insert(3, "\n");
+        assert_eq!(lines(&table), vec!["one", "two"]);
+        assert_eq!(table.line_start(1), Some(4));
+    }
+
+    #[test]
+    fn test_delete_across_pieces() {
+        let mut table = PieceTable::new("abc\ndef\n".to_string());
+        table.insert(2, "XY");
+        // "abXYc\ndef\n" => delete "Yc\nd"
+        table.delete(3..7);
+        assert_eq!(table.text(), "abXef\n");
+        assert_eq!(table.line_count(), 1);
+    }
+
+    #[test]
+    fn test_delete_is_clamped() {
+        let mut table = PieceTable::new("abc".to_string());
+        table.delete(2..100);
+        assert_eq!(table.text(), "ab");
+        table.delete(5..6);
+        assert_eq!(table.text(), "ab");
+    }
+
+    #[test]
+    fn test_typing_merges_pieces() {
+        let mut table = PieceTable::new("ac".to_string());
+        table.insert(1, "b");
+        table.insert(2, "b");
+        table.insert(3, "b");
+        assert_eq!(table.text(), "abbbc");
+        // original "a", one merged added piece, original "c".
+        assert_eq!(table.pieces.len(), 3);
+    }
+
+    #[test]
+    fn test_line_spanning_pieces_is_owned() {
+        let mut table = PieceTable::new("one\ntwo\n".to_string());
+        table.insert(5, "X");
+        assert!(matches!(table.line(0), Some(Cow::Borrowed("one"))));
+        assert_eq!(table.line(1).as_deref(), Some("tXwo"));
+        assert!(matches!(table.line(1), Some(Cow::Owned(_))));
+    }
+
+    #[test]
+    fn test_ends_with_newline() {
+        let mut table = PieceTable::new("a".to_string());
+        assert!(!table.ends_with_newline());
+        table.insert(1, "\n");
+        assert!(table.ends_with_newline());
+        assert_eq!(table.line_count(), 1);
+    }
+}
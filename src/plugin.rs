@@ -0,0 +1,74 @@
+//! # Plugins
+//!
+//! Extends the editor without changing it: a [`Plugin`] adds commands
+//! (run from the command line or a script like the built-in ones) and
+//! hooks that run when a file is opened or saved. Plugins see the editor
+//! only through the [`Host`] API, which covers the buffer text, the cursor,
+//! messages and running other commands.
+//!
+//! Plugins are written in Rust, against the library, and added with
+//! [`Editor::add_plugin`](crate::Editor::add_plugin). This is only the
+//! groundwork for scripting: there is no Lua interpreter, and nothing
+//! loads an `init.lua`. An embedded one would be a single `Plugin` that
+//! forwards these calls to user code.
+
+use std::{fmt, path::PathBuf};
+
+use crate::notifications::Severity;
+
+/// When a hook runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// A file was opened.
+    Open,
+    /// The buffer is about to be saved; changes made now are saved too.
+    Save,
+}
+
+/// What the editor offers plugins. Lines and columns start at 0; columns
+/// count characters.
+pub trait Host {
+    /// The whole text of the buffer.
+    fn text(&self) -> String;
+    fn line_count(&self) -> usize;
+    fn line(&self, index: usize) -> Option<String>;
+    /// The file the buffer belongs to, if any.
+    fn file_name(&self) -> Option<PathBuf>;
+    /// The line and column of the cursor.
+    fn cursor(&self) -> (usize, usize);
+    /// Moves the cursor, scrolling it into view.
+    fn set_cursor(&mut self, line: usize, col: usize);
+    /// Inserts `text` at the cursor.
+    fn insert(&mut self, text: &str);
+    /// Replaces the whole text of the buffer.
+    fn set_text(&mut self, text: &str);
+    /// Shows a message in the status bar.
+    fn notify(&mut self, severity: Severity, text: &str);
+    /// Runs a command as if typed on the command line.
+    fn run_command(&mut self, line: &str);
+}
+
+pub trait Plugin: fmt::Debug {
+    /// The name used in messages about the plugin.
+    fn name(&self) -> &str;
+
+    /// The names of the commands the plugin adds.
+    fn commands(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Runs the command `name` with the rest of the command line as `args`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with a message for the status bar.
+    fn run_command(&mut self, name: &str, args: &str, host: &mut dyn Host) -> Result<(), String> {
+        let _ = (args, host);
+        Err(format!("{}: no command {name}", self.name()))
+    }
+
+    /// Called at each [`Hook`].
+    fn on_hook(&mut self, hook: Hook, host: &mut dyn Host) {
+        let _ = (hook, host);
+    }
+}
@@ -0,0 +1,129 @@
+//! # Cursor Positions
+//!
+//! Remembers where the cursor was in each file when it was last left, like
+//! the marks Vim keeps in its viminfo file, so that opening the file again
+//! puts the cursor back there. The positions are persisted as a plain
+//! text state file (`<line>\t<column>\t<path>` per line, most recently
+//! left first) under the XDG state directory, and only the most recent
+//! ones are kept. `set norestorecursor` turns it off.
+
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{dirs, error::Result, operator::Pos};
+
+/// Name of the state file inside [`dirs::state_dir()`].
+const FILE_NAME: &str = "positions";
+
+/// How many files we remember positions in before dropping the oldest.
+const MAX_ENTRIES: usize = 200;
+
+/// Where the cursor was left in each file, most recent first.
+#[derive(Debug, Default, Clone)]
+pub struct Positions {
+    entries: Vec<(PathBuf, Pos)>,
+    /// Where the positions are persisted. `None` means "memory only".
+    store: Option<PathBuf>,
+}
+
+impl Positions {
+    /// Loads the positions from the default state file, if one can be
+    /// located. A missing or unreadable file yields none.
+    #[must_use]
+    pub fn load_default() -> Self {
+        dirs::state_dir().map_or_else(Self::default, |dir| Self::load(dir.join(FILE_NAME)))
+    }
+
+    /// Loads the positions from `store`, remembering it as the save
+    /// location.
+    #[must_use]
+    pub fn load(store: PathBuf) -> Self {
+        let entries = fs::read_to_string(&store)
+            .map(|contents| contents.lines().filter_map(parse_entry).collect())
+            .unwrap_or_default();
+        Self {
+            entries,
+            store: Some(store),
+        }
+    }
+
+    /// Where the cursor was last left in `path`.
+    #[must_use]
+    pub fn get(&self, path: &Path) -> Option<Pos> {
+        let path = fs::canonicalize(path).ok()?;
+        self.entries
+            .iter()
+            .find(|(entry, _)| *entry == path)
+            .map(|&(_, pos)| pos)
+    }
+
+    /// Remembers that the cursor was left at `pos` in `path`, and saves
+    /// the positions if that changed them.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the state file cannot be written.
+    pub fn record(&mut self, path: &Path, pos: Pos) -> Result<()> {
+        let Ok(path) = fs::canonicalize(path) else {
+            return Ok(());
+        };
+        if self.entries.first() == Some(&(path.clone(), pos)) {
+            return Ok(());
+        }
+        self.entries.retain(|(entry, _)| *entry != path);
+        self.entries.insert(0, (path, pos));
+        self.entries.truncate(MAX_ENTRIES);
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        if let Some(parent) = store.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut contents = String::new();
+        for (path, (line, col)) in &self.entries {
+            let _ = writeln!(contents, "{line}\t{col}\t{}", path.display());
+        }
+        fs::write(store, contents).map_err(Into::into)
+    }
+}
+
+fn parse_entry(line: &str) -> Option<(PathBuf, Pos)> {
+    let mut fields = line.splitn(3, '\t');
+    let line = fields.next()?.parse().ok()?;
+    let col = fields.next()?.parse().ok()?;
+    Some((PathBuf::from(fields.next()?), (line, col)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn test_positions_survive_a_restart() {
+        let dir = env::temp_dir().join(format!("hecto-positions-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.txt");
+        fs::write(&file, "").unwrap();
+        let store = dir.join("state").join(FILE_NAME);
+
+        let mut positions = Positions::load(store.clone());
+        assert_eq!(positions.get(&file), None);
+        positions.record(&file, (3, 7)).unwrap();
+        positions.record(&file, (4, 1)).unwrap();
+        positions.record(&dir.join("missing.txt"), (1, 1)).unwrap();
+
+        let positions = Positions::load(store);
+        assert_eq!(positions.get(&file), Some((4, 1)));
+        assert_eq!(positions.entries.len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
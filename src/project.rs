@@ -0,0 +1,193 @@
+//! # Projects
+//!
+//! The project is the directory tree the editor was started in. Its root
+//! is the top of the repository the working directory is in (the nearest
+//! directory above with `.git` or `.hg` in it), or else the nearest with
+//! another [marker](MARKERS) in it, like `Cargo.toml`. The root is where
+//! `find-file` (`Ctrl+O`) lists files to open from and where
+//! `pgrep PATTERN` searches, wherever the working directory is below it.
+//!
+//! A `.hecto.toml` in the root is read after the user's
+//! [config file](crate::config), and wins over it for that project. It
+//! may set options, keys and abbreviations; it may not name commands to run, so opening
+//! a project someone else wrote cannot run anything: its `[formatters]`
+//! and `[linters]` sections and `makeprg` are ignored, and reported.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::config::Config;
+
+/// What the project's own config file is called.
+pub const CONFIG_FILE: &str = ".hecto.toml";
+
+/// What the top of a repository has in it.
+pub const REPOSITORIES: &[&str] = &[".git", ".hg"];
+
+/// What marks the root of a project outside a repository.
+pub const MARKERS: &[&str] = &[
+    CONFIG_FILE,
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "go.mod",
+];
+
+/// The directories that hold what a build made, or what it downloaded,
+/// rather than the project's own files.
+pub const SKIPPED: &[&str] = &["target", "node_modules", "__pycache__"];
+
+/// The most files [`files`] lists, so a huge tree cannot hang the editor.
+const MAX_FILES: usize = 20_000;
+
+/// The root of the project `dir` is in: the top of its repository, or
+/// else the nearest of it and the directories above it with a marker.
+#[must_use]
+pub fn root(dir: &Path) -> Option<PathBuf> {
+    let marked = |markers: &[&str]| {
+        dir.ancestors()
+            .find(|ancestor| markers.iter().any(|marker| ancestor.join(marker).exists()))
+            .map(Path::to_path_buf)
+    };
+    marked(REPOSITORIES).or_else(|| marked(MARKERS))
+}
+
+/// What the `.hecto.toml` in `root` configures, if there is one, and what
+/// is wrong with it, or left out because it would run a command.
+#[must_use]
+pub fn config(root: &Path) -> (Config, Vec<String>) {
+    let (mut config, problems) = Config::load(&root.join(CONFIG_FILE));
+    let mut problems: Vec<String> = problems
+        .iter()
+        .map(|problem| format!("{CONFIG_FILE}: {problem}"))
+        .collect();
+    for (section, commands) in [
+        ("formatters", &mut config.formatters),
+        ("linters", &mut config.linters),
+    ] {
+        if !commands.is_empty() {
+            commands.clear();
+            problems.push(format!("{CONFIG_FILE}: [{section}] is ignored"));
+        }
+    }
+    config.options.retain(|(name, _)| {
+        let runs = matches!(name.as_str(), "makeprg" | "mp");
+        if runs {
+            problems.push(format!("{CONFIG_FILE}: {name} is ignored"));
+        }
+        !runs
+    });
+    (config, problems)
+}
+
+/// `path` as seen from `dir`: relative to it if `path` is inside it or
+/// above it, as given otherwise. Both are absolute.
+#[must_use]
+pub fn relative(path: &Path, dir: &Path) -> PathBuf {
+    if let Ok(below) = path.strip_prefix(dir) {
+        return if below.as_os_str().is_empty() {
+            PathBuf::from(".")
+        } else {
+            below.to_path_buf()
+        };
+    }
+    match dir.strip_prefix(path) {
+        Ok(above) => above.components().map(|_| "..").collect(),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// The files in `root` and the directories below it, relative to it and
+/// sorted, leaving out hidden ones and [build output](SKIPPED).
+#[must_use]
+pub fn files(root: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(root.join(&dir)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') {
+                continue;
+            }
+            let path = dir.join(&*name);
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() && !SKIPPED.contains(&&*name) => pending.push(path),
+                Ok(kind) if kind.is_dir() => {}
+                Ok(_) if found.len() < MAX_FILES => found.push(path),
+                _ => {}
+            }
+        }
+    }
+    found.sort();
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_the_root_is_the_repository_or_the_nearest_marked_directory() {
+        let dir = std::env::temp_dir().join(format!("hecto-project-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/deep")).unwrap();
+        fs::create_dir_all(dir.join("target/debug")).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::write(dir.join("Cargo.toml"), "").unwrap();
+        fs::write(dir.join("src/main.rs"), "").unwrap();
+        fs::write(dir.join("src/deep/a.rs"), "").unwrap();
+        fs::write(dir.join("src/deep/go.mod"), "").unwrap();
+        fs::write(dir.join("target/debug/hecto"), "").unwrap();
+        fs::write(dir.join(".git/HEAD"), "").unwrap();
+
+        assert_eq!(root(&dir.join("src/deep")), Some(dir.clone()));
+        assert_eq!(
+            files(&dir),
+            [
+                PathBuf::from("Cargo.toml"),
+                PathBuf::from("src/deep/a.rs"),
+                PathBuf::from("src/deep/go.mod"),
+                PathBuf::from("src/main.rs"),
+            ]
+        );
+        fs::remove_dir_all(dir.join(".git")).unwrap();
+        assert_eq!(root(&dir.join("src/deep")), Some(dir.join("src/deep")));
+        assert_eq!(root(&dir.join("src")), Some(dir.clone()));
+
+        assert_eq!(relative(&dir, &dir.join("src/deep")), Path::new("../.."));
+        assert_eq!(relative(&dir.join("src"), &dir), Path::new("src"));
+        assert_eq!(relative(&dir, &dir), Path::new("."));
+        assert_eq!(
+            relative(Path::new("/elsewhere"), &dir),
+            Path::new("/elsewhere")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_the_project_config_cannot_run_commands() {
+        let dir = std::env::temp_dir().join(format!("hecto-project-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(CONFIG_FILE),
+            "[options]\ntabstop = 2\nmakeprg = \"rm -rf ~\"\n[linters]\nrust = \"evil\"\n",
+        )
+        .unwrap();
+        let (config, problems) = config(&dir);
+        assert_eq!(config.options, [("tabstop".to_string(), "2".to_string())]);
+        assert!(config.linters.is_empty());
+        assert_eq!(
+            problems,
+            [
+                ".hecto.toml: [linters] is ignored",
+                ".hecto.toml: makeprg is ignored"
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
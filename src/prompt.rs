@@ -0,0 +1,442 @@
+//! # Prompt
+//!
+//! A line of text typed at the bottom of the screen, behind a short label:
+//! what the [command line](crate::command_line) is made of, and anything
+//! else that asks for a line of input. It takes care of the editing, so
+//! the features built on it only decide what the line means:
+//!
+//! - `Left`, `Right`, `Home` and `End` move the cursor; `Backspace` and
+//!   `Delete` remove the character before or under it, `Ctrl+W` the word
+//!   before it and `Ctrl+U` everything before it.
+//! - `Up` and `Down` go back and forth through the lines entered before,
+//!   which each kind of prompt keeps in a [`History`] of its own, saved
+//!   between sessions.
+//! - `Tab` asks the owner to complete the word before the cursor; with
+//!   more than one way to, pressing it again (or `Shift+Tab`) goes through
+//!   them, listed in a popup above the prompt.
+//! - `Enter` accepts the line and `Esc` (or `Backspace` on an empty line,
+//!   as in Vim) cancels.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crossterm::event::{KeyCode, KeyModifiers};
+
+use crate::{dirs, error::Result, keymap::KeyPress, layout::LineLayout, screen::fit_to_width};
+
+/// How many lines a history keeps before dropping the oldest.
+const MAX_HISTORY: usize = 100;
+
+/// What a prompt wants after a key press.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptAction {
+    /// Still typing.
+    Pending,
+    Cancelled,
+    /// `Enter` was pressed on this text.
+    Accepted(String),
+    /// `Tab` was pressed: the owner should complete the text, if it can.
+    Complete,
+}
+
+/// The lines entered at a prompt, oldest first, without repeats in a row.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct History {
+    entries: Vec<String>,
+    /// Where the history is saved, one line per entry. `None` means
+    /// "memory only".
+    store: Option<PathBuf>,
+}
+
+impl History {
+    /// Loads the history of prompts of `kind` (such as `command`) from its
+    /// file under [`dirs::state_dir()`], if one can be located. A missing
+    /// or unreadable file yields an empty history.
+    #[must_use]
+    pub fn load_default(kind: &str) -> Self {
+        dirs::state_dir().map_or_else(Self::default, |dir| {
+            Self::load(dir.join(format!("{kind}_history")))
+        })
+    }
+
+    /// Loads the history from `store`, remembering it as the save location.
+    #[must_use]
+    pub fn load(store: PathBuf) -> Self {
+        let entries = fs::read_to_string(&store)
+            .map(|contents| contents.lines().map(ToString::to_string).collect())
+            .unwrap_or_default();
+        Self {
+            entries,
+            store: Some(store),
+        }
+    }
+
+    /// Adds `line` as the newest entry, unless it is empty or the same as
+    /// the newest already, and saves the history.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the history file cannot be written.
+    pub fn push(&mut self, line: &str) -> Result<()> {
+        if line.is_empty() || self.entries.last().is_some_and(|last| last == line) {
+            return Ok(());
+        }
+        self.entries.push(line.to_string());
+        let excess = self.entries.len().saturating_sub(MAX_HISTORY);
+        self.entries.drain(..excess);
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        if let Some(parent) = store.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut contents = self.entries.join("\n");
+        contents.push('\n');
+        fs::write(store, contents).map_err(Into::into)
+    }
+
+    /// The entries, oldest first.
+    #[must_use]
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Prompt {
+    /// Shown in front of the text, like `:`.
+    label: String,
+    text: String,
+    /// In characters from the start of the text.
+    cursor: usize,
+    history: History,
+    /// While going through the history: the entry shown, and what was
+    /// typed before the first `Up`.
+    browsing: Option<(usize, String)>,
+    completing: Option<Completing>,
+}
+
+/// The ways to complete a word that `Tab` goes through.
+#[derive(Debug, Clone)]
+struct Completing {
+    /// Where the word starts, in characters.
+    start: usize,
+    matches: Vec<String>,
+    /// The match shown.
+    index: usize,
+}
+
+impl Prompt {
+    /// An empty prompt behind `label`, going back through `history`.
+    #[must_use]
+    pub fn new(label: &str, history: History) -> Self {
+        Self {
+            label: label.to_string(),
+            history,
+            ..Self::default()
+        }
+    }
+
+    /// Handles a key typed into the prompt.
+    pub fn handle_key(&mut self, key: KeyPress) -> PromptAction {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if let Some(completing) = &mut self.completing {
+            let count = completing.matches.len();
+            let index = match key.code {
+                KeyCode::Tab => Some(completing.index.saturating_add(1)),
+                KeyCode::BackTab => Some(completing.index.saturating_add(count).saturating_sub(1)),
+                _ => None,
+            };
+            if let Some(index) = index.and_then(|index| index.checked_rem(count)) {
+                completing.index = index;
+                self.show_completion();
+                return PromptAction::Pending;
+            }
+        }
+        self.completing = None;
+        match key.code {
+            KeyCode::Enter => return PromptAction::Accepted(std::mem::take(&mut self.text)),
+            KeyCode::Esc => return PromptAction::Cancelled,
+            KeyCode::Backspace if self.text.is_empty() => return PromptAction::Cancelled,
+            KeyCode::Tab => return PromptAction::Complete,
+            KeyCode::Up => self.browse(true),
+            KeyCode::Down => self.browse(false),
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => {
+                self.cursor = self.cursor.saturating_add(1).min(self.text.chars().count());
+            }
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.chars().count(),
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.remove(self.cursor.saturating_sub(1), self.cursor);
+            }
+            KeyCode::Delete => self.remove(self.cursor, self.cursor.saturating_add(1)),
+            KeyCode::Char('u') if ctrl => self.remove(0, self.cursor),
+            KeyCode::Char('w') if ctrl => self.remove(self.word_start(), self.cursor),
+            _ => {
+                if let Some(ch) = key.text().filter(|ch| *ch != '\t') {
+                    let at = self.byte_index(self.cursor);
+                    self.text.insert(at, ch);
+                    self.cursor = self.cursor.saturating_add(1);
+                }
+            }
+        }
+        PromptAction::Pending
+    }
+
+    /// The text typed so far.
+    #[must_use]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replaces the text, with the cursor after it.
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = text.chars().count();
+    }
+
+    /// Completes the word that starts at character `start` and ends at the
+    /// cursor to the first of `matches`, and lets `Tab` go through the
+    /// rest. Returns `false`, changing nothing, if there are none.
+    pub fn complete(&mut self, start: usize, matches: Vec<String>) -> bool {
+        if matches.is_empty() {
+            return false;
+        }
+        self.completing = Some(Completing {
+            start,
+            matches,
+            index: 0,
+        });
+        self.show_completion();
+        true
+    }
+
+    /// The ways to complete the word being completed, and which is shown;
+    /// `None` unless there is more than one.
+    #[must_use]
+    pub fn completions(&self) -> Option<(&[String], usize)> {
+        self.completing
+            .as_ref()
+            .filter(|completing| completing.matches.len() > 1)
+            .map(|completing| (completing.matches.as_slice(), completing.index))
+    }
+
+    /// Whether the cursor is after the last character.
+    #[must_use]
+    pub fn at_end(&self) -> bool {
+        self.cursor == self.text.chars().count()
+    }
+
+    /// The row as shown on screen, at most `width` characters.
+    #[must_use]
+    pub fn render(&self, width: usize) -> String {
+        let mut row = format!("{}{}", self.label, self.text);
+        fit_to_width(&mut row, width);
+        row
+    }
+
+    /// The screen column of the cursor.
+    #[must_use]
+    pub fn cursor_column(&self) -> usize {
+        let before = self
+            .text
+            .get(..self.byte_index(self.cursor))
+            .unwrap_or_default();
+        LineLayout::new(&format!("{}{before}", self.label)).width()
+    }
+
+    /// Shows the entry before (`back`) or after the one shown; past the
+    /// newest, what was typed before going back.
+    fn browse(&mut self, back: bool) {
+        let entries = self.history.entries();
+        let (index, draft) = match self.browsing.take() {
+            Some(browsing) => browsing,
+            None => (entries.len(), self.text.clone()),
+        };
+        let index = if back {
+            index.saturating_sub(1)
+        } else {
+            index.saturating_add(1).min(entries.len())
+        };
+        let browsing = index < entries.len();
+        let text = entries
+            .get(index)
+            .map_or(draft.as_str(), String::as_str)
+            .to_string();
+        self.set_text(&text);
+        if browsing {
+            self.browsing = Some((index, draft));
+        }
+    }
+
+    /// Puts the match chosen in place of the word being completed.
+    fn show_completion(&mut self) {
+        let Some(completing) = &self.completing else {
+            return;
+        };
+        let start = self.byte_index(completing.start);
+        let word = completing
+            .matches
+            .get(completing.index)
+            .cloned()
+            .unwrap_or_default();
+        let before = self.text.get(..start).unwrap_or_default().to_string();
+        self.set_text(&format!("{before}{word}"));
+    }
+
+    /// Removes the characters from `start` up to `end`, and puts the
+    /// cursor where they were.
+    fn remove(&mut self, start: usize, end: usize) {
+        let range = self.byte_index(start)..self.byte_index(end);
+        self.text.replace_range(range, "");
+        self.cursor = start;
+    }
+
+    /// Where the word before the cursor starts, past any spaces after it.
+    fn word_start(&self) -> usize {
+        let before: Vec<char> = self.text.chars().take(self.cursor).collect();
+        let spaces = before
+            .iter()
+            .rev()
+            .take_while(|ch| ch.is_whitespace())
+            .count();
+        let word = before
+            .iter()
+            .rev()
+            .skip(spaces)
+            .take_while(|ch| !ch.is_whitespace())
+            .count();
+        self.cursor.saturating_sub(spaces).saturating_sub(word)
+    }
+
+    /// The byte offset of character `index`, or the text length past it.
+    fn byte_index(&self, index: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(index)
+            .map_or(self.text.len(), |(at, _)| at)
+    }
+}
+
+/// The paths that complete `prefix`, sorted: files and directories in the
+/// directory it names (the current one if none) whose names start with
+/// what follows. Directories end in `/`, to go on completing inside. Hidden
+/// files only fit a prefix that starts with a dot.
+#[must_use]
+pub fn complete_path(prefix: &str) -> Vec<String> {
+    let (dir, stem) = prefix.rfind('/').map_or(("", prefix), |slash| {
+        prefix.split_at(slash.saturating_add(1))
+    });
+    let Ok(entries) = fs::read_dir(if dir.is_empty() {
+        Path::new(".")
+    } else {
+        Path::new(dir)
+    }) else {
+        return Vec::new();
+    };
+    let mut matches: Vec<String> = entries
+        .filter_map(std::result::Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let fits = name.starts_with(stem) && (stem.starts_with('.') || !name.starts_with('.'));
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            fits.then(|| format!("{dir}{name}{slash}"))
+        })
+        .collect();
+    matches.sort_unstable();
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(prompt: &mut Prompt, code: KeyCode) -> PromptAction {
+        prompt.handle_key(KeyPress::new(code, KeyModifiers::NONE))
+    }
+
+    fn type_text(prompt: &mut Prompt, text: &str) {
+        for ch in text.chars() {
+            press(prompt, KeyCode::Char(ch));
+        }
+    }
+
+    #[test]
+    fn test_editing_in_the_middle() {
+        let mut prompt = Prompt::new("/", History::default());
+        type_text(&mut prompt, "héllo wrld");
+        for _ in 0..3 {
+            press(&mut prompt, KeyCode::Left);
+        }
+        type_text(&mut prompt, "o");
+        assert_eq!(prompt.render(80), "/héllo world");
+        assert_eq!(prompt.cursor_column(), 9);
+        press(&mut prompt, KeyCode::Home);
+        press(&mut prompt, KeyCode::Delete);
+        press(&mut prompt, KeyCode::End);
+        press(&mut prompt, KeyCode::Backspace);
+        assert_eq!(prompt.text(), "éllo worl");
+
+        prompt.handle_key(KeyPress::new(KeyCode::Char('w'), KeyModifiers::CONTROL));
+        assert_eq!(prompt.text(), "éllo ");
+        press(&mut prompt, KeyCode::Left);
+        prompt.handle_key(KeyPress::new(KeyCode::Char('u'), KeyModifiers::CONTROL));
+        assert_eq!(prompt.text(), " ");
+        assert_eq!(
+            press(&mut prompt, KeyCode::Enter),
+            PromptAction::Accepted(" ".to_string())
+        );
+    }
+
+    #[test]
+    fn test_going_through_the_history() {
+        let store = std::env::temp_dir().join(format!("hecto-history-{}", std::process::id()));
+        let mut history = History::load(store.clone());
+        for line in ["one", "two", "two", ""] {
+            history.push(line).unwrap();
+        }
+        assert_eq!(history.entries(), ["one", "two"]);
+        let history = History::load(store.clone());
+        assert_eq!(history.entries(), ["one", "two"]);
+        fs::remove_file(store).unwrap();
+
+        let mut prompt = Prompt::new(":", history);
+        type_text(&mut prompt, "dra");
+        press(&mut prompt, KeyCode::Up);
+        assert_eq!(prompt.text(), "two");
+        press(&mut prompt, KeyCode::Up);
+        press(&mut prompt, KeyCode::Up);
+        assert_eq!(prompt.text(), "one");
+        press(&mut prompt, KeyCode::Down);
+        assert_eq!(prompt.text(), "two");
+        press(&mut prompt, KeyCode::Down);
+        assert_eq!(prompt.text(), "dra");
+        assert!(prompt.at_end());
+    }
+
+    #[test]
+    fn test_tab_goes_through_the_completions() {
+        let mut prompt = Prompt::new(":", History::default());
+        type_text(&mut prompt, "e sr");
+        assert_eq!(press(&mut prompt, KeyCode::Tab), PromptAction::Complete);
+        let matches = vec!["src/".to_string(), "srv/".to_string()];
+        assert!(prompt.complete(2, matches));
+        assert_eq!(prompt.text(), "e src/");
+        assert_eq!(prompt.completions().map(|(_, index)| index), Some(0));
+        press(&mut prompt, KeyCode::Tab);
+        assert_eq!(prompt.text(), "e srv/");
+        press(&mut prompt, KeyCode::BackTab);
+        press(&mut prompt, KeyCode::BackTab);
+        assert_eq!(prompt.text(), "e srv/");
+        type_text(&mut prompt, "x");
+        assert_eq!(prompt.completions(), None);
+        assert!(!prompt.complete(0, Vec::new()));
+
+        assert_eq!(complete_path("src/prom"), ["src/prompt.rs"]);
+        assert!(complete_path("").contains(&"src/".to_string()));
+        assert!(!complete_path("").iter().any(|path| path.starts_with('.')));
+    }
+}
@@ -0,0 +1,237 @@
+//! # Quickfix List
+//!
+//! A list of places in files, each with a message: what `grep` found, the
+//! errors a build reported, a linter's complaints. Filling it opens a pane
+//! at the bottom of the screen that lists them; `F8` (`]q` in normal mode)
+//! jumps to the next and `Shift+F8` (`[q`) to the previous, opening the
+//! file if need be. As in Vim, `copen` and `cclose` show and hide the
+//! pane, and `cnext`, `cprevious` and `cc N` move through the list.
+//!
+//! Anything that prints places the way compilers and `grep -n` do,
+//! `file:line:column: message` or `file:line: message`, can fill it, and so
+//! can rustc, which puts the place on a line of its own under the message;
+//! see [`parse`]. `make` runs the build command (the `makeprg` option) and
+//! jumps to the first error it reports; `todo` lists the `todowords` in
+//! comments.
+
+use std::path::PathBuf;
+
+/// A place in a file, and what was said about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub path: PathBuf,
+    /// From 0, like the buffer's.
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+}
+
+impl Entry {
+    /// The entry as it is listed, with lines and columns from 1.
+    #[must_use]
+    pub fn label(&self) -> String {
+        format!(
+            "{}:{}:{}: {}",
+            self.path.display(),
+            self.line.saturating_add(1),
+            self.col.saturating_add(1),
+            self.message
+        )
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct QuickfixList {
+    /// What filled the list, like `grep TODO`.
+    title: String,
+    entries: Vec<Entry>,
+    /// The entry jumped to last.
+    current: Option<usize>,
+    /// Whether the pane listing it is shown.
+    open: bool,
+}
+
+impl QuickfixList {
+    /// A list of `entries` from `title`, none of them jumped to yet.
+    #[must_use]
+    pub fn new(title: &str, entries: Vec<Entry>) -> Self {
+        Self {
+            title: title.to_string(),
+            entries,
+            current: None,
+            open: false,
+        }
+    }
+
+    #[must_use]
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    #[must_use]
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[must_use]
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn set_open(&mut self, open: bool) {
+        self.open = open;
+    }
+
+    /// The index of the entry jumped to last.
+    #[must_use]
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Makes entry `index` the current one, and returns it; `None` if
+    /// there is no such entry.
+    pub fn select(&mut self, index: usize) -> Option<&Entry> {
+        let entry = self.entries.get(index)?;
+        self.current = Some(index);
+        Some(entry)
+    }
+
+    /// Moves on to the entry after the current one (the first, if none
+    /// was jumped to yet). `None` past the last.
+    pub fn select_next(&mut self) -> Option<&Entry> {
+        let index = self.current.map_or(0, |current| current.saturating_add(1));
+        self.select(index)
+    }
+
+    /// Moves back to the entry before the current one. `None` before the
+    /// first.
+    pub fn select_previous(&mut self) -> Option<&Entry> {
+        let index = self.current?.checked_sub(1)?;
+        self.select(index)
+    }
+}
+
+/// The places in `output` (a compiler's or `grep -n`'s), one per line that
+/// starts with one, in order; other lines are left out. rustc's
+/// `--> file:line:column` lines take the message from the `error: ...` or
+/// `warning: ...` line above them.
+#[must_use]
+pub fn parse(output: &str) -> Vec<Entry> {
+    let mut heading = "";
+    let mut entries = Vec::new();
+    for line in output.lines() {
+        if let Some(place) = line.trim_start().strip_prefix("--> ") {
+            entries.extend(parse_line(&format!("{}: {heading}", place.trim_end())));
+        } else if is_heading(line) {
+            heading = line.trim_end();
+        } else {
+            entries.extend(parse_line(line));
+        }
+    }
+    entries
+}
+
+/// Whether `line` starts one of rustc's diagnostics, like
+/// `error[E0425]: cannot find value`.
+fn is_heading(line: &str) -> bool {
+    ["error", "warning"].iter().any(|kind| {
+        line.strip_prefix(kind)
+            .is_some_and(|rest| rest.starts_with(':') || rest.starts_with('['))
+    })
+}
+
+/// The first entry in `entries` that is an error, or else the first.
+#[must_use]
+pub fn first_error(entries: &[Entry]) -> Option<usize> {
+    let is_error = |entry: &Entry| entry.message.starts_with("error");
+    entries
+        .iter()
+        .position(is_error)
+        .or_else(|| (!entries.is_empty()).then_some(0))
+}
+
+/// The place `line` starts with: `file:line:column: message` or
+/// `file:line: message`. The file name may itself hold colons (`C:\`), so
+/// each is tried until one is followed by a line number.
+fn parse_line(line: &str) -> Option<Entry> {
+    line.match_indices(':').find_map(|(at, _)| {
+        let (path, rest) = (line.get(..at)?, line.get(at.saturating_add(1)..)?);
+        let (number, rest) = rest.split_once(':')?;
+        let number = number.parse::<usize>().ok()?;
+        if path.trim().is_empty() {
+            return None;
+        }
+        let (col, message) = match rest.split_once(':') {
+            Some((col, message)) if col.parse::<usize>().is_ok() => {
+                (col.parse::<usize>().unwrap_or_default(), message)
+            }
+            _ => (1, rest),
+        };
+        Some(Entry {
+            path: PathBuf::from(path.strip_prefix("./").unwrap_or(path)),
+            line: number.saturating_sub(1),
+            col: col.saturating_sub(1),
+            message: message.trim().to_string(),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_compiler_and_grep_output() {
+        let output = "src/main.rs:3:5: error: expected `;`\n\
+                      note: something else\n\
+                      C:\\src\\lib.c:10: warning: unused\n\
+                      ./notes.txt:7:TODO: write this\n";
+        let entries = parse(output);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].label(), "src/main.rs:3:5: error: expected `;`");
+        assert_eq!(entries[1].path, PathBuf::from("C:\\src\\lib.c"));
+        assert_eq!((entries[1].line, entries[1].col), (9, 0));
+        assert_eq!(entries[2].label(), "notes.txt:7:1: TODO: write this");
+    }
+
+    #[test]
+    fn test_parse_rustc_output() {
+        let output = "   Compiling hecto v0.1.0\n\
+                      warning: unused variable: `x`\n \
+                      --> src/main.rs:2:9\n  \
+                      |\n\
+                      error[E0425]: cannot find value `y` in this scope\n  \
+                      --> src/lib.rs:10:5\n\
+                      error: could not compile `hecto`\n";
+        let entries = parse(output);
+        assert_eq!(
+            entries[0].label(),
+            "src/main.rs:2:9: warning: unused variable: `x`"
+        );
+        assert_eq!(
+            entries[1].label(),
+            "src/lib.rs:10:5: error[E0425]: cannot find value `y` in this scope"
+        );
+        assert_eq!(entries.len(), 2);
+        assert_eq!(first_error(&entries), Some(1));
+        assert_eq!(first_error(&entries[..1]), Some(0));
+        assert_eq!(first_error(&[]), None);
+    }
+
+    #[test]
+    fn test_moving_through_the_list() {
+        let mut list = QuickfixList::new("grep x", parse("a:1: one\nb:2: two\n"));
+        assert_eq!(list.select_previous(), None);
+        assert_eq!(list.select_next().map(|entry| entry.line), Some(0));
+        assert_eq!(list.select_next().map(|entry| entry.line), Some(1));
+        assert_eq!(list.select_next(), None);
+        assert_eq!(list.current(), Some(1));
+        assert_eq!(list.select_previous().map(|entry| entry.line), Some(0));
+        assert_eq!(list.select(5), None);
+    }
+}
@@ -0,0 +1,209 @@
+//! # Recent Files
+//!
+//! Keeps a small, most-recent-first list of files the user has opened, along
+//! with the time each one was last opened. The list is persisted as a plain
+//! text state file (`<timestamp>\t<path>` per line) under the XDG data
+//! directory so it survives restarts.
+//!
+//! The editor feeds it into the welcome screen and into the recent-files
+//! picker (`Ctrl+R`).
+
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{dirs, error::Result};
+
+/// Name of the state file inside [`dirs::data_dir()`].
+const FILE_NAME: &str = "recent_files";
+
+/// How many entries we remember before dropping the oldest ones.
+const MAX_ENTRIES: usize = 20;
+
+/// A single remembered file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecentFile {
+    pub path: PathBuf,
+    /// Seconds since the Unix epoch when the file was last opened.
+    pub opened_at: u64,
+}
+
+/// The list of recently opened files, most recent first.
+#[derive(Debug, Default, Clone)]
+pub struct RecentFiles {
+    entries: Vec<RecentFile>,
+    /// Where the list is persisted. `None` means "memory only".
+    store: Option<PathBuf>,
+}
+
+impl RecentFiles {
+    /// Loads the list from the default state file, if one can be located.
+    ///
+    /// A missing or unreadable state file yields an empty list: losing the
+    /// history is never worth refusing to start.
+    #[must_use]
+    pub fn load_default() -> Self {
+        dirs::data_dir().map_or_else(Self::default, |dir| Self::load(dir.join(FILE_NAME)))
+    }
+
+    /// Loads the list from `store`, remembering it as the save location.
+    #[must_use]
+    pub fn load(store: PathBuf) -> Self {
+        let entries = fs::read_to_string(&store)
+            .map(|contents| contents.lines().filter_map(parse_entry).collect())
+            .unwrap_or_default();
+        Self {
+            entries,
+            store: Some(store),
+        }
+    }
+
+    /// Moves (or inserts) `path` to the top of the list, stamped with the
+    /// current time, and persists the list.
+    pub fn record(&mut self, path: &Path) -> Result<()> {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.entries.retain(|entry| entry.path != path);
+        self.entries.insert(
+            0,
+            RecentFile {
+                path,
+                opened_at: now(),
+            },
+        );
+        self.entries.truncate(MAX_ENTRIES);
+        self.save()
+    }
+
+    /// Writes the list to its state file, creating the directory if needed.
+    pub fn save(&self) -> Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        if let Some(parent) = store.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut contents = String::new();
+        for entry in &self.entries {
+            let _ = writeln!(contents, "{}\t{}", entry.opened_at, entry.path.display());
+        }
+        fs::write(store, contents).map_err(Into::into)
+    }
+
+    #[must_use]
+    pub fn entries(&self) -> &[RecentFile] {
+        &self.entries
+    }
+}
+
+impl RecentFile {
+    /// A one-line description such as `src/main.rs (3h ago)`.
+    #[must_use]
+    pub fn label(&self) -> String {
+        let age = now().saturating_sub(self.opened_at);
+        format!("{} ({})", self.path.display(), format_age(age))
+    }
+}
+
+fn parse_entry(line: &str) -> Option<RecentFile> {
+    let (opened_at, path) = line.split_once('\t')?;
+    Some(RecentFile {
+        path: PathBuf::from(path),
+        opened_at: opened_at.parse().ok()?,
+    })
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Formats a number of seconds as a coarse, human-friendly age.
+fn format_age(secs: u64) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+
+    match secs {
+        0..MINUTE => "just now".to_string(),
+        MINUTE..HOUR => format!("{}m ago", secs.saturating_div(MINUTE)),
+        HOUR..DAY => format!("{}h ago", secs.saturating_div(HOUR)),
+        _ => format!("{}d ago", secs.saturating_div(DAY)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn temp_store(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("hecto-recent-{name}-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_record_moves_entry_to_front() {
+        let store = temp_store("front");
+        let mut recent = RecentFiles::load(store.clone());
+
+        recent.record(Path::new("/tmp/a.txt")).unwrap();
+        recent.record(Path::new("/tmp/b.txt")).unwrap();
+        recent.record(Path::new("/tmp/a.txt")).unwrap();
+
+        let paths: Vec<_> = recent.entries().iter().map(|e| e.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("/tmp/a.txt"), PathBuf::from("/tmp/b.txt")]
+        );
+        let _ = fs::remove_file(store);
+    }
+
+    #[test]
+    fn test_round_trip_through_state_file() {
+        let store = temp_store("round-trip");
+        let mut recent = RecentFiles::load(store.clone());
+        recent.record(Path::new("/tmp/does-not-exist.rs")).unwrap();
+
+        let reloaded = RecentFiles::load(store.clone());
+        assert_eq!(reloaded.entries(), recent.entries());
+        let _ = fs::remove_file(store);
+    }
+
+    #[test]
+    fn test_parse_entry_rejects_garbage() {
+        assert!(parse_entry("not a timestamp\t/tmp/x").is_none());
+        assert!(parse_entry("no tab here").is_none());
+        assert_eq!(
+            parse_entry("42\t/tmp/with\ttab"),
+            Some(RecentFile {
+                path: PathBuf::from("/tmp/with\ttab"),
+                opened_at: 42
+            })
+        );
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(5), "just now");
+        assert_eq!(format_age(120), "2m ago");
+        assert_eq!(format_age(7200), "2h ago");
+        assert_eq!(format_age(3 * 86_400), "3d ago");
+    }
+
+    #[test]
+    fn test_truncates_to_max_entries() {
+        let store = temp_store("max");
+        let mut recent = RecentFiles::load(store.clone());
+        for i in 0..30 {
+            recent.record(Path::new(&format!("/tmp/f{i}"))).unwrap();
+        }
+        assert_eq!(recent.entries().len(), MAX_ENTRIES);
+        assert_eq!(recent.entries()[0].path, PathBuf::from("/tmp/f29"));
+        let _ = fs::remove_file(store);
+    }
+}
@@ -0,0 +1,167 @@
+//! # Registers
+//!
+//! Where yanked and deleted text is kept for pasting, as in Vim. A register
+//! is chosen by typing `"` and its name before the operator or paste
+//! (`"ayy`, `"ap`); without one, the unnamed register is used.
+//!
+//! | Name      | Holds                                                   |
+//! |-----------|---------------------------------------------------------|
+//! | `"`       | the last text yanked or deleted, in any register        |
+//! | `0`       | the last text yanked without naming a register          |
+//! | `1`–`9`   | the last nine deletes, newest first                     |
+//! | `a`–`z`   | whatever was put there; `A`–`Z` add to the end instead  |
+//! | `+`, `*`  | the [system clipboard](crate::clipboard)                |
+//!
+//! `:registers` lists them all.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::operator::Yank;
+
+/// How many deletes the numbered registers keep.
+const NUMBERED: usize = 9;
+
+#[derive(Debug, Default, Clone)]
+pub struct Registers {
+    unnamed: Option<Yank>,
+    yanked: Option<Yank>,
+    /// The numbered registers, `1` first.
+    deleted: VecDeque<Yank>,
+    named: BTreeMap<char, Yank>,
+}
+
+impl Registers {
+    /// Whether `name` is a register that can be chosen with `"`.
+    #[must_use]
+    pub fn is_valid(name: char) -> bool {
+        name == '"' || is_clipboard(name) || name.is_ascii_digit() || name.is_ascii_alphabetic()
+    }
+
+    /// Keeps what a yank (or, if `deleted`, a delete) took, in `register`
+    /// or where it goes by default.
+    pub fn store(&mut self, register: Option<char>, yank: Yank, deleted: bool) {
+        match register.filter(|name| *name != '"') {
+            Some(name) if name.is_ascii_uppercase() => {
+                let name = name.to_ascii_lowercase();
+                let yank = match self.named.remove(&name) {
+                    Some(old) => append(old, &yank),
+                    None => yank,
+                };
+                self.named.insert(name, yank);
+            }
+            Some(name) if name.is_ascii_lowercase() => {
+                self.named.insert(name, yank);
+            }
+            // A digit names where text would go anyway.
+            _ if deleted => {
+                self.deleted.push_front(yank);
+                self.deleted.truncate(NUMBERED);
+            }
+            _ => self.yanked = Some(yank),
+        }
+        self.unnamed = match register.filter(char::is_ascii_alphabetic) {
+            Some(name) => self.named.get(&name.to_ascii_lowercase()).cloned(),
+            None if deleted => self.deleted.front().cloned(),
+            None => self.yanked.clone(),
+        };
+    }
+
+    /// What `register` (or the unnamed one) holds.
+    #[must_use]
+    pub fn get(&self, register: Option<char>) -> Option<&Yank> {
+        match register.unwrap_or('"') {
+            '"' => self.unnamed.as_ref(),
+            '0' => self.yanked.as_ref(),
+            digit @ '1'..='9' => {
+                let index = digit.to_digit(10).and_then(|n| usize::try_from(n).ok())?;
+                self.deleted.get(index.saturating_sub(1))
+            }
+            name => self.named.get(&name.to_ascii_lowercase()),
+        }
+    }
+
+    /// Each register that holds something, with its name, in the order of
+    /// the table above.
+    #[must_use]
+    pub fn list(&self) -> Vec<(char, &Yank)> {
+        let numbered = ('1'..='9').zip(&self.deleted);
+        [('"', &self.unnamed), ('0', &self.yanked)]
+            .into_iter()
+            .filter_map(|(name, yank)| Some((name, yank.as_ref()?)))
+            .chain(numbered)
+            .chain(self.named.iter().map(|(name, yank)| (*name, yank)))
+            .collect()
+    }
+}
+
+/// Whether `name` is `+` or `*`, the system clipboard, which the editor
+/// reads and writes itself.
+#[must_use]
+pub fn is_clipboard(name: char) -> bool {
+    matches!(name, '+' | '*')
+}
+
+/// `yank` added to the end of `old`; lines stay lines.
+fn append(old: Yank, yank: &Yank) -> Yank {
+    let mut text = old.text;
+    if yank.linewise && !old.linewise && !text.is_empty() {
+        text.push('\n');
+    }
+    text.push_str(&yank.text);
+    Yank {
+        text,
+        linewise: old.linewise || yank.linewise,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yank(text: &str) -> Yank {
+        Yank {
+            text: text.to_string(),
+            linewise: false,
+        }
+    }
+
+    #[test]
+    fn test_deletes_shift_through_the_numbered_registers() {
+        let mut registers = Registers::default();
+        registers.store(None, yank("kept"), false);
+        for text in ["one", "two", "three"] {
+            registers.store(None, yank(text), true);
+        }
+        assert_eq!(registers.get(None), Some(&yank("three")));
+        assert_eq!(registers.get(Some('0')), Some(&yank("kept")));
+        assert_eq!(registers.get(Some('1')), Some(&yank("three")));
+        assert_eq!(registers.get(Some('3')), Some(&yank("one")));
+        assert_eq!(registers.get(Some('4')), None);
+
+        for _ in 0..NUMBERED {
+            registers.store(None, yank("more"), true);
+        }
+        assert_eq!(registers.get(Some('9')), Some(&yank("more")));
+        assert_eq!(registers.list().len(), 11);
+    }
+
+    #[test]
+    fn test_named_registers_replace_or_append() {
+        let mut registers = Registers::default();
+        registers.store(Some('a'), yank("x"), true);
+        registers.store(Some('A'), yank("y"), false);
+        assert_eq!(registers.get(Some('a')), Some(&yank("xy")));
+        assert_eq!(registers.get(None), Some(&yank("xy")));
+        // Naming a register keeps the default ones as they were.
+        assert_eq!(registers.get(Some('1')), None);
+
+        let line = Yank {
+            text: "line\n".to_string(),
+            linewise: true,
+        };
+        registers.store(Some('A'), line, false);
+        assert_eq!(registers.get(Some('A')).unwrap().text, "xy\nline\n");
+        assert!(Registers::is_valid('"') && Registers::is_valid('7'));
+        assert!(!Registers::is_valid('%'));
+    }
+}
@@ -0,0 +1,101 @@
+//! # Render Scheduling
+//!
+//! Decides when the main loop redraws. Anything that changes what is on
+//! screen (a key press, text arriving from a loader, a posted notification)
+//! only marks the screen dirty; the redraw itself happens once the frame
+//! budget since the previous one has passed. Nothing changed means nothing
+//! is drawn, and a burst of changes costs at most one frame per budget.
+
+use std::time::{Duration, Instant};
+
+/// The shortest time between two frames: about 60 per second.
+pub const FRAME_BUDGET: Duration = Duration::from_millis(16);
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderScheduler {
+    budget: Duration,
+    /// Whether something changed since the last frame. Starts out set, so
+    /// the first frame is drawn.
+    dirty: bool,
+    last_frame: Option<Instant>,
+}
+
+impl Default for RenderScheduler {
+    fn default() -> Self {
+        Self::new(FRAME_BUDGET)
+    }
+}
+
+impl RenderScheduler {
+    #[must_use]
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            dirty: true,
+            last_frame: None,
+        }
+    }
+
+    /// Asks for a redraw.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// How long until the next frame may be drawn: `None` if nothing needs
+    /// drawing, zero if it is due now.
+    #[must_use]
+    pub fn wait(&self, now: Instant) -> Option<Duration> {
+        if !self.dirty {
+            return None;
+        }
+        Some(self.last_frame.map_or(Duration::ZERO, |last| {
+            self.budget
+                .saturating_sub(now.saturating_duration_since(last))
+        }))
+    }
+
+    /// Whether a frame should be drawn now.
+    #[must_use]
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.wait(now) == Some(Duration::ZERO)
+    }
+
+    /// Records that a frame was drawn at `now`.
+    pub fn rendered(&mut self, now: Instant) {
+        self.dirty = false;
+        self.last_frame = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_frame_is_due_at_once() {
+        let scheduler = RenderScheduler::default();
+        assert!(scheduler.is_due(Instant::now()));
+    }
+
+    #[test]
+    fn test_clean_screen_is_not_redrawn() {
+        let mut scheduler = RenderScheduler::default();
+        let start = Instant::now();
+        scheduler.rendered(start);
+        assert_eq!(scheduler.wait(start + Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn test_changes_wait_for_the_budget() {
+        let mut scheduler = RenderScheduler::new(Duration::from_millis(16));
+        let start = Instant::now();
+        scheduler.rendered(start);
+        scheduler.mark_dirty();
+        assert_eq!(
+            scheduler.wait(start + Duration::from_millis(10)),
+            Some(Duration::from_millis(6))
+        );
+        assert!(!scheduler.is_due(start + Duration::from_millis(10)));
+        assert!(scheduler.is_due(start + Duration::from_millis(16)));
+    }
+}
@@ -0,0 +1,278 @@
+//! # Screen
+//!
+//! Remembers what is currently shown on the terminal so a refresh only
+//! redraws the rows whose content actually changed. Moving the cursor over
+//! a line and leaving it alone is far cheaper than clearing and reprinting
+//! it, which matters on slow or SSH-attached terminals.
+//!
+//! Each refresh is encoded into a single in-memory buffer (hide cursor,
+//! changed rows, cursor placement, show cursor) which is then written and
+//! flushed at once, so the terminal never shows a half-drawn frame. On
+//! terminals that support it, the frame is additionally wrapped in
+//! synchronized-update sequences (DEC mode 2026) so the terminal itself
+//! defers painting until the frame is complete.
+
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    style::{ContentStyle, Print, PrintStyledContent, StyledContent},
+    terminal::{BeginSynchronizedUpdate, Clear, ClearType, EndSynchronizedUpdate},
+};
+
+use crate::{
+    capabilities::{Capabilities, ColorSupport},
+    error::Result,
+    frame::{Cell, Frame, Row, CONTINUATION},
+    terminal::{Position, Terminal},
+};
+
+#[derive(Debug, Default, Clone)]
+pub struct Screen {
+    /// The rows as they were last drawn. Empty means "unknown", which forces
+    /// every row to be drawn on the next call to [`Screen::draw`].
+    rows: Vec<Row>,
+    /// Whether frames are wrapped in synchronized-update sequences.
+    synchronized: bool,
+    /// Colors are converted down to what the terminal can show.
+    colors: ColorSupport,
+}
+
+impl Screen {
+    /// Creates a screen using whatever optional features the terminal has.
+    #[must_use]
+    pub fn new(capabilities: Capabilities) -> Self {
+        Self {
+            rows: Vec::new(),
+            synchronized: capabilities.synchronized_output,
+            colors: capabilities.colors,
+        }
+    }
+
+    /// Draws `frame`, skipping rows that are identical to what was drawn last
+    /// time, and leaves the cursor at `cursor`. Flushes exactly once.
+    pub fn draw(&mut self, terminal: &mut Terminal, frame: &Frame, cursor: Position) -> Result<()> {
+        let mut bytes = Vec::new();
+        if self.synchronized {
+            crossterm::queue!(bytes, BeginSynchronizedUpdate)?;
+        }
+        crossterm::queue!(bytes, Hide)?;
+        for (index, row) in frame.rows().iter().enumerate() {
+            if self.rows.get(index) == Some(row) {
+                continue;
+            }
+            let row_u16: u16 = index.try_into()?;
+            crossterm::queue!(bytes, MoveTo(0, row_u16), Clear(ClearType::CurrentLine))?;
+            encode_row(&mut bytes, row, self.colors)?;
+        }
+        let col_u16: u16 = cursor.col.try_into()?;
+        let row_u16: u16 = cursor.row.try_into()?;
+        crossterm::queue!(bytes, MoveTo(col_u16, row_u16), Show)?;
+        if self.synchronized {
+            crossterm::queue!(bytes, EndSynchronizedUpdate)?;
+        }
+        terminal.write(&bytes)?;
+        terminal.execute()?;
+
+        self.rows = frame.rows().to_vec();
+        Ok(())
+    }
+
+    /// Forgets what is on screen, so the next draw repaints every row. Call
+    /// this whenever something else has written to the terminal.
+    pub fn invalidate(&mut self) {
+        self.rows.clear();
+    }
+}
+
+/// Appends the commands printing `row` to `bytes`, one command per run of
+/// equally styled cells. Trailing unstyled blanks are left to the preceding
+/// line clear. Colors are adapted to `colors` on the way out.
+fn encode_row(bytes: &mut Vec<u8>, row: &[Cell], colors: ColorSupport) -> Result<()> {
+    let visible = row
+        .iter()
+        .rposition(|cell| *cell != Cell::default())
+        .map_or(0, |last| last.saturating_add(1));
+
+    let mut run = String::new();
+    let mut run_style = ContentStyle::default();
+    for cell in row.iter().take(visible) {
+        if cell.style != run_style && !run.is_empty() {
+            encode_run(bytes, &run, colors.adapt_style(run_style))?;
+            run.clear();
+        }
+        run_style = cell.style;
+        if cell.symbol != CONTINUATION {
+            run.push(cell.symbol);
+        }
+    }
+    if !run.is_empty() {
+        encode_run(bytes, &run, colors.adapt_style(run_style))?;
+    }
+    Ok(())
+}
+
+fn encode_run(bytes: &mut Vec<u8>, text: &str, style: ContentStyle) -> Result<()> {
+    if style == ContentStyle::default() {
+        crossterm::queue!(bytes, Print(text))?;
+    } else {
+        crossterm::queue!(bytes, PrintStyledContent(StyledContent::new(style, text)))?;
+    }
+    Ok(())
+}
+
+/// Cuts `row` to at most `width` characters.
+pub fn fit_to_width(row: &mut String, width: usize) {
+    if let Some((byte_index, _)) = row.char_indices().nth(width) {
+        row.truncate(byte_index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::{Size, TestBackend};
+    use crossterm::style::Stylize;
+
+    fn frame(rows: &[&str]) -> Frame {
+        let mut frame = Frame::new(Size {
+            width: 10,
+            height: rows.len(),
+        });
+        for (index, row) in rows.iter().enumerate() {
+            frame.set_text(index, 0, row, ContentStyle::default());
+        }
+        frame
+    }
+
+    fn terminal() -> (Terminal, TestBackend) {
+        let backend = TestBackend::new(Size {
+            width: 10,
+            height: 3,
+        });
+        (Terminal::new(backend.clone()), backend)
+    }
+
+    fn draw(screen: &mut Screen, frame: &Frame) -> String {
+        let (mut terminal, backend) = terminal();
+        screen
+            .draw(&mut terminal, frame, Position::default())
+            .unwrap();
+        String::from_utf8_lossy(&backend.take_output()).into_owned()
+    }
+
+    #[test]
+    fn test_first_draw_prints_every_row() {
+        let mut screen = Screen::default();
+        let out = draw(&mut screen, &frame(&["alpha", "beta"]));
+        assert!(out.contains("alpha") && out.contains("beta"));
+    }
+
+    #[test]
+    fn test_only_changed_rows_are_redrawn() {
+        let mut screen = Screen::default();
+        draw(&mut screen, &frame(&["alpha", "beta"]));
+
+        let out = draw(&mut screen, &frame(&["alpha", "gamma"]));
+        assert!(!out.contains("alpha"), "Unchanged row was redrawn: {out}");
+        assert!(out.contains("gamma"));
+        // Row 1 => "\x1B[2;1H"
+        assert!(out.contains("[2;1H"), "Expected a move to the changed row");
+    }
+
+    #[test]
+    fn test_identical_frame_only_moves_cursor() {
+        let mut screen = Screen::default();
+        draw(&mut screen, &frame(&["same"]));
+
+        let out = draw(&mut screen, &frame(&["same"]));
+        assert!(!out.contains("same"));
+        assert!(!out.contains("[2K"), "Expected no line to be cleared");
+    }
+
+    #[test]
+    fn test_invalidate_forces_full_redraw() {
+        let mut screen = Screen::default();
+        draw(&mut screen, &frame(&["same"]));
+
+        screen.invalidate();
+        assert!(draw(&mut screen, &frame(&["same"])).contains("same"));
+    }
+
+    #[test]
+    fn test_cursor_is_hidden_while_drawing() {
+        let mut screen = Screen::default();
+        let (mut terminal, backend) = terminal();
+        screen
+            .draw(
+                &mut terminal,
+                &frame(&["text"]),
+                Position { col: 3, row: 0 },
+            )
+            .unwrap();
+        let out = String::from_utf8_lossy(&backend.take_output()).into_owned();
+
+        let hide = out.find("[?25l").expect("Expected the cursor to be hidden");
+        let text = out.find("text").expect("Expected the row");
+        let show = out.find("[?25h").expect("Expected the cursor to be shown");
+        assert!(hide < text && text < show);
+        assert!(out.contains("[1;4H"), "Expected the cursor at col 3");
+        assert_eq!(backend.screen()[0], "text");
+        assert_eq!(backend.cursor(), Position { col: 3, row: 0 });
+    }
+
+    #[test]
+    fn test_synchronized_output_wraps_frame() {
+        let mut screen = Screen::new(Capabilities {
+            synchronized_output: true,
+            ..Capabilities::default()
+        });
+        let out = draw(&mut screen, &frame(&["text"]));
+        assert!(
+            out.starts_with("\u{1b}[?2026h"),
+            "Expected begin; got {out:?}"
+        );
+        assert!(out.ends_with("\u{1b}[?2026l"), "Expected end; got {out:?}");
+    }
+
+    #[test]
+    fn test_no_synchronized_output_by_default() {
+        let out = draw(&mut Screen::default(), &frame(&["text"]));
+        assert!(!out.contains("2026"));
+    }
+
+    #[test]
+    fn test_styled_cells_are_printed_with_style() {
+        let mut frame = frame(&["a"]);
+        frame.set_text(0, 1, "b", ContentStyle::new().red());
+
+        let out = draw(&mut Screen::default(), &frame);
+        // "a" plain, then "b" in red (38;5;9), then the color is reset (39).
+        assert!(
+            out.contains("a\u{1b}[38;5;9mb\u{1b}[39m"),
+            "Expected a styled run; got {out:?}"
+        );
+    }
+
+    #[test]
+    fn test_rgb_is_downconverted_for_the_terminal() {
+        let mut frame = frame(&[""]);
+        let style = ContentStyle::new().with(crossterm::style::Color::Rgb { r: 255, g: 0, b: 0 });
+        frame.set_text(0, 0, "x", style);
+
+        let mut screen = Screen::new(Capabilities {
+            colors: ColorSupport::Ansi256,
+            ..Capabilities::default()
+        });
+        let out = draw(&mut screen, &frame);
+        assert!(
+            out.contains("[38;5;196mx"),
+            "Expected palette color; got {out:?}"
+        );
+    }
+
+    #[test]
+    fn test_fit_to_width_counts_characters() {
+        let mut row = "héllo".to_string();
+        fit_to_width(&mut row, 2);
+        assert_eq!(row, "hé");
+    }
+}
@@ -0,0 +1,274 @@
+//! # Scripts
+//!
+//! `hecto --script FILE [PATHS...]` runs the commands in `FILE` without a
+//! terminal and exits, for batch edits. Given paths, the script runs once
+//! for each, with that file already open. A script is one command per line:
+//!
+//! ```text
+//! # Bump the version and save.
+//! open Cargo.toml
+//! goto 3
+//! replace "0.1.0" "0.2.0"
+//! save
+//! ```
+//!
+//! | Command              | Does                                         |
+//! |----------------------|----------------------------------------------|
+//! | `open PATH`          | opens a file                                 |
+//! | `goto LINE[:COLUMN]` | moves the cursor (both start at 1)           |
+//! | `replace OLD NEW`    | replaces every `OLD`; fails if there is none |
+//! | `insert TEXT`        | inserts text at the cursor                   |
+//! | `save`               | saves the buffer                             |
+//!
+//! Anything else is run like a key binding's command (`save-all`, `quit`)
+//! or on the command line (`set tabstop=8`). Arguments with spaces are
+//! written in double quotes, with `\"`, `\\`, `\n` and `\t` escapes.
+//!
+//! The script stops at the first command that fails, and `hecto` exits
+//! with an error naming its line.
+
+use std::{fs, path::Path};
+
+use crate::{
+    error::{Error, Result},
+    terminal::{Size, Terminal, TestBackend},
+    Editor,
+};
+
+/// The size of the screen a script's editor pretends to have.
+const SCREEN: Size = Size {
+    width: 80,
+    height: 24,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptCommand {
+    Open(String),
+    /// Zero-based line and column.
+    Goto {
+        line: usize,
+        col: usize,
+    },
+    Replace {
+        old: String,
+        new: String,
+    },
+    Insert(String),
+    Save,
+    /// Any other command, run by name or on the command line.
+    Other(String),
+}
+
+/// Runs the script in `path`, once for each of `files` (or once if there
+/// are none).
+///
+/// # Errors
+///
+/// Fails if the script cannot be read or parsed, or one of its commands
+/// fails.
+pub fn run_file(path: &Path, files: &[String]) -> Result<()> {
+    let text = fs::read_to_string(path).map_err(|err| Error::at(path, err))?;
+    let script_error = |line, message| Error::Script {
+        path: path.to_path_buf(),
+        line,
+        message,
+    };
+    let commands = parse(&text).map_err(|(line, message)| script_error(line, message))?;
+    if files.is_empty() {
+        return run(&commands).map_err(|(line, message)| script_error(line, message));
+    }
+    for file in files {
+        let mut with_file = vec![(0, ScriptCommand::Open(file.clone()))];
+        with_file.extend(commands.iter().cloned());
+        run(&with_file)
+            .map_err(|(line, message)| script_error(line, format!("{file}: {message}")))?;
+    }
+    Ok(())
+}
+
+/// Runs `commands` (with their line numbers) in a fresh headless editor,
+/// stopping at the first failure.
+fn run(commands: &[(usize, ScriptCommand)]) -> std::result::Result<(), (usize, String)> {
+    let mut editor = Editor::with_terminal(Terminal::new(TestBackend::new(SCREEN)));
+    for (line, command) in commands {
+        editor
+            .run_script_command(command)
+            .map_err(|message| (*line, message))?;
+        if editor.should_quit() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Parses a script into its commands, each with its line number.
+///
+/// # Errors
+///
+/// Fails with the line number and a message at the first line that is not
+/// a valid command.
+pub fn parse(text: &str) -> std::result::Result<Vec<(usize, ScriptCommand)>, (usize, String)> {
+    let mut commands = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let number = index.saturating_add(1);
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let command = parse_line(trimmed).map_err(|message| (number, message))?;
+        commands.push((number, command));
+    }
+    Ok(commands)
+}
+
+fn parse_line(line: &str) -> std::result::Result<ScriptCommand, String> {
+    let words = split_words(line)?;
+    let (name, args) = words.split_first().ok_or("empty command")?;
+    let command = match (name.as_str(), args) {
+        ("open", [path]) => ScriptCommand::Open(path.clone()),
+        ("goto", [position]) => parse_goto(position)?,
+        ("replace", [old, new]) if !old.is_empty() => ScriptCommand::Replace {
+            old: old.clone(),
+            new: new.clone(),
+        },
+        ("insert", [text]) => ScriptCommand::Insert(text.clone()),
+        ("save", []) => ScriptCommand::Save,
+        ("open", _) => return Err("usage: open PATH".to_string()),
+        ("goto", _) => return Err("usage: goto LINE[:COLUMN]".to_string()),
+        ("replace", _) => return Err("usage: replace OLD NEW".to_string()),
+        ("insert", _) => return Err("usage: insert TEXT".to_string()),
+        _ => ScriptCommand::Other(line.to_string()),
+    };
+    Ok(command)
+}
+
+fn parse_goto(position: &str) -> std::result::Result<ScriptCommand, String> {
+    let (line, col) = position.split_once(':').unwrap_or((position, "1"));
+    let number = |text: &str| {
+        text.parse::<usize>()
+            .ok()
+            .and_then(|number| number.checked_sub(1))
+            .ok_or_else(|| format!("not a position: {position}"))
+    };
+    Ok(ScriptCommand::Goto {
+        line: number(line)?,
+        col: number(col)?,
+    })
+}
+
+/// Splits a line at spaces, keeping double-quoted words together.
+fn split_words(line: &str) -> std::result::Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&ch) = chars.peek() {
+        if ch.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut word = String::new();
+        if ch == '"' {
+            chars.next();
+            loop {
+                match chars.next().ok_or("unterminated quote")? {
+                    '"' => break,
+                    '\\' => match chars.next().ok_or("unterminated quote")? {
+                        'n' => word.push('\n'),
+                        't' => word.push('\t'),
+                        other => word.push(other),
+                    },
+                    other => word.push(other),
+                }
+            }
+        } else {
+            while let Some(ch) = chars.next_if(|ch| !ch.is_whitespace()) {
+                word.push(ch);
+            }
+        }
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let commands = parse(
+            "# comment\n\
+             open notes.txt\n\
+             goto 3:5\n\
+             \n\
+             replace \"a b\" \"c\\\"d\\n\"\n\
+             set ts=8\n\
+             save\n",
+        )
+        .unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                (2, ScriptCommand::Open("notes.txt".to_string())),
+                (3, ScriptCommand::Goto { line: 2, col: 4 }),
+                (
+                    5,
+                    ScriptCommand::Replace {
+                        old: "a b".to_string(),
+                        new: "c\"d\n".to_string()
+                    }
+                ),
+                (6, ScriptCommand::Other("set ts=8".to_string())),
+                (7, ScriptCommand::Save),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_line() {
+        assert_eq!(
+            parse("save\ngoto 0\n"),
+            Err((2, "not a position: 0".to_string()))
+        );
+        assert_eq!(
+            parse("replace \"a\n"),
+            Err((1, "unterminated quote".to_string()))
+        );
+        assert_eq!(
+            parse("open a b\n"),
+            Err((1, "usage: open PATH".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_run_file_edits_each_file() {
+        let dir = std::env::temp_dir().join(format!("hecto-script-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("edit.hecto");
+        fs::write(
+            &script,
+            "replace \"0.1.0\" \"0.2.0\"\ngoto 1\ninsert \"# \"\nsave\n",
+        )
+        .unwrap();
+        let files: Vec<String> = ["a.txt", "b.txt"]
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                fs::write(&path, "version 0.1.0\n").unwrap();
+                path.display().to_string()
+            })
+            .collect();
+
+        run_file(&script, &files).unwrap();
+        for file in &files {
+            assert_eq!(fs::read_to_string(file).unwrap(), "# version 0.2.0\n");
+        }
+
+        // Nothing left to replace the second time around.
+        let err = run_file(&script, &files[..1]).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            format!("{}:1: {}: not found: 0.1.0", script.display(), files[0])
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
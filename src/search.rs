@@ -0,0 +1,99 @@
+//! # Search
+//!
+//! `/` in normal mode opens the command line with a `/` typed; the text
+//! after it is searched for from the cursor on, going round past the end of
+//! the buffer, and `n` and `N` go on to the next and previous place it
+//! occurs. Text is matched as typed, case and all. Every place it occurs is
+//! highlighted (see [`layer`](crate::layer)) until `nohlsearch` hides them
+//! or the next search shows them again.
+
+use std::ops::Range;
+
+use crate::{buffer::Buffer, operator::Pos};
+
+/// The last text searched for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Search {
+    pub pattern: String,
+    /// Whether the places it occurs are highlighted.
+    pub shown: bool,
+}
+
+/// Where `pattern` occurs in line `line` of `buffer`, in graphemes.
+#[must_use]
+pub fn matches(buffer: &Buffer, line: usize, pattern: &str) -> Vec<Range<usize>> {
+    let Some(text) = buffer.get(line).filter(|_| !pattern.is_empty()) else {
+        return Vec::new();
+    };
+    let layout = buffer.layout(line);
+    text.match_indices(pattern)
+        .map(|(at, found)| {
+            layout.index_of_byte(at)..layout.index_of_byte(at.saturating_add(found.len()))
+        })
+        .collect()
+}
+
+/// Where `pattern` next occurs after `from` in `buffer` (before it, unless
+/// `forward`), and whether getting there went round the end of the buffer.
+#[must_use]
+pub fn find(buffer: &Buffer, from: Pos, pattern: &str, forward: bool) -> Option<(Pos, bool)> {
+    find_with(buffer, from, forward, |line| matches(buffer, line, pattern))
+}
+
+/// Like [`find`], but for whatever `matches` finds in a line (the columns
+/// of each, in order) rather than a pattern.
+pub fn find_with(
+    buffer: &Buffer,
+    from: Pos,
+    forward: bool,
+    mut matches: impl FnMut(usize) -> Vec<Range<usize>>,
+) -> Option<(Pos, bool)> {
+    let count = buffer.line_count();
+    let lines: Vec<(usize, bool)> = if forward {
+        (from.0..count)
+            .map(|line| (line, false))
+            .chain((0..=from.0).map(|line| (line, true)))
+            .collect()
+    } else {
+        (0..=from.0)
+            .rev()
+            .map(|line| (line, false))
+            .chain((from.0..count).rev().map(|line| (line, true)))
+            .collect()
+    };
+    lines.into_iter().find_map(|(line, wrapped)| {
+        let mut starts = matches(line)
+            .into_iter()
+            .map(|found| found.start)
+            .filter(|&col| {
+                // The cursor's own line is looked at twice: the part past
+                // the cursor first, the rest after going round.
+                line != from.0 || (if forward { col > from.1 } else { col < from.1 }) != wrapped
+            });
+        let col = if forward {
+            starts.next()
+        } else {
+            starts.next_back()
+        };
+        col.map(|col| ((line, col), wrapped))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_goes_round_the_end() {
+        let mut buffer = Buffer::default();
+        buffer.insert(0, 0, "ab ab\nxé ab\n");
+        assert_eq!(matches(&buffer, 0, "ab"), [0..2, 3..5]);
+        assert_eq!(matches(&buffer, 1, "ab").first(), Some(&(3..5)));
+        assert_eq!(find(&buffer, (0, 0), "ab", true), Some(((0, 3), false)));
+        assert_eq!(find(&buffer, (1, 3), "ab", true), Some(((0, 0), true)));
+        assert_eq!(find(&buffer, (0, 3), "ab", false), Some(((0, 0), false)));
+        assert_eq!(find(&buffer, (0, 0), "ab", false), Some(((1, 3), true)));
+        assert_eq!(find(&buffer, (0, 0), "zz", true), None);
+        assert_eq!(find(&buffer, (0, 0), "", true), None);
+    }
+}
@@ -0,0 +1,208 @@
+//! # Selections
+//!
+//! The text between an anchor, where selecting started, and the cursor,
+//! which keeps moving while the anchor stays put. Visual mode selects this
+//! way (`v` for characters, `V` for whole lines); whatever is selected is
+//! then handed to an [`Operator`](crate::operator::Operator) as a [`Span`].
+//!
+//! A block (`ctrl-v`) is the rectangle with the anchor and the cursor at
+//! opposite corners: the same columns of each line between them. It is not
+//! one stretch of text, so it is taken apart into its [`rows`](Selection::rows).
+//! Columns count characters, not screen cells.
+//!
+//! Outside of visual mode, `Shift` with the arrow keys (and `Home`, `End`,
+//! `PageUp`, `PageDown`) selects the way GUI editors do: up to the cursor,
+//! not including the character under it.
+
+use std::{
+    cmp::{max, min},
+    ops::Range,
+};
+
+use crate::{
+    buffer::Buffer,
+    operator::{Pos, Span},
+};
+
+/// What a selection takes between its two ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    /// Every character from one end to the other, both included.
+    Chars,
+    /// The whole lines of both ends and those in between.
+    Lines,
+    /// The columns between both ends, on each of their lines.
+    Block,
+    /// Like [`Shape::Chars`], but the character at the cursor's end is
+    /// left out.
+    Between,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    /// The end that stays where selecting started.
+    pub anchor: Pos,
+    pub shape: Shape,
+}
+
+impl Selection {
+    #[must_use]
+    pub fn new(anchor: Pos, shape: Shape) -> Self {
+        Self { anchor, shape }
+    }
+
+    /// The selected text with the cursor at `cursor`. For a block, the
+    /// whole lines it runs across.
+    #[must_use]
+    pub fn span(&self, buffer: &Buffer, cursor: Pos) -> Span {
+        let (start, last) = (min(self.anchor, cursor), max(self.anchor, cursor));
+        match self.shape {
+            Shape::Lines | Shape::Block => Span {
+                start: (start.0, 0),
+                end: (last.0, 0),
+                linewise: true,
+            },
+            Shape::Between => Span {
+                start,
+                end: last,
+                linewise: false,
+            },
+            Shape::Chars => {
+                // The last character is included; at the end of a line,
+                // that is the line break.
+                let len = buffer.line_len(last.0);
+                let end = if last.1 < len {
+                    (last.0, last.1.saturating_add(1))
+                } else if last.0.saturating_add(1) < buffer.line_count() {
+                    (last.0.saturating_add(1), 0)
+                } else {
+                    (last.0, len)
+                };
+                Span {
+                    start,
+                    end,
+                    linewise: false,
+                }
+            }
+        }
+    }
+
+    /// The selected columns of `line`, end excluded, or `None` if none
+    /// are. The column just past the end of the line stands for its line
+    /// break.
+    #[must_use]
+    pub fn columns(&self, buffer: &Buffer, cursor: Pos, line: usize) -> Option<Range<usize>> {
+        let (start, last) = (min(self.anchor, cursor), max(self.anchor, cursor));
+        if !(start.0..=last.0).contains(&line) {
+            return None;
+        }
+        let len = buffer.line_len(line);
+        let through_break = len.saturating_add(1);
+        let columns = match self.shape {
+            Shape::Lines => 0..through_break,
+            Shape::Block => {
+                // Lines too short to reach the block have nothing selected.
+                let (left, right) = self.block_columns(cursor);
+                min(left, len)..min(right, len)
+            }
+            Shape::Between => {
+                let from = if line == start.0 { start.1 } else { 0 };
+                let to = if line == last.0 {
+                    last.1
+                } else {
+                    through_break
+                };
+                from..to
+            }
+            Shape::Chars => {
+                let from = if line == start.0 { start.1 } else { 0 };
+                let to = if line == last.0 {
+                    min(last.1.saturating_add(1), through_break)
+                } else {
+                    through_break
+                };
+                from..to
+            }
+        };
+        Some(columns)
+    }
+
+    /// The first line of the block and its first column.
+    #[must_use]
+    pub fn top_left(&self, cursor: Pos) -> Pos {
+        (min(self.anchor.0, cursor.0), self.block_columns(cursor).0)
+    }
+
+    /// Each selected line with its selected columns.
+    #[must_use]
+    pub fn rows(&self, buffer: &Buffer, cursor: Pos) -> Vec<(usize, Range<usize>)> {
+        let (first, last) = (min(self.anchor.0, cursor.0), max(self.anchor.0, cursor.0));
+        (first..=last)
+            .filter_map(|line| Some((line, self.columns(buffer, cursor, line)?)))
+            .collect()
+    }
+
+    /// The columns of a block, end excluded.
+    fn block_columns(&self, cursor: Pos) -> (usize, usize) {
+        let (left, right) = (min(self.anchor.1, cursor.1), max(self.anchor.1, cursor.1));
+        (left, right.saturating_add(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(text: &str) -> Buffer {
+        let mut buffer = Buffer::default();
+        buffer.replace_text(text);
+        buffer
+    }
+
+    #[test]
+    fn test_character_selection_includes_both_ends() {
+        let buffer = buffer("hello\nworld\n");
+        let selection = Selection::new((1, 2), Shape::Chars);
+        let span = selection.span(&buffer, (0, 3));
+        assert_eq!(span.text(&buffer), "lo\nwor");
+        assert_eq!(selection.columns(&buffer, (0, 3), 0), Some(3..6));
+        assert_eq!(selection.columns(&buffer, (0, 3), 1), Some(0..3));
+        // Selecting the end of a line takes its line break.
+        let span = Selection::new((0, 5), Shape::Chars).span(&buffer, (0, 5));
+        assert_eq!(span.text(&buffer), "\n");
+    }
+
+    #[test]
+    fn test_selection_between_leaves_the_end_out() {
+        let buffer = buffer("hello\nworld\n");
+        let selection = Selection::new((1, 2), Shape::Between);
+        assert_eq!(selection.span(&buffer, (0, 3)).text(&buffer), "lo\nwo");
+        assert_eq!(selection.columns(&buffer, (0, 3), 0), Some(3..6));
+        assert_eq!(selection.columns(&buffer, (0, 3), 1), Some(0..2));
+        assert_eq!(selection.span(&buffer, (1, 2)).text(&buffer), "");
+    }
+
+    #[test]
+    fn test_line_selection_takes_whole_lines() {
+        let buffer = buffer("a\nbc\nd\n");
+        let selection = Selection::new((1, 1), Shape::Lines);
+        assert_eq!(selection.span(&buffer, (0, 0)).text(&buffer), "a\nbc\n");
+        assert_eq!(selection.columns(&buffer, (0, 0), 1), Some(0..3));
+        assert_eq!(selection.columns(&buffer, (0, 0), 2), None);
+    }
+
+    #[test]
+    fn test_block_selection_takes_the_same_columns() {
+        let buffer = buffer("abcd\nx\nefgh\n");
+        let selection = Selection::new((2, 2), Shape::Block);
+        assert_eq!(
+            selection.rows(&buffer, (0, 1)),
+            vec![(0, 1..3), (1, 1..1), (2, 1..3)]
+        );
+        assert_eq!(selection.top_left((0, 1)), (0, 1));
+        assert_eq!(
+            selection.span(&buffer, (0, 1)).text(&buffer),
+            "abcd\nx\nefgh\n"
+        );
+    }
+}
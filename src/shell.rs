@@ -0,0 +1,158 @@
+//! # Shell Commands
+//!
+//! Runs a command line in the shell for the editor, as Vim does: `:!cmd`
+//! shows what the command prints, and `:r !cmd` inserts it below the
+//! cursor's line (`:r !date`, `:r !uuidgen`). The shell is `$SHELL`, or
+//! `sh` if that is not set; on Windows, `cmd`. Commands get no input, so
+//! one that waits for some ends at once instead of hanging the editor,
+//! except for [`filter`]s, which are given text to change.
+
+use std::{
+    env,
+    io::Write,
+    process::{Command, Stdio},
+    thread,
+};
+
+use crate::{
+    clipboard::normalize_line_breaks,
+    error::{Error, Result},
+};
+
+/// What a command printed, and whether it succeeded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+    pub success: bool,
+    /// How it ended, like `exit status: 1`.
+    pub status: String,
+}
+
+/// Runs `command` in the shell and returns what it printed.
+///
+/// # Errors
+///
+/// Fails if the shell cannot be run, or the command fails; the error then
+/// says what it printed to standard error, or its exit status.
+pub fn run(command: &str) -> Result<String> {
+    let output = capture(command)?;
+    if output.success {
+        return Ok(output.stdout);
+    }
+    Err(output.error(command))
+}
+
+impl From<std::process::Output> for Output {
+    fn from(output: std::process::Output) -> Self {
+        let text = |bytes: &[u8]| normalize_line_breaks(&String::from_utf8_lossy(bytes));
+        Self {
+            stdout: text(&output.stdout),
+            stderr: text(&output.stderr),
+            success: output.status.success(),
+            status: output.status.to_string(),
+        }
+    }
+}
+
+impl Output {
+    /// Why `command`, which printed this, failed: the last thing it
+    /// printed to standard error, or else its exit status.
+    #[must_use]
+    pub fn error(&self, command: &str) -> Error {
+        let message = self
+            .stderr
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .map_or(self.status.clone(), ToString::to_string);
+        Error::Command {
+            command: command.to_string(),
+            message,
+        }
+    }
+}
+
+/// Runs `command` in the shell and returns what it printed, whether it
+/// succeeded or not, for commands whose failures say something too (a
+/// build, `grep`).
+///
+/// # Errors
+///
+/// Fails if the shell cannot be run.
+pub fn capture(command: &str) -> Result<Output> {
+    let output = shell().arg(command).stdin(Stdio::null()).output()?;
+    Ok(Output::from(output))
+}
+
+/// Runs `command` in the shell with `input` as its standard input, and
+/// returns what it printed, as a [formatter](crate::format) does.
+///
+/// # Errors
+///
+/// Fails if the shell cannot be run, or the command fails; the error then
+/// says what it printed to standard error, or its exit status.
+pub fn filter(command: &str, input: &str) -> Result<String> {
+    let mut child = shell()
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Written from another thread, so a command that prints as it reads
+    // cannot fill its output pipe while this one waits to write.
+    let writer = child.stdin.take().map(|mut stdin| {
+        let input = input.to_string();
+        thread::spawn(move || {
+            // A command that stops reading early is not a failure here.
+            let _ = stdin.write_all(input.as_bytes());
+        })
+    });
+    let output = Output::from(child.wait_with_output()?);
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    if output.success {
+        return Ok(output.stdout);
+    }
+    Err(output.error(command))
+}
+
+/// `text` quoted for the shell, as one word.
+#[must_use]
+pub fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// The shell, ready to be given a command line.
+fn shell() -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new(env::var_os("SHELL").unwrap_or_else(|| "sh".into()));
+        shell.arg("-c");
+        shell
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_or_what_went_wrong() {
+        assert_eq!(run("printf 'a\\r\\nb'").unwrap(), "a\nb");
+        assert_eq!(
+            run("echo oops >&2; exit 3").unwrap_err().to_string(),
+            "echo oops >&2; exit 3: oops"
+        );
+        assert_eq!(
+            run("exit 2").unwrap_err().to_string(),
+            "exit 2: exit status: 2"
+        );
+        assert_eq!(filter("tr a-z A-Z", "abc\n").unwrap(), "ABC\n");
+        assert!(filter("cat >/dev/null; exit 1", "abc").is_err());
+    }
+}
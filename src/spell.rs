@@ -0,0 +1,440 @@
+//! # Spell Checking
+//!
+//! With the `spell` option on, the words the dictionary does not know are
+//! underlined in the theme's `spell.bad`: anywhere in plain text and
+//! Markdown, and only in the comments and strings of code, whose
+//! identifiers are not words. In normal mode, `]s` and `[s` jump to the
+//! next and previous of them, going round at the ends of the buffer; `z=`
+//! lists what the word at the cursor may have been meant to be in a popup
+//! under it, where `Up`, `Down` and `Enter` (or `1` to `9`) put one in; and
+//! `zg` adds the word to the user's own list, so it is known from then on.
+//!
+//! The dictionary is the one for the language `spelllang` names (`en_US`
+//! to start with): a Hunspell `.dic` file from `/usr/share/hunspell` or
+//! `/usr/share/myspell`, or else the word list in `/usr/share/dict/words`,
+//! plus the words in `spell/<spelllang>.add` in the config directory (see
+//! [`dirs::config_dir()`]), where `zg` writes. Of a `.dic` file only the
+//! words are taken, not the forms its affix rules make; a word that is not
+//! found is looked for again without the usual English endings (`-s`,
+//! `-ed`, `-ing`, `-ly`, ...).
+//!
+//! Single letters, words with digits, and words with capitals past the
+//! first (acronyms, `camelCase`) are never taken for misspelled.
+
+use std::{
+    collections::HashSet,
+    fs,
+    io::Write,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    buffer::Buffer,
+    dirs,
+    error::{Error, Result},
+    highlight::Scope,
+};
+
+/// Where Hunspell dictionaries are installed.
+const DICTIONARY_DIRS: &[&str] = &["/usr/share/hunspell", "/usr/share/myspell"];
+
+/// The word list used when there is no dictionary for the language.
+const WORD_LIST: &str = "/usr/share/dict/words";
+
+/// The most suggestions `z=` lists.
+const MAX_SUGGESTIONS: usize = 10;
+
+/// Endings taken off a word not found, and what its stem may have lost to
+/// them (`flies` from `fly`, `making` from `make`).
+const ENDINGS: &[(&str, &str)] = &[
+    ("'s", ""),
+    ("s", ""),
+    ("es", ""),
+    ("ies", "y"),
+    ("ed", ""),
+    ("ed", "e"),
+    ("ied", "y"),
+    ("ing", ""),
+    ("ing", "e"),
+    ("ly", ""),
+    ("er", ""),
+    ("est", ""),
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct Dictionary {
+    /// The language it is for, like `en_US`.
+    lang: String,
+    words: HashSet<String>,
+    /// The user's own word list, which added words are written to.
+    added: Option<PathBuf>,
+}
+
+impl Dictionary {
+    /// Loads the dictionary for `lang`, and the user's words for it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no dictionary for `lang`, or it cannot be read.
+    pub fn load(lang: &str) -> Result<Self> {
+        let path = DICTIONARY_DIRS
+            .iter()
+            .map(|dir| Path::new(dir).join(format!("{lang}.dic")))
+            .chain([PathBuf::from(WORD_LIST)])
+            .find(|path| path.is_file())
+            .ok_or_else(|| Error::NoDictionary {
+                lang: lang.to_string(),
+            })?;
+        let bytes = fs::read(&path).map_err(|err| Error::at(&path, err))?;
+        let mut dictionary = Self::parse(lang, &String::from_utf8_lossy(&bytes));
+        dictionary.added =
+            dirs::config_dir().map(|dir| dir.join("spell").join(format!("{lang}.add")));
+        if let Some(added) = dictionary
+            .added
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+        {
+            dictionary.extend(&added);
+        }
+        Ok(dictionary)
+    }
+
+    /// A dictionary for `lang` of the words in `text`: a word list, one
+    /// word to a line, or a Hunspell `.dic` file.
+    #[must_use]
+    pub fn parse(lang: &str, text: &str) -> Self {
+        let mut dictionary = Self {
+            lang: lang.to_string(),
+            ..Self::default()
+        };
+        dictionary.extend(text);
+        dictionary
+    }
+
+    /// Adds the words of `text`, leaving out a `.dic` file's count on its
+    /// first line and the affix flags after each word (`walk/DGS`).
+    fn extend(&mut self, text: &str) {
+        let words = text
+            .lines()
+            .map(|line| line.split('/').next().unwrap_or_default().trim())
+            .filter(|word| !word.is_empty() && !word.chars().all(|ch| ch.is_ascii_digit()));
+        self.words.extend(words.map(ToString::to_string));
+    }
+
+    #[must_use]
+    pub fn lang(&self) -> &str {
+        &self.lang
+    }
+
+    /// Adds `word`, for good: it is written to the user's word list.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the word list cannot be written; the word is known until
+    /// the editor quits all the same.
+    pub fn add(&mut self, word: &str) -> Result<()> {
+        self.words.insert(word.to_string());
+        let Some(path) = &self.added else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| Error::at(dir, err))?;
+        }
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{word}"))
+            .map_err(|err| Error::at(path, err))
+    }
+
+    /// Whether `word` is spelled right, or is not checked at all.
+    #[must_use]
+    pub fn is_correct(&self, word: &str) -> bool {
+        if !is_checked(word) {
+            return true;
+        }
+        let lower = word.to_lowercase();
+        [word, lower.as_str()].iter().any(|word| {
+            self.words.contains(*word)
+                || ENDINGS.iter().any(|(ending, lost)| {
+                    word.strip_suffix(ending)
+                        .filter(|stem| stem.chars().count() > 1)
+                        .is_some_and(|stem| self.has_stem(stem, lost))
+                })
+        })
+    }
+
+    /// Whether `stem`, which lost `lost` to an ending, is a word: as it is,
+    /// with what it lost, or with a consonant doubled before the ending
+    /// (`running`) taken off.
+    fn has_stem(&self, stem: &str, lost: &str) -> bool {
+        let mut chars = stem.chars().rev();
+        let doubled = chars.next() == chars.next();
+        self.words.contains(stem)
+            || (!lost.is_empty() && self.words.contains(&format!("{stem}{lost}")))
+            || (doubled
+                && stem
+                    .get(..stem.len().saturating_sub(1))
+                    .is_some_and(|single| self.words.contains(single)))
+    }
+
+    /// The words `word` may have been meant to be, likeliest first: those
+    /// a letter or two away from it, capitalized like it.
+    #[must_use]
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let lower = word.to_lowercase();
+        let length = lower.chars().count();
+        let mut found: Vec<(usize, &String)> = self
+            .words
+            .iter()
+            .filter(|candidate| candidate.chars().count().abs_diff(length) <= 2)
+            .filter_map(|candidate| {
+                let distance = distance(&lower, &candidate.to_lowercase());
+                (distance <= 2).then_some((distance, candidate))
+            })
+            .collect();
+        found.sort();
+        let capitalized = word.chars().next().is_some_and(char::is_uppercase);
+        let mut seen = HashSet::new();
+        found
+            .into_iter()
+            .map(|(_, candidate)| {
+                if capitalized {
+                    capitalize(candidate)
+                } else {
+                    candidate.clone()
+                }
+            })
+            .filter(|candidate| seen.insert(candidate.clone()))
+            .take(MAX_SUGGESTIONS)
+            .collect()
+    }
+}
+
+/// Whether `word` is one to check: not a single letter, nor with digits,
+/// nor with capitals past the first.
+fn is_checked(word: &str) -> bool {
+    let mut chars = word.chars();
+    chars.next().is_some_and(char::is_alphabetic)
+        && word.chars().nth(1).is_some()
+        && chars.all(|ch| !ch.is_uppercase() && !ch.is_numeric())
+}
+
+/// `word` with its first letter a capital.
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// How many letters must be put in, taken out, changed or swapped with
+/// the next to turn `a` into `b`.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // The distances from the start of `a` so far to each start of `b`, for
+    // the last row, the one before it, and the one being worked out.
+    let mut before: Vec<usize> = Vec::new();
+    let mut last: Vec<usize> = (0..=b.len()).collect();
+    for (i, &ca) in a.iter().enumerate() {
+        let mut row = vec![i.saturating_add(1)];
+        for (j, &cb) in b.iter().enumerate() {
+            let at = |row: &[usize], index: usize| row.get(index).copied().unwrap_or(usize::MAX);
+            let changed = at(&last, j).saturating_add(usize::from(ca != cb));
+            let taken = at(&last, j.saturating_add(1)).saturating_add(1);
+            let put = at(&row, j).saturating_add(1);
+            let mut best = changed.min(taken).min(put);
+            let swapped = i > 0
+                && j > 0
+                && a.get(i.saturating_sub(1)) == Some(&cb)
+                && b.get(j.saturating_sub(1)) == Some(&ca);
+            if swapped {
+                best = best.min(at(&before, j.saturating_sub(1)).saturating_add(1));
+            }
+            row.push(best);
+        }
+        before = std::mem::replace(&mut last, row);
+    }
+    last.last().copied().unwrap_or_default()
+}
+
+/// The bytes of `text` its words take: runs of letters, with apostrophes
+/// inside them (`don't`).
+fn words(text: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut rest = text.char_indices().peekable();
+    std::iter::from_fn(move || {
+        let (start, _) = rest.find(|(_, ch)| ch.is_alphanumeric())?;
+        let mut end = start;
+        while let Some(&(at, ch)) = rest.peek() {
+            if ch.is_alphanumeric() {
+                end = at.saturating_add(ch.len_utf8());
+            } else if ch != '\'' {
+                break;
+            }
+            rest.next();
+        }
+        Some(start..end.max(start.saturating_add(1)))
+    })
+}
+
+/// The columns of the misspelled words on `line` of `buffer`: in all of
+/// it for prose, in the comments and strings for code.
+#[must_use]
+pub fn misspelled(buffer: &Buffer, line: usize, dictionary: &Dictionary) -> Vec<Range<usize>> {
+    let Some(text) = buffer.get(line) else {
+        return Vec::new();
+    };
+    let regions: Vec<Range<usize>> = if buffer.filetype.language.name == "text" {
+        let whole = 0..text.len();
+        vec![whole]
+    } else {
+        buffer
+            .highlight(line)
+            .into_iter()
+            .filter(|token| matches!(token.scope, Scope::Comment | Scope::String))
+            .map(|token| token.bytes)
+            .collect()
+    };
+    let layout = buffer.layout(line);
+    regions
+        .into_iter()
+        .flat_map(|region| {
+            let part = text.get(region.clone()).unwrap_or_default();
+            words(part)
+                .filter(|bytes| !dictionary.is_correct(part.get(bytes.clone()).unwrap_or_default()))
+                .map(move |bytes| {
+                    region.start.saturating_add(bytes.start)..region.start.saturating_add(bytes.end)
+                })
+                .collect::<Vec<_>>()
+        })
+        .map(|bytes| layout.index_of_byte(bytes.start)..layout.index_of_byte(bytes.end))
+        .collect()
+}
+
+/// The columns of the word on `line` of `buffer` at column `col`, or the
+/// first one after it.
+#[must_use]
+pub fn word_at(buffer: &Buffer, line: usize, col: usize) -> Option<Range<usize>> {
+    let text = buffer.get(line)?;
+    let layout = buffer.layout(line);
+    let cols = words(&text)
+        .map(|bytes| layout.index_of_byte(bytes.start)..layout.index_of_byte(bytes.end))
+        .find(|cols| cols.end > col);
+    cols
+}
+
+/// What `z=` offers for a misspelled word.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestions {
+    pub line: usize,
+    /// The columns of the word.
+    pub cols: Range<usize>,
+    words: Vec<String>,
+    chosen: usize,
+}
+
+impl Suggestions {
+    /// Suggestions of `words` for the word at `cols` of `line`; `None` if
+    /// there are none.
+    #[must_use]
+    pub fn new(line: usize, cols: Range<usize>, words: Vec<String>) -> Option<Self> {
+        (!words.is_empty()).then_some(Self {
+            line,
+            cols,
+            words,
+            chosen: 0,
+        })
+    }
+
+    /// Chooses the next word, going round past the last.
+    pub fn select_next(&mut self) {
+        let next = self.chosen.saturating_add(1);
+        self.chosen = if next < self.words.len() { next } else { 0 };
+    }
+
+    /// Chooses the previous word, going round past the first.
+    pub fn select_previous(&mut self) {
+        self.chosen = self
+            .chosen
+            .checked_sub(1)
+            .unwrap_or_else(|| self.words.len().saturating_sub(1));
+    }
+
+    /// Chooses word `index`, if there is one.
+    pub fn select(&mut self, index: usize) -> bool {
+        let exists = index < self.words.len();
+        if exists {
+            self.chosen = index;
+        }
+        exists
+    }
+
+    #[must_use]
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    #[must_use]
+    pub fn chosen_index(&self) -> usize {
+        self.chosen
+    }
+
+    #[must_use]
+    pub fn chosen(&self) -> Option<&str> {
+        self.words.get(self.chosen).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filetype;
+
+    fn dictionary() -> Dictionary {
+        Dictionary::parse(
+            "en_US",
+            "6\nwalk/DGS\nfly/S\nmake\nrun\nthe\nword\nworld\nLondon\n",
+        )
+    }
+
+    #[test]
+    fn test_words_and_their_forms_are_known() {
+        let dictionary = dictionary();
+        for word in [
+            "walk",
+            "Walking",
+            "flies",
+            "making",
+            "running",
+            "London",
+            "x",
+            "HTTP",
+            "camelCase",
+            "v2",
+        ] {
+            assert!(dictionary.is_correct(word), "{word}");
+        }
+        for word in ["wlak", "teh", "london"] {
+            assert!(!dictionary.is_correct(word), "{word}");
+        }
+        assert_eq!(dictionary.suggest("Teh"), ["The"]);
+        assert_eq!(dictionary.suggest("wrod"), ["word", "world"]);
+        assert_eq!(distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_only_comments_and_strings_of_code_are_checked() {
+        let dictionary = dictionary();
+        let mut buffer = Buffer::default();
+        buffer.replace_text("the wlak = 1; // walk teh don't\n");
+        assert_eq!(misspelled(&buffer, 0, &dictionary), [4..8, 22..25, 26..31]);
+        buffer.filetype = filetype::detect(Some(Path::new("main.rs")), None);
+        assert_eq!(misspelled(&buffer, 0, &dictionary), [22..25, 26..31]);
+        assert_eq!(word_at(&buffer, 0, 8), Some(11..12));
+        assert_eq!(word_at(&buffer, 0, 31), None);
+    }
+}
@@ -0,0 +1,137 @@
+//! # Status Bar
+//!
+//! The last row of the screen: shows the [`Mode`] (when the `modal` option
+//! is on), which file is open, how long it is and how it is stored, followed
+//! by the most recent status message (e.g. the
+//! result of a save) and its [`Severity`]. A file's
+//! [filetype](crate::filetype) is shown unless it is plain text, and, if
+//! the file is in a Git work tree, how the [repository](crate::git) stands.
+//! So is how the file is [indented](crate::indent), once that is known.
+
+use std::fmt::Write;
+
+use crate::{
+    buffer::Buffer, compression::Compression, filetype, keymap::Mode, layout::LineLayout,
+    notifications::Severity, screen::fit_to_width,
+};
+
+/// Separates the message from the file information.
+const SEPARATOR: &str = " | ";
+
+#[derive(Debug, Default, Clone)]
+pub struct StatusBar {
+    /// A short message shown after the file information, if any.
+    pub message: Option<String>,
+    /// How serious the message is, which decides its color.
+    pub severity: Severity,
+    /// The branch of the repository the file is in and how it stands, if
+    /// it is in one, as in `main* ↑1`.
+    pub repository: Option<String>,
+}
+
+impl StatusBar {
+    /// Shows `message`, replacing the previous one.
+    pub fn show(&mut self, severity: Severity, message: impl Into<String>) {
+        self.message = Some(message.into());
+        self.severity = severity;
+    }
+
+    pub fn clear(&mut self) {
+        self.message = None;
+        self.severity = Severity::Info;
+    }
+
+    /// The screen column the message starts at in [`StatusBar::render`].
+    #[must_use]
+    pub fn message_column(&self, buffer: &Buffer, mode: Option<Mode>) -> usize {
+        LineLayout::new(&self.describe(buffer, mode))
+            .width()
+            .saturating_add(SEPARATOR.len())
+    }
+
+    /// Builds the status bar row, cut to `width`. `mode` is shown if given.
+    #[must_use]
+    pub fn render(&self, buffer: &Buffer, mode: Option<Mode>, width: usize) -> String {
+        let mut status = self.describe(buffer, mode);
+        if let Some(message) = &self.message {
+            status.push_str(SEPARATOR);
+            status.push_str(message);
+        }
+        fit_to_width(&mut status, width);
+        status
+    }
+
+    /// The buffer's description, then the repository's, if there is one.
+    fn describe(&self, buffer: &Buffer, mode: Option<Mode>) -> String {
+        let description = describe(buffer, mode);
+        match &self.repository {
+            Some(repository) => format!("{description}{SEPARATOR}{repository}"),
+            None => description,
+        }
+    }
+}
+
+/// Describes the buffer, e.g. `app.log.gz (modified) - 120 lines [gzip]`
+/// or `main.rs - 80 lines [rust] [spaces:4]`, after the mode if there is
+/// one. How it is indented is shown if the file was found to be indented.
+fn describe(buffer: &Buffer, mode: Option<Mode>) -> String {
+    let name = buffer.display_name();
+    let lines = buffer.line_count();
+    let mut description = format!("{name} - {lines} lines");
+    if buffer.filetype != filetype::TEXT {
+        let _ = write!(description, " [{}]", buffer.filetype.name);
+    }
+    if buffer.detected_indent.is_some() {
+        let _ = write!(description, " [{}]", buffer.settings().indent);
+    }
+    if buffer.compression != Compression::None {
+        let _ = write!(description, " [{}]", buffer.compression);
+    }
+    match mode {
+        Some(mode) => format!("{mode}{SEPARATOR}{description}"),
+        None => description,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_describe_unnamed_buffer() {
+        assert_eq!(describe(&Buffer::default(), None), "[No Name] - 0 lines");
+        assert_eq!(
+            describe(&Buffer::default(), Some(Mode::Normal)),
+            "NORMAL | [No Name] - 0 lines"
+        );
+    }
+
+    #[test]
+    fn test_describe_shows_compression() {
+        let mut buffer = Buffer::default();
+        buffer.file_name = Some(PathBuf::from("app.log.gz"));
+        buffer.compression = Compression::Gzip;
+        buffer.replace_text("a\nb\n");
+        assert_eq!(describe(&buffer, None), "app.log.gz - 2 lines [gzip]");
+        let mut buffer = Buffer::for_file(Path::new("Makefile"));
+        assert_eq!(describe(&buffer, None), "Makefile - 0 lines [make]");
+        buffer.detected_indent = Some(crate::buffer::Indent::Tabs);
+        assert_eq!(describe(&buffer, None), "Makefile - 0 lines [make] [tabs]");
+    }
+
+    #[test]
+    fn test_render_includes_message_and_fits_width() {
+        let mut status_bar = StatusBar::default();
+        status_bar.show(Severity::Info, "Saved.");
+        let row = status_bar.render(&Buffer::default(), None, 80);
+        assert_eq!(row, "[No Name] - 0 lines | Saved.");
+        assert_eq!(status_bar.message_column(&Buffer::default(), None), 22);
+        assert_eq!(status_bar.render(&Buffer::default(), None, 9), "[No Name]");
+
+        status_bar.repository = Some("main*".to_string());
+        let row = status_bar.render(&Buffer::default(), None, 80);
+        assert_eq!(row, "[No Name] - 0 lines | main* | Saved.");
+        assert_eq!(status_bar.message_column(&Buffer::default(), None), 30);
+    }
+}
@@ -0,0 +1,272 @@
+//! # Tags
+//!
+//! Code navigation without a language server: `ctags -R` (Universal or
+//! Exuberant Ctags) writes a `tags` file listing where each function,
+//! type and so on is defined, and the editor reads it. `Alt+.` (`Ctrl+]`
+//! in normal mode) jumps to the definition of the identifier at the
+//! cursor, and `tag NAME` on the command line to that of `NAME`; where
+//! there are several, the first is jumped to and all are put in the
+//! quickfix list. Each jump pushes where it came from on a stack, and
+//! `Alt+,` (`Ctrl+T`) pops back there.
+//!
+//! The `tags` file is looked for in the working directory and then in
+//! each directory above it, and the files it names are taken relative to
+//! where it is. It is read again on every jump, so regenerating it needs
+//! nothing more.
+
+use std::{
+    env, fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    completion::is_word_char,
+    error::{Error, Result},
+    layout::LineLayout,
+    quickfix::Entry,
+};
+
+/// What the tags file is called.
+const FILE_NAME: &str = "tags";
+
+/// Where in its file a tag is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Address {
+    /// A line number, from 0.
+    Line(usize),
+    /// A search pattern for the line, as in `/^fn main() {$/`: its text,
+    /// and whether it must match the whole line or only its start.
+    Pattern { text: String, whole_line: bool },
+}
+
+impl Address {
+    /// The line of `text` it is at, from 0.
+    #[must_use]
+    pub fn find(&self, text: &str) -> Option<usize> {
+        match self {
+            Self::Line(line) => Some(*line),
+            Self::Pattern {
+                text: pattern,
+                whole_line,
+            } => text.lines().position(|line| {
+                if *whole_line {
+                    line == pattern
+                } else {
+                    line.starts_with(pattern.as_str())
+                }
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: String,
+    /// The file it is in, as the tags file names it, from where that is.
+    pub path: PathBuf,
+    pub address: Address,
+    /// What it is, like `function` or `f`, if the tags file says.
+    pub kind: Option<String>,
+}
+
+/// The tags file in `dir` or the nearest directory above it, as a path
+/// relative to `dir`.
+#[must_use]
+pub fn locate(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().enumerate().find_map(|(depth, ancestor)| {
+        ancestor.join(FILE_NAME).is_file().then(|| {
+            let mut path: PathBuf = std::iter::repeat_n("..", depth).collect();
+            path.push(FILE_NAME);
+            path
+        })
+    })
+}
+
+/// The tags named `name` in the tags file for the working directory.
+///
+/// # Errors
+///
+/// Fails if there is no tags file, or it cannot be read.
+pub fn find(name: &str) -> Result<Vec<Tag>> {
+    let dir = env::current_dir()?;
+    let path = locate(&dir).ok_or_else(|| {
+        Error::at(
+            Path::new(FILE_NAME),
+            std::io::Error::from(std::io::ErrorKind::NotFound),
+        )
+    })?;
+    let text = fs::read(&path).map_err(|err| Error::at(&path, err))?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    Ok(parse(&String::from_utf8_lossy(&text), name, base))
+}
+
+/// The tags named `name` in `text`, a tags file in `base`.
+#[must_use]
+pub fn parse(text: &str, name: &str, base: &Path) -> Vec<Tag> {
+    text.lines()
+        .filter(|line| {
+            line.strip_prefix(name)
+                .is_some_and(|rest| rest.starts_with('\t'))
+        })
+        .filter_map(|line| parse_line(line, base))
+        .collect()
+}
+
+/// A line of a tags file: the name, the file and the address, separated
+/// by tabs, then possibly `;"` and more fields, the kind among them.
+fn parse_line(line: &str, base: &Path) -> Option<Tag> {
+    let mut fields = line.splitn(3, '\t');
+    let name = fields.next()?;
+    let file = fields.next()?;
+    let rest = fields.next()?;
+    let (address, extra) = split_address(rest)?;
+    let kind = extra
+        .split('\t')
+        .map(|field| field.strip_prefix("kind:").unwrap_or(field))
+        .find(|field| !field.is_empty() && !field.contains(':'))
+        .map(ToString::to_string);
+    Some(Tag {
+        name: name.to_string(),
+        path: base.join(file),
+        address,
+        kind,
+    })
+}
+
+/// The address `rest` starts with, and the fields after it.
+fn split_address(rest: &str) -> Option<(Address, &str)> {
+    fn extra(after: &str) -> &str {
+        after
+            .strip_prefix(";\"")
+            .unwrap_or(after)
+            .trim_start_matches('\t')
+    }
+    let Some(delimiter) = rest.chars().next().filter(|ch| matches!(ch, '/' | '?')) else {
+        let digits = rest
+            .find(|ch: char| !ch.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let number: usize = rest.get(..digits)?.parse().ok()?;
+        let address = Address::Line(number.saturating_sub(1));
+        return Some((address, extra(rest.get(digits..)?)));
+    };
+    let mut text = String::new();
+    let mut chars = rest.char_indices().skip(1);
+    while let Some((at, ch)) = chars.next() {
+        match ch {
+            '\\' => text.extend(chars.next().map(|(_, escaped)| escaped)),
+            _ if ch == delimiter => {
+                let text = text.strip_prefix('^').unwrap_or(&text);
+                let (text, whole_line) = match text.strip_suffix('$') {
+                    Some(text) => (text, true),
+                    None => (text, false),
+                };
+                let address = Address::Pattern {
+                    text: text.to_string(),
+                    whole_line,
+                };
+                return Some((address, extra(rest.get(at.saturating_add(1)..)?)));
+            }
+            _ => text.push(ch),
+        }
+    }
+    None
+}
+
+/// Where `tags` are, as quickfix entries: their lines are looked for in
+/// their files, and the column is that of the name in its line. Those that
+/// cannot be found are left out.
+#[must_use]
+pub fn entries(tags: &[Tag]) -> Vec<Entry> {
+    tags.iter()
+        .filter_map(|tag| {
+            let text = fs::read_to_string(&tag.path).ok()?;
+            let line = tag.address.find(&text)?;
+            let content = text.lines().nth(line).unwrap_or_default();
+            let col = content
+                .find(&tag.name)
+                .map_or(0, |byte| LineLayout::new(content).index_of_byte(byte));
+            let message = match &tag.kind {
+                Some(kind) => format!("{kind} {}", tag.name),
+                None => tag.name.clone(),
+            };
+            Some(Entry {
+                path: tag.path.clone(),
+                line,
+                col,
+                message,
+            })
+        })
+        .collect()
+}
+
+/// The bytes of the identifier in `line` at byte `at`, or else the first
+/// one after it.
+#[must_use]
+pub fn identifier_at(line: &str, at: usize) -> Option<Range<usize>> {
+    let start = if line.get(at..)?.starts_with(is_word_char) {
+        line.get(..at)?
+            .char_indices()
+            .rev()
+            .find(|&(_, ch)| !is_word_char(ch))
+            .map_or(0, |(byte, ch)| byte.saturating_add(ch.len_utf8()))
+    } else {
+        at
+    };
+    let from = line.get(start..)?;
+    let begin = start.saturating_add(from.find(is_word_char)?);
+    let end = line
+        .get(begin..)?
+        .find(|ch: char| !is_word_char(ch))
+        .map_or(line.len(), |length| begin.saturating_add(length));
+    Some(begin..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAGS: &str = "!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted/\n\
+                        Editor\tsrc/editor.rs\t/^pub struct Editor {$/;\"\ts\n\
+                        main\tsrc/main.rs\t/^fn main() {$/;\"\tkind:function\tline:3\n\
+                        main\tother/main.c\t12;\"\tf\n\
+                        mainly\tsrc/a.rs\t/^fn mainly(a: \\/\\/)/;\"\tf\n";
+
+    #[test]
+    fn test_tags_are_parsed() {
+        let tags = parse(TAGS, "main", Path::new(".."));
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].path, Path::new("../src/main.rs"));
+        assert_eq!(
+            tags[0].address,
+            Address::Pattern {
+                text: "fn main() {".to_string(),
+                whole_line: true
+            }
+        );
+        assert_eq!(tags[0].kind.as_deref(), Some("function"));
+        assert_eq!(
+            (&tags[1].address, tags[1].kind.as_deref()),
+            (&Address::Line(11), Some("f"))
+        );
+        let mainly = &parse(TAGS, "mainly", Path::new(""))[0];
+        assert_eq!(
+            mainly.address,
+            Address::Pattern {
+                text: "fn mainly(a: //)".to_string(),
+                whole_line: false
+            }
+        );
+        assert_eq!(mainly.address.find("\nfn mainly(a: //) {}\n"), Some(1));
+        assert!(parse(TAGS, "Edit", Path::new("")).is_empty());
+    }
+
+    #[test]
+    fn test_identifier_at_cursor() {
+        let line = "let x = foo_bar(y);";
+        assert_eq!(identifier_at(line, 10), Some(8..15));
+        assert_eq!(identifier_at(line, 8), Some(8..15));
+        assert_eq!(identifier_at(line, 15), Some(16..17));
+        assert_eq!(identifier_at(line, 18), None);
+    }
+}
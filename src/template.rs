@@ -0,0 +1,308 @@
+//! # Templates
+//!
+//! The date and time, and what is known about the file being edited, put
+//! in as text. `insert-timestamp` puts in the local date and time as
+//! `timestampformat` says (`%Y-%m-%d %H:%M` to start with), and
+//! `insert-timestamp FORMAT` as `FORMAT` does. Formats take these
+//! `strftime` fields; anything else is kept as it is:
+//!
+//! | Field | Stands for                  | Field | Stands for                |
+//! |-------|-----------------------------|-------|---------------------------|
+//! | `%Y`  | the year, `2025`            | `%H`  | the hour, `00`–`23`       |
+//! | `%y`  | the year's last digits      | `%I`  | the hour, `01`–`12`       |
+//! | `%m`  | the month, `01`–`12`        | `%p`  | `AM` or `PM`              |
+//! | `%b`  | the month's name, `Oct`     | `%M`  | the minute                |
+//! | `%B`  | the month's name, `October` | `%S`  | the second                |
+//! | `%d`  | the day, `01`–`31`          | `%z`  | the UTC offset, `+0200`   |
+//! | `%e`  | the day, ` 1`–`31`          | `%s`  | seconds since 1970        |
+//! | `%a`  | the weekday, `Wed`          | `%F`  | `%Y-%m-%d`                |
+//! | `%A`  | the weekday, `Wednesday`    | `%T`  | `%H:%M:%S`                |
+//! | `%%`  | a `%`                       |       |                           |
+//!
+//! Snippets, as [abbreviations](crate::abbreviation) expand to and code
+//! completion puts in, can use these variables (`$AUTHOR`, or
+//! `${AUTHOR:nobody}` with a default), for file headers and the like:
+//! `TM_FILENAME`, `TM_FILENAME_BASE`, `TM_DIRECTORY` and `TM_FILEPATH`
+//! name the buffer's file; `AUTHOR` is the `author` option, or else the
+//! user's login name; `CURRENT_YEAR`, `CURRENT_YEAR_SHORT`,
+//! `CURRENT_MONTH`, `CURRENT_MONTH_NAME`, `CURRENT_MONTH_NAME_SHORT`,
+//! `CURRENT_DATE`, `CURRENT_DAY_NAME`, `CURRENT_DAY_NAME_SHORT`,
+//! `CURRENT_HOUR`, `CURRENT_MINUTE`, `CURRENT_SECOND` and
+//! `CURRENT_SECONDS_UNIX` are parts of the local time.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// What the variables of a snippet stand for, for one file at one time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    file_name: Option<PathBuf>,
+    author: String,
+    /// Seconds since 1970, UTC.
+    time: i64,
+    /// Seconds local time is ahead of UTC.
+    offset: i64,
+}
+
+impl Template {
+    /// The variables for the file `file_name`, written by `author` (the
+    /// user's login name if empty), now.
+    #[must_use]
+    pub fn new(file_name: Option<&Path>, author: &str) -> Self {
+        let time = now();
+        let author = if author.is_empty() {
+            std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .unwrap_or_default()
+        } else {
+            author.to_string()
+        };
+        Self {
+            file_name: file_name.map(Path::to_path_buf),
+            author,
+            time,
+            offset: utc_offset(time),
+        }
+    }
+
+    /// What the variable `name` stands for, if it is one.
+    #[must_use]
+    pub fn variable(&self, name: &str) -> Option<String> {
+        let file_name = self.file_name.as_deref();
+        let part = |format| Some(self.format(format));
+        match name {
+            "TM_FILENAME" => Some(file_name?.file_name()?.to_string_lossy().into_owned()),
+            "TM_FILENAME_BASE" => Some(file_name?.file_stem()?.to_string_lossy().into_owned()),
+            "TM_DIRECTORY" => Some(file_name?.parent()?.to_string_lossy().into_owned()),
+            "TM_FILEPATH" => Some(file_name?.to_string_lossy().into_owned()),
+            "AUTHOR" => Some(self.author.clone()),
+            "CURRENT_YEAR" => part("%Y"),
+            "CURRENT_YEAR_SHORT" => part("%y"),
+            "CURRENT_MONTH" => part("%m"),
+            "CURRENT_MONTH_NAME" => part("%B"),
+            "CURRENT_MONTH_NAME_SHORT" => part("%b"),
+            "CURRENT_DATE" => part("%d"),
+            "CURRENT_DAY_NAME" => part("%A"),
+            "CURRENT_DAY_NAME_SHORT" => part("%a"),
+            "CURRENT_HOUR" => part("%H"),
+            "CURRENT_MINUTE" => part("%M"),
+            "CURRENT_SECOND" => part("%S"),
+            "CURRENT_SECONDS_UNIX" => part("%s"),
+            _ => None,
+        }
+    }
+
+    /// The local time the variables are for, formatted by `format`.
+    #[must_use]
+    pub fn format(&self, format: &str) -> String {
+        format_time(format, self.time, self.offset)
+    }
+}
+
+/// `time` (seconds since 1970, UTC) as `format` lays it out, in the time
+/// zone `offset` seconds ahead of UTC.
+#[must_use]
+pub fn format_time(format: &str, time: i64, offset: i64) -> String {
+    let local = time.saturating_add(offset);
+    let days = local.div_euclid(SECONDS_PER_DAY);
+    let seconds = local.rem_euclid(SECONDS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    let (hour, minute, second) = (
+        seconds.div_euclid(3600),
+        seconds.rem_euclid(3600).div_euclid(60),
+        seconds.rem_euclid(60),
+    );
+    let month_name = usize::try_from(month.saturating_sub(1))
+        .ok()
+        .and_then(|index| MONTHS.get(index))
+        .copied()
+        .unwrap_or_default();
+    // 1970-01-01 was a Thursday.
+    let weekday = usize::try_from(days.saturating_add(4).rem_euclid(7))
+        .ok()
+        .and_then(|index| WEEKDAYS.get(index))
+        .copied()
+        .unwrap_or_default();
+    let hour12 = match hour.rem_euclid(12) {
+        0 => 12,
+        hour => hour,
+    };
+    let mut text = String::new();
+    let mut chars = format.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            text.push(ch);
+            continue;
+        }
+        let field = match chars.next() {
+            Some('Y') => year.to_string(),
+            Some('y') => format!("{:02}", year.rem_euclid(100)),
+            Some('m') => format!("{month:02}"),
+            Some('b') => month_name.chars().take(3).collect(),
+            Some('B') => month_name.to_string(),
+            Some('d') => format!("{day:02}"),
+            Some('e') => format!("{day:2}"),
+            Some('a') => weekday.chars().take(3).collect(),
+            Some('A') => weekday.to_string(),
+            Some('H') => format!("{hour:02}"),
+            Some('I') => format!("{hour12:02}"),
+            Some('p') => if hour < 12 { "AM" } else { "PM" }.to_string(),
+            Some('M') => format!("{minute:02}"),
+            Some('S') => format!("{second:02}"),
+            Some('z') => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let minutes = offset.unsigned_abs().checked_div(60).unwrap_or(0);
+                let (hours, minutes) = (
+                    minutes.checked_div(60).unwrap_or(0),
+                    minutes.checked_rem(60).unwrap_or(0),
+                );
+                format!("{sign}{hours:02}{minutes:02}")
+            }
+            Some('s') => time.to_string(),
+            Some('F') => format_time("%Y-%m-%d", time, offset),
+            Some('T') => format_time("%H:%M:%S", time, offset),
+            Some('%') | None => "%".to_string(),
+            Some(other) => format!("%{other}"),
+        };
+        text.push_str(&field);
+    }
+    text
+}
+
+/// The year, month and day `days` days after 1970-01-01 (Howard Hinnant's
+/// `civil_from_days`).
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days.saturating_add(719_468);
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = day_of_era
+        .saturating_sub(day_of_era.div_euclid(1460))
+        .saturating_add(day_of_era.div_euclid(36_524))
+        .saturating_sub(day_of_era.div_euclid(146_096))
+        .div_euclid(365);
+    let day_of_year = day_of_era.saturating_sub(
+        year_of_era
+            .saturating_mul(365)
+            .saturating_add(year_of_era.div_euclid(4))
+            .saturating_sub(year_of_era.div_euclid(100)),
+    );
+    // Months counted from March, so the leap day comes last.
+    let month = day_of_year
+        .saturating_mul(5)
+        .saturating_add(2)
+        .div_euclid(153);
+    let day = day_of_year
+        .saturating_sub(month.saturating_mul(153).saturating_add(2).div_euclid(5))
+        .saturating_add(1);
+    let month = if month < 10 {
+        month.saturating_add(3)
+    } else {
+        month.saturating_sub(9)
+    };
+    let year = year_of_era
+        .saturating_add(era.saturating_mul(400))
+        .saturating_add(i64::from(month <= 2));
+    (year, month, day)
+}
+
+/// Seconds since 1970, UTC.
+fn now() -> i64 {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    i64::try_from(since_epoch.as_secs()).unwrap_or(i64::MAX)
+}
+
+/// How many seconds local time is ahead of UTC at `time`.
+#[cfg(unix)]
+fn utc_offset(time: i64) -> i64 {
+    let Some(time) = libc::time_t::try_from(time).ok() else {
+        return 0;
+    };
+    // SAFETY: `tm` is plain data, which all zeroes is a valid value of;
+    // `localtime_r` only reads `time` and writes `tm`, both valid here.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&raw const time, &raw mut tm) }.is_null() {
+        return 0;
+    }
+    // `tm_gmtoff` is a `c_long`, narrower than `i64` on some targets.
+    #[allow(clippy::useless_conversion)]
+    i64::from(tm.tm_gmtoff)
+}
+
+#[cfg(not(unix))]
+fn utc_offset(_time: i64) -> i64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_times_are_formatted() {
+        // 2025-10-15 09:05:07 UTC, a Wednesday.
+        let time = 1_760_519_107;
+        assert_eq!(
+            format_time("%Y-%m-%d %H:%M:%S", time, 0),
+            "2025-10-15 09:05:07"
+        );
+        assert_eq!(
+            format_time("%a %e %b %y, %I:%M %p %z", time, 2 * 3600),
+            "Wed 15 Oct 25, 11:05 AM +0200"
+        );
+        assert_eq!(
+            format_time("%A %B %F %T %% %q", time, -(9 * 3600 + 30 * 60)),
+            "Tuesday October 2025-10-14 23:35:07 % %q"
+        );
+        assert_eq!(format_time("%F", 951_782_400, 0), "2000-02-29");
+        assert_eq!(format_time("%F %a", 0, 0), "1970-01-01 Thu");
+    }
+
+    #[test]
+    fn test_variables_name_the_file_and_author() {
+        let template = Template::new(Some(Path::new("src/main.rs")), "Ada");
+        assert_eq!(template.variable("TM_FILENAME").as_deref(), Some("main.rs"));
+        assert_eq!(
+            template.variable("TM_FILENAME_BASE").as_deref(),
+            Some("main")
+        );
+        assert_eq!(template.variable("TM_DIRECTORY").as_deref(), Some("src"));
+        assert_eq!(template.variable("AUTHOR").as_deref(), Some("Ada"));
+        assert_eq!(
+            template.variable("CURRENT_YEAR"),
+            Some(template.format("%Y"))
+        );
+        assert_eq!(template.variable("NOPE"), None);
+        assert_eq!(Template::new(None, "").variable("TM_FILENAME"), None);
+    }
+}
@@ -2,122 +2,415 @@
 //!
 //! This module provides a thin layer on top of [crossterm](https://docs.rs/crossterm)
 //! for basic terminal operations: enabling/disabling raw mode, clearing the screen,
-//! printing text, etc. Instead of writing directly to `stdout()`, it calls
-//! [`io_provider::out()`](crate::io_provider::out), which returns a real or fake writer
-//! depending on whether we're in test mode. This allows us to capture output in tests
-//! without interacting with a real terminal.
+//! printing text, etc.
+//!
+//! Everything goes through a [`Terminal`], which owns a [`TerminalBackend`]:
+//! [`CrosstermBackend`] talks to the real terminal, while [`TestBackend`]
+//! keeps the output in memory and interprets it into a virtual screen. The
+//! editor is handed its terminal, so tests (and headless tools) can run it
+//! end to end without a TTY.
+
+mod backend;
+mod test_backend;
 
-use std::io::Write;
+use std::time::Duration;
 
 use crossterm::{
-    style::Print,
-    terminal::{self, disable_raw_mode, enable_raw_mode, Clear, ClearType},
+    cursor::{Hide, MoveTo, SetCursorStyle, Show},
+    event::{
+        DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    style::{
+        ContentStyle, Print, PrintStyledContent, ResetColor, SetAttribute, SetBackgroundColor,
+        SetForegroundColor, StyledContent,
+    },
+    terminal::{Clear, ClearType},
+    Command, QueueableCommand,
 };
 
+pub use backend::{CrosstermBackend, TerminalBackend};
+pub use crossterm::style::{Attribute, Color};
+pub use test_backend::TestBackend;
+
+use crate::base64;
 use crate::error::Result;
-use crate::io_provider::out;
 
 /// Represents an on‐screen position: (column, row).
 ///
 /// Note that this is *not* the same as a logical location in a text document.
 /// The editor or other modules might need to do scrolling or mapping from
 /// text lines to terminal rows.
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Position {
     pub col: usize,
     pub row: usize,
 }
 
+/// How long the screen stays inverted for a visual bell.
+pub const FLASH_DURATION: Duration = Duration::from_millis(80);
+
+/// How the user is told that a command could not be carried out.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Bell {
+    /// The terminal beeps (or does whatever it is configured to do on BEL).
+    #[default]
+    Audible,
+    /// The screen flashes briefly.
+    Visual,
+    /// Nothing happens.
+    None,
+}
+
+/// The shape of the cursor.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CursorStyle {
+    #[default]
+    Block,
+    Bar,
+    Underline,
+}
+
+/// Colors and attributes applied to printed text.
+pub type Style = ContentStyle;
+
 /// Represents the size of the terminal: (width, height).
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Size {
     pub width: usize,
     pub height: usize,
 }
 
-/// Initializes the terminal environment by enabling raw mode, clearing the
-/// screen, and moving the cursor to the top‐left.
-pub fn initialize() -> Result<()> {
-    enable_raw_mode()?;
-    clear_screen()?;
-    cursor::move_to(Position::default())?;
-    execute()
+/// A terminal to draw on. Commands are queued; nothing reaches the
+/// terminal before [`Terminal::execute`] flushes them (unless noted).
+#[derive(Debug)]
+pub struct Terminal {
+    backend: Box<dyn TerminalBackend>,
 }
 
-/// Disables raw mode and flushes any queued commands before returning.
-pub fn terminate() -> Result<()> {
-    execute()?;
-    disable_raw_mode().map_err(Into::into)
+/// The real terminal.
+impl Default for Terminal {
+    fn default() -> Self {
+        Self::new(CrosstermBackend::default())
+    }
 }
 
-/// Returns the current terminal size in (columns, rows) as a [`Size`].
-///
-/// Internally, crossterm uses `u16`, so we convert them to `usize`.
-pub fn size() -> Result<Size> {
-    let (width, height) = terminal::size()?;
-    Ok(Size {
-        width: width.into(),
-        height: height.into(),
-    })
-}
+impl Terminal {
+    #[must_use]
+    pub fn new(backend: impl TerminalBackend + 'static) -> Self {
+        Self {
+            backend: Box::new(backend),
+        }
+    }
 
-/// Clears the entire terminal screen.
-/// (No implicit flush; call [`execute()`] to flush.)
-pub fn clear_screen() -> Result<()> {
-    crossterm::queue!(out(), Clear(ClearType::All)).map_err(Into::into)
-}
+    fn queue(&mut self, command: impl Command) -> Result<()> {
+        self.backend.writer().queue(command)?;
+        Ok(())
+    }
 
-/// Clears the current line in the terminal.
-/// (No implicit flush; call [`execute()`] to flush.)
-pub fn clear_line() -> Result<()> {
-    crossterm::queue!(out(), Clear(ClearType::CurrentLine)).map_err(Into::into)
-}
+    /// Initializes the terminal environment by enabling raw mode, clearing the
+    /// screen, and moving the cursor to the top‐left.
+    ///
+    /// # Errors
+    ///
+    /// Fails if raw mode cannot be enabled or the terminal cannot be written to.
+    pub fn initialize(&mut self) -> Result<()> {
+        self.backend.enable_raw_mode()?;
+        self.clear_screen()?;
+        self.move_cursor_to(Position::default())?;
+        self.execute()
+    }
 
-/// Prints the given string to the terminal.
-/// (No implicit flush; call [`execute()`] to flush.)
-pub fn print(s: &str) -> Result<()> {
-    crossterm::queue!(out(), Print(s)).map_err(Into::into)
-}
+    /// Disables raw mode and flushes any queued commands before returning.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be flushed or raw mode cannot be disabled.
+    pub fn terminate(&mut self) -> Result<()> {
+        self.execute()?;
+        self.backend.disable_raw_mode().map_err(Into::into)
+    }
 
-/// Flushes (executes) any queued terminal commands.
-///
-/// In normal usage, you might call this infrequently. For instance, you might
-/// enqueue several prints or clears, then flush once.
-pub fn execute() -> Result<()> {
-    out().flush().map_err(Into::into)
-}
+    /// Hands the terminal back to the shell and stops the process (`Ctrl+Z`).
+    /// Once the user resumes it, the terminal is initialized again; the caller
+    /// must redraw everything, as the shell has written over the screen.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be restored or re-initialized, or if the
+    /// process cannot be stopped.
+    pub fn suspend(&mut self) -> Result<()> {
+        self.move_cursor_to(Position::default())?;
+        self.clear_screen()?;
+        self.show_cursor()?;
+        self.terminate()?;
+        self.backend.suspend_process()?;
+        self.initialize()
+    }
 
-pub mod cursor {
-    //! # `cursor` Submodule
-    //!
-    //! Provides methods to manipulate the cursor using crossterm commands, but
-    //! writes to [`io_provider::out()`](crate::io_provider::out) so that in tests
-    //! we can capture the output rather than altering the real terminal.
+    /// Returns the current terminal size in (columns, rows) as a [`Size`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal size cannot be queried.
+    pub fn size(&self) -> Result<Size> {
+        self.backend.size().map_err(Into::into)
+    }
 
-    use super::{Position, Result};
-    use crate::io_provider::out;
-    use crossterm::cursor::{Hide, MoveTo, Show};
+    /// Asks the terminal to report keys with the kitty keyboard protocol, so
+    /// combinations the legacy encoding merges (`Ctrl+I`/`Tab`,
+    /// `Shift+Enter`/`Enter`) arrive as distinct events. Terminals without the
+    /// protocol ignore the request and keep sending legacy sequences.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn enable_keyboard_enhancement(&mut self) -> Result<()> {
+        self.queue(PushKeyboardEnhancementFlags(
+            KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                | KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS,
+        ))
+    }
+
+    /// Restores the keyboard reporting that was active before
+    /// [`Terminal::enable_keyboard_enhancement`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn disable_keyboard_enhancement(&mut self) -> Result<()> {
+        self.queue(PopKeyboardEnhancementFlags)
+    }
 
-    /// Hides the terminal cursor (does not flush automatically).
-    /// (No implicit flush; call [`terminal::execute()`] to flush.)
-    pub fn hide() -> Result<()> {
-        crossterm::queue!(out(), Hide).map_err(Into::into)
+    /// Asks the terminal to send pasted text as one paste event instead of
+    /// typing it key by key, so pasted line breaks are not taken for
+    /// `Enter`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn enable_bracketed_paste(&mut self) -> Result<()> {
+        self.queue(EnableBracketedPaste)
     }
 
-    /// Shows the terminal cursor (does not flush automatically).
-    /// (No implicit flush; call [`terminal::execute()`] to flush.)
-    pub fn show() -> Result<()> {
-        crossterm::queue!(out(), Show).map_err(Into::into)
+    /// Undoes [`Terminal::enable_bracketed_paste`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn disable_bracketed_paste(&mut self) -> Result<()> {
+        self.queue(DisableBracketedPaste)
+    }
+
+    /// Asks the terminal to report mouse clicks, as [`Event::Mouse`].
+    ///
+    /// [`Event::Mouse`]: crossterm::event::Event::Mouse
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn enable_mouse_capture(&mut self) -> Result<()> {
+        self.queue(EnableMouseCapture)
+    }
+
+    /// Undoes [`Terminal::enable_mouse_capture`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn disable_mouse_capture(&mut self) -> Result<()> {
+        self.queue(DisableMouseCapture)
+    }
+
+    /// Clears the entire terminal screen.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn clear_screen(&mut self) -> Result<()> {
+        self.queue(Clear(ClearType::All))
+    }
+
+    /// Clears the current line in the terminal. Screen refreshes encode
+    /// their own line clears; this is for direct drawing.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn clear_line(&mut self) -> Result<()> {
+        self.queue(Clear(ClearType::CurrentLine))
+    }
+
+    /// Prints the given string to the terminal.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn print(&mut self, s: &str) -> Result<()> {
+        self.queue(Print(s))
+    }
+
+    /// Prints the given string in `style`, restoring the previous colors and
+    /// attributes afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn print_styled(&mut self, s: &str, style: Style) -> Result<()> {
+        self.queue(PrintStyledContent(StyledContent::new(style, s)))
+    }
+
+    /// Sets the color of the text printed from now on.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn set_foreground(&mut self, color: Color) -> Result<()> {
+        self.queue(SetForegroundColor(color))
+    }
+
+    /// Sets the background color of the text printed from now on.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn set_background(&mut self, color: Color) -> Result<()> {
+        self.queue(SetBackgroundColor(color))
+    }
+
+    /// Turns on an attribute (bold, underline, reverse, ...) for the text
+    /// printed from now on.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn set_attribute(&mut self, attribute: Attribute) -> Result<()> {
+        self.queue(SetAttribute(attribute))
+    }
+
+    /// Resets colors and attributes to the terminal defaults.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn reset(&mut self) -> Result<()> {
+        self.queue(ResetColor)
+    }
+
+    /// Puts `text` on the system clipboard with an OSC 52 escape sequence. The
+    /// terminal does the copying, so this works over SSH too, on terminals that
+    /// allow it (others ignore the sequence).
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn copy_to_clipboard(&mut self, text: &str) -> Result<()> {
+        let sequence = format!("\x1b]52;c;{}\x07", base64::encode(text.as_bytes()));
+        self.write(sequence.as_bytes())
+    }
+
+    /// Rings the bell the way `bell` asks for. A visual bell inverts the screen
+    /// colors until [`Terminal::end_flash`], which the caller does after
+    /// [`FLASH_DURATION`] rather than waiting here; unlike the other
+    /// commands here, it flushes.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn ring_bell(&mut self, bell: Bell) -> Result<()> {
+        match bell {
+            Bell::Audible => self.write(b"\x07"),
+            Bell::Visual => {
+                // DECSCNM: reverse video on, then off again.
+                self.write(b"\x1b[?5h")?;
+                self.execute()
+            }
+            Bell::None => Ok(()),
+        }
+    }
+
+    /// Puts the colors a visual bell inverted back.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn end_flash(&mut self) -> Result<()> {
+        self.write(b"\x1b[?5l")?;
+        self.execute()
+    }
+
+    /// Writes already-encoded output (e.g. a whole frame) to the terminal.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.backend.writer().write_all(bytes).map_err(Into::into)
+    }
+
+    /// Flushes (executes) any queued terminal commands.
+    ///
+    /// In normal usage, you might call this infrequently. For instance, you might
+    /// enqueue several prints or clears, then flush once.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be flushed.
+    pub fn execute(&mut self) -> Result<()> {
+        self.backend.writer().flush().map_err(Into::into)
+    }
+
+    /// Hides the terminal cursor.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn hide_cursor(&mut self) -> Result<()> {
+        self.queue(Hide)
+    }
+
+    /// Shows the terminal cursor.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn show_cursor(&mut self) -> Result<()> {
+        self.queue(Show)
+    }
+
+    /// Changes the shape of the cursor. The shape stays until it is changed
+    /// again or [`Terminal::reset_cursor_style`] restores the user's own.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn set_cursor_style(&mut self, style: CursorStyle) -> Result<()> {
+        self.queue(match style {
+            CursorStyle::Block => SetCursorStyle::SteadyBlock,
+            CursorStyle::Bar => SetCursorStyle::SteadyBar,
+            CursorStyle::Underline => SetCursorStyle::SteadyUnderScore,
+        })
+    }
+
+    /// Restores the cursor shape configured in the user's terminal.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal cannot be written to.
+    pub fn reset_cursor_style(&mut self) -> Result<()> {
+        self.queue(SetCursorStyle::DefaultUserShape)
     }
 
     /// Moves the cursor to the given [`Position`]: (col, row).
     ///
-    /// If `col` or `row` exceed `u16::MAX`, it returns a conversion error.
-    /// (No implicit flush; call [`terminal::execute()`] to flush.)
-    pub fn move_to(pos: Position) -> Result<()> {
+    /// # Errors
+    ///
+    /// Fails if `col` or `row` do not fit in a `u16`, or if the terminal
+    /// cannot be written to.
+    pub fn move_cursor_to(&mut self, pos: Position) -> Result<()> {
         let col_u16: u16 = pos.col.try_into()?;
         let row_u16: u16 = pos.row.try_into()?;
-        crossterm::queue!(out(), MoveTo(col_u16, row_u16)).map_err(Into::into)
+        self.queue(MoveTo(col_u16, row_u16))
     }
 }
 
@@ -125,26 +418,33 @@ pub mod cursor {
 mod tests {
     //! # Terminal Unit Tests
     //!
-    //! These tests confirm that the terminal module writes the expected ANSI
-    //! sequences to our fake writer (in test mode). We then parse those output
-    //! bytes to verify correctness. It also checks for certain functions that
-    //! do not necessarily write sequences (like `size()`).
+    //! These tests confirm that the terminal writes the expected ANSI
+    //! sequences to an in-memory [`TestBackend`], and where it matters, what
+    //! they leave on its virtual screen.
 
     use super::*;
-    use crate::io_provider::take_out_contents;
 
-    /// Checks if `initialize()` produces sequences for raw mode enable, screen clear,
-    /// and moving cursor to (0, 0). We primarily verify the screen clear and cursor move
-    /// since raw mode enabling does not produce a visible ANSI code in crossterm.
+    fn terminal() -> (Terminal, TestBackend) {
+        let backend = TestBackend::new(Size {
+            width: 20,
+            height: 5,
+        });
+        (Terminal::new(backend.clone()), backend)
+    }
+
+    fn output(backend: &TestBackend) -> String {
+        String::from_utf8_lossy(&backend.take_output()).into_owned()
+    }
+
+    /// Checks if `initialize()` enables raw mode, clears the screen, and
+    /// moves the cursor to (0, 0).
     #[test]
     fn test_initialize() {
-        initialize().unwrap();
+        let (mut terminal, backend) = terminal();
+        terminal.initialize().unwrap();
 
-        let contents = take_out_contents();
-        let output = String::from_utf8_lossy(&contents);
-
-        // Typically, crossterm clearing the screen might include "[2J"
-        // and moving the cursor to the top-left is often something like "[H" or "[1;1H".
+        let output = output(&backend);
+        assert!(backend.is_raw_mode());
         assert!(
             output.contains("[2J"),
             "Expected '[2J' for clearing screen in initialize()"
@@ -155,116 +455,216 @@ mod tests {
         );
     }
 
-    /// Tests that `terminate()` doesn't produce an error. It should flush queued commands
-    /// and disable raw mode (which doesn't typically generate visible ANSI codes).
+    /// Tests that `terminate()` flushes and disables raw mode without
+    /// clearing the screen again.
     #[test]
     fn test_terminate() {
-        // We won't queue anything special here; just ensure no error
-        terminate().unwrap();
-
-        let contents = take_out_contents();
-        let output = String::from_utf8_lossy(&contents);
+        let (mut terminal, backend) = terminal();
+        terminal.initialize().unwrap();
+        let _ = backend.take_output();
 
-        // Disabling raw mode does not typically produce ANSI sequences that remain in the buffer.
-        // So we just confirm there's nothing suspicious or no error occurred.
+        terminal.terminate().unwrap();
+        assert!(!backend.is_raw_mode());
         assert!(
-            !output.contains("[2J"),
+            !output(&backend).contains("[2J"),
             "Did not expect a second screen clear in terminate()"
         );
     }
 
-    /// Checks the `size()` function. This calls `crossterm::terminal::size()`,
-    /// which depends on the environment. We primarily verify it doesn't error
-    /// and that the reported size is not zero.
+    /// Suspending restores the terminal, stops the process, then
+    /// initializes the terminal again: the screen is cleared once on the way
+    /// out and once on the way back.
+    #[test]
+    fn test_suspend() {
+        let (mut terminal, backend) = terminal();
+        terminal.suspend().unwrap();
+
+        let output = output(&backend);
+        assert_eq!(backend.suspensions(), 1);
+        assert!(backend.is_raw_mode());
+        assert_eq!(output.matches("[2J").count(), 2, "got: {output}");
+        assert!(output.contains("[?25h"), "Expected the cursor to be shown");
+    }
+
     #[test]
     fn test_size() {
-        let sz = size().unwrap();
-        // In many terminals, width and height should be > 0.
-        // But there's no universal guarantee in all CI or container environments.
-        // We do a basic sanity check:
-        assert!(
-            sz.width > 0,
-            "Expected terminal width to be > 0 (actual: {})",
-            sz.width
-        );
-        assert!(
-            sz.height > 0,
-            "Expected terminal height to be > 0 (actual: {})",
-            sz.height
+        let (terminal, _) = terminal();
+        assert_eq!(
+            terminal.size().unwrap(),
+            Size {
+                width: 20,
+                height: 5
+            }
         );
     }
 
     #[test]
-    fn test_clear_screen() {
-        clear_screen().unwrap();
-        execute().unwrap();
+    fn test_keyboard_enhancement_push_and_pop() {
+        let (mut terminal, backend) = terminal();
+        terminal.enable_keyboard_enhancement().unwrap();
+        terminal.disable_keyboard_enhancement().unwrap();
+        terminal.execute().unwrap();
+
+        // Flags 1 (disambiguate) | 4 (alternate keys), then pop one entry.
+        assert_eq!(backend.take_output(), b"\x1b[>5u\x1b[<1u");
+    }
 
-        let contents = take_out_contents();
-        let output = String::from_utf8_lossy(&contents);
+    #[test]
+    fn test_bracketed_paste_on_and_off() {
+        let (mut terminal, backend) = terminal();
+        terminal.enable_bracketed_paste().unwrap();
+        terminal.disable_bracketed_paste().unwrap();
+        terminal.execute().unwrap();
+        assert_eq!(backend.take_output(), b"\x1b[?2004h\x1b[?2004l");
+    }
+
+    #[test]
+    fn test_clear_screen() {
+        let (mut terminal, backend) = terminal();
+        terminal.print("text").unwrap();
+        terminal.clear_screen().unwrap();
+        terminal.execute().unwrap();
 
         // crossterm typically uses "\x1B[2J" to clear the screen
-        assert!(
-            output.contains("[2J"),
-            "Expected [2J in clear screen command"
-        );
+        assert!(output(&backend).contains("[2J"));
+        assert!(backend.screen().iter().all(String::is_empty));
     }
 
     /// Tests clearing the current line; crossterm emits "[2K".
     #[test]
     fn test_clear_line() {
-        clear_line().unwrap();
-        execute().unwrap();
+        let (mut terminal, backend) = terminal();
+        terminal.print("text").unwrap();
+        terminal.clear_line().unwrap();
+        terminal.execute().unwrap();
 
-        let contents = take_out_contents();
-        let output = String::from_utf8_lossy(&contents);
-        dbg!(&output);
-
-        // Usually, "[K" is the ANSI for "Clear line"
-        // But crossterm often uses "[2K" for "Clear current line"
-        assert!(output.contains("[2K"), "Expected [2K in clear line command");
+        assert!(
+            output(&backend).contains("[2K"),
+            "Expected [2K in clear line command"
+        );
+        assert_eq!(backend.screen()[0], "");
     }
 
     #[test]
     fn test_print() {
-        print("Hello, world!").unwrap();
-        execute().unwrap();
+        let (mut terminal, backend) = terminal();
+        terminal.print("Hello, world!").unwrap();
+        terminal.execute().unwrap();
+
+        assert_eq!(backend.screen()[0], "Hello, world!");
+    }
 
-        let contents = take_out_contents();
-        let output = String::from_utf8_lossy(&contents);
-        assert!(output.contains("Hello, world!"));
+    #[test]
+    fn test_print_styled() {
+        let (mut terminal, backend) = terminal();
+        let style = Style {
+            foreground_color: Some(Color::Red),
+            ..Style::default()
+        };
+        terminal.print_styled("warning", style).unwrap();
+        terminal.execute().unwrap();
+
+        // Red foreground, the text, then back to the default foreground.
+        assert_eq!(output(&backend), "\x1b[38;5;9mwarning\x1b[39m");
+        assert_eq!(backend.screen()[0], "warning");
+    }
+
+    #[test]
+    fn test_set_colors_attributes_and_reset() {
+        let (mut terminal, backend) = terminal();
+        terminal
+            .set_foreground(Color::Rgb { r: 1, g: 2, b: 3 })
+            .unwrap();
+        terminal.set_background(Color::Blue).unwrap();
+        terminal.set_attribute(Attribute::Bold).unwrap();
+        terminal.reset().unwrap();
+        terminal.execute().unwrap();
+
+        let output = output(&backend);
+        assert!(output.contains("[38;2;1;2;3m"), "foreground: {output}");
+        assert!(output.contains("[48;5;12m"), "background: {output}");
+        assert!(output.contains("[1m"), "bold: {output}");
+        assert!(output.ends_with("\x1b[0m"), "reset: {output}");
+    }
+
+    #[test]
+    fn test_copy_to_clipboard() {
+        let (mut terminal, backend) = terminal();
+        terminal.copy_to_clipboard("foobar").unwrap();
+        terminal.execute().unwrap();
+
+        assert_eq!(backend.take_output(), b"\x1b]52;c;Zm9vYmFy\x07");
+    }
+
+    #[test]
+    fn test_ring_bell() {
+        let (mut terminal, backend) = terminal();
+        terminal.ring_bell(Bell::Audible).unwrap();
+        terminal.execute().unwrap();
+        assert_eq!(backend.take_output(), b"\x07");
+
+        terminal.ring_bell(Bell::Visual).unwrap();
+        assert_eq!(backend.take_output(), b"\x1b[?5h");
+        terminal.end_flash().unwrap();
+        assert_eq!(backend.take_output(), b"\x1b[?5l");
+
+        terminal.ring_bell(Bell::None).unwrap();
+        assert!(backend.take_output().is_empty());
+    }
+
+    #[test]
+    fn test_write() {
+        let (mut terminal, backend) = terminal();
+        terminal.write(b"raw bytes").unwrap();
+        terminal.execute().unwrap();
+
+        assert_eq!(backend.take_output(), b"raw bytes");
     }
 
     #[test]
     fn test_cursor_hide_show() {
-        cursor::hide().unwrap();
-        cursor::show().unwrap();
-        execute().unwrap();
+        let (mut terminal, backend) = terminal();
+        terminal.hide_cursor().unwrap();
+        terminal.show_cursor().unwrap();
+        terminal.execute().unwrap();
 
-        let contents = take_out_contents();
-        let output = String::from_utf8_lossy(&contents);
+        let output = output(&backend);
         // crossterm uses "[?25l" for Hide, "[?25h" for Show
         assert!(output.contains("[?25l"), "Expected hide command [\"?25l\"]");
         assert!(output.contains("[?25h"), "Expected show command [\"?25h\"]");
     }
 
     #[test]
-    fn test_cursor_move_to_ok() {
-        cursor::move_to(Position { col: 10, row: 5 }).unwrap();
-        execute().unwrap();
+    fn test_cursor_styles() {
+        let (mut terminal, backend) = terminal();
+        terminal.set_cursor_style(CursorStyle::Block).unwrap();
+        terminal.set_cursor_style(CursorStyle::Underline).unwrap();
+        terminal.set_cursor_style(CursorStyle::Bar).unwrap();
+        terminal.reset_cursor_style().unwrap();
+        terminal.execute().unwrap();
+
+        assert_eq!(backend.take_output(), b"\x1b[2 q\x1b[4 q\x1b[6 q\x1b[0 q");
+    }
 
-        let contents = take_out_contents();
-        let output = String::from_utf8_lossy(&contents);
-        // Crossterm with MoveTo(10,5) often => "\x1B[6;11H" (ESC [ row+1 ; col+1 H)
-        assert!(
-            output.contains("[6;11H"),
-            "Expected some form of row=5+1; col=10+1"
-        );
+    #[test]
+    fn test_cursor_move_to_ok() {
+        let (mut terminal, backend) = terminal();
+        terminal
+            .move_cursor_to(Position { col: 10, row: 3 })
+            .unwrap();
+        terminal.execute().unwrap();
+
+        // Crossterm with MoveTo(10,3) => "\x1B[4;11H" (ESC [ row+1 ; col+1 H)
+        assert!(output(&backend).contains("[4;11H"));
+        assert_eq!(backend.cursor(), Position { col: 10, row: 3 });
     }
 
     #[test]
     fn test_cursor_move_to_overflow() {
         // col=70000 => exceeds u16::MAX => should fail
-        let err = cursor::move_to(Position { col: 70000, row: 5 })
+        let (mut terminal, _) = terminal();
+        let err = terminal
+            .move_cursor_to(Position { col: 70000, row: 5 })
             .unwrap_err()
             .to_string();
         assert!(
@@ -0,0 +1,101 @@
+//! # Terminal Backends
+//!
+//! The few things [`Terminal`](super::Terminal) cannot do by writing escape
+//! sequences: switching raw mode, asking for the window size, stopping the
+//! process for job control, and the output stream itself.
+
+use std::{
+    fmt,
+    io::{self, Stdout, Write},
+};
+
+use super::Size;
+
+pub trait TerminalBackend: fmt::Debug {
+    /// Switches the terminal to raw mode (no line buffering, no echo).
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal mode cannot be changed.
+    fn enable_raw_mode(&mut self) -> io::Result<()>;
+
+    /// Switches raw mode off again.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the terminal mode cannot be changed.
+    fn disable_raw_mode(&mut self) -> io::Result<()>;
+
+    /// The size of the terminal window.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the size cannot be queried.
+    fn size(&self) -> io::Result<Size>;
+
+    /// Stops the process as if the user pressed `Ctrl+Z` in a shell,
+    /// returning once it is resumed.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the process cannot be stopped.
+    fn suspend_process(&mut self) -> io::Result<()>;
+
+    /// Where escape sequences and text are written to.
+    fn writer(&mut self) -> &mut dyn Write;
+}
+
+/// The real terminal, through crossterm and `stdout`.
+#[derive(Debug)]
+pub struct CrosstermBackend {
+    stdout: Stdout,
+}
+
+impl Default for CrosstermBackend {
+    fn default() -> Self {
+        Self {
+            stdout: io::stdout(),
+        }
+    }
+}
+
+impl TerminalBackend for CrosstermBackend {
+    fn enable_raw_mode(&mut self) -> io::Result<()> {
+        crossterm::terminal::enable_raw_mode()
+    }
+
+    fn disable_raw_mode(&mut self) -> io::Result<()> {
+        crossterm::terminal::disable_raw_mode()
+    }
+
+    fn size(&self) -> io::Result<Size> {
+        let (width, height) = crossterm::terminal::size()?;
+        Ok(Size {
+            width: width.into(),
+            height: height.into(),
+        })
+    }
+
+    #[cfg(unix)]
+    fn suspend_process(&mut self) -> io::Result<()> {
+        // SAFETY: `raise` has no memory-safety preconditions; SIGTSTP has its
+        // default disposition, which stops the process until SIGCONT.
+        if unsafe { libc::raise(libc::SIGTSTP) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn suspend_process(&mut self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "suspending is not supported on this platform",
+        ))
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        &mut self.stdout
+    }
+}
@@ -0,0 +1,347 @@
+//! # In-Memory Terminal
+//!
+//! A [`TerminalBackend`] for tests and headless use. Everything written to
+//! it is kept, and also interpreted into a grid of characters the way a
+//! terminal would show it, so tests can assert on what the user would see
+//! rather than on escape sequences.
+//!
+//! Only the sequences the editor emits are understood: cursor movement,
+//! screen and line clears, and printable text. Styles, modes and OSC
+//! sequences are skipped.
+
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
+
+use super::{backend::TerminalBackend, Position, Size};
+use crate::layout::char_width;
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+/// Fills the right half of a double-width character in the grid.
+const CONTINUATION: char = '\0';
+
+/// Clones share the same state, so a test can keep one handle while the
+/// editor owns another.
+#[derive(Debug, Clone)]
+pub struct TestBackend {
+    state: Rc<RefCell<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    size: Size,
+    /// Everything written since the last [`TestBackend::take_output`].
+    output: Vec<u8>,
+    /// Bytes of an escape sequence or character that is not complete yet.
+    pending: Vec<u8>,
+    grid: Vec<Vec<char>>,
+    cursor: Position,
+    raw_mode: bool,
+    suspensions: usize,
+    bells: usize,
+}
+
+impl TestBackend {
+    /// A blank terminal of the given size.
+    #[must_use]
+    pub fn new(size: Size) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(State {
+                size,
+                output: Vec::new(),
+                pending: Vec::new(),
+                grid: blank_grid(size),
+                cursor: Position::default(),
+                raw_mode: false,
+                suspensions: 0,
+                bells: 0,
+            })),
+        }
+    }
+
+    /// Takes (removes) everything written so far.
+    #[must_use]
+    pub fn take_output(&self) -> Vec<u8> {
+        std::mem::take(&mut self.state.borrow_mut().output)
+    }
+
+    /// The visible text, one string per row, without trailing blanks.
+    #[must_use]
+    pub fn screen(&self) -> Vec<String> {
+        self.state
+            .borrow()
+            .grid
+            .iter()
+            .map(|row| {
+                let text: String = row.iter().filter(|&&ch| ch != CONTINUATION).collect();
+                text.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    /// Where the cursor was left.
+    #[must_use]
+    pub fn cursor(&self) -> Position {
+        self.state.borrow().cursor
+    }
+
+    /// Changes the reported size (the grid is cleared), e.g. before sending
+    /// a resize event.
+    pub fn resize(&self, size: Size) {
+        let mut state = self.state.borrow_mut();
+        state.size = size;
+        state.grid = blank_grid(size);
+    }
+
+    #[must_use]
+    pub fn is_raw_mode(&self) -> bool {
+        self.state.borrow().raw_mode
+    }
+
+    /// How many times the process would have been suspended.
+    #[must_use]
+    pub fn suspensions(&self) -> usize {
+        self.state.borrow().suspensions
+    }
+
+    /// How many times the audible bell was rung.
+    #[must_use]
+    pub fn bells(&self) -> usize {
+        self.state.borrow().bells
+    }
+}
+
+impl TerminalBackend for TestBackend {
+    fn enable_raw_mode(&mut self) -> io::Result<()> {
+        self.state.borrow_mut().raw_mode = true;
+        Ok(())
+    }
+
+    fn disable_raw_mode(&mut self) -> io::Result<()> {
+        self.state.borrow_mut().raw_mode = false;
+        Ok(())
+    }
+
+    fn size(&self) -> io::Result<Size> {
+        Ok(self.state.borrow().size)
+    }
+
+    fn suspend_process(&mut self) -> io::Result<()> {
+        let mut state = self.state.borrow_mut();
+        state.suspensions = state.suspensions.saturating_add(1);
+        Ok(())
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        self
+    }
+}
+
+impl Write for TestBackend {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.borrow_mut();
+        state.output.extend_from_slice(buf);
+        state.pending.extend_from_slice(buf);
+        state.interpret();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn blank_grid(size: Size) -> Vec<Vec<char>> {
+    vec![vec![' '; size.width]; size.height]
+}
+
+impl State {
+    /// Applies every complete sequence or character in `pending`.
+    fn interpret(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        let mut rest = pending.as_slice();
+        while let Some(&first) = rest.first() {
+            let consumed = match first {
+                ESC => self.escape(rest),
+                BEL => {
+                    self.bells = self.bells.saturating_add(1);
+                    Some(1)
+                }
+                b'\r' => {
+                    self.cursor.col = 0;
+                    Some(1)
+                }
+                b'\n' => {
+                    self.cursor.row = self
+                        .cursor
+                        .row
+                        .saturating_add(1)
+                        .min(self.size.height.saturating_sub(1));
+                    Some(1)
+                }
+                _ => self.text(rest),
+            };
+            let Some(consumed) = consumed else {
+                // Incomplete; wait for the next write.
+                break;
+            };
+            rest = rest.get(consumed..).unwrap_or_default();
+        }
+        self.pending = rest.to_vec();
+    }
+
+    /// Handles the escape sequence at the start of `bytes`, returning its
+    /// length, or `None` if it is not complete yet.
+    fn escape(&mut self, bytes: &[u8]) -> Option<usize> {
+        match bytes.get(1)? {
+            b'[' => {
+                let end = bytes
+                    .iter()
+                    .skip(2)
+                    .position(|byte| (0x40..=0x7e).contains(byte))?
+                    .saturating_add(2);
+                let params = std::str::from_utf8(bytes.get(2..end)?).unwrap_or_default();
+                self.csi(params, *bytes.get(end)?);
+                Some(end.saturating_add(1))
+            }
+            b']' => {
+                // OSC, terminated by BEL or ST (ESC \).
+                let body = bytes.get(2..)?;
+                body.iter()
+                    .enumerate()
+                    .find_map(|(index, &byte)| match byte {
+                        BEL => Some(index.saturating_add(3)),
+                        ESC if body.get(index.saturating_add(1)) == Some(&b'\\') => {
+                            Some(index.saturating_add(4))
+                        }
+                        _ => None,
+                    })
+            }
+            _ => Some(2),
+        }
+    }
+
+    fn csi(&mut self, params: &str, command: u8) {
+        if params.starts_with(['?', '>', '<', '=']) {
+            // Private modes (cursor visibility, synchronized output, ...).
+            return;
+        }
+        let numbers: Vec<usize> = params
+            .split(';')
+            .map(|param| param.parse().unwrap_or(0))
+            .collect();
+        let first = numbers.first().copied().unwrap_or(0);
+        match command {
+            b'H' => {
+                let row = first.saturating_sub(1);
+                let col = numbers.get(1).copied().unwrap_or(0).saturating_sub(1);
+                self.cursor = Position {
+                    col: col.min(self.size.width.saturating_sub(1)),
+                    row: row.min(self.size.height.saturating_sub(1)),
+                };
+            }
+            b'J' if first == 2 => self.grid = blank_grid(self.size),
+            b'K' => {
+                let Position { col, row } = self.cursor;
+                if let Some(cells) = self.grid.get_mut(row) {
+                    let from = if first == 2 { 0 } else { col };
+                    for cell in cells.iter_mut().skip(from) {
+                        *cell = ' ';
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Prints the character at the start of `bytes`, returning its length.
+    fn text(&mut self, bytes: &[u8]) -> Option<usize> {
+        let len = match bytes.first()? {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            _ => 4,
+        };
+        let Ok(text) = std::str::from_utf8(bytes.get(..len)?) else {
+            return Some(1);
+        };
+        let Some(ch) = text.chars().next() else {
+            return Some(len);
+        };
+        let width = char_width(ch);
+        if width == 0 || ch.is_control() {
+            return Some(len);
+        }
+        let Position { col, row } = self.cursor;
+        if let Some(cells) = self.grid.get_mut(row) {
+            if let Some(cell) = cells.get_mut(col) {
+                *cell = ch;
+            }
+            if width == 2 {
+                if let Some(cell) = cells.get_mut(col.saturating_add(1)) {
+                    *cell = CONTINUATION;
+                }
+            }
+        }
+        self.cursor.col = col.saturating_add(width).min(self.size.width);
+        Some(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend() -> TestBackend {
+        TestBackend::new(Size {
+            width: 10,
+            height: 3,
+        })
+    }
+
+    #[test]
+    fn test_text_and_cursor_movement() {
+        let mut backend = backend();
+        backend
+            .write_all(b"abc\x1b[2;3Hxy\x1b[31mz\x1b[0m")
+            .unwrap();
+        assert_eq!(backend.screen(), vec!["abc", "  xyz", ""]);
+        assert_eq!(backend.cursor(), Position { col: 5, row: 1 });
+    }
+
+    #[test]
+    fn test_sequences_split_across_writes() {
+        let mut backend = backend();
+        backend.write_all(b"\x1b[2").unwrap();
+        backend.write_all(b";1Hh\xc3").unwrap();
+        backend.write_all(b"\xa9").unwrap();
+        assert_eq!(backend.screen()[1], "hé");
+    }
+
+    #[test]
+    fn test_clears_and_wide_characters() {
+        let mut backend = backend();
+        backend.write_all("日本\r\nline".as_bytes()).unwrap();
+        assert_eq!(backend.screen()[0], "日本");
+        backend.write_all(b"\x1b[2;3H\x1b[K").unwrap();
+        assert_eq!(backend.screen()[1], "li");
+        backend.write_all(b"\x1b[2J").unwrap();
+        assert_eq!(backend.screen(), vec!["", "", ""]);
+    }
+
+    #[test]
+    fn test_osc_and_private_modes_are_skipped() {
+        let mut backend = backend();
+        backend
+            .write_all(b"\x1b[?25l\x1b]52;c;Zm9v\x07ok\x1b[?2026h\x07")
+            .unwrap();
+        assert_eq!(backend.screen()[0], "ok");
+        assert_eq!(backend.bells(), 1);
+        assert!(!backend.take_output().is_empty());
+        assert!(backend.take_output().is_empty());
+    }
+}
@@ -0,0 +1,442 @@
+//! # Themes
+//!
+//! A theme maps the parts of the screen (its *elements*) to colors and
+//! attributes. Two themes are built in, `dark` (the default) and `light`;
+//! more can be added as `themes/<name>.toml` in the config directory (see
+//! [`dirs::config_dir()`]), where a theme with a built-in name replaces the
+//! built-in one. Switch with `set theme=light`, or `theme = "light"` in the
+//! `[options]` of the config file.
+//!
+//! A theme file uses the same small TOML subset as the config file:
+//!
+//! ```toml
+//! # Start from a built-in theme and change a few elements.
+//! inherits = "dark"
+//!
+//! [styles]
+//! status_bar = "black on cyan bold"
+//! "syntax.comment" = "#808080 italic"
+//! ```
+//!
+//! A style lists a foreground color, `on` and a background color, and any
+//! of `bold`, `dim`, `italic`, `underlined` and `reverse`; each part is
+//! optional. Colors are names (`red`, `dark_blue`, `grey`, ...), numbers
+//! from the 256-color palette, or `#rrggbb`.
+//!
+//! An element the theme does not mention falls back to its parent
+//! (`syntax.keyword.control` to `syntax.keyword` to `syntax`), and finally
+//! to `text`.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use crossterm::style::{Attribute, Color, ContentStyle};
+
+use crate::{config, dirs};
+
+/// The theme used unless another is chosen.
+pub const DEFAULT_THEME: &str = "dark";
+
+/// The elements themes can style.
+pub const ELEMENTS: &[&str] = &[
+    "text",
+    "status_bar",
+    "status_bar.inactive",
+    "command_line",
+    "message.warning",
+    "message.error",
+    "empty_line",
+    "line_number",
+    "selection",
+    "search",
+    "matching_bracket",
+    "popup",
+    "markdown.heading",
+    "markdown.code",
+    "markdown.strong",
+    "markdown.emphasis",
+    "markdown.link",
+    "gutter",
+    "sign.error",
+    "sign.warning",
+    "sign.info",
+    "sign.added",
+    "sign.modified",
+    "sign.removed",
+    "diff.added",
+    "diff.changed",
+    "diagnostic.error",
+    "diagnostic.warning",
+    "diagnostic.info",
+    "spell.bad",
+    "syntax.comment",
+    "syntax.comment.todo",
+    "syntax.keyword",
+    "syntax.string",
+    "syntax.number",
+    "syntax.inserted",
+    "syntax.deleted",
+    "syntax.type",
+    "syntax.function",
+];
+
+const DARK: &str = r#"
+[styles]
+status_bar = "black on grey"
+"status_bar.inactive" = "black on dark_grey"
+"message.warning" = "dark_yellow on grey bold"
+"message.error" = "dark_red on grey bold"
+empty_line = "dark_blue"
+line_number = "dark_grey"
+selection = "white on dark_blue"
+search = "black on yellow"
+matching_bracket = "on dark_grey bold"
+popup = "white on dark_grey"
+"markdown.heading" = "bold"
+"markdown.code" = "cyan"
+"markdown.strong" = "bold"
+"markdown.emphasis" = "italic"
+"markdown.link" = "blue underlined"
+"sign.error" = "red bold"
+"sign.warning" = "yellow bold"
+"sign.info" = "cyan"
+"sign.added" = "green"
+"sign.modified" = "blue"
+"sign.removed" = "red"
+"diff.added" = "on dark_green"
+"diff.changed" = "on dark_blue"
+"diagnostic.error" = "red underlined"
+"diagnostic.warning" = "yellow underlined"
+"diagnostic.info" = "underlined"
+"spell.bad" = "magenta underlined"
+"syntax.comment" = "dark_grey italic"
+"syntax.comment.todo" = "yellow bold"
+"syntax.keyword" = "magenta bold"
+"syntax.string" = "green"
+"syntax.number" = "cyan"
+"syntax.inserted" = "green"
+"syntax.deleted" = "red"
+"syntax.type" = "yellow"
+"syntax.function" = "blue"
+"#;
+
+const LIGHT: &str = r#"
+[styles]
+status_bar = "white on dark_grey"
+"status_bar.inactive" = "dark_grey on grey"
+"message.warning" = "yellow on dark_grey bold"
+"message.error" = "red on dark_grey bold"
+empty_line = "blue"
+line_number = "dark_grey"
+selection = "black on cyan"
+search = "black on yellow"
+matching_bracket = "on grey bold"
+popup = "black on grey"
+"markdown.heading" = "bold"
+"markdown.code" = "dark_blue"
+"markdown.strong" = "bold"
+"markdown.emphasis" = "italic"
+"markdown.link" = "dark_blue underlined"
+"sign.error" = "dark_red bold"
+"sign.warning" = "dark_yellow bold"
+"sign.info" = "dark_cyan"
+"sign.added" = "dark_green"
+"sign.modified" = "dark_blue"
+"sign.removed" = "dark_red"
+"diff.added" = "on green"
+"diff.changed" = "on cyan"
+"diagnostic.error" = "dark_red underlined"
+"diagnostic.warning" = "dark_yellow underlined"
+"diagnostic.info" = "underlined"
+"spell.bad" = "dark_magenta underlined"
+"syntax.comment" = "dark_grey italic"
+"syntax.comment.todo" = "dark_red bold"
+"syntax.keyword" = "dark_magenta bold"
+"syntax.string" = "dark_green"
+"syntax.number" = "dark_cyan"
+"syntax.inserted" = "dark_green"
+"syntax.deleted" = "dark_red"
+"syntax.type" = "dark_yellow"
+"syntax.function" = "dark_blue"
+"#;
+
+const BUILT_IN: &[(&str, &str)] = &[("dark", DARK), ("light", LIGHT)];
+
+/// How many `inherits` steps are followed before giving up on a cycle.
+const MAX_INHERITANCE: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub name: String,
+    styles: HashMap<String, ContentStyle>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::load_from(None, DEFAULT_THEME).unwrap_or_else(|_| Self {
+            name: DEFAULT_THEME.to_string(),
+            styles: HashMap::new(),
+        })
+    }
+}
+
+impl Theme {
+    /// Loads the theme called `name`, from the user's theme directory or the
+    /// built-in themes.
+    ///
+    /// # Errors
+    ///
+    /// Fails with a message if there is no such theme or its file is broken.
+    pub fn load(name: &str) -> Result<Self, String> {
+        let dir = dirs::config_dir().map(|dir| dir.join("themes"));
+        Self::load_from(dir.as_deref(), name)
+    }
+
+    /// Like [`Theme::load`], looking for user themes in `dir`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with a message if there is no such theme or its file is broken.
+    pub fn load_from(dir: Option<&Path>, name: &str) -> Result<Self, String> {
+        let mut chain = Vec::new();
+        let mut next = Some(name.to_string());
+        while let Some(name) = next {
+            if chain.len() >= MAX_INHERITANCE {
+                return Err(format!("theme {name}: too many `inherits`"));
+            }
+            let text = read_theme(dir, &name)?;
+            let (inherits, styles) =
+                parse(&text).map_err(|problem| format!("theme {name}: {problem}"))?;
+            chain.push(styles);
+            next = inherits;
+        }
+        let mut styles = HashMap::new();
+        // The theme that was asked for last, so it overrides what it inherits.
+        for layer in chain.into_iter().rev() {
+            styles.extend(layer);
+        }
+        Ok(Self {
+            name: name.to_string(),
+            styles,
+        })
+    }
+
+    /// The names of all themes: the built-in ones and the user's.
+    #[must_use]
+    pub fn available() -> Vec<String> {
+        let mut names: Vec<String> = BUILT_IN.iter().map(|(name, _)| name.to_string()).collect();
+        if let Some(entries) =
+            dirs::config_dir().and_then(|dir| fs::read_dir(dir.join("themes")).ok())
+        {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|ext| ext == "toml") {
+                    if let Some(stem) = path.file_stem() {
+                        names.push(stem.to_string_lossy().into_owned());
+                    }
+                }
+            }
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// The style of `element`, falling back to its parents and then `text`.
+    #[must_use]
+    pub fn style(&self, element: &str) -> ContentStyle {
+        let mut element = element;
+        loop {
+            if let Some(style) = self.styles.get(element) {
+                return *style;
+            }
+            match element.rsplit_once('.') {
+                Some((parent, _)) => element = parent,
+                None => break,
+            }
+        }
+        self.styles.get("text").copied().unwrap_or_default()
+    }
+}
+
+fn read_theme(dir: Option<&Path>, name: &str) -> Result<String, String> {
+    if let Some(dir) = dir {
+        let path = dir.join(format!("{name}.toml"));
+        match fs::read_to_string(&path) {
+            Ok(text) => return Ok(text),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(format!("cannot read {}: {err}", path.display())),
+        }
+    }
+    BUILT_IN
+        .iter()
+        .find(|(built_in, _)| *built_in == name)
+        .map(|(_, text)| (*text).to_string())
+        .ok_or_else(|| format!("Unknown theme: {name}"))
+}
+
+/// Parses a theme file into the theme it inherits from (if any) and its
+/// styles.
+fn parse(text: &str) -> Result<(Option<String>, HashMap<String, ContentStyle>), String> {
+    let mut inherits = None;
+    let mut styles = HashMap::new();
+    let mut section = String::new();
+    for (index, line) in text.lines().enumerate() {
+        let number = index.saturating_add(1);
+        let line = config::strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.trim().to_string();
+            if section != "styles" {
+                return Err(format!("line {number}: unknown section [{section}]"));
+            }
+            continue;
+        }
+        let (key, value) = config::parse_pair(line)
+            .ok_or_else(|| format!("line {number}: expected `key = value`"))?;
+        match (section.as_str(), key.as_str()) {
+            ("", "inherits") => inherits = Some(value),
+            ("", _) => return Err(format!("line {number}: unknown setting `{key}`")),
+            _ => {
+                let style =
+                    parse_style(&value).map_err(|problem| format!("line {number}: {problem}"))?;
+                styles.insert(key, style);
+            }
+        }
+    }
+    Ok((inherits, styles))
+}
+
+/// Parses a style such as `white on dark_blue bold`.
+///
+/// # Errors
+///
+/// Fails with a message naming the word that is not a color or attribute.
+pub fn parse_style(text: &str) -> Result<ContentStyle, String> {
+    let mut style = ContentStyle::default();
+    let mut words = text.split_whitespace();
+    while let Some(word) = words.next() {
+        let attribute = match word {
+            "bold" => Attribute::Bold,
+            "dim" => Attribute::Dim,
+            "italic" => Attribute::Italic,
+            "underlined" | "underline" => Attribute::Underlined,
+            "reverse" => Attribute::Reverse,
+            "on" => {
+                let color = words
+                    .next()
+                    .ok_or_else(|| "expected a color after `on`".to_string())?;
+                style.background_color = Some(parse_color(color)?);
+                continue;
+            }
+            _ if style.foreground_color.is_none() => {
+                style.foreground_color = Some(parse_color(word)?);
+                continue;
+            }
+            _ => return Err(format!("not a color or attribute: {word}")),
+        };
+        style.attributes.set(attribute);
+    }
+    Ok(style)
+}
+
+fn parse_color(text: &str) -> Result<Color, String> {
+    let unknown = || format!("not a color or attribute: {text}");
+    if let Some(hex) = text.strip_prefix('#') {
+        let channel = |range: std::ops::Range<usize>| {
+            hex.get(range)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+        };
+        return match (hex.len(), channel(0..2), channel(2..4), channel(4..6)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Color::Rgb { r, g, b }),
+            _ => Err(unknown()),
+        };
+    }
+    if let Ok(value) = text.parse::<u8>() {
+        return Ok(Color::AnsiValue(value));
+    }
+    Color::try_from(text).map_err(|()| unknown())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_style() {
+        let style = parse_style("white on dark_blue bold").unwrap();
+        assert_eq!(style.foreground_color, Some(Color::White));
+        assert_eq!(style.background_color, Some(Color::DarkBlue));
+        assert!(style.attributes.has(Attribute::Bold));
+
+        let style = parse_style("on #10ff00 reverse").unwrap();
+        assert_eq!(style.foreground_color, None);
+        assert_eq!(
+            style.background_color,
+            Some(Color::Rgb {
+                r: 0x10,
+                g: 0xff,
+                b: 0
+            })
+        );
+        assert_eq!(
+            parse_style("208").unwrap().foreground_color,
+            Some(Color::AnsiValue(208))
+        );
+
+        assert_eq!(
+            parse_style("red blue"),
+            Err("not a color or attribute: blue".to_string())
+        );
+        assert!(parse_style("#12345").is_err());
+        assert!(parse_style("red on").is_err());
+    }
+
+    #[test]
+    fn test_built_in_themes() {
+        for (name, _) in BUILT_IN {
+            let theme = Theme::load_from(None, name).unwrap();
+            assert_ne!(theme.style("status_bar"), ContentStyle::default());
+        }
+        assert_eq!(
+            Theme::load_from(None, "neon"),
+            Err("Unknown theme: neon".to_string())
+        );
+    }
+
+    #[test]
+    fn test_elements_fall_back_to_their_parents() {
+        let theme = Theme::default();
+        assert_eq!(
+            theme.style("syntax.keyword.control"),
+            theme.style("syntax.keyword")
+        );
+        assert_eq!(theme.style("gutter"), ContentStyle::default());
+    }
+
+    #[test]
+    fn test_user_themes_inherit() {
+        let dir = std::env::temp_dir().join(format!("hecto-themes-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("mine.toml"),
+            "inherits = \"light\"\n[styles]\nstatus_bar = \"red\"\ntext = \"black\"\n",
+        )
+        .unwrap();
+        fs::write(dir.join("broken.toml"), "[styles]\ntext = \"sparkly\"\n").unwrap();
+        fs::write(dir.join("loop.toml"), "inherits = \"loop\"\n").unwrap();
+
+        let mine = Theme::load_from(Some(&dir), "mine").unwrap();
+        let light = Theme::load_from(None, "light").unwrap();
+        assert_eq!(mine.style("status_bar"), parse_style("red").unwrap());
+        assert_eq!(mine.style("search"), light.style("search"));
+        assert_eq!(mine.style("gutter"), parse_style("black").unwrap());
+        assert_eq!(
+            Theme::load_from(Some(&dir), "broken"),
+            Err("theme broken: line 2: not a color or attribute: sparkly".to_string())
+        );
+        assert!(Theme::load_from(Some(&dir), "loop").is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
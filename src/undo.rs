@@ -0,0 +1,331 @@
+//! # Undo
+//!
+//! Every edit to a buffer is recorded as the bytes it replaced and the
+//! bytes it put in their place, so it can be taken back. Edits are grouped
+//! into steps, undone and redone together: in normal mode, everything one
+//! command changes (`ciw` and what is typed after it up to `Esc` is one);
+//! otherwise, a run of typing, or whatever one key or command line did.
+//! `undo` (`u` in normal mode, `Alt+Z`) takes back the last step and
+//! `redo` (`Ctrl+R` in normal mode, `Alt+Shift+Z`) does it again, each
+//! putting the cursor back where it was when the step began. A new edit
+//! after undoing drops the steps that could have been redone.
+//!
+//! With the `undofile` option on, a buffer's steps are written to a file
+//! of their own in the XDG state directory whenever it is saved, and read
+//! back when the file is opened again, so undo reaches past a restart. The
+//! history is only used if the file is exactly as it was saved; it is off
+//! by default, since it keeps what was deleted from the file around.
+
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{dirs, error::Result, operator::Pos};
+
+/// The directory inside [`dirs::state_dir()`] the undo files are kept in.
+const DIR_NAME: &str = "undo";
+
+/// The first line of an undo file, naming its format.
+const HEADER: &str = "hecto-undo 1";
+
+/// How many steps are kept before the oldest are forgotten.
+const MAX_STEPS: usize = 1000;
+
+/// One change to the text: at byte `at`, `removed` was replaced by
+/// `inserted`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub at: usize,
+    pub removed: String,
+    pub inserted: String,
+}
+
+/// The edits undone together, and where the cursor was before them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub edits: Vec<Edit>,
+    pub cursor: Pos,
+}
+
+/// The steps of a buffer that can be undone, and those that can be redone.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct History {
+    done: Vec<Step>,
+    /// The steps undone, the most recently undone last.
+    undone: Vec<Step>,
+    /// Whether the next edit joins the last step.
+    open: bool,
+    /// Where the cursor is as the next step begins.
+    cursor: Pos,
+    /// How many steps were done when the buffer was last saved, if going
+    /// back or forth can still get there.
+    saved: Option<usize>,
+}
+
+impl History {
+    /// No edits yet, with the text as it is saved.
+    #[must_use]
+    pub fn saved() -> Self {
+        Self {
+            saved: Some(0),
+            ..Self::default()
+        }
+    }
+
+    /// Adds `edit` to the current step, or starts a new one with it.
+    pub fn record(&mut self, edit: Edit) {
+        self.undone.clear();
+        if self.saved.is_some_and(|saved| saved > self.done.len()) {
+            self.saved = None;
+        }
+        let Some(step) = self.done.last_mut().filter(|_| self.open) else {
+            self.done.push(Step {
+                edits: vec![edit],
+                cursor: self.cursor,
+            });
+            self.open = true;
+            if self.done.len() > MAX_STEPS {
+                self.done.remove(0);
+                self.saved = self.saved.and_then(|saved| saved.checked_sub(1));
+            }
+            return;
+        };
+        match step.edits.last_mut() {
+            // Typing adds to the text it typed before.
+            Some(last)
+                if edit.removed.is_empty()
+                    && last.removed.is_empty()
+                    && last.at.saturating_add(last.inserted.len()) == edit.at =>
+            {
+                last.inserted.push_str(&edit.inserted);
+            }
+            _ => step.edits.push(edit),
+        }
+    }
+
+    /// Ends the current step: the next edit starts another, with the cursor
+    /// at `cursor`.
+    pub fn seal(&mut self, cursor: Pos) {
+        self.open = false;
+        self.cursor = cursor;
+    }
+
+    /// Takes the last step done off, to be undone.
+    pub fn undo(&mut self) -> Option<Step> {
+        self.open = false;
+        let step = self.done.pop()?;
+        self.undone.push(step.clone());
+        Some(step)
+    }
+
+    /// Takes the last step undone back, to be done again.
+    pub fn redo(&mut self) -> Option<Step> {
+        self.open = false;
+        let step = self.undone.pop()?;
+        self.done.push(step.clone());
+        Some(step)
+    }
+
+    /// Notes that the text is as it is saved now.
+    pub fn mark_saved(&mut self) {
+        self.open = false;
+        self.saved = Some(self.done.len());
+    }
+
+    /// Whether the text is as it was last saved.
+    #[must_use]
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.done.len())
+    }
+
+    /// Writes the steps to `store`, for the file whose text is `text`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be written.
+    pub fn write(&self, store: &Path, text: &str) -> Result<()> {
+        let mut contents = format!("{HEADER}\nhash\t{:016x}\n", hash(text.as_bytes()));
+        for (marker, steps) in [("done", &self.done), ("undone", &self.undone)] {
+            let _ = writeln!(contents, "{marker}");
+            for step in steps {
+                let (line, col) = step.cursor;
+                let _ = writeln!(contents, "step\t{line}\t{col}");
+                for edit in &step.edits {
+                    let _ = writeln!(
+                        contents,
+                        "{}\t{}\t{}",
+                        edit.at,
+                        escape(&edit.removed),
+                        escape(&edit.inserted)
+                    );
+                }
+            }
+        }
+        if let Some(parent) = store.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_private(store, &contents)
+    }
+
+    /// The steps written to `store`, if it holds them for a file whose
+    /// text is `text` now.
+    #[must_use]
+    pub fn read(store: &Path, text: &str) -> Option<Self> {
+        let contents = fs::read_to_string(store).ok()?;
+        let mut lines = contents.lines();
+        if lines.next()? != HEADER {
+            return None;
+        }
+        let expected = format!("hash\t{:016x}", hash(text.as_bytes()));
+        if lines.next()? != expected {
+            return None;
+        }
+        let mut history = Self::default();
+        let mut undone = false;
+        for line in lines {
+            let steps = if undone {
+                &mut history.undone
+            } else {
+                &mut history.done
+            };
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields.as_slice() {
+                ["done"] => {}
+                ["undone"] => undone = true,
+                ["step", line, col] => steps.push(Step {
+                    edits: Vec::new(),
+                    cursor: (line.parse().ok()?, col.parse().ok()?),
+                }),
+                [at, removed, inserted] => steps.last_mut()?.edits.push(Edit {
+                    at: at.parse().ok()?,
+                    removed: unescape(removed),
+                    inserted: unescape(inserted),
+                }),
+                _ => return None,
+            }
+        }
+        history.mark_saved();
+        Some(history)
+    }
+}
+
+/// Where the undo history of the file at `path` is kept, if there is a
+/// state directory.
+#[must_use]
+pub fn store_for(path: &Path) -> Option<PathBuf> {
+    let path = fs::canonicalize(path).ok()?;
+    let name = format!("{:016x}", hash(path.to_string_lossy().as_bytes()));
+    Some(dirs::state_dir()?.join(DIR_NAME).join(name))
+}
+
+/// The 64-bit FNV-1a hash of `bytes`, which stays the same from one build
+/// to the next.
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// `text` with its backslashes, tabs and line breaks escaped, so it fits
+/// in a field of a line.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        match (ch, ch == '\\') {
+            (_, true) => match chars.next() {
+                Some('t') => unescaped.push('\t'),
+                Some('r') => unescaped.push('\r'),
+                Some('n') => unescaped.push('\n'),
+                Some(other) => unescaped.push(other),
+                None => unescaped.push('\\'),
+            },
+            (ch, false) => unescaped.push(ch),
+        }
+    }
+    unescaped
+}
+
+/// Writes `contents` to `path`, readable only by the user where
+/// permissions can say so: it may hold text deleted from their files.
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    use std::{io::Write as _, os::unix::fs::OpenOptionsExt};
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(contents.as_bytes()).map_err(Into::into)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    fs::write(path, contents).map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(at: usize, text: &str) -> Edit {
+        Edit {
+            at,
+            removed: String::new(),
+            inserted: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_edits_are_grouped_into_steps() {
+        let mut history = History::default();
+        history.record(insert(0, "a"));
+        history.record(insert(1, "b"));
+        history.seal((0, 2));
+        history.record(Edit {
+            at: 0,
+            removed: "ab".to_string(),
+            inserted: String::new(),
+        });
+        let step = history.undo().unwrap();
+        assert_eq!(step.cursor, (0, 2));
+        assert_eq!(history.undo().unwrap().edits, [insert(0, "ab")]);
+        assert_eq!(history.undo(), None);
+        assert!(history.redo().is_some());
+        history.record(insert(2, "c"));
+        assert_eq!(history.redo(), None);
+    }
+
+    #[test]
+    fn test_history_is_read_back_for_the_same_text() {
+        let store = std::env::temp_dir()
+            .join(format!("hecto-undo-{}", std::process::id()))
+            .join("history");
+        let mut history = History::default();
+        history.record(insert(0, "tab\there\n\\n"));
+        history.seal((1, 0));
+        history.record(insert(0, "x"));
+        history.undo();
+        history.write(&store, "text").unwrap();
+
+        let read = History::read(&store, "text").unwrap();
+        assert_eq!(
+            (read.done.clone(), read.undone.clone()),
+            (history.done, history.undone)
+        );
+        assert!(read.is_saved());
+        assert_eq!(History::read(&store, "changed text"), None);
+        fs::remove_dir_all(store.parent().unwrap()).unwrap();
+    }
+}
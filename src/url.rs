@@ -0,0 +1,274 @@
+//! # URLs
+//!
+//! Percent-encoding, as `url-encode` and `url-decode` do to the selected
+//! text: every byte of its UTF-8 but letters, digits and `-._~` becomes
+//! `%XX`, and back. A `+` is left a `+` both ways, as outside a form it
+//! is no space.
+//!
+//! It also finds the URL or file name at the cursor for `open-at-cursor`
+//! (`gx`, `gf`): a word with a scheme (`https://`, `mailto:`) or starting
+//! `www.` is a URL, opened with the system's handler (`xdg-open`, or
+//! `open` on macOS, the URL protocol handler on Windows); anything else is taken for a
+//! file, which may end in `:LINE` or `:LINE:COLUMN` as compilers write
+//! them, to open in the editor; a `#fragment` after it, as links to a
+//! heading have, is left out. Quotes, brackets and the punctuation of
+//! the sentence around it are not part of it, and a Markdown link's
+//! target is taken from `[text](target)`.
+
+use std::{
+    fmt::Write,
+    process::{Command, Stdio},
+};
+
+use crate::error::{self, Error};
+
+/// What [`target_at`] finds to open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// An address for the system to open.
+    Url(String),
+    /// A file, and the line and column to go to, from 0, if it says.
+    File {
+        path: String,
+        line: Option<usize>,
+        col: Option<usize>,
+    },
+}
+
+/// `text` with what a URL cannot hold as it is percent-encoded.
+#[must_use]
+pub fn encode(text: &str) -> String {
+    encode_keeping(text, b"")
+}
+
+/// [`encode`], keeping the bytes of `kept` as they are too, like the `/`
+/// of a path.
+#[must_use]
+pub fn encode_keeping(text: &str, kept: &[u8]) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) || kept.contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
+/// `text` with its `%XX` escapes decoded.
+///
+/// # Errors
+///
+/// Fails with what is wrong if an escape is broken, or what they stand
+/// for is not UTF-8 text.
+pub fn decode(text: &str) -> Result<String, String> {
+    let mut decoded = Vec::with_capacity(text.len());
+    let mut bytes = text.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'%' {
+            decoded.push(byte);
+            continue;
+        }
+        let escape: Vec<u8> = bytes.by_ref().take(2).collect();
+        let value = std::str::from_utf8(&escape)
+            .ok()
+            .filter(|digits| digits.len() == 2)
+            .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| format!("Not a % escape: %{}", String::from_utf8_lossy(&escape)))?;
+        decoded.push(value);
+    }
+    String::from_utf8(decoded).map_err(|_| "The decoded text is not UTF-8.".to_string())
+}
+
+/// The URL or file name around byte `at` of `line`, if there is a word
+/// there.
+#[must_use]
+pub fn target_at(line: &str, at: usize) -> Option<Target> {
+    let is_break = |ch: char| ch.is_whitespace() || "\"'`<>".contains(ch);
+    if line.get(at..)?.chars().next().is_none_or(is_break) {
+        return None;
+    }
+    let start = line[..at]
+        .char_indices()
+        .rev()
+        .find(|&(_, ch)| is_break(ch))
+        .map_or(0, |(index, ch)| index.saturating_add(ch.len_utf8()));
+    let end = line[at..]
+        .find(is_break)
+        .map_or(line.len(), |length| at.saturating_add(length));
+    let mut word = &line[start..end];
+    if let Some((_, target)) = word.split_once("](") {
+        word = target;
+    }
+    word = trim_punctuation(word.trim_start_matches(['(', '[', '{']));
+    if word.is_empty() {
+        return None;
+    }
+    if let Some(path) = word.strip_prefix("file://") {
+        let path = without_fragment(path);
+        return Some(Target::File {
+            path: decode(path).unwrap_or_else(|_| path.to_string()),
+            line: None,
+            col: None,
+        });
+    }
+    if let Some(scheme_end) = word.find("://") {
+        let scheme_start = word[..scheme_end]
+            .char_indices()
+            .rev()
+            .find(|&(_, ch)| !ch.is_ascii_alphanumeric() && !"+.-".contains(ch))
+            .map_or(0, |(index, ch)| index.saturating_add(ch.len_utf8()));
+        if word[scheme_start..scheme_end].starts_with(|ch: char| ch.is_ascii_alphabetic()) {
+            return Some(Target::Url(word[scheme_start..].to_string()));
+        }
+    }
+    if word.starts_with("mailto:") {
+        return Some(Target::Url(word.to_string()));
+    }
+    if word.starts_with("www.") {
+        return Some(Target::Url(format!("https://{word}")));
+    }
+    // `file:LINE:COLUMN`, the numbers from 1.
+    let mut path = without_fragment(word);
+    let mut numbers = Vec::new();
+    while numbers.len() < 2 {
+        let Some((before, number)) = path.rsplit_once(':') else {
+            break;
+        };
+        let Ok(number) = number.parse::<usize>() else {
+            break;
+        };
+        numbers.insert(0, number.saturating_sub(1));
+        path = before;
+    }
+    (!path.is_empty()).then(|| Target::File {
+        path: path.to_string(),
+        line: numbers.first().copied(),
+        col: numbers.get(1).copied(),
+    })
+}
+
+/// `path` without the `#fragment` of a link to a part of the file, unless
+/// the `#` starts it.
+fn without_fragment(path: &str) -> &str {
+    match path.split_once('#') {
+        Some((file, _)) if !file.is_empty() => file,
+        _ => path,
+    }
+}
+
+/// `word` without the punctuation a sentence puts after it, or brackets
+/// it closes that it did not open.
+fn trim_punctuation(mut word: &str) -> &str {
+    loop {
+        let trimmed = word.trim_end_matches(['.', ',', ';', ':', '!', '?']);
+        let trimmed = match trimmed.chars().last() {
+            Some(close @ (')' | ']' | '}')) => {
+                let open = match close {
+                    ')' => '(',
+                    ']' => '[',
+                    _ => '{',
+                };
+                if trimmed.matches(close).count() > trimmed.matches(open).count() {
+                    &trimmed[..trimmed.len().saturating_sub(1)]
+                } else {
+                    trimmed
+                }
+            }
+            _ => trimmed,
+        };
+        if trimmed.len() == word.len() {
+            return word;
+        }
+        word = trimmed;
+    }
+}
+
+/// Opens `url` with the system's handler for it, leaving it running.
+///
+/// # Errors
+///
+/// Fails if the handler cannot be started.
+pub fn open(url: &str) -> error::Result<()> {
+    let mut command = if cfg!(windows) {
+        // Not `cmd /C start`, which would run what follows a `&` in it.
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+    command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| Error::Command {
+            command: command.get_program().to_string_lossy().into_owned(),
+            message: err.to_string(),
+        })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_encoding_round_trips() {
+        let text = "a b&c=d/é~x+1";
+        assert_eq!(encode(text), "a%20b%26c%3Dd%2F%C3%A9~x%2B1");
+        assert_eq!(decode(&encode(text)).as_deref(), Ok(text));
+        assert_eq!(decode("100%25+ok").as_deref(), Ok("100%+ok"));
+        assert_eq!(decode("%zz").unwrap_err(), "Not a % escape: %zz");
+        assert_eq!(decode("50%").unwrap_err(), "Not a % escape: %");
+        assert_eq!(decode("%C3").unwrap_err(), "The decoded text is not UTF-8.");
+    }
+
+    #[test]
+    fn test_targets_are_found_at_the_cursor() {
+        let url = |text: &str| Some(Target::Url(text.to_string()));
+        let file = |path: &str, line, col| {
+            Some(Target::File {
+                path: path.to_string(),
+                line,
+                col,
+            })
+        };
+        let line = "See https://example.com/a_(b)?q=1, or (www.rust-lang.org).";
+        assert_eq!(target_at(line, 10), url("https://example.com/a_(b)?q=1"));
+        assert_eq!(target_at(line, 42), url("https://www.rust-lang.org"));
+        assert_eq!(target_at(line, 3), None);
+        assert_eq!(
+            target_at("[docs](docs/guide.md#intro)", 2),
+            file("docs/guide.md", None, None)
+        );
+        assert_eq!(
+            target_at("file:///tmp/a.html#top", 0),
+            file("/tmp/a.html", None, None)
+        );
+        assert_eq!(target_at("href=\"http://x.org\"", 8), url("http://x.org"));
+        assert_eq!(
+            target_at("error at src/main.rs:12:5: oops", 12),
+            file("src/main.rs", Some(11), Some(4))
+        );
+        assert_eq!(
+            target_at("see ~/notes.txt:3.", 6),
+            file("~/notes.txt", Some(2), None)
+        );
+        assert_eq!(
+            target_at("file:///tmp/a%20b.txt", 0),
+            file("/tmp/a b.txt", None, None)
+        );
+        assert_eq!(target_at("mailto:me@x.org", 3), url("mailto:me@x.org"));
+        assert_eq!(target_at("\"...\"", 2), None);
+        assert_eq!(
+            target_at("文档：https://example.com/docs", "文档：".len()),
+            url("https://example.com/docs")
+        );
+    }
+}
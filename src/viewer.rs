@@ -1,8 +1,4 @@
-use crate::{
-    buffer::Buffer,
-    error::Result,
-    terminal::{self, Size},
-};
+use crate::{buffer::Buffer, error::Result, screen::fit_to_width, terminal::Size};
 
 const NAME: &str = env!("CARGO_PKG_NAME");
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -10,138 +6,246 @@ const VERSION: &str = env!("CARGO_PKG_VERSION");
 #[derive(Debug, Default, Clone)]
 pub struct View {
     pub buffer: Buffer,
+    /// Recently opened files, listed under the welcome message.
+    pub recent_files: Vec<String>,
+    /// Index of the buffer line shown in the first screen row.
+    pub scroll_offset: usize,
 }
 
 impl View {
-    pub fn load(&mut self, file_name: &str) {
-        if let Ok(buffer) = Buffer::load(file_name) {
-            self.buffer = buffer;
-        }
-    }
-
-    /// Render all the rows of the editor’s screen content.
+    /// Replaces the buffer with the contents of `file_name`. On failure the
+    /// current buffer is left untouched.
     ///
-    /// Clears each line, then either render a welcome message row or an empty row
-    /// (with a “~” in the first column).
-    pub fn render(&self) -> Result<()> {
-        if self.buffer.is_empty() {
-            Self::render_welcome()?;
-        } else {
-            self.render_buffer()?;
-        }
+    /// # Errors
+    ///
+    /// Fails if the file cannot be read or is not valid UTF-8.
+    pub fn load(&mut self, file_name: &str) -> Result<()> {
+        self.buffer = Buffer::load(file_name)?;
         Ok(())
     }
 
-    fn render_welcome() -> Result<()> {
-        let Size { height, .. } = terminal::size()?;
+    /// Scrolls one line up, stopping at the top of the buffer.
+    pub fn scroll_up(&mut self) {
+        self.scroll_offset = self.scroll_offset.saturating_sub(1);
+    }
+
+    /// Scrolls one line down, stopping once the last line reaches the bottom
+    /// of a `height`-row text area.
+    pub fn scroll_down(&mut self, height: usize) {
+        let max = self.buffer.line_count().saturating_sub(height);
+        self.scroll_offset = self.scroll_offset.saturating_add(1).min(max);
+    }
+
+    /// Scrolls a `height`-row text area one screen up, stopping at the top.
+    pub fn scroll_page_up(&mut self, height: usize) {
+        self.scroll_offset = self.scroll_offset.saturating_sub(height);
+    }
+
+    /// Scrolls a `height`-row text area one screen down, stopping once the
+    /// last line reaches the bottom.
+    pub fn scroll_page_down(&mut self, height: usize) {
+        self.scroll_offset = self.scroll_offset.saturating_add(height);
+        self.clamp_scroll(height);
+    }
 
-        for row in 0..height {
-            terminal::clear_line()?;
+    /// Keeps the scroll offset valid for a `height`-row text area, e.g. after
+    /// the terminal was resized.
+    pub fn clamp_scroll(&mut self, height: usize) {
+        let max = self.buffer.line_count().saturating_sub(height);
+        self.scroll_offset = self.scroll_offset.min(max);
+    }
 
-            if row == height.saturating_div(3) {
-                render_welcome_row()?;
-            } else {
-                render_empty_row()?;
-            }
+    /// Scrolls so the last line is at the bottom of a `height`-row text area.
+    pub fn scroll_to_bottom(&mut self, height: usize) {
+        self.scroll_offset = self.buffer.line_count().saturating_sub(height);
+    }
 
-            if row.saturating_add(1) < height {
-                terminal::print("\r\n")?;
-            }
+    /// Builds the rows of the editor’s screen content for a text area of
+    /// the given size.
+    ///
+    /// Each row is either a buffer line, the welcome message, or an empty row
+    /// (with a “~” in the first column). Rows are cut to the screen width.
+    #[must_use]
+    pub fn render(&self, size: Size) -> Vec<String> {
+        let mut rows = if self.buffer.is_empty() {
+            self.render_welcome(size)
+        } else {
+            self.render_buffer(size)
+        };
+        for row in &mut rows {
+            fit_to_width(row, size.width);
         }
-        Ok(())
+        rows
     }
 
-    fn render_buffer(&self) -> Result<()> {
-        let Size { height, .. } = terminal::size()?;
+    fn render_welcome(&self, Size { height, width }: Size) -> Vec<String> {
+        let welcome_row = height.saturating_div(3);
+        // The recent files start two rows below the welcome message.
+        let recent_row = welcome_row.saturating_add(2);
+
+        (0..height)
+            .map(|row| {
+                if row == welcome_row {
+                    render_welcome_row(width)
+                } else if let Some(recent) = row
+                    .checked_sub(recent_row)
+                    .and_then(|index| self.recent_files.get(index))
+                {
+                    render_recent_row(recent)
+                } else {
+                    render_empty_row()
+                }
+            })
+            .collect()
+    }
 
-        for row in 0..height {
-            terminal::clear_line()?;
+    fn render_buffer(&self, size: Size) -> Vec<String> {
+        render_lines(&self.buffer, self.scroll_offset, size)
+    }
 
-            if let Some(line) = self.buffer.get(row) {
-                terminal::print(line)?;
-            } else {
-                render_empty_row()?;
-            }
+    /// Whether screen row `row` shows a line of the buffer, rather than
+    /// the welcome screen or the filler past the end of the buffer.
+    #[must_use]
+    pub fn shows_line(&self, row: usize) -> bool {
+        shows_line(&self.buffer, self.scroll_offset, row)
+    }
 
-            if row.saturating_add(1) < height {
-                terminal::print("\r\n")?;
-            }
-        }
-        Ok(())
+    /// Screen column of grapheme `col` of line `line`. Columns past the end
+    /// of the line continue one cell per column after it.
+    #[must_use]
+    pub fn screen_column(&self, line: usize, col: usize) -> usize {
+        screen_column(&self.buffer, line, col)
     }
 }
 
+/// Screen column of grapheme `col` of line `line` of `buffer`, as
+/// [`View::screen_column`] has it.
+#[must_use]
+pub fn screen_column(buffer: &Buffer, line: usize, col: usize) -> usize {
+    let layout = buffer.layout(line);
+    let beyond = col.saturating_sub(layout.len());
+    layout.column(col.min(layout.len())).saturating_add(beyond)
+}
+
+/// The rows of `buffer` from line `scroll_offset` on, for a text area of
+/// `size`; past its end, empty rows. This is how a buffer that is not in
+/// the view is drawn, e.g. in another window.
+#[must_use]
+pub fn render_lines(
+    buffer: &Buffer,
+    scroll_offset: usize,
+    Size { height, width }: Size,
+) -> Vec<String> {
+    (0..height)
+        .map(|row| {
+            let index = row.saturating_add(scroll_offset);
+            buffer.get(index).map_or_else(render_empty_row, |line| {
+                buffer.layout(index).render(&line, width)
+            })
+        })
+        .collect()
+}
+
+/// Whether row `row` of [`render_lines`] shows a line of `buffer`.
+#[must_use]
+pub fn shows_line(buffer: &Buffer, scroll_offset: usize, row: usize) -> bool {
+    !buffer.is_empty() && row.saturating_add(scroll_offset) < buffer.line_count()
+}
+
 /// Render an empty row, indicated by a single “~” in the leftmost column.
-fn render_empty_row() -> Result<()> {
-    terminal::print("~")
+fn render_empty_row() -> String {
+    "~".to_string()
 }
 
 /// Render the “welcome message” row, centered horizontally.
 /// (We don’t require perfect centering; it’s just approximate.)
-fn render_welcome_row() -> Result<()> {
-    let mut welcome_message = format!("{NAME} editor -- version {VERSION}");
-    let width = terminal::size()?.width;
+fn render_welcome_row(width: usize) -> String {
+    let welcome_message = format!("{NAME} editor -- version {VERSION}");
     let len = welcome_message.len();
 
     let padding = (width.saturating_sub(len)).saturating_div(2);
     // We put a “~” at the start, then some spaces, then the message.
     let leading_spaces = " ".repeat(padding.saturating_sub(1));
-    welcome_message = format!("~{leading_spaces}{welcome_message}");
+    format!("~{leading_spaces}{welcome_message}")
+}
 
-    // If the message is bigger than the width, we truncate
-    welcome_message.truncate(width);
-    terminal::print(&welcome_message)
+/// Render one recent-file entry on the welcome screen, aligned under the
+/// welcome message.
+fn render_recent_row(path: &str) -> String {
+    format!("~    {path}")
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{io_provider::take_out_contents, terminal};
+    use super::*;
+
+    const SIZE: Size = Size {
+        width: 80,
+        height: 24,
+    };
 
     #[test]
     fn test_render_welcome() {
-        // We'll call `super::render_welcome()` directly and check
-        // the buffer for something like "~    <PackageName> editor -- version <Version>".
-        super::render_welcome_row().unwrap();
-        terminal::execute().unwrap();
-
-        let contents = take_out_contents();
-        let out = String::from_utf8_lossy(&contents);
         // We expect something like "~ <some spaces>my_crate editor -- version 1.0.0"
+        let row = render_welcome_row(80);
+        assert!(row.starts_with('~'), "Expected the row to start with '~'");
         assert!(
-            out.contains("editor -- version"),
+            row.contains("editor -- version"),
             "Expected welcome message in output"
         );
     }
 
     #[test]
     fn test_render_empty() {
-        super::render_empty_row().unwrap();
-        terminal::execute().unwrap();
-
-        let contents = take_out_contents();
-        let out = String::from_utf8_lossy(&contents);
-        // Should just print "~"
-        assert!(out.contains('~'), "Expected a single '~' for empty row");
+        // Should just be "~"
+        assert_eq!(
+            render_empty_row(),
+            "~",
+            "Expected a single '~' for empty row"
+        );
     }
 
     #[test]
     fn test_render() {
-        let view = super::View::default();
-
-        view.render().unwrap();
-        terminal::execute().unwrap();
+        let view = View::default();
 
-        let contents = take_out_contents();
-        let out = String::from_utf8_lossy(&contents);
-        // We'll check at least for a bunch of "~" characters,
-        // as many as the terminal height (but we can't be certain what the terminal size is).
+        let rows = view.render(SIZE);
+        // One row per line of the text area, mostly "~".
+        assert_eq!(rows.len(), SIZE.height);
         assert!(
-            out.contains('~'),
+            rows.iter().any(|row| row == "~"),
             "Expected at least some empty row symbols (~)"
         );
         assert!(
-            out.contains("editor -- version"),
+            rows.iter().any(|row| row.contains("editor -- version")),
             "Expected the welcome row somewhere in the output"
         );
     }
+
+    #[test]
+    fn test_render_welcome_lists_recent_files() {
+        let view = View {
+            recent_files: vec!["/tmp/notes.txt".to_string()],
+            ..View::default()
+        };
+
+        let rows = view.render(SIZE);
+        assert!(
+            rows.iter().any(|row| row.contains("/tmp/notes.txt")),
+            "Expected recent files under the welcome message"
+        );
+    }
+
+    #[test]
+    fn test_render_buffer_is_scrolled_and_cut_to_width() {
+        let mut view = View::default();
+        view.buffer.replace_text("first\nsecond line\n");
+        view.scroll_offset = 1;
+
+        let rows = view.render(Size {
+            width: 6,
+            height: 2,
+        });
+        assert_eq!(rows, vec!["second", "~"]);
+    }
 }
@@ -0,0 +1,82 @@
+//! # Which-key
+//!
+//! While a chord is half typed (`space` for the leader, `ctrl-x`, ...), a
+//! popup above the status bar lists the keys that can finish it and the
+//! command each runs, so bindings need not be remembered.
+
+use std::fmt::Write;
+
+use crate::{command::EditorCommand, screen::fit_to_width};
+
+/// Spaces between columns.
+const GAP: usize = 2;
+
+/// The rows of the popup for the chord `prefix`: a title, then `entries`
+/// (the keys left to type and their command) in as many columns as fit in
+/// `width`, filled top to bottom. At most `height` rows; entries that do
+/// not fit are left out.
+#[must_use]
+pub fn render(
+    prefix: &str,
+    entries: &[(String, EditorCommand)],
+    width: usize,
+    height: usize,
+) -> Vec<String> {
+    let cells: Vec<String> = entries
+        .iter()
+        .map(|(keys, command)| format!("{keys}  {}", command.name().unwrap_or("?")))
+        .collect();
+    let cell_width = cells
+        .iter()
+        .map(|cell| cell.chars().count())
+        .max()
+        .unwrap_or_default()
+        .saturating_add(GAP);
+    let columns = width.checked_div(cell_width).unwrap_or_default().max(1);
+    let rows = cells.len().div_ceil(columns).min(height.saturating_sub(1));
+    if rows == 0 {
+        return Vec::new();
+    }
+    let mut lines = vec![format!("{prefix}-")];
+    lines.extend((0..rows).map(|row| {
+        let mut line = cells.iter().skip(row).step_by(rows).take(columns).fold(
+            String::new(),
+            |mut line, cell| {
+                // Writing to a `String` cannot fail.
+                let _ = write!(line, "{cell:<cell_width$}");
+                line
+            },
+        );
+        line.truncate(line.trim_end().len());
+        fit_to_width(&mut line, width);
+        line
+    }));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(names: &[(&str, &str)]) -> Vec<(String, EditorCommand)> {
+        names
+            .iter()
+            .map(|(keys, name)| ((*keys).to_string(), EditorCommand::from_name(name).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_entries_fill_columns_top_to_bottom() {
+        let entries = entries(&[("a", "save-all"), ("q", "quit"), ("w", "save")]);
+        assert_eq!(
+            render("space", &entries, 30, 10),
+            vec!["space-", "a  save-all  w  save", "q  quit"]
+        );
+        // One column when the screen is narrow, cut to the height.
+        assert_eq!(
+            render("space", &entries, 5, 3),
+            vec!["space-", "a  sa", "q  qu"]
+        );
+        assert!(render("space", &[], 80, 10).is_empty());
+    }
+}
@@ -0,0 +1,590 @@
+//! # Windows
+//!
+//! The text area can be split into windows, each showing a buffer with a
+//! cursor and scroll position of its own: `Ctrl+W s` splits the focused
+//! window in two, one above the other, `Ctrl+W v` side by side, `Ctrl+W w`
+//! moves the focus to the next window (as does clicking in one) and
+//! `Ctrl+W c` closes the focused one. Windows side by side are kept apart
+//! by a column of `│`. `Ctrl+W +` and `Ctrl+W -` make the focused window
+//! taller or shorter, `Ctrl+W >` and `Ctrl+W <` wider or narrower, and
+//! `Ctrl+W =` makes them all the same size.
+//!
+//! A [`Layout`] only decides where the windows go on screen. It is a tree:
+//! each split divides its part of the screen among windows or further
+//! splits, and remembers how big each one is so a resized terminal keeps
+//! their proportions. What the windows show is up to the editor.
+
+use crate::{buffer_list::BufferId, operator::Pos};
+
+/// The number a window is known by while it is open.
+pub type WindowId = usize;
+
+/// The fewest rows a window can have: one of text and its status line.
+const MIN_HEIGHT: usize = 2;
+
+/// The fewest columns a window can take: one of text and the separator.
+const MIN_WIDTH: usize = 2;
+
+/// What a window shows and where its cursor is. The editor keeps these
+/// for the windows without the focus; the focused window's live in the
+/// view instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub buffer: BufferId,
+    pub cursor: Pos,
+    /// The first line on screen.
+    pub scroll_offset: usize,
+}
+
+/// A part of the screen.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub row: usize,
+    pub col: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// How a split lines up what it divides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// One above the other.
+    Horizontal,
+    /// Side by side.
+    Vertical,
+}
+
+impl Direction {
+    /// The fewest cells along it a window can have.
+    fn min_extent(self) -> usize {
+        match self {
+            Self::Horizontal => MIN_HEIGHT,
+            Self::Vertical => MIN_WIDTH,
+        }
+    }
+}
+
+impl Rect {
+    /// Whether the cell at `row`, `col` is in the rectangle.
+    #[must_use]
+    pub fn contains(self, row: usize, col: usize) -> bool {
+        (self.row..self.row.saturating_add(self.height)).contains(&row)
+            && (self.col..self.col.saturating_add(self.width)).contains(&col)
+    }
+
+    /// How far the rectangle reaches along `direction`.
+    fn extent(self, direction: Direction) -> usize {
+        match direction {
+            Direction::Horizontal => self.height,
+            Direction::Vertical => self.width,
+        }
+    }
+
+    /// The `size` cells along `direction` that start `offset` cells in.
+    fn slice(self, direction: Direction, offset: usize, size: usize) -> Self {
+        match direction {
+            Direction::Horizontal => Self {
+                row: self.row.saturating_add(offset),
+                height: size,
+                ..self
+            },
+            Direction::Vertical => Self {
+                col: self.col.saturating_add(offset),
+                width: size,
+                ..self
+            },
+        }
+    }
+
+    /// The part `size` cells long, `offset` cells in along `direction`,
+    /// that a split gives one of its parts; a part side by side with
+    /// another after it gives up its last column to the separator, which
+    /// comes second.
+    fn part(
+        self,
+        direction: Direction,
+        offset: usize,
+        size: usize,
+        last: bool,
+    ) -> (Self, Option<Self>) {
+        let part = self.slice(direction, offset, size);
+        if direction == Direction::Horizontal || last {
+            return (part, None);
+        }
+        let width = size.saturating_sub(1);
+        let separator = part.slice(direction, width, 1);
+        (Self { width, ..part }, Some(separator))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Window(WindowId),
+    /// Windows or further splits, one after the other along the direction,
+    /// each with its size along it.
+    Split(Direction, Vec<(Node, usize)>),
+}
+
+impl Node {
+    /// Whether window `id` is in it.
+    fn contains(&self, id: WindowId) -> bool {
+        match self {
+            Self::Window(window) => *window == id,
+            Self::Split(_, children) => children.iter().any(|(child, _)| child.contains(id)),
+        }
+    }
+
+    /// The fewest cells along `direction` it can be shrunk to.
+    fn min_size(&self, direction: Direction) -> usize {
+        match self {
+            Self::Window(_) => direction.min_extent(),
+            Self::Split(split, children) => {
+                let sizes = children.iter().map(|(child, _)| child.min_size(direction));
+                if *split == direction {
+                    sizes.fold(0, usize::saturating_add)
+                } else {
+                    sizes.max().unwrap_or_default()
+                }
+            }
+        }
+    }
+
+    /// The first window in it, top left.
+    fn first_window(&self) -> WindowId {
+        match self {
+            Self::Window(id) => *id,
+            Self::Split(_, children) => children
+                .first()
+                .map_or_else(WindowId::default, |(child, _)| child.first_window()),
+        }
+    }
+}
+
+/// Where the windows are. It starts with window 0 alone.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    root: Node,
+    /// The part of the screen the windows share.
+    area: Rect,
+    /// The last number given to a window.
+    last_id: WindowId,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            root: Node::Window(0),
+            area: Rect::default(),
+            last_id: 0,
+        }
+    }
+}
+
+impl Layout {
+    /// Whether there is more than one window.
+    #[must_use]
+    pub fn is_split(&self) -> bool {
+        matches!(self.root, Node::Split(..))
+    }
+
+    /// Spreads the windows over `area`, each keeping its share of the
+    /// space it had.
+    pub fn set_area(&mut self, area: Rect) {
+        self.area = area;
+        fit(&mut self.root, area);
+    }
+
+    /// Each window with its part of the screen, top to bottom and left to
+    /// right.
+    #[must_use]
+    pub fn windows(&self) -> Vec<(WindowId, Rect)> {
+        let mut windows = Vec::new();
+        place(&self.root, self.area, &mut windows, &mut Vec::new());
+        windows
+    }
+
+    /// The columns between windows side by side, one cell wide.
+    #[must_use]
+    pub fn separators(&self) -> Vec<Rect> {
+        let mut separators = Vec::new();
+        place(&self.root, self.area, &mut Vec::new(), &mut separators);
+        separators
+    }
+
+    /// The window the cell at `row`, `col` is in, if any.
+    #[must_use]
+    pub fn window_at(&self, row: usize, col: usize) -> Option<WindowId> {
+        self.windows()
+            .into_iter()
+            .find_map(|(id, rect)| rect.contains(row, col).then_some(id))
+    }
+
+    /// The part of the screen of window `id`.
+    #[must_use]
+    pub fn rect(&self, id: WindowId) -> Option<Rect> {
+        self.windows()
+            .into_iter()
+            .find_map(|(window, rect)| (window == id).then_some(rect))
+    }
+
+    /// The window after `id`, going back to the first after the last.
+    #[must_use]
+    pub fn next(&self, id: WindowId) -> WindowId {
+        let windows = self.windows();
+        let index = windows.iter().position(|(window, _)| *window == id);
+        let next = index.map_or(0, |index| index.saturating_add(1));
+        windows
+            .get(next)
+            .or_else(|| windows.first())
+            .map_or(id, |(window, _)| *window)
+    }
+
+    /// Splits window `id` in two along `direction`, and returns the number
+    /// of the new window, which comes second. `None` if there is no room.
+    pub fn split(&mut self, id: WindowId, direction: Direction) -> Option<WindowId> {
+        let extent = self.rect(id)?.extent(direction);
+        if extent < direction.min_extent().saturating_mul(2) {
+            return None;
+        }
+        let new = self.last_id.saturating_add(1);
+        split_in(&mut self.root, id, new, direction, extent).then(|| {
+            self.last_id = new;
+            new
+        })
+    }
+
+    /// Makes window `id` `amount` cells bigger along `direction` (or, if
+    /// not `grow`, smaller), taking the space from the part after it in the
+    /// nearest split that way (the part before, if it is last) or giving
+    /// it back. No part gets smaller than a window can be. Returns whether
+    /// anything changed.
+    pub fn resize(
+        &mut self,
+        id: WindowId,
+        direction: Direction,
+        grow: bool,
+        amount: usize,
+    ) -> bool {
+        let changed = resize_in(&mut self.root, id, direction, grow, amount) == Some(true);
+        fit(&mut self.root, self.area);
+        changed
+    }
+
+    /// Shares the space of every split evenly among its parts.
+    pub fn equalize(&mut self) {
+        equalize_in(&mut self.root);
+        fit(&mut self.root, self.area);
+    }
+
+    /// Closes window `id`, giving its space to the one before it (or
+    /// after, if it was first). Returns the window that took the space,
+    /// or `None` if `id` is the only window.
+    pub fn close(&mut self, id: WindowId) -> Option<WindowId> {
+        close_in(&mut self.root, id)
+    }
+}
+
+/// Splits window `id` in `node`, which is `extent` cells long along
+/// `direction` if it is the window itself. Returns whether it was found.
+fn split_in(
+    node: &mut Node,
+    id: WindowId,
+    new: WindowId,
+    direction: Direction,
+    extent: usize,
+) -> bool {
+    match node {
+        Node::Window(window) if *window == id => {
+            let half = extent.saturating_div(2);
+            *node = Node::Split(
+                direction,
+                vec![
+                    (Node::Window(id), extent.saturating_sub(half)),
+                    (Node::Window(new), half),
+                ],
+            );
+            true
+        }
+        Node::Window(_) => false,
+        Node::Split(split, children) => {
+            // A window split the same way as its split becomes one more of
+            // its parts, rather than a split of its own.
+            let same = *split == direction;
+            if let Some(index) = children
+                .iter()
+                .position(|(child, _)| same && *child == Node::Window(id))
+            {
+                let size = children.get(index).map_or(0, |(_, size)| *size);
+                let half = size.saturating_div(2);
+                if let Some((_, kept)) = children.get_mut(index) {
+                    *kept = size.saturating_sub(half);
+                }
+                children.insert(index.saturating_add(1), (Node::Window(new), half));
+                return true;
+            }
+            children
+                .iter_mut()
+                .any(|(child, _)| split_in(child, id, new, direction, extent))
+        }
+    }
+}
+
+/// Removes window `id` from `node`; see [`Layout::close`].
+fn close_in(node: &mut Node, id: WindowId) -> Option<WindowId> {
+    let Node::Split(_, children) = node else {
+        return None;
+    };
+    let taker = match children
+        .iter()
+        .position(|(child, _)| *child == Node::Window(id))
+    {
+        Some(index) => {
+            let (_, size) = children.remove(index);
+            let neighbor = index.saturating_sub(1);
+            let (child, kept) = children.get_mut(neighbor)?;
+            *kept = kept.saturating_add(size);
+            child.first_window()
+        }
+        None => children
+            .iter_mut()
+            .find_map(|(child, _)| close_in(child, id))?,
+    };
+    // A split left with one part is just that part.
+    let only = match node {
+        Node::Split(_, children) if children.len() == 1 => children.pop().map(|(child, _)| child),
+        _ => None,
+    };
+    if let Some(only) = only {
+        *node = only;
+    }
+    Some(taker)
+}
+
+/// Resizes window `id` in `node`; see [`Layout::resize`]. `None` if the
+/// window is not in it, else whether it was resized.
+fn resize_in(
+    node: &mut Node,
+    id: WindowId,
+    direction: Direction,
+    grow: bool,
+    amount: usize,
+) -> Option<bool> {
+    let Node::Split(split, children) = node else {
+        return node.contains(id).then_some(false);
+    };
+    let index = children.iter().position(|(child, _)| child.contains(id))?;
+    let resized = resize_in(&mut children.get_mut(index)?.0, id, direction, grow, amount)?;
+    if resized || *split != direction {
+        return Some(resized);
+    }
+    let neighbor = if index.saturating_add(1) < children.len() {
+        index.saturating_add(1)
+    } else {
+        index.checked_sub(1)?
+    };
+    let (from, to) = if grow {
+        (neighbor, index)
+    } else {
+        (index, neighbor)
+    };
+    let (giver, size) = children.get(from)?;
+    let moved = amount.min(size.saturating_sub(giver.min_size(direction)));
+    if let Some((_, size)) = children.get_mut(from) {
+        *size = size.saturating_sub(moved);
+    }
+    if let Some((_, size)) = children.get_mut(to) {
+        *size = size.saturating_add(moved);
+    }
+    Some(moved > 0)
+}
+
+/// Evens out the sizes of the parts of every split in `node`.
+fn equalize_in(node: &mut Node) {
+    if let Node::Split(_, children) = node {
+        let total = children
+            .iter()
+            .fold(0_usize, |sum, (_, size)| sum.saturating_add(*size));
+        let mut sizes = vec![1; children.len()];
+        rescale(&mut sizes, total);
+        for ((child, size), new) in children.iter_mut().zip(sizes) {
+            *size = new;
+            equalize_in(child);
+        }
+    }
+}
+
+/// Scales the sizes of the parts of every split in `node` to fill `area`.
+fn fit(node: &mut Node, area: Rect) {
+    if let Node::Split(direction, children) = node {
+        let mut sizes: Vec<usize> = children.iter().map(|(_, size)| *size).collect();
+        rescale(&mut sizes, area.extent(*direction));
+        let count = children.len();
+        let mut offset = 0_usize;
+        for (index, ((child, size), new)) in children.iter_mut().zip(sizes).enumerate() {
+            *size = new;
+            let last = index.saturating_add(1) == count;
+            fit(child, area.part(*direction, offset, new, last).0);
+            offset = offset.saturating_add(new);
+        }
+    }
+}
+
+/// Adds each window in `node`, laid out in `area`, to `windows`, and the
+/// separators between them to `separators`.
+fn place(node: &Node, area: Rect, windows: &mut Vec<(WindowId, Rect)>, separators: &mut Vec<Rect>) {
+    match node {
+        Node::Window(id) => windows.push((*id, area)),
+        Node::Split(direction, children) => {
+            let mut offset = 0_usize;
+            for (index, (child, size)) in children.iter().enumerate() {
+                let last = index.saturating_add(1) == children.len();
+                let (part, separator) = area.part(*direction, offset, *size, last);
+                place(child, part, windows, separators);
+                separators.extend(separator);
+                offset = offset.saturating_add(*size);
+            }
+        }
+    }
+}
+
+/// Scales `sizes` to add up to `total`, keeping their proportions; what
+/// rounding leaves over goes to the last.
+fn rescale(sizes: &mut [usize], total: usize) {
+    let old = sizes
+        .iter()
+        .fold(0_usize, |sum, size| sum.saturating_add(*size));
+    if old == total {
+        return;
+    }
+    for size in sizes.iter_mut() {
+        *size = size
+            .saturating_mul(total)
+            .checked_div(old)
+            .unwrap_or_default();
+    }
+    let given = sizes
+        .iter()
+        .fold(0_usize, |sum, size| sum.saturating_add(*size));
+    if let Some(last) = sizes.last_mut() {
+        *last = last.saturating_add(total.saturating_sub(given));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(height: usize) -> Layout {
+        let mut layout = Layout::default();
+        layout.set_area(Rect {
+            row: 0,
+            col: 0,
+            width: 80,
+            height,
+        });
+        layout
+    }
+
+    fn heights(layout: &Layout) -> Vec<(WindowId, usize, usize)> {
+        layout
+            .windows()
+            .into_iter()
+            .map(|(id, rect)| (id, rect.row, rect.height))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_shares_the_space() {
+        let mut layout = layout(23);
+        assert!(!layout.is_split());
+        assert_eq!(layout.split(0, Direction::Horizontal), Some(1));
+        assert_eq!(heights(&layout), vec![(0, 0, 12), (1, 12, 11)]);
+        assert_eq!(layout.split(0, Direction::Horizontal), Some(2));
+        assert_eq!(heights(&layout), vec![(0, 0, 6), (2, 6, 6), (1, 12, 11)]);
+        assert_eq!(layout.next(2), 1);
+        assert_eq!(layout.next(1), 0);
+
+        for _ in 0..3 {
+            layout.split(0, Direction::Horizontal);
+        }
+        assert_eq!(layout.split(0, Direction::Horizontal), None);
+    }
+
+    #[test]
+    fn test_side_by_side_windows_have_a_separator() {
+        let mut layout = layout(23);
+        assert_eq!(layout.split(0, Direction::Vertical), Some(1));
+        assert_eq!(layout.split(0, Direction::Horizontal), Some(2));
+        let rects: Vec<(WindowId, Rect)> = layout.windows();
+        let rect = |row, col, width, height| Rect {
+            row,
+            col,
+            width,
+            height,
+        };
+        assert_eq!(
+            rects,
+            vec![
+                (0, rect(0, 0, 39, 12)),
+                (2, rect(12, 0, 39, 11)),
+                (1, rect(0, 40, 40, 23)),
+            ]
+        );
+        assert_eq!(layout.separators(), vec![rect(0, 39, 1, 23)]);
+        assert_eq!(layout.window_at(15, 10), Some(2));
+        assert_eq!(layout.window_at(5, 39), None);
+        assert_eq!(layout.window_at(22, 79), Some(1));
+        assert_eq!(layout.window_at(23, 0), None);
+    }
+
+    #[test]
+    fn test_resize_and_equalize() {
+        let mut layout = layout(23);
+        layout.split(0, Direction::Horizontal);
+        layout.split(1, Direction::Vertical);
+        assert!(layout.resize(0, Direction::Horizontal, true, 3));
+        assert_eq!(heights(&layout), vec![(0, 0, 15), (1, 15, 8), (2, 15, 8)]);
+        // Window 2 is last: it grows at the expense of window 1.
+        assert!(layout.resize(2, Direction::Vertical, true, 10));
+        assert_eq!(layout.rect(2).map(|rect| rect.width), Some(50));
+        // Its height is that of the split it shares with window 1.
+        assert!(layout.resize(2, Direction::Horizontal, false, 20));
+        assert_eq!(heights(&layout), vec![(0, 0, 21), (1, 21, 2), (2, 21, 2)]);
+        assert!(!layout.resize(1, Direction::Horizontal, false, 1));
+        assert!(!layout.resize(0, Direction::Vertical, true, 1));
+
+        layout.equalize();
+        assert_eq!(heights(&layout), vec![(0, 0, 11), (1, 11, 12), (2, 11, 12)]);
+        assert_eq!(layout.rect(1).map(|rect| rect.width), Some(39));
+    }
+
+    #[test]
+    fn test_close_gives_the_space_back() {
+        let mut layout = layout(24);
+        layout.split(0, Direction::Horizontal);
+        layout.split(1, Direction::Horizontal);
+        assert_eq!(layout.close(0), Some(1));
+        assert_eq!(heights(&layout), vec![(1, 0, 18), (2, 18, 6)]);
+        assert_eq!(layout.close(2), Some(1));
+        assert!(!layout.is_split());
+        assert_eq!(layout.close(1), None);
+    }
+
+    #[test]
+    fn test_resizing_keeps_proportions() {
+        let mut layout = layout(20);
+        layout.split(0, Direction::Horizontal);
+        layout.split(1, Direction::Horizontal);
+        assert_eq!(heights(&layout), vec![(0, 0, 10), (1, 10, 5), (2, 15, 5)]);
+        layout.set_area(Rect {
+            height: 40,
+            ..layout.area
+        });
+        assert_eq!(heights(&layout), vec![(0, 0, 20), (1, 20, 10), (2, 30, 10)]);
+        layout.set_area(Rect {
+            height: 9,
+            ..layout.area
+        });
+        assert_eq!(heights(&layout), vec![(0, 0, 4), (1, 4, 2), (2, 6, 3)]);
+    }
+}